@@ -0,0 +1,44 @@
+//! Verified accounts. The `account_id` a client sends is only its say-so; it counts as
+//! verified when it comes with a token from the mod's account service, the HMAC-SHA256
+//! of the id under the key that service shares with this server (`account_token_key`,
+//! hex). Without a key configured no account is verified.
+
+use ring::hmac;
+
+use crate::config::CONFIG;
+use crate::utils::decode_hex;
+
+/// Whether `token` proves the client owns `account_id`
+pub fn verify(account_id: &str, token: &str) -> bool {
+    let Some(key) = CONFIG.get().account_token_key.as_deref().and_then(decode_hex) else {
+        return false;
+    };
+    verify_with(&key, account_id, token)
+}
+
+fn verify_with(key: &[u8], account_id: &str, token: &str) -> bool {
+    let Some(signature) = decode_hex(token.trim()) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::verify(&key, account_id.as_bytes(), &signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::encode_hex;
+
+    #[test]
+    fn test_only_tokens_signed_for_the_account_verify() {
+        let key = b"shared with the account service";
+        let sign = |account_id: &str| {
+            let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+            encode_hex(hmac::sign(&key, account_id.as_bytes()).as_ref())
+        };
+        assert!(verify_with(key, "fil", &sign("fil")));
+        assert!(!verify_with(key, "fil", &sign("other")));
+        assert!(!verify_with(b"another key", "fil", &sign("fil")));
+        assert!(!verify_with(key, "fil", "not hex"));
+    }
+}
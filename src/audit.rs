@@ -0,0 +1,169 @@
+//! Append-only audit trail of lobby lifetime events, one JSON object per line,
+//! for settling disputes after the fact ("the host changed lives mid-game").
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, mpsc};
+
+use serde::Serialize;
+use tracing::error;
+
+use crate::config::CONFIG;
+use crate::game_mode::GameMode;
+use crate::lobby::options_history::OptionsDiff;
+use crate::messages::Standing;
+use crate::utils::now_millis;
+
+/// Set for offline runs (e.g. `simulate`) whose lobbies are not real
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+/// Lines are written on their own thread so lobbies never wait on the disk,
+/// and in the order they were recorded
+static WRITER: LazyLock<Mutex<mpsc::Sender<(PathBuf, String)>>> = LazyLock::new(|| {
+    let (tx, rx) = mpsc::channel::<(PathBuf, String)>();
+    std::thread::spawn(move || {
+        let mut open: Option<(PathBuf, File)> = None;
+        for (path, line) in rx {
+            if let Err(e) = append(&mut open, &path, &line) {
+                error!("Failed to write audit log {:?}: {}", path, e);
+                open = None;
+            }
+        }
+    });
+    Mutex::new(tx)
+});
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    LobbyCreated {
+        host_id: String,
+        game_mode: GameMode,
+        ruleset: String,
+    },
+    OptionsChanged {
+        changed_by: String,
+        changes: OptionsDiff,
+        /// Whether a game was running when the options changed
+        during_game: bool,
+    },
+    PlayerJoined {
+        player_id: String,
+        username: String,
+        /// Account the account service vouched for
+        account_id: Option<String>,
+        /// Account the client named without proof, anyone can claim any id
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unverified_account_id: Option<String>,
+    },
+    PlayerLeft {
+        player_id: String,
+    },
+    PlayerKicked {
+        player_id: String,
+        reason: String,
+    },
+    GameStarted {
+        players: Vec<String>,
+    },
+    GameEnded {
+        standings: Vec<Standing>,
+        unverified: Vec<String>,
+    },
+    /// A player broke a rule the server enforces, e.g. playing another deck
+    RulesViolation {
+        player_id: String,
+        rule: String,
+        detail: String,
+    },
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp: u64,
+    lobby_code: &'a str,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+pub fn mute() {
+    MUTED.store(true, Ordering::Relaxed);
+}
+
+/// Append an event for `lobby_code` to the audit log, if one is configured
+pub fn record(lobby_code: &str, event: AuditEvent) {
+    if MUTED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(path) = CONFIG.get().audit_log_path.clone() else {
+        return;
+    };
+    let Some(line) = render(now_millis(), lobby_code, &event) else {
+        return;
+    };
+    let writer = WRITER.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = writer.send((path, line));
+}
+
+fn render(timestamp: u64, lobby_code: &str, event: &AuditEvent) -> Option<String> {
+    let record = AuditRecord {
+        timestamp,
+        lobby_code,
+        event,
+    };
+    serde_json::to_string(&record).ok()
+}
+
+/// Keeps the file open between lines, reopening when the configured path changes
+fn append(open: &mut Option<(PathBuf, File)>, path: &Path, line: &str) -> std::io::Result<()> {
+    if open.as_ref().is_none_or(|(current, _)| current != path) {
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        *open = Some((path.to_path_buf(), file));
+    }
+    let (_, file) = open.as_mut().expect("audit log was just opened");
+    writeln!(file, "{}", line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_audit_lines_append_in_order() {
+        let path = std::env::temp_dir().join(format!("audit-{}.log", uuid::Uuid::new_v4()));
+        let mut open = None;
+        let changes = OptionsDiff::from([("starting_lives".to_string(), Value::from(7))]);
+        let events = [
+            AuditEvent::PlayerLeft {
+                player_id: "p2".to_string(),
+            },
+            AuditEvent::OptionsChanged {
+                changed_by: "p1".to_string(),
+                changes,
+                during_game: true,
+            },
+        ];
+        for (timestamp, event) in events.iter().enumerate() {
+            let line = render(timestamp as u64, "ABCDE", event).unwrap();
+            append(&mut open, &path, &line).unwrap();
+        }
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<Value> = log.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "player_left");
+        assert_eq!(lines[1]["event"], "options_changed");
+        assert_eq!(lines[1]["lobby_code"], "ABCDE");
+        assert_eq!(lines[1]["timestamp"], 1);
+        assert_eq!(lines[1]["changes"]["starting_lives"], 7);
+    }
+}
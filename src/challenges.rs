@@ -0,0 +1,237 @@
+//! Custom challenge definitions shared through the server.
+//!
+//! Hosts upload a definition once and put the returned id in
+//! `LobbyOptions.challenge`; the other players fetch it by that id.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+
+use rand::Rng;
+use rusqlite::{Connection, OptionalExtension, params};
+use tracing::error;
+
+use crate::sqlite_store::SqliteStore;
+use crate::utils::now_millis;
+
+/// Ids of shared challenges start with this, telling them apart from built-in challenge keys
+pub const SHARED_CHALLENGE_PREFIX: &str = "shared_";
+/// Largest definition accepted, in bytes
+pub const MAX_CHALLENGE_BYTES: usize = 16 * 1024;
+/// Uploads a single connection may make, whoever it says it is
+pub const MAX_UPLOADS_PER_CONNECTION: u32 = 10;
+const MAX_CHALLENGE_NAME: usize = 64;
+/// Challenges a single verified account may keep on the server
+const MAX_CHALLENGES_PER_ACCOUNT: i64 = 50;
+const CHALLENGE_ID_LEN: usize = 8;
+const UNAVAILABLE: &str = "Shared challenges are unavailable";
+
+/// Every stored challenge id, so lobby options can be checked without the database
+static KNOWN_IDS: LazyLock<RwLock<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Whether a shared challenge with this id was uploaded
+pub fn is_known(id: &str) -> bool {
+    KNOWN_IDS.read().unwrap_or_else(|e| e.into_inner()).contains(id)
+}
+
+pub struct SharedChallenges {
+    store: SqliteStore,
+}
+
+impl SharedChallenges {
+    /// The challenges uploaded so far; a database that doesn't exist yet is left for
+    /// the first upload to create
+    pub async fn open(path: PathBuf) -> Self {
+        let existing = path.exists();
+        let challenges = Self {
+            store: SqliteStore::new("Shared challenges", path, Self::init),
+        };
+        if existing {
+            challenges.load().await;
+        }
+        challenges
+    }
+
+    fn init(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS shared_challenges (
+                id TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL,
+                definition TEXT NOT NULL,
+                uploaded_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS shared_challenges_account
+                ON shared_challenges (account_id)",
+        )
+    }
+
+    async fn load(&self) {
+        let ids = self.store.run(|conn| {
+            let mut stmt = conn.prepare("SELECT id FROM shared_challenges")?;
+            let ids = stmt.query_map([], |row| row.get(0))?;
+            ids.collect::<rusqlite::Result<HashSet<String>>>()
+        });
+        match ids.await {
+            Some(Ok(ids)) => *KNOWN_IDS.write().unwrap_or_else(|e| e.into_inner()) = ids,
+            Some(Err(e)) => error!("Failed to load shared challenge ids: {}", e),
+            None => {}
+        }
+    }
+
+    /// Store a definition and return its id; uploading the same one again returns the id
+    /// it already has. Uploads by a verified account count against its quota, others
+    /// are limited per connection before they get here
+    pub fn upload(
+        &self,
+        account_id: Option<String>,
+        definition: String,
+    ) -> impl Future<Output = Result<String, &'static str>> + Send + 'static {
+        let stored = validate(&definition).map(|()| {
+            self.store.run(move |conn| store_challenge(conn, account_id.as_deref(), &definition))
+        });
+        async move {
+            let id = stored?.await.unwrap_or(Err(UNAVAILABLE))?;
+            KNOWN_IDS.write().unwrap_or_else(|e| e.into_inner()).insert(id.clone());
+            Ok(id)
+        }
+    }
+
+    pub fn get(&self, id: String) -> impl Future<Output = Option<String>> + Send + 'static {
+        let definition = id.starts_with(SHARED_CHALLENGE_PREFIX).then(|| {
+            self.store.run(move |conn| {
+                conn.query_row(
+                    "SELECT definition FROM shared_challenges WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .ok()
+                .flatten()
+            })
+        });
+        async move { definition?.await.flatten() }
+    }
+}
+
+/// Anonymous uploads are stored without an owner
+fn store_challenge(
+    conn: &Connection,
+    account_id: Option<&str>,
+    definition: &str,
+) -> Result<String, &'static str> {
+    let unavailable = |_| UNAVAILABLE;
+    let owner = account_id.unwrap_or("");
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT id FROM shared_challenges WHERE account_id = ?1 AND definition = ?2",
+            params![owner, definition],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(unavailable)?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    if account_id.is_some() {
+        let uploaded: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM shared_challenges WHERE account_id = ?1",
+                params![owner],
+                |row| row.get(0),
+            )
+            .map_err(unavailable)?;
+        if uploaded >= MAX_CHALLENGES_PER_ACCOUNT {
+            return Err("You have uploaded too many challenges");
+        }
+    }
+
+    let id = generate_challenge_id();
+    conn.execute(
+        "INSERT INTO shared_challenges (id, account_id, definition, uploaded_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![id, owner, definition, now_millis() as i64],
+    )
+    .map_err(unavailable)?;
+    Ok(id)
+}
+
+/// A definition must be a JSON object with a name; the rules themselves are up to the clients
+fn validate(definition: &str) -> Result<(), &'static str> {
+    if definition.len() > MAX_CHALLENGE_BYTES {
+        return Err("Challenge definition is too large");
+    }
+    let value: serde_json::Value =
+        serde_json::from_str(definition).map_err(|_| "Challenge definition is not valid JSON")?;
+    let name = value
+        .as_object()
+        .and_then(|object| object.get("name"))
+        .and_then(|name| name.as_str())
+        .map(str::trim)
+        .ok_or("Challenge definition needs a name")?;
+    if name.is_empty() || name.len() > MAX_CHALLENGE_NAME {
+        return Err("Challenge names must be 1 to 64 characters");
+    }
+    Ok(())
+}
+
+fn generate_challenge_id() -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let mut rng = rand::rng();
+    let suffix: String = (0..CHALLENGE_ID_LEN)
+        .map(|_| CHARSET[rng.random_range(0..CHARSET.len())] as char)
+        .collect();
+    format!("{}{}", SHARED_CHALLENGE_PREFIX, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_mode::GameMode;
+
+    fn in_memory() -> SharedChallenges {
+        SharedChallenges {
+            store: SqliteStore::in_memory(SharedChallenges::init),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_challenges_are_validated_and_fetched_by_id() {
+        let challenges = in_memory();
+        let upload =
+            |definition: &str| challenges.upload(Some("fil".to_string()), definition.to_string());
+        assert!(upload("not json").await.is_err());
+        assert!(upload(r#"{"rules": []}"#).await.is_err());
+        let too_large = format!(r#"{{"name": "big", "pad": "{}"}}"#, "x".repeat(MAX_CHALLENGE_BYTES));
+        assert!(upload(&too_large).await.is_err());
+
+        let definition = r#"{"name": "No Discards", "rules": {"discards": 0}}"#;
+        let id = upload(definition).await.unwrap();
+        assert!(id.starts_with(SHARED_CHALLENGE_PREFIX));
+        assert!(is_known(&id));
+        assert_eq!(upload(definition).await, Ok(id.clone()));
+        assert_eq!(challenges.get(id.clone()).await.as_deref(), Some(definition));
+        assert!(challenges.get("c_omelette_1".to_string()).await.is_none());
+
+        // Lobbies can only pick shared challenges that were uploaded
+        let mut options = GameMode::Attrition.get_default_options();
+        options.challenge = id;
+        assert!(options.validate().is_ok());
+        options.challenge = format!("{}NOTHERE1", SHARED_CHALLENGE_PREFIX);
+        assert_eq!(options.validate(), Err("No shared challenge with that id"));
+    }
+
+    #[tokio::test]
+    async fn test_only_verified_accounts_count_against_the_account_quota() {
+        let challenges = in_memory();
+        let definition = |n: i64| format!(r#"{{"name": "House rule {}"}}"#, n);
+        for n in 0..MAX_CHALLENGES_PER_ACCOUNT {
+            challenges.upload(Some("fil".to_string()), definition(n)).await.unwrap();
+        }
+        let over = challenges.upload(Some("fil".to_string()), definition(-1)).await;
+        assert_eq!(over, Err("You have uploaded too many challenges"));
+        // Claiming that account's id without proof doesn't use up its quota
+        assert!(challenges.upload(None, definition(-1)).await.is_ok());
+    }
+}
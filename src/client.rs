@@ -1,10 +1,27 @@
+use crate::messages::protocol::{self, LEGACY_PROTOCOL};
 use crate::messages::{
-    ClientToServer, CoordinatorMessage, LobbyJoinData, LobbyMessage, ServerToClient,
+    ActionTag, ClientFrame, ClientToServer, CoordinatorMessage, LobbyChannel, LobbyJoinData,
+    LobbyMessage, LobbySendError, ServerToClient, Subscriptions,
 };
+use crate::accounts;
+use crate::challenges::{MAX_CHALLENGE_BYTES, MAX_UPLOADS_PER_CONNECTION};
+use crate::config::CONFIG;
+use crate::game_mode::GameMode;
+use crate::invites::Invite;
+use crate::lobby::tutorial::TUTORIAL_RULESET;
+use crate::connections::ConnectionMessage;
+use crate::metrics::{METRICS, Metrics};
+use crate::scheduled_events;
+use crate::talisman_number::ScoreFormat;
+use crate::utils::now_millis;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::IoSlice;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicU8, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, error, info};
@@ -17,6 +34,23 @@ pub struct ClientProfile {
     pub username: String,
     pub colour: u8, // 0-255 instead of string
     pub mod_hash: String,
+    /// Stable account identifier provided by the client, if any
+    pub account_id: Option<String>,
+    /// `account_id` came with a valid token from the account service
+    #[serde(default)]
+    pub account_verified: bool,
+    /// Server-run practice opponent
+    #[serde(default)]
+    pub is_bot: bool,
+    /// Wants joker and deck previews as patches against the previous payload
+    #[serde(default)]
+    pub preview_patches: bool,
+    /// Wants lobby messages numbered, to resume after a quick reconnect
+    #[serde(default)]
+    pub resumable: bool,
+    /// Message classes the client opted out of, applied by every lobby it joins
+    #[serde(skip)]
+    pub subscriptions: Subscriptions,
 }
 impl Default for ClientProfile {
     fn default() -> Self {
@@ -25,17 +59,56 @@ impl Default for ClientProfile {
             username: "Guest".to_string(),
             colour: 0,
             mod_hash: "".to_string(),
+            account_id: None,
+            account_verified: false,
+            is_bot: false,
+            preview_patches: false,
+            resumable: false,
+            subscriptions: Subscriptions::default(),
         }
     }
-    
+
+}
+
+impl ClientProfile {
+    /// The account id, when the account service vouched for it
+    pub fn verified_account(&self) -> Option<&str> {
+        self.account_id.as_deref().filter(|_| self.account_verified)
+    }
+}
+
+/// Where a connection is in its life. Lobby cleanup goes through the coordinator
+/// once per stay in a lobby, whether the client left or its socket died.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientLifecycle {
+    /// Not in a lobby
+    Connected,
+    InLobby,
+    /// Asked to leave its lobby, the coordinator is cleaning up
+    Leaving,
+    /// Socket closed, nothing more goes out for this client
+    Disconnected,
 }
 
 #[derive(Debug, Clone)]
 pub struct Client {
-    pub lobby_channel: Option<mpsc::UnboundedSender<LobbyMessage>>,
+    pub lobby_channel: Option<LobbyChannel>,
     pub coordinator_channel: Option<mpsc::UnboundedSender<CoordinatorMessage>>,
     pub profile: ClientProfile,
     pub current_lobby: Option<String>,
+    /// Lobbies watched on top of `current_lobby`, by code
+    pub spectating: HashMap<String, LobbyChannel>,
+    pub latency_ms: Option<u32>,
+    /// Frames from this client carry a CRC32, see `NegotiateFraming`
+    pub frame_checksums: bool,
+    /// The client's `ScoreFormat`, shared with its writer
+    pub score_format: Arc<AtomicU8>,
+    /// Counts the lobbies entered, see `LobbyMessage::ClientJoin`
+    lobby_generation: u64,
+    last_pong_nonce: u32,
+    lifecycle: ClientLifecycle,
+    /// Challenges shared over this connection, the upload quota for accounts nobody vouched for
+    challenge_uploads: u32,
 }
 
 impl Client {
@@ -48,36 +121,165 @@ impl Client {
                 username: "Guest".to_string(),
                 colour: 0,
                 mod_hash: "".to_string(),
+                account_id: None,
+                account_verified: false,
+                is_bot: false,
+                preview_patches: false,
+                resumable: false,
+                subscriptions: Subscriptions::default(),
             },
             current_lobby: None,
+            spectating: HashMap::new(),
+            latency_ms: None,
+            frame_checksums: false,
+            score_format: Arc::new(AtomicU8::new(ScoreFormat::Native as u8)),
+            lobby_generation: 0,
+            last_pong_nonce: 0,
+            lifecycle: ClientLifecycle::Connected,
+            challenge_uploads: 0,
         }
     }
 
     pub fn send_to_coordinator(
         &self,
         message: CoordinatorMessage,
-    ) -> Result<(), mpsc::error::SendError<CoordinatorMessage>> {
+    ) -> Result<(), Box<mpsc::error::SendError<CoordinatorMessage>>> {
         if let Some(coordinator_tx) = &self.coordinator_channel {
-            coordinator_tx.send(message)
+            coordinator_tx.send(message).map_err(Box::new)
         } else {
-            Err(mpsc::error::SendError(message))
+            Err(Box::new(mpsc::error::SendError(message)))
         }
     }
-    pub fn send_to_lobby(
+    pub async fn send_to_lobby(
         &self,
         message: ClientToServer,
-    ) -> Result<(), mpsc::error::SendError<LobbyMessage>> {
+        seq: Option<u64>,
+    ) -> Result<(), LobbySendError> {
+        let lobby_message = LobbyMessage::client_action(
+            self.profile.id.clone(),
+            message,
+            seq,
+            self.lobby_generation,
+        );
         if let Some(lobby_tx) = &self.lobby_channel {
-            lobby_tx.send(LobbyMessage::client_action(
-                self.profile.id.clone(),
-                message,
-            ))
+            lobby_tx.send_action(lobby_message).await
+        } else {
+            Err(Box::new(mpsc::error::SendError(lobby_message)))
+        }
+    }
+
+    /// Stop watching a lobby; false when the client wasn't
+    pub fn stop_spectating(&mut self, lobby_code: &str) -> bool {
+        let Some(lobby_tx) = self.spectating.remove(lobby_code) else {
+            return false;
+        };
+        let _ = lobby_tx.send_control(LobbyMessage::SpectatorLeave {
+            client_id: self.profile.id.clone(),
+        });
+        true
+    }
+
+    /// Generation to join the next lobby under
+    fn next_lobby_generation(&self) -> u64 {
+        self.lobby_generation + 1
+    }
+
+    /// Become a player in `lobby_code`, no longer watching it from the outside. The
+    /// channel, code and generation change together, so nothing sent from here on
+    /// carries the old lobby's generation.
+    fn entered_lobby(&mut self, lobby_code: String, lobby_tx: LobbyChannel, generation: u64) {
+        self.stop_spectating(&lobby_code);
+        self.lobby_channel = Some(lobby_tx);
+        self.current_lobby = Some(lobby_code);
+        self.lobby_generation = generation;
+        self.lifecycle = ClientLifecycle::InLobby;
+    }
+
+    fn left_lobby(&mut self) {
+        self.current_lobby = None;
+        self.lobby_channel = None;
+        self.lobby_generation += 1;
+        if self.lifecycle == ClientLifecycle::Leaving {
+            self.lifecycle = ClientLifecycle::Connected;
+        }
+    }
+
+    /// Cleanup the coordinator owes the client's lobby; `None` unless the client is in
+    /// one, so a leave followed by a dropped socket only cleans up once
+    fn leave_lobby_message(&mut self, connection_lost: bool) -> Option<CoordinatorMessage> {
+        if self.lifecycle != ClientLifecycle::InLobby {
+            return None;
+        }
+        self.lifecycle = if connection_lost {
+            ClientLifecycle::Disconnected
         } else {
-            Err(mpsc::error::SendError(LobbyMessage::client_action(
-                self.profile.id.clone(),
-                message,
-            )))
+            ClientLifecycle::Leaving
+        };
+        Some(CoordinatorMessage::ClientDisconnected {
+            client_id: self.profile.id.clone(),
+            coordinator_tx: self.coordinator_channel.clone()?,
+            connection_lost,
+        })
+    }
+
+    /// The socket closed: stop watching lobbies, leave the one the client is in unless
+    /// it already did, and go offline
+    fn close_connection(&mut self) {
+        let spectated: Vec<String> = self.spectating.keys().cloned().collect();
+        for lobby_code in spectated {
+            self.stop_spectating(&lobby_code);
         }
+        if let Some(leave) = self.leave_lobby_message(true) {
+            let _ = self.send_to_coordinator(leave);
+        }
+        self.lifecycle = ClientLifecycle::Disconnected;
+        let _ = self.send_to_coordinator(CoordinatorMessage::ClientOffline {
+            client_id: self.profile.id.clone(),
+        });
+    }
+
+    /// Catch up with a lobby the server moved this client to after its own closed;
+    /// false when the client isn't in one
+    async fn follow_merged_lobby(&mut self) -> bool {
+        if !self.lobby_channel.as_ref().is_some_and(LobbyChannel::is_closed) {
+            return false;
+        }
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let find = CoordinatorMessage::FindClientLobby {
+            client_id: self.profile.id.clone(),
+            reply_tx,
+        };
+        if self.send_to_coordinator(find).is_err() {
+            return false;
+        }
+        match reply_rx.await {
+            Ok(Some(LobbyJoinData {
+                lobby_code,
+                lobby_tx,
+            })) => {
+                self.entered_lobby(lobby_code, lobby_tx, self.lobby_generation);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record a pong and forward the measured RTT to the lobby; stale pongs are ignored
+    pub fn record_pong(&mut self, nonce: u32, server_time: u64) -> Option<u32> {
+        if nonce <= self.last_pong_nonce {
+            return None;
+        }
+        self.last_pong_nonce = nonce;
+        let rtt_ms = now_millis().saturating_sub(server_time).min(u32::MAX as u64) as u32;
+        self.latency_ms = Some(rtt_ms);
+
+        if let Some(lobby_tx) = &self.lobby_channel {
+            lobby_tx.send_lossy(LobbyMessage::LatencyUpdate {
+                client_id: self.profile.id.clone(),
+                rtt_ms,
+            });
+        }
+        Some(rtt_ms)
     }
 }
 
@@ -87,6 +289,9 @@ enum ReadActionError {
     Io(std::io::Error),
     EmptyFrame,
     Oversized { len: usize, max: usize },
+    ChecksumMismatch,
+    /// Well-formed frame naming an action this server doesn't know
+    Unsupported(String),
     Malformed(rmp_serde::decode::Error),
 }
 
@@ -98,6 +303,8 @@ impl std::fmt::Display for ReadActionError {
             ReadActionError::Oversized { len, max } => {
                 write!(f, "oversized frame {len} > {max}")
             }
+            ReadActionError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            ReadActionError::Unsupported(action) => write!(f, "unsupported action {action}"),
             ReadActionError::Malformed(e) => write!(f, "malformed message: {e}"),
         }
     }
@@ -106,9 +313,137 @@ impl std::fmt::Display for ReadActionError {
 impl std::error::Error for ReadActionError {}
 
 const MAX_MESSAGE_SIZE: usize = 256 * 1024; // 256 KiB safety cap
+/// Cap on a payload sent in chunks; only deck and joker previews get past `MAX_MESSAGE_SIZE`
+const MAX_CHUNKED_SIZE: usize = 4 * 1024 * 1024;
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+/// Lobbies one client may spectate at once
+const MAX_SPECTATED_LOBBIES: usize = 4;
+/// A client hears about its bad frames at most this often
+const PROTOCOL_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+/// Distinct unsupported actions named in one warning
+const MAX_LISTED_UNSUPPORTED: usize = 16;
+
+/// Bad frames from one client, answered with an occasional `protocolWarnings`
+/// instead of an error per frame so a broken client isn't flooded with replies
+#[derive(Debug, Default)]
+struct BadFrames {
+    /// Empty or unparseable since the last warning
+    malformed: u32,
+    /// Unsupported actions since the last warning
+    unsupported: u32,
+    unsupported_actions: Vec<String>,
+    /// Empty or unparseable frames over the whole connection. Unsupported actions
+    /// don't count, they mean a newer client rather than a broken one.
+    errors: u32,
+    last_warning: Option<Instant>,
+}
+
+impl BadFrames {
+    fn malformed(&mut self) {
+        self.malformed += 1;
+        self.errors += 1;
+    }
+
+    fn unsupported(&mut self, action: String) {
+        self.unsupported += 1;
+        if self.unsupported_actions.len() < MAX_LISTED_UNSUPPORTED
+            && !self.unsupported_actions.contains(&action)
+        {
+            self.unsupported_actions.push(action);
+        }
+    }
+
+    /// The client sent more broken frames than the server puts up with (0 means no limit)
+    fn exceeded(&self, max_bad_frames: u32) -> bool {
+        max_bad_frames > 0 && self.errors >= max_bad_frames
+    }
+
+    /// Everything since the last warning, once one is due (or right away when `force`d)
+    fn take_warning(&mut self, now: Instant, force: bool) -> Option<ServerToClient> {
+        if self.malformed == 0 && self.unsupported == 0 {
+            return None;
+        }
+        let due = self
+            .last_warning
+            .is_none_or(|last| now.duration_since(last) >= PROTOCOL_WARNING_INTERVAL);
+        if !due && !force {
+            return None;
+        }
+        self.last_warning = Some(now);
+        Some(ServerToClient::ProtocolWarnings {
+            malformed: std::mem::take(&mut self.malformed),
+            unsupported: std::mem::take(&mut self.unsupported),
+            unsupported_actions: std::mem::take(&mut self.unsupported_actions),
+        })
+    }
+}
+
+/// A frame arriving in `chunk`s, reassembled once `chunkEnd` comes
+#[derive(Debug)]
+struct ChunkedFrame {
+    id: u32,
+    total_len: usize,
+    buf: Vec<u8>,
+}
+
+impl ChunkedFrame {
+    /// Take one chunk frame. Other actions pass straight through, a finished frame comes
+    /// back as the action it carries; anything off abandons the transfer.
+    fn accept(
+        pending: &mut Option<Self>,
+        action: ClientToServer,
+        version: u32,
+    ) -> Result<Option<ClientToServer>, &'static str> {
+        match action {
+            ClientToServer::ChunkBegin { id, total_len } => {
+                let total_len = total_len as usize;
+                *pending = None;
+                if total_len == 0 || total_len > MAX_CHUNKED_SIZE {
+                    return Err("Chunked message too large");
+                }
+                *pending = Some(Self {
+                    id,
+                    total_len,
+                    buf: Vec::new(),
+                });
+                Ok(None)
+            }
+            ClientToServer::Chunk { id, data } => {
+                let Some(chunked) = pending.as_mut().filter(|chunked| chunked.id == id) else {
+                    return Err("No chunked message in progress");
+                };
+                if chunked.buf.len() + data.len() > chunked.total_len {
+                    *pending = None;
+                    return Err("Chunks exceed the announced size");
+                }
+                chunked.buf.extend_from_slice(&data);
+                Ok(None)
+            }
+            ClientToServer::ChunkEnd { id } => {
+                let Some(chunked) = pending.take_if(|chunked| chunked.id == id) else {
+                    return Err("No chunked message in progress");
+                };
+                if chunked.buf.len() != chunked.total_len {
+                    return Err("Chunked message is incomplete");
+                }
+                let frame = parse_frame(&chunked.buf, version)
+                    .map_err(|_| "Chunked message is malformed")?;
+                if !frame.action.is_chunkable() {
+                    return Err("Only deck and joker payloads may be chunked");
+                }
+                Ok(Some(frame.action))
+            }
+            action => Ok(Some(action)),
+        }
+    }
+}
 
 // Read one action from the socket; uses '?' for IO steps
-async fn read_client_action(reader: &mut OwnedReadHalf) -> Result<ClientToServer, ReadActionError> {
+async fn read_client_action<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    checksums: bool,
+    version: u32,
+) -> Result<ClientFrame, ReadActionError> {
     let mut length_bytes = [0u8; 4];
     reader
         .read_exact(&mut length_bytes)
@@ -124,12 +459,36 @@ async fn read_client_action(reader: &mut OwnedReadHalf) -> Result<ClientToServer
             max: MAX_MESSAGE_SIZE,
         });
     }
+    let mut checksum_bytes = [0u8; 4];
+    if checksums {
+        reader
+            .read_exact(&mut checksum_bytes)
+            .await
+            .map_err(ReadActionError::Io)?;
+    }
     let mut buf = vec![0u8; length];
     reader
         .read_exact(&mut buf)
         .await
         .map_err(ReadActionError::Io)?;
-    rmp_serde::from_slice::<ClientToServer>(&buf).map_err(ReadActionError::Malformed)
+    if checksums && u32::from_be_bytes(checksum_bytes) != crc32fast::hash(&buf) {
+        return Err(ReadActionError::ChecksumMismatch);
+    }
+    parse_frame(&buf, version)
+}
+
+/// Decode a frame's payload, renamed actions of older clients included
+fn parse_frame(buf: &[u8], version: u32) -> Result<ClientFrame, ReadActionError> {
+    let frame = rmp_serde::from_slice::<ClientFrame>(buf).map_err(ReadActionError::Malformed)?;
+    if let ClientToServer::Unknown = frame.action {
+        let action = rmp_serde::from_slice::<ActionTag>(buf)
+            .map(|tag| tag.action)
+            .unwrap_or_default();
+        let version = frame.v.map_or(version, protocol::negotiate);
+        return protocol::upgrade_frame(buf, &action, version)
+            .ok_or(ReadActionError::Unsupported(action));
+    }
+    Ok(frame)
 }
 
 /// Simple client handler using message passing
@@ -138,30 +497,103 @@ pub async fn handle_client(
     socket_writer: OwnedWriteHalf,
     addr: SocketAddr,
     coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+    connections_tx: mpsc::UnboundedSender<ConnectionMessage>,
 ) {
     // Create channels for this client - use Vec<u8> for MessagePack compatibility
     let (writer_tx, writer_rx) = mpsc::unbounded_channel::<Arc<ServerToClient>>();
 
+    Metrics::incr(&METRICS.connected_clients);
+    let max_clients = CONFIG.get().max_clients as u64;
+    if max_clients > 0 && METRICS.connected_clients.load(Ordering::Relaxed) > max_clients {
+        Metrics::decr(&METRICS.connected_clients);
+        info!("Refusing client from {}: connection limit reached", addr);
+        let _ = writer_tx.send(Arc::new(ServerToClient::ServerFull {
+            message: "Server is full".to_string(),
+            queue_position: None,
+        }));
+        drop(writer_tx);
+        let protocol = Arc::new(AtomicU32::new(LEGACY_PROTOCOL));
+        let score_format = Arc::new(AtomicU8::new(ScoreFormat::Native as u8));
+        handle_client_writer(socket_writer, writer_rx, protocol, score_format).await;
+        return;
+    }
+
     let mut client: Client = Client::new(Some(coordinator_tx.clone()));
     let client_id = client.profile.id.clone();
 
     info!("Client {} connected from {}", client_id, addr);
 
+    let last_activity = Arc::new(AtomicU64::new(now_millis()));
+    let (close_tx, mut close_rx) = oneshot::channel();
+    let _ = connections_tx.send(ConnectionMessage::Opened {
+        client_id: client_id.clone(),
+        addr,
+        last_activity: Arc::clone(&last_activity),
+        close_tx,
+    });
+
     // Send initial handshake
     let connected_response = Arc::new(ServerToClient::connected(client_id.clone()));
     let _ = writer_tx.send(connected_response);
+    let events = scheduled_events::active_events();
+    if !events.is_empty() {
+        let _ = writer_tx.send(Arc::new(ServerToClient::ServerEvents { events }));
+    }
+
+    // Legacy until the client announces a version, shared so the writer can follow
+    let protocol = Arc::new(AtomicU32::new(LEGACY_PROTOCOL));
 
     // Spawn task to handle writing to the client socket
-    let write_task = tokio::spawn(handle_client_writer(socket_writer, writer_rx));
+    let write_task = tokio::spawn(handle_client_writer(
+        socket_writer,
+        writer_rx,
+        Arc::clone(&protocol),
+        Arc::clone(&client.score_format),
+    ));
+    let ping_task = tokio::spawn(handle_client_pinger(writer_tx.clone()));
 
     let mut reader = socket_reader;
+    // Newest sequence id read, so corrupted frames can be resent from there
+    let mut last_seq: Option<u64> = None;
+    let mut bad_frames = BadFrames::default();
+    let mut chunked: Option<ChunkedFrame> = None;
 
     // ---- Read loop using helper ----
     loop {
-        match read_client_action(&mut reader).await {
-            Ok(action) => {
+        let frame = tokio::select! {
+            frame = read_client_action(
+                &mut reader,
+                client.frame_checksums,
+                protocol.load(Ordering::Relaxed),
+            ) => frame,
+            Ok(()) = &mut close_rx => {
+                info!("Client {} timed out", client_id);
+                break;
+            }
+        };
+        last_activity.store(now_millis(), Ordering::Relaxed);
+        match frame {
+            Ok(ClientFrame { v, seq, action }) => {
+                // Replies from here on use the announced version
+                if let Some(v) = v {
+                    protocol.store(protocol::negotiate(v), Ordering::Relaxed);
+                }
+                if seq.is_some() {
+                    last_seq = seq;
+                }
+                let version = protocol.load(Ordering::Relaxed);
+                let action = match ChunkedFrame::accept(&mut chunked, action, version) {
+                    Ok(Some(action)) => action,
+                    Ok(None) => continue,
+                    Err(message) => {
+                        debug!("Client {} chunked message refused: {}", client_id, message);
+                        let _ = writer_tx.send(Arc::new(ServerToClient::error(message)));
+                        continue;
+                    }
+                };
                 if let Err(e) =
-                    handle_client_action(client_id.clone(), action, &mut client, &writer_tx).await
+                    handle_client_action(client_id.clone(), action, seq, &mut client, &writer_tx)
+                        .await
                 {
                     error!("Action error for client {}: {}", client_id, e);
                     let _ = writer_tx.send(Arc::new(ServerToClient::error(&format!(
@@ -171,9 +603,8 @@ pub async fn handle_client(
                 }
             }
             Err(ReadActionError::EmptyFrame) => {
-                error!("Client {} sent empty frame", client_id);
-                let _ = writer_tx.send(Arc::new(ServerToClient::error("Empty message")));
-                continue;
+                debug!("Client {} sent empty frame", client_id);
+                bad_frames.malformed();
             }
             Err(ReadActionError::Oversized { len, max }) => {
                 error!(
@@ -183,48 +614,163 @@ pub async fn handle_client(
                 let _ = writer_tx.send(Arc::new(ServerToClient::error("Message too large")));
                 break; // Protocol abuse -> disconnect
             }
+            Err(ReadActionError::ChecksumMismatch) => {
+                // The length prefix was intact, so the stream is still in sync
+                info!("Client {} sent a corrupted frame", client_id);
+                let _ = writer_tx.send(Arc::new(ServerToClient::FrameCorrupted { last_seq }));
+            }
+            Err(ReadActionError::Unsupported(action)) => {
+                // Newer clients during a rollout, not an error on their side
+                info!("Client {} sent unsupported action '{}'", client_id, action);
+                Metrics::incr(&METRICS.unsupported_actions);
+                bad_frames.unsupported(action);
+            }
             Err(ReadActionError::Malformed(e)) => {
-                error!("Failed to parse MessagePack from {}: {}", addr, e);
-                let _ = writer_tx.send(Arc::new(ServerToClient::error("Malformed message")));
-                continue; // Allow next messages
+                debug!("Failed to parse MessagePack from {}: {}", addr, e);
+                bad_frames.malformed();
             }
             Err(ReadActionError::Io(e)) => {
                 info!("Client {} disconnected: {}", client_id, e);
                 break;
             }
         }
+
+        let exceeded = bad_frames.exceeded(CONFIG.get().max_bad_frames);
+        if let Some(warning) = bad_frames.take_warning(Instant::now(), exceeded) {
+            let _ = writer_tx.send(Arc::new(warning));
+        }
+        if exceeded {
+            info!("Client {} sent too many bad frames", client_id);
+            let _ = writer_tx.send(Arc::new(ServerToClient::error("Too many malformed messages")));
+            break;
+        }
     }
 
     // Cleanup on disconnect
-    let _ = coordinator_tx.send(CoordinatorMessage::ClientDisconnected {
+    client.close_connection();
+    let _ = connections_tx.send(ConnectionMessage::Closed {
         client_id: client_id.clone(),
-        coordinator_tx: coordinator_tx.clone(),
     });
 
     // Cancel background tasks
     write_task.abort();
+    ping_task.abort();
+    Metrics::decr(&METRICS.connected_clients);
 
     debug!("Client cleanup complete");
 }
 
+/// Outgoing messages waiting for the socket, bulk payloads behind everything else.
+/// Each lane keeps its own order.
+#[derive(Default)]
+struct WriterLanes {
+    priority: VecDeque<Arc<ServerToClient>>,
+    bulk: VecDeque<Arc<ServerToClient>>,
+}
+
+impl WriterLanes {
+    fn push(&mut self, message: Arc<ServerToClient>) {
+        if message.is_bulk() {
+            self.bulk.push_back(message);
+        } else {
+            self.priority.push_back(message);
+        }
+    }
+
+    fn pop(&mut self) -> Option<Arc<ServerToClient>> {
+        self.priority.pop_front().or_else(|| self.bulk.pop_front())
+    }
+}
+
 /// Handle writing messages to the client socket
 async fn handle_client_writer(
     mut writer: OwnedWriteHalf,
     mut rx: mpsc::UnboundedReceiver<Arc<ServerToClient>>,
+    protocol: Arc<AtomicU32>,
+    score_format: Arc<AtomicU8>,
 ) {
-    while let Some(message) = rx.recv().await {
-        // Send 4-byte length header + MessagePack data
-        let buff = message.to_msgpack();
+    let mut checksums = false;
+    let mut lanes = WriterLanes::default();
+    let mut header = [0u8; FRAME_HEADER_MAX];
+    loop {
+        // Sort in everything that queued up during the last write before picking the next one
+        while let Ok(message) = rx.try_recv() {
+            lanes.push(message);
+        }
+        let message = match lanes.pop() {
+            Some(message) => message,
+            None => match rx.recv().await {
+                Some(message) => message,
+                None => break,
+            },
+        };
+        let payload = protocol::encode_shared(
+            &message,
+            protocol.load(Ordering::Relaxed),
+            ScoreFormat::from_u8(score_format.load(Ordering::Relaxed)),
+        );
+        let header = frame_header(&payload, checksums, &mut header);
+        if let Err(e) = write_frame(&mut writer, header, &payload).await {
+            error!("Failed to write frame: {}", e);
+            break;
+        }
+        // The confirmation itself still goes out in the old framing
+        if let ServerToClient::FramingNegotiated { checksums: enabled } = *message {
+            checksums = enabled;
+        }
+    }
+}
 
-        let length = buff.len() as u32;
-        let length_bytes = length.to_be_bytes();
+/// Length prefix plus CRC32
+const FRAME_HEADER_MAX: usize = 8;
 
-        if let Err(e) = writer.write_all(&length_bytes).await {
-            error!("Failed to write length header: {}", e);
-            break;
+/// Frames are a 4-byte length header, the optional CRC32 of the payload, then the
+/// MessagePack payload. Fills `buf` with the header and returns the used part.
+fn frame_header<'a>(payload: &[u8], checksums: bool, buf: &'a mut [u8; FRAME_HEADER_MAX]) -> &'a [u8] {
+    buf[..4].copy_from_slice(&(payload.len() as u32).to_be_bytes());
+    if !checksums {
+        return &buf[..4];
+    }
+    buf[4..].copy_from_slice(&crc32fast::hash(payload).to_be_bytes());
+    &buf[..]
+}
+
+/// Write header and payload together without copying them into one buffer, usually
+/// in a single vectored write
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    header: &[u8],
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let total = header.len() + payload.len();
+    let mut written = 0;
+    while written < total {
+        let n = if written < header.len() {
+            let parts = [IoSlice::new(&header[written..]), IoSlice::new(payload)];
+            writer.write_vectored(&parts).await?
+        } else {
+            writer.write(&payload[written - header.len()..]).await?
+        };
+        if n == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
         }
-        if let Err(e) = writer.write_all(&buff).await {
-            error!("Failed to write MessagePack data: {}", e);
+        written += n;
+    }
+    Ok(())
+}
+
+/// Periodically ping the client so RTT can be measured from its pongs
+async fn handle_client_pinger(tx: mpsc::UnboundedSender<Arc<ServerToClient>>) {
+    let mut interval = tokio::time::interval(PING_INTERVAL);
+    let mut nonce: u32 = 0;
+    loop {
+        interval.tick().await;
+        nonce += 1;
+        let ping = Arc::new(ServerToClient::Ping {
+            nonce,
+            server_time: now_millis(),
+        });
+        if tx.send(ping).is_err() {
             break;
         }
     }
@@ -234,13 +780,35 @@ async fn handle_client_writer(
 async fn handle_client_action(
     client_id: String,
     action: ClientToServer,
+    seq: Option<u64>,
     client: &mut Client,
     response_tx: &mpsc::UnboundedSender<Arc<ServerToClient>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     match action {
-        ClientToServer::KeepAlive {} => {
-            // Simple keep-alive response
-            let response = Arc::new(ServerToClient::KeepAliveResponse {});
+        ClientToServer::KeepAlive { nonce } => {
+            // Echo the nonce so clients can match responses to requests
+            let response = Arc::new(ServerToClient::KeepAliveResponse {
+                nonce,
+                server_time: now_millis(),
+            });
+            response_tx.send(response)?;
+        }
+        ClientToServer::NegotiateFraming { checksums } => {
+            // Frames read after this one use the new framing
+            client.frame_checksums = checksums;
+            response_tx.send(Arc::new(ServerToClient::FramingNegotiated { checksums }))?;
+        }
+        ClientToServer::Pong { nonce, server_time } => {
+            if let Some(rtt_ms) = client.record_pong(nonce, server_time) {
+                debug!("Client {} RTT: {}ms", client_id, rtt_ms);
+            }
+        }
+        ClientToServer::GetServerTime { client_time } => {
+            // Echo the client's timestamp so it can estimate RTT and clock offset
+            let response = Arc::new(ServerToClient::ServerTime {
+                client_time,
+                server_time: now_millis(),
+            });
             response_tx.send(response)?;
         }
         ClientToServer::Version { version } => {
@@ -252,25 +820,66 @@ async fn handle_client_action(
             username: new_username,
             colour: new_colour,
             mod_hash: new_mod_hash,
+            account_id,
+            account_token,
+            preview_patches,
+            resumable,
+            score_format,
         } => {
             client.profile.username = new_username.clone();
             client.profile.colour = new_colour as u8; // Convert i32 to u8
             client.profile.mod_hash = new_mod_hash.clone();
+            client.profile.account_verified = account_id
+                .as_deref()
+                .zip(account_token.as_deref())
+                .is_some_and(|(account_id, token)| accounts::verify(account_id, token));
+            client.profile.account_id = account_id;
+            client.profile.preview_patches = preview_patches;
+            client.profile.resumable = resumable;
+            client.score_format.store(score_format as u8, Ordering::Relaxed);
 
             debug!(
                 "Client {} set client data: username={}, colour={}, mod_hash={}",
                 client_id, new_username, new_colour, new_mod_hash
             );
+            // Presence is best effort, the client works without it
+            let _ = client.send_to_coordinator(CoordinatorMessage::ClientRegistered {
+                client_id,
+                client_profile: client.profile.clone(),
+            });
         }
-        ClientToServer::CreateLobby { ruleset, game_mode } => {
+        ClientToServer::SetSubscriptions {
+            ref subscribe,
+            ref unsubscribe,
+        } => {
+            client.profile.subscriptions.update(subscribe, unsubscribe);
+            // The lobby's broadcaster does the filtering and keeps its own copy
+            for lobby_tx in client.spectating.values() {
+                let _ = lobby_tx.send_control(LobbyMessage::SpectatorSubscriptions {
+                    client_id: client_id.clone(),
+                    subscriptions: client.profile.subscriptions,
+                });
+            }
+            if client.lobby_channel.is_some() {
+                client.send_to_lobby(action, seq).await?;
+            }
+        }
+        ClientToServer::CreateLobby { .. } | ClientToServer::StartTutorial {} => {
+            let (ruleset, game_mode, tutorial) = match action {
+                ClientToServer::CreateLobby { ruleset, game_mode } => (ruleset, game_mode, false),
+                _ => (TUTORIAL_RULESET.to_string(), GameMode::Attrition, true),
+            };
             let (tx, rx) = oneshot::channel::<LobbyJoinData>();
+            let lobby_generation = client.next_lobby_generation();
             client.send_to_coordinator(CoordinatorMessage::CreateLobby {
                 client_id,
                 ruleset,
                 game_mode,
                 client_response_tx: response_tx.clone(),
                 client_profile: client.profile.clone(),
+                lobby_generation,
                 request_tx: tx,
+                tutorial,
             })?;
 
             if let Ok(LobbyJoinData {
@@ -278,21 +887,48 @@ async fn handle_client_action(
                 lobby_tx,
             }) = rx.await
             {
-                client.lobby_channel = Some(lobby_tx);
-                client.current_lobby = Some(lobby_code);
+                client.entered_lobby(lobby_code, lobby_tx, lobby_generation);
             } else {
                 let error_response = Arc::new(ServerToClient::error("Failed to create lobby"));
                 response_tx.send(error_response)?;
             }
         }
-        ClientToServer::JoinLobby { code } => {
+        ClientToServer::JoinLobby { .. }
+        | ClientToServer::ResumeLobby { .. }
+        | ClientToServer::JoinInvite { .. } => {
+            let (code, resume_after, invite, reconnect_token) = match action {
+                ClientToServer::ResumeLobby {
+                    code,
+                    last_seq,
+                    reconnect_token,
+                } => (code, Some(last_seq), None, Some(reconnect_token)),
+                ClientToServer::JoinLobby {
+                    code,
+                    reconnect_token,
+                } => (code, None, None, reconnect_token),
+                ClientToServer::JoinInvite { token } => {
+                    match Invite::verify(&token, now_millis() / 1000) {
+                        Ok(invite) => (invite.lobby_code.clone(), None, Some(invite), None),
+                        Err(message) => {
+                            response_tx.send(Arc::new(ServerToClient::error(message)))?;
+                            return Ok(());
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            };
             let (tx, rx) = oneshot::channel::<LobbyJoinData>();
+            let lobby_generation = client.next_lobby_generation();
             client.send_to_coordinator(CoordinatorMessage::JoinLobby {
                 client_id,
                 lobby_code: code,
                 client_response_tx: response_tx.clone(),
                 client_profile: client.profile.clone(),
+                lobby_generation,
                 request_tx: tx,
+                resume_after,
+                invite,
+                reconnect_token,
             })?;
 
             if let Ok(LobbyJoinData {
@@ -300,42 +936,142 @@ async fn handle_client_action(
                 lobby_tx,
             }) = rx.await
             {
-                client.lobby_channel = Some(lobby_tx);
-                client.current_lobby = Some(lobby_code);
+                client.entered_lobby(lobby_code, lobby_tx, lobby_generation);
             } else {
                 let error_response = Arc::new(ServerToClient::error("Failed to join lobby"));
                 response_tx.send(error_response)?;
             }
         }
+        ClientToServer::RejoinLast { reconnect_token } => {
+            let (tx, rx) = oneshot::channel::<LobbyJoinData>();
+            let lobby_generation = client.next_lobby_generation();
+            client.send_to_coordinator(CoordinatorMessage::RejoinLast {
+                client_id,
+                client_response_tx: response_tx.clone(),
+                client_profile: client.profile.clone(),
+                lobby_generation,
+                request_tx: tx,
+                reconnect_token,
+            })?;
+
+            // The coordinator already told the client why when there is nothing to rejoin
+            if let Ok(LobbyJoinData {
+                lobby_code,
+                lobby_tx,
+            }) = rx.await
+            {
+                client.entered_lobby(lobby_code, lobby_tx, lobby_generation);
+            }
+        }
+        ClientToServer::SpectateLobby { code } => {
+            client.spectating.retain(|_, lobby_tx| !lobby_tx.is_closed());
+            let refusal = if client.current_lobby.as_ref() == Some(&code) {
+                Some("You are playing in this lobby")
+            } else if client.spectating.contains_key(&code) {
+                Some("Already spectating this lobby")
+            } else if client.spectating.len() >= MAX_SPECTATED_LOBBIES {
+                Some("Spectating too many lobbies")
+            } else {
+                None
+            };
+            if let Some(message) = refusal {
+                response_tx.send(Arc::new(ServerToClient::error(message)))?;
+                return Ok(());
+            }
+            let (tx, rx) = oneshot::channel::<LobbyJoinData>();
+            client.send_to_coordinator(CoordinatorMessage::SpectateLobby {
+                client_id,
+                lobby_code: code,
+                request_tx: tx,
+                client_response_tx: response_tx.clone(),
+                subscriptions: client.profile.subscriptions,
+            })?;
+            // The client was already told why when it can't spectate
+            if let Ok(LobbyJoinData {
+                lobby_code,
+                lobby_tx,
+            }) = rx.await
+            {
+                client.spectating.insert(lobby_code, lobby_tx);
+            }
+        }
+        ClientToServer::StopSpectating { code } => {
+            if !client.stop_spectating(&code) {
+                response_tx.send(Arc::new(ServerToClient::error("Not spectating this lobby")))?;
+            }
+        }
+        ClientToServer::ClaimVanityCode { .. } | ClientToServer::ReleaseVanityCode {} => {
+            let Some(account_id) = client.profile.verified_account().map(str::to_string) else {
+                response_tx.send(Arc::new(ServerToClient::error(
+                    "Vanity codes need a verified account",
+                )))?;
+                return Ok(());
+            };
+            let code = match action {
+                ClientToServer::ClaimVanityCode { code } => Some(code),
+                _ => None,
+            };
+            client.send_to_coordinator(CoordinatorMessage::SetVanityCode {
+                account_id,
+                code,
+                client_response_tx: response_tx.clone(),
+            })?;
+        }
+        ClientToServer::UploadChallenge { definition } => {
+            // Refuse oversized uploads here instead of shipping them to the coordinator
+            if definition.len() > MAX_CHALLENGE_BYTES {
+                response_tx.send(Arc::new(ServerToClient::error(
+                    "Challenge definition is too large",
+                )))?;
+                return Ok(());
+            }
+            if client.challenge_uploads >= MAX_UPLOADS_PER_CONNECTION {
+                response_tx.send(Arc::new(ServerToClient::error(
+                    "You have uploaded too many challenges",
+                )))?;
+                return Ok(());
+            }
+            client.challenge_uploads += 1;
+            client.send_to_coordinator(CoordinatorMessage::UploadChallenge {
+                account_id: client.profile.verified_account().map(str::to_string),
+                definition,
+                client_response_tx: response_tx.clone(),
+            })?;
+        }
+        ClientToServer::ListLobbies { region } => {
+            client.send_to_coordinator(CoordinatorMessage::ListOpenLobbies {
+                region,
+                client_response_tx: response_tx.clone(),
+            })?;
+        }
+        ClientToServer::GetChallenge { id } => {
+            client.send_to_coordinator(CoordinatorMessage::GetChallenge {
+                id,
+                client_response_tx: response_tx.clone(),
+            })?;
+        }
         ClientToServer::LeaveLobby {} => {
             info!("Client {} leaving lobby", client_id);
-            match client.lobby_channel.as_ref() {
-                Some(_) => {
-                    if let Some(coordinator_tx) = client.coordinator_channel.clone() {
-                        client.send_to_coordinator(CoordinatorMessage::ClientDisconnected {
-                            client_id: client_id.clone(),
-                            coordinator_tx: coordinator_tx.clone(),
-                        })?;
-                    } else {
-                        error!(
-                            "Coordinator channel missing for client {} when leaving lobby",
-                            client_id
-                        );
-                    }
-                }
+            match client.leave_lobby_message(false) {
+                Some(leave) => client.send_to_coordinator(leave)?,
                 None => {
-                    error!(
-                        "Lobby channel missing for client {} when leaving lobby",
-                        client_id
-                    );
+                    error!("Client {} is not in a lobby to leave", client_id);
                 }
             }
 
-            client.current_lobby = None;
-            client.lobby_channel = None;
+            client.left_lobby();
         }
         _ => {
-            client.send_to_lobby(action)?;
+            if let Err(refused) = client.send_to_lobby(action, seq).await
+                && let mpsc::error::SendError(LobbyMessage::ClientAction { action, seq, .. }) =
+                    *refused
+            {
+                // Our lobby may have merged into another one, the action goes there
+                if !client.follow_merged_lobby().await {
+                    return Err("Lobby closed".into());
+                }
+                client.send_to_lobby(action, seq).await?;
+            }
         }
     }
     Ok(())
@@ -352,7 +1088,7 @@ mod tests{
         let mut client = Client::new(None);
         let (tx, mut rx) = mpsc::unbounded_channel();
         let client_id = client.profile.id.clone();
-        let _ = handle_client_action(client_id, action, &mut client, &tx).await;
+        let _ = handle_client_action(client_id, action, None, &mut client, &tx).await;
         let mut responses = Vec::new();
         while let Ok(msg) = rx.try_recv() {
             responses.push(msg);
@@ -360,10 +1096,201 @@ mod tests{
         (client, responses)
     }
 
+    fn encode_frame(payload: &[u8], checksums: bool) -> Vec<u8> {
+        let mut header = [0u8; FRAME_HEADER_MAX];
+        let mut frame = frame_header(payload, checksums, &mut header).to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_frames_survive_partial_writes() {
+        let payload = rmp_serde::to_vec_named(&ClientFrame {
+            v: None,
+            seq: Some(9),
+            action: ClientToServer::LeaveLobby {},
+        })
+        .unwrap();
+        // A tiny pipe only takes a few bytes per write
+        let (mut writer, mut reader) = tokio::io::duplex(3);
+        let mut header = [0u8; FRAME_HEADER_MAX];
+        let header = frame_header(&payload, true, &mut header);
+        let mut received = Vec::new();
+        let (written, read) = tokio::join!(write_frame(&mut writer, header, &payload), async {
+            let mut buf = vec![0u8; header.len() + payload.len()];
+            let read = reader.read_exact(&mut buf).await;
+            received = buf;
+            read
+        });
+        written.unwrap();
+        read.unwrap();
+        assert_eq!(received, encode_frame(&payload, true));
+    }
+
+    #[test]
+    fn test_bulk_payloads_wait_behind_other_messages() {
+        let mut lanes = WriterLanes::default();
+        let deck = ServerToClient::ReceivePlayerDeck {
+            player_id: "p2".to_string(),
+            deck: "x".repeat(10_000),
+        };
+        lanes.push(Arc::new(deck));
+        lanes.push(Arc::new(ServerToClient::StartBlind { server_time: 1 }));
+        lanes.push(Arc::new(ServerToClient::Ping { nonce: 1, server_time: 2 }));
+
+        assert!(matches!(*lanes.pop().unwrap(), ServerToClient::StartBlind { .. }));
+        assert!(matches!(*lanes.pop().unwrap(), ServerToClient::Ping { .. }));
+        assert!(lanes.pop().unwrap().is_bulk());
+        assert!(lanes.pop().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_action_is_reported_not_malformed() {
+        let payload = rmp_serde::to_vec_named(&serde_json::json!({
+            "action": "someFutureAction",
+            "seq": 4,
+            "extra": [1, 2, 3],
+        }))
+        .unwrap();
+        let frame = encode_frame(&payload, false);
+        let result = read_client_action(&mut frame.as_slice(), false, LEGACY_PROTOCOL).await;
+        assert!(matches!(result, Err(ReadActionError::Unsupported(a)) if a == "someFutureAction"));
+
+        // Known actions with fields this server doesn't know still parse
+        let payload = rmp_serde::to_vec_named(&serde_json::json!({
+            "action": "leaveLobby",
+            "reason": "newer clients send this",
+        }))
+        .unwrap();
+        let frame = encode_frame(&payload, false);
+        assert!(read_client_action(&mut frame.as_slice(), false, LEGACY_PROTOCOL).await.is_ok());
+    }
+
+    #[test]
+    fn test_bad_frames_are_reported_in_batches() {
+        let mut bad_frames = BadFrames::default();
+        let start = Instant::now();
+        bad_frames.malformed();
+        assert!(bad_frames.take_warning(start, false).is_some());
+
+        for _ in 0..3 {
+            bad_frames.malformed();
+            bad_frames.unsupported("someFutureAction".to_string());
+        }
+        assert!(bad_frames.take_warning(start + Duration::from_secs(1), false).is_none());
+        let later = start + PROTOCOL_WARNING_INTERVAL;
+        assert!(matches!(
+            bad_frames.take_warning(later, false),
+            Some(ServerToClient::ProtocolWarnings { malformed: 3, unsupported: 3, unsupported_actions })
+                if unsupported_actions == ["someFutureAction"]
+        ));
+        assert!(bad_frames.take_warning(later + PROTOCOL_WARNING_INTERVAL, false).is_none());
+
+        // Unsupported actions never add up to a disconnect
+        assert!(bad_frames.exceeded(4));
+        assert!(!bad_frames.exceeded(5));
+        assert!(!bad_frames.exceeded(0));
+    }
+
+    #[test]
+    fn test_chunked_deck_is_reassembled_past_the_frame_cap() {
+        let deck = "c".repeat(MAX_MESSAGE_SIZE + 1);
+        let payload = rmp_serde::to_vec_named(&ClientFrame {
+            v: None,
+            seq: None,
+            action: ClientToServer::SendPlayerDeck { deck: deck.clone() },
+        })
+        .unwrap();
+        let version = protocol::CURRENT_PROTOCOL;
+        let mut pending = None;
+        // Clients send chunk data as MessagePack bin, not as an array of numbers
+        #[derive(Serialize)]
+        struct WireChunk<'a> {
+            action: &'static str,
+            id: u32,
+            data: &'a serde_bytes::Bytes,
+        }
+        let frame = rmp_serde::to_vec_named(&WireChunk {
+            action: "chunk",
+            id: 1,
+            data: serde_bytes::Bytes::new(&payload[..8]),
+        })
+        .unwrap();
+        assert!(matches!(
+            parse_frame(&frame, version).map(|frame| frame.action),
+            Ok(ClientToServer::Chunk { id: 1, data }) if data == payload[..8]
+        ));
+
+        let begin = ClientToServer::ChunkBegin { id: 1, total_len: payload.len() as u32 };
+        assert!(matches!(ChunkedFrame::accept(&mut pending, begin, version), Ok(None)));
+        for data in payload.chunks(MAX_MESSAGE_SIZE / 2) {
+            let chunk = ClientToServer::Chunk { id: 1, data: data.to_vec() };
+            assert!(matches!(ChunkedFrame::accept(&mut pending, chunk, version), Ok(None)));
+        }
+        let end = ClientToServer::ChunkEnd { id: 1 };
+        assert!(matches!(
+            ChunkedFrame::accept(&mut pending, end, version),
+            Ok(Some(ClientToServer::SendPlayerDeck { deck: received })) if received == deck
+        ));
+
+        // Ordinary actions pass through, oversized or overrunning transfers are refused
+        let keep_alive = ClientToServer::KeepAlive { nonce: None };
+        assert!(matches!(
+            ChunkedFrame::accept(&mut pending, keep_alive, version),
+            Ok(Some(ClientToServer::KeepAlive { .. }))
+        ));
+        let huge = ClientToServer::ChunkBegin { id: 2, total_len: MAX_CHUNKED_SIZE as u32 + 1 };
+        assert!(ChunkedFrame::accept(&mut pending, huge, version).is_err());
+        let begin = ClientToServer::ChunkBegin { id: 3, total_len: 4 };
+        assert!(ChunkedFrame::accept(&mut pending, begin, version).is_ok());
+        let overrun = ClientToServer::Chunk { id: 3, data: vec![0; 5] };
+        assert!(ChunkedFrame::accept(&mut pending, overrun, version).is_err());
+        assert!(pending.is_none());
+
+        // Only deck and joker payloads may take the long way round
+        let payload = rmp_serde::to_vec_named(&ClientFrame {
+            v: None,
+            seq: None,
+            action: ClientToServer::LeaveLobby {},
+        })
+        .unwrap();
+        let begin = ClientToServer::ChunkBegin { id: 4, total_len: payload.len() as u32 };
+        let _ = ChunkedFrame::accept(&mut pending, begin, version);
+        let chunk = ClientToServer::Chunk { id: 4, data: payload };
+        let _ = ChunkedFrame::accept(&mut pending, chunk, version);
+        let end = ClientToServer::ChunkEnd { id: 4 };
+        assert!(ChunkedFrame::accept(&mut pending, end, version).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_checksummed_frames_detect_corruption() {
+        let frame = ClientFrame {
+            v: None,
+            seq: Some(3),
+            action: ClientToServer::LeaveLobby {},
+        };
+        let payload = rmp_serde::to_vec_named(&frame).unwrap();
+        let encoded = encode_frame(&payload, true);
+        let read = read_client_action(&mut encoded.as_slice(), true, LEGACY_PROTOCOL)
+            .await
+            .unwrap();
+        assert_eq!(read.seq, Some(3));
+
+        let mut corrupted = encoded.clone();
+        *corrupted.last_mut().unwrap() ^= 0x40;
+        let result = read_client_action(&mut corrupted.as_slice(), true, LEGACY_PROTOCOL).await;
+        assert!(matches!(result, Err(ReadActionError::ChecksumMismatch)));
+
+        // Without negotiation frames stay plain length + payload
+        let plain = encode_frame(&payload, false);
+        assert_eq!(plain.len(), payload.len() + 4);
+        assert!(read_client_action(&mut plain.as_slice(), false, LEGACY_PROTOCOL).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_handle_client_action_keepalive() {
-        let (_client, responses) = test_handle_client_action_helper_async(ClientToServer::KeepAlive {}).await;
-        assert!(contains_response_of_type(&responses, &ServerToClient::KeepAliveResponse {}));
+        let (_client, responses) = test_handle_client_action_helper_async(ClientToServer::KeepAlive { nonce: Some(7) }).await;
+        assert!(contains_response_of_type(&responses, &ServerToClient::KeepAliveResponse { nonce: None, server_time: 0 }));
     }
 
     #[tokio::test]
@@ -372,16 +1299,123 @@ mod tests{
         assert!(contains_response_of_type::<ServerToClient>(&responses, &ServerToClient::VersionOk {}));
     }
 
+    #[tokio::test]
+    async fn test_lobby_cleanup_runs_once_per_stay() {
+        let leaves = |rx: &mut mpsc::UnboundedReceiver<CoordinatorMessage>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter_map(|message| match message {
+                    CoordinatorMessage::ClientDisconnected { connection_lost, .. } => Some(connection_lost),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let (coordinator_tx, mut coordinator_rx) = mpsc::unbounded_channel();
+        let (response_tx, _response_rx) = mpsc::unbounded_channel();
+        let (lobby_tx, _lobby_rx) = crate::messages::lobby_channel();
+
+        // The socket dies mid-lobby
+        let mut client = Client::new(Some(coordinator_tx.clone()));
+        client.entered_lobby("ABCDE".to_string(), lobby_tx.clone(), 1);
+        client.close_connection();
+        client.close_connection();
+        assert_eq!(leaves(&mut coordinator_rx), [true]);
+        assert_eq!(client.lifecycle, ClientLifecycle::Disconnected);
+
+        // Leaving first, then the socket dies
+        let mut client = Client::new(Some(coordinator_tx.clone()));
+        client.entered_lobby("ABCDE".to_string(), lobby_tx, 1);
+        let id = client.profile.id.clone();
+        let leave = ClientToServer::LeaveLobby {};
+        handle_client_action(id.clone(), leave, None, &mut client, &response_tx).await.unwrap();
+        assert_eq!(client.lifecycle, ClientLifecycle::Connected);
+        let leave = ClientToServer::LeaveLobby {};
+        handle_client_action(id, leave, None, &mut client, &response_tx).await.unwrap();
+        client.close_connection();
+        assert_eq!(leaves(&mut coordinator_rx), [false]);
+
+        // Never in a lobby, only the offline notice goes out
+        let mut client = Client::new(Some(coordinator_tx));
+        client.close_connection();
+        assert!(matches!(
+            coordinator_rx.try_recv(),
+            Ok(CoordinatorMessage::ClientOffline { .. })
+        ));
+        assert!(coordinator_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_joining_a_spectated_lobby_stops_spectating_it() {
+        let mut client = Client::new(None);
+        let (watched_tx, mut watched_rx) = crate::messages::lobby_channel();
+        client.spectating.insert("ABCDE".to_string(), watched_tx.clone());
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let spectate = ClientToServer::SpectateLobby { code: "ABCDE".to_string() };
+        let _ = handle_client_action(client.profile.id.clone(), spectate, None, &mut client, &tx).await;
+        assert!(matches!(&*rx.try_recv().unwrap(), ServerToClient::Error { .. }));
+
+        client.entered_lobby("ABCDE".to_string(), watched_tx, 1);
+        assert!(client.spectating.is_empty());
+        assert_eq!(client.current_lobby.as_deref(), Some("ABCDE"));
+        assert!(matches!(
+            watched_rx.recv().await,
+            Some(LobbyMessage::SpectatorLeave { client_id }) if client_id == client.profile.id
+        ));
+        assert!(!client.stop_spectating("ABCDE"));
+    }
+
     #[tokio::test]
     async fn test_handle_client_action_set_client_data() {
         let (client, _responses) = test_handle_client_action_helper_async(ClientToServer::SetClientData {
             username: "Alice".to_string(),
             colour: 42,
             mod_hash: "abc123".to_string(),
+            account_id: Some("acc-1".to_string()),
+            account_token: None,
+            preview_patches: false,
+            resumable: false,
+            score_format: ScoreFormat::Talisman,
         }).await;
         assert_eq!(client.profile.username, "Alice");
         assert_eq!(client.profile.colour, 42);
         assert_eq!(client.profile.mod_hash, "abc123");
+        assert_eq!(client.profile.account_id.as_deref(), Some("acc-1"));
+        assert_eq!(
+            ScoreFormat::from_u8(client.score_format.load(Ordering::Relaxed)),
+            ScoreFormat::Talisman
+        );
+    }
+
+    #[test]
+    fn test_client_frame_sequence_is_optional() {
+        #[derive(serde::Serialize)]
+        struct Sequenced<'a> {
+            action: &'a str,
+            seq: u64,
+            blind: u32,
+        }
+        let bytes = rmp_serde::to_vec_named(&Sequenced { action: "skip", seq: 4, blind: 2 }).unwrap();
+        let frame: ClientFrame = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(frame.seq, Some(4));
+        assert!(matches!(frame.action, ClientToServer::Skip { blind: 2 }));
+
+        let bytes = rmp_serde::to_vec_named(&ClientToServer::PlayHand {
+            score: crate::talisman_number::TalismanNumber::Regular(120.0),
+            hands_left: 3,
+            breakdown: None,
+        })
+        .unwrap();
+        let frame: ClientFrame = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(frame.seq, None);
+        assert!(matches!(frame.action, ClientToServer::PlayHand { hands_left: 3, .. }));
+    }
+
+    #[test]
+    fn test_record_pong_ignores_stale_nonces() {
+        let mut client = Client::new(None);
+        assert!(client.record_pong(2, now_millis()).is_some());
+        assert!(client.record_pong(1, now_millis()).is_none());
+        assert!(client.latency_ms.is_some());
     }
 
     #[test]
@@ -0,0 +1,347 @@
+//! Typed client for talking to a running server, for bots, load tests and
+//! integration tests. Speaks the same length-prefixed MessagePack framing as the
+//! game client.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::mpsc;
+
+pub use crate::client::ClientProfile;
+pub use crate::game_mode::GameMode;
+use crate::messages::protocol::CURRENT_PROTOCOL;
+use crate::messages::ClientFrame;
+pub use crate::messages::{ClientToServer, OutcomeReason, Standing};
+pub use crate::talisman_number::{ScoreFormat, TalismanNumber};
+
+const MAX_EVENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// A decoded server message. `ServerToClient` is send-only, so the messages a bot
+/// acts on are mirrored here field for field; the rest arrive as `Other`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action")]
+pub enum ServerEvent {
+    #[serde(rename = "connected")]
+    Connected {
+        client_id: String,
+        #[serde(default)]
+        region: Option<String>,
+    },
+    #[serde(rename = "error")]
+    Error { message: String },
+    #[serde(rename = "serverNotice")]
+    ServerNotice { message: String },
+    #[serde(rename = "serverFull")]
+    ServerFull {
+        message: String,
+        queue_position: Option<usize>,
+    },
+    #[serde(rename = "serverRedirect")]
+    ServerRedirect { host: String, port: u16 },
+    #[serde(rename = "joinedLobby")]
+    JoinedLobby {
+        player_id: String,
+        lobby_data: JoinedLobby,
+    },
+    #[serde(rename = "playerJoinedLobby")]
+    PlayerJoinedLobby { player: LobbyPlayer },
+    #[serde(rename = "playerLeftLobby")]
+    PlayerLeftLobby { player_id: String, host_id: String },
+    #[serde(rename = "kicked")]
+    Kicked { reason: String },
+    #[serde(rename = "gameStarted")]
+    GameStarted { seed: String, stake: i32 },
+    #[serde(rename = "startBlind")]
+    StartBlind { server_time: u64 },
+    #[serde(rename = "endPvp")]
+    EndPvp { won: bool, reason: OutcomeReason },
+    #[serde(rename = "winGame")]
+    WinGame {
+        reason: OutcomeReason,
+        standings: Vec<Standing>,
+    },
+    #[serde(rename = "loseGame")]
+    LoseGame {
+        reason: OutcomeReason,
+        standings: Vec<Standing>,
+    },
+    /// Any other message: `action` is the wire tag and `data` the full message
+    #[serde(skip)]
+    Other {
+        action: String,
+        data: serde_json::Value,
+    },
+}
+
+/// The part of `joinedLobby`'s lobby a client needs to find it again
+#[derive(Debug, Clone, Deserialize)]
+pub struct JoinedLobby {
+    pub code: String,
+}
+
+/// A player as lobby messages describe them
+#[derive(Debug, Clone, Deserialize)]
+pub struct LobbyPlayer {
+    pub profile: ClientProfile,
+}
+
+impl ServerEvent {
+    /// The message as the types above describe it, `Other` when they don't
+    fn decode(data: serde_json::Value) -> Self {
+        serde_json::from_value(data.clone()).unwrap_or_else(|_| {
+            let action = data
+                .get("action")
+                .and_then(|action| action.as_str())
+                .unwrap_or_default()
+                .to_string();
+            ServerEvent::Other { action, data }
+        })
+    }
+}
+
+pub struct SdkClient {
+    writer: OwnedWriteHalf,
+    events: mpsc::UnboundedReceiver<ServerEvent>,
+    client_id: String,
+    next_seq: u64,
+}
+
+impl SdkClient {
+    /// Connect and wait for the `connected` handshake
+    pub async fn connect(addr: SocketAddr) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("Failed to connect to {addr}"))?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+
+        let (events_tx, events) = mpsc::unbounded_channel();
+        tokio::spawn(read_events(reader, events_tx));
+
+        let mut client = Self {
+            writer,
+            events,
+            client_id: String::new(),
+            next_seq: 1,
+        };
+        let connected = client.wait_for(Duration::from_secs(5), |event| match event {
+            ServerEvent::Connected { client_id, .. } => Some(client_id),
+            _ => None,
+        });
+        client.client_id = connected.await?;
+        Ok(client)
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Send an action, numbered so the lobby can drop replays
+    pub async fn send(&mut self, action: ClientToServer) -> anyhow::Result<()> {
+        let frame = ClientFrame {
+            v: Some(CURRENT_PROTOCOL),
+            seq: Some(self.next_seq),
+            action,
+        };
+        self.next_seq += 1;
+        let body = rmp_serde::to_vec_named(&frame)?;
+        self.writer.write_all(&(body.len() as u32).to_be_bytes()).await?;
+        self.writer.write_all(&body).await?;
+        Ok(())
+    }
+
+    pub async fn set_client_data(&mut self, username: &str, colour: u8) -> anyhow::Result<()> {
+        self.send(ClientToServer::SetClientData {
+            username: username.to_string(),
+            colour,
+            mod_hash: String::new(),
+            account_id: None,
+            account_token: None,
+            preview_patches: false,
+            resumable: false,
+            score_format: ScoreFormat::Native,
+        })
+        .await
+    }
+
+    /// Create a lobby and return its code
+    pub async fn create_lobby(
+        &mut self,
+        ruleset: &str,
+        game_mode: GameMode,
+    ) -> anyhow::Result<String> {
+        self.send(ClientToServer::CreateLobby {
+            ruleset: ruleset.to_string(),
+            game_mode,
+        })
+        .await?;
+        self.joined_lobby_code().await
+    }
+
+    /// Join a lobby by code and return the code on success
+    pub async fn join(&mut self, code: &str) -> anyhow::Result<String> {
+        self.send(ClientToServer::JoinLobby {
+            code: code.to_string(),
+            reconnect_token: None,
+        })
+        .await?;
+        self.joined_lobby_code().await
+    }
+
+    pub async fn set_ready(&mut self, is_ready: bool) -> anyhow::Result<()> {
+        self.send(ClientToServer::SetReady { is_ready }).await
+    }
+
+    pub async fn play_hand(&mut self, score: TalismanNumber, hands_left: u8) -> anyhow::Result<()> {
+        self.send(ClientToServer::PlayHand {
+            score,
+            hands_left,
+            breakdown: None,
+        })
+        .await
+    }
+
+    pub async fn leave(&mut self) -> anyhow::Result<()> {
+        self.send(ClientToServer::LeaveLobby {}).await
+    }
+
+    /// Next server message, or `None` once the connection closed
+    pub async fn next_event(&mut self) -> Option<ServerEvent> {
+        self.events.recv().await
+    }
+
+    /// Skip messages until `pick` takes one, and return what it made of it
+    pub async fn wait_for<T>(
+        &mut self,
+        timeout: Duration,
+        mut pick: impl FnMut(ServerEvent) -> Option<T>,
+    ) -> anyhow::Result<T> {
+        tokio::time::timeout(timeout, async {
+            while let Some(event) = self.events.recv().await {
+                if let Some(picked) = pick(event) {
+                    return Ok(picked);
+                }
+            }
+            bail!("Connection closed while waiting")
+        })
+        .await
+        .context("Timed out waiting for the server")?
+    }
+
+    async fn joined_lobby_code(&mut self) -> anyhow::Result<String> {
+        let joined = self.wait_for(Duration::from_secs(5), |event| match event {
+            ServerEvent::JoinedLobby { lobby_data, .. } => Some(Ok(lobby_data.code)),
+            ServerEvent::Error { message } | ServerEvent::ServerFull { message, .. } => {
+                Some(Err(message))
+            }
+            ServerEvent::ServerRedirect { host, port } => {
+                Some(Err(format!("Redirected to {host}:{port}")))
+            }
+            _ => None,
+        });
+        match joined.await? {
+            Ok(code) => Ok(code),
+            Err(reason) => bail!("Lobby request refused: {reason}"),
+        }
+    }
+}
+
+async fn read_events(mut reader: OwnedReadHalf, events_tx: mpsc::UnboundedSender<ServerEvent>) {
+    loop {
+        let mut length_bytes = [0u8; 4];
+        if reader.read_exact(&mut length_bytes).await.is_err() {
+            return;
+        }
+        let length = u32::from_be_bytes(length_bytes) as usize;
+        if length > MAX_EVENT_SIZE {
+            return;
+        }
+        let mut buf = vec![0u8; length];
+        if reader.read_exact(&mut buf).await.is_err() {
+            return;
+        }
+        let Ok(data) = rmp_serde::from_slice::<serde_json::Value>(&buf) else {
+            continue;
+        };
+        if events_tx.send(ServerEvent::decode(data)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lobby_coordinator::lobby_coordinator;
+    use tokio::net::TcpListener;
+
+    async fn spawn_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Games played here aren't real, keep them out of the usage stats
+        crate::usage_stats::mute();
+        let (coordinator_tx, coordinator_rx) = mpsc::unbounded_channel();
+        tokio::spawn(lobby_coordinator(coordinator_rx, coordinator_tx.clone()));
+        let (connections_tx, connections_rx) = mpsc::unbounded_channel();
+        tokio::spawn(crate::connections::run_connection_registry(connections_rx));
+        tokio::spawn(crate::accept_loop(listener, coordinator_tx, connections_tx));
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_create_and_join_lobby() {
+        let addr = spawn_server().await;
+
+        let mut host = SdkClient::connect(addr).await.unwrap();
+        host.set_client_data("host", 1).await.unwrap();
+        let code = host.create_lobby("ranked", GameMode::Attrition).await.unwrap();
+
+        let mut guest = SdkClient::connect(addr).await.unwrap();
+        assert_ne!(host.client_id(), guest.client_id());
+        guest.set_client_data("guest", 2).await.unwrap();
+        assert_eq!(guest.join(&code).await.unwrap(), code);
+
+        let joined = host.wait_for(Duration::from_secs(5), |event| match event {
+            ServerEvent::PlayerJoinedLobby { player } => Some(player.profile.id),
+            _ => None,
+        });
+        assert_eq!(joined.await.unwrap(), guest.client_id());
+
+        assert!(guest.join("NOPE").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejoin_last_needs_a_verified_account() {
+        let addr = spawn_server().await;
+        let claim_account = |username: &str| ClientToServer::SetClientData {
+            username: username.to_string(),
+            colour: 1,
+            mod_hash: String::new(),
+            account_id: Some("victim".to_string()),
+            account_token: None,
+            preview_patches: false,
+            resumable: false,
+            score_format: ScoreFormat::Native,
+        };
+
+        let mut victim = SdkClient::connect(addr).await.unwrap();
+        victim.send(claim_account("victim")).await.unwrap();
+        victim.create_lobby("ranked", GameMode::Attrition).await.unwrap();
+
+        // Naming the same account without its token doesn't lead into the lobby
+        let mut impostor = SdkClient::connect(addr).await.unwrap();
+        impostor.send(claim_account("impostor")).await.unwrap();
+        impostor.send(ClientToServer::RejoinLast { reconnect_token: None }).await.unwrap();
+        let answer = impostor.wait_for(Duration::from_secs(5), |event| match event {
+            ServerEvent::Error { message } => Some(message),
+            ServerEvent::JoinedLobby { .. } => Some("joined".to_string()),
+            _ => None,
+        });
+        assert_eq!(answer.await.unwrap(), "Rejoining needs a verified account");
+    }
+}
@@ -0,0 +1,314 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, RwLock};
+use tracing::{error, info, warn};
+
+use crate::federation::FederationConfig;
+use crate::game_mode::GameMode;
+use crate::lobby_codes::LobbyCodeStyle;
+use crate::scheduled_events::ScheduledEvent;
+use crate::webhooks::WebhookConfig;
+
+/// Server tunables: defaults, overlaid by the config file, overlaid by the environment
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Number of recent events each lobby keeps for bug reports
+    pub lobby_event_history: usize,
+    /// Directory bug reports are written to
+    pub bug_report_dir: PathBuf,
+    /// How long a host's slot reservation stays valid
+    pub slot_reservation_secs: u64,
+    /// Read operator commands from stdin (off by default, only read at startup)
+    pub console_enabled: bool,
+    /// Directory in-progress lobbies are checkpointed to
+    pub checkpoint_dir: PathBuf,
+    /// How often running games are checkpointed (0 disables checkpointing)
+    pub checkpoint_interval_secs: u64,
+    /// Most lobbies running at once (0 means no limit)
+    pub max_lobbies: usize,
+    /// Per game mode lobby caps, on top of `max_lobbies`
+    pub max_lobbies_per_mode: HashMap<GameMode, usize>,
+    /// Most clients connected at once (0 means no limit)
+    pub max_clients: usize,
+    /// Queue lobby creation over the cap instead of refusing it
+    pub lobby_queue_enabled: bool,
+    /// Idle lobby tasks kept ready to hand out, so bursts of new lobbies don't wait on spawning
+    pub lobby_pool_size: usize,
+    /// TCP addresses to accept clients on, IPv4 or IPv6 (only read at startup)
+    pub listen: Vec<SocketAddr>,
+    /// Endpoints notified of lobby and game events
+    pub webhooks: Vec<WebhookConfig>,
+    /// SQLite database holding claimed vanity lobby codes (only read at startup)
+    pub vanity_db_path: PathBuf,
+    /// Address of the read-only presence feed, off when unset (only read at startup)
+    pub presence_listen: Option<SocketAddr>,
+    /// Tokens presence subscribers authenticate with
+    pub presence_tokens: Vec<String>,
+    /// Address serving `/metrics`, `/healthz` and `/readyz`, off when unset (only read at startup)
+    pub metrics_listen: Option<SocketAddr>,
+    /// Tokens operators present as `Authorization: Bearer <token>` on the `/admin`
+    /// endpoints, which stay closed without one
+    pub admin_tokens: Vec<String>,
+    /// Append-only log of lobby lifetime events, off when unset
+    pub audit_log_path: Option<PathBuf>,
+    /// SQLite database holding player reports (only read at startup)
+    pub reports_db_path: PathBuf,
+    /// SQLite database holding shared custom challenges (only read at startup)
+    pub challenges_db_path: PathBuf,
+    /// SQLite database holding the anonymous usage stats (only read at startup)
+    pub usage_db_path: PathBuf,
+    /// How long a player who drops mid-game keeps their seat (0 ends their game at once)
+    pub disconnect_grace_secs: u64,
+    /// Hang up on clients that send nothing, not even pongs, for this long (0 disables)
+    pub idle_timeout_secs: u64,
+    /// Central stats service finished games are reported to, off when unset
+    pub federation: Option<FederationConfig>,
+    /// Hang up on clients after this many empty or unparseable frames (0 means no limit)
+    pub max_bad_frames: u32,
+    /// Windows in which new lobbies start with special rule modifiers
+    pub scheduled_events: Vec<ScheduledEvent>,
+    /// How codes for new lobbies look
+    pub lobby_code_style: LobbyCodeStyle,
+    /// Region this instance runs in (e.g. `eu-west`), told to clients so they can pick nearby games
+    pub region: Option<String>,
+    /// Hex key the account service signs account tokens with, no account is verified
+    /// without it
+    pub account_token_key: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            lobby_event_history: 200,
+            bug_report_dir: PathBuf::from("bug_reports"),
+            slot_reservation_secs: 120,
+            console_enabled: false,
+            checkpoint_dir: PathBuf::from("checkpoints"),
+            checkpoint_interval_secs: 30,
+            max_lobbies: 0,
+            max_lobbies_per_mode: HashMap::new(),
+            max_clients: 0,
+            lobby_queue_enabled: true,
+            lobby_pool_size: 4,
+            listen: vec![SocketAddr::from(([0, 0, 0, 0], 8788))],
+            webhooks: Vec::new(),
+            vanity_db_path: PathBuf::from("vanity_codes.sqlite"),
+            presence_listen: None,
+            presence_tokens: Vec::new(),
+            metrics_listen: None,
+            admin_tokens: Vec::new(),
+            audit_log_path: None,
+            reports_db_path: PathBuf::from("player_reports.sqlite"),
+            challenges_db_path: PathBuf::from("shared_challenges.sqlite"),
+            usage_db_path: PathBuf::from("usage_stats.sqlite"),
+            disconnect_grace_secs: 60,
+            idle_timeout_secs: 30,
+            federation: None,
+            max_bad_frames: 50,
+            scheduled_events: Vec::new(),
+            lobby_code_style: LobbyCodeStyle::Alphanumeric,
+            region: None,
+            account_token_key: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read the config file (if any) and apply environment overrides on top
+    pub fn load() -> Result<Self, String> {
+        let path = config_file_path();
+        let from_file = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Invalid config file {:?}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("Failed to read config file {:?}: {}", path, e)),
+        };
+        Ok(from_file.with_env_overrides())
+    }
+
+    fn with_env_overrides(self) -> Self {
+        Self {
+            lobby_event_history: env_or("BMP_LOBBY_EVENT_HISTORY", self.lobby_event_history),
+            bug_report_dir: std::env::var("BMP_BUG_REPORT_DIR")
+                .map(PathBuf::from)
+                .unwrap_or(self.bug_report_dir),
+            slot_reservation_secs: env_or("BMP_SLOT_RESERVATION_SECS", self.slot_reservation_secs),
+            console_enabled: env_or("BMP_CONSOLE", self.console_enabled),
+            checkpoint_dir: std::env::var("BMP_CHECKPOINT_DIR")
+                .map(PathBuf::from)
+                .unwrap_or(self.checkpoint_dir),
+            checkpoint_interval_secs: env_or(
+                "BMP_CHECKPOINT_INTERVAL_SECS",
+                self.checkpoint_interval_secs,
+            ),
+            max_lobbies: env_or("BMP_MAX_LOBBIES", self.max_lobbies),
+            max_lobbies_per_mode: self.max_lobbies_per_mode,
+            max_clients: env_or("BMP_MAX_CLIENTS", self.max_clients),
+            lobby_queue_enabled: env_or("BMP_LOBBY_QUEUE", self.lobby_queue_enabled),
+            lobby_pool_size: env_or("BMP_LOBBY_POOL_SIZE", self.lobby_pool_size),
+            listen: env_listen_addrs().unwrap_or(self.listen),
+            webhooks: env_webhooks().unwrap_or(self.webhooks),
+            vanity_db_path: std::env::var("BMP_VANITY_DB")
+                .map(PathBuf::from)
+                .unwrap_or(self.vanity_db_path),
+            presence_listen: std::env::var("BMP_PRESENCE_LISTEN")
+                .ok()
+                .and_then(|addr| addr.parse().ok())
+                .or(self.presence_listen),
+            presence_tokens: std::env::var("BMP_PRESENCE_TOKENS")
+                .map(|tokens| tokens.split(',').map(|t| t.trim().to_string()).collect())
+                .unwrap_or(self.presence_tokens),
+            metrics_listen: std::env::var("BMP_METRICS_LISTEN")
+                .ok()
+                .and_then(|addr| addr.parse().ok())
+                .or(self.metrics_listen),
+            admin_tokens: std::env::var("BMP_ADMIN_TOKENS")
+                .map(|tokens| tokens.split(',').map(|t| t.trim().to_string()).collect())
+                .unwrap_or(self.admin_tokens),
+            audit_log_path: std::env::var("BMP_AUDIT_LOG")
+                .ok()
+                .map(PathBuf::from)
+                .or(self.audit_log_path),
+            reports_db_path: std::env::var("BMP_REPORTS_DB")
+                .map(PathBuf::from)
+                .unwrap_or(self.reports_db_path),
+            challenges_db_path: std::env::var("BMP_CHALLENGES_DB")
+                .map(PathBuf::from)
+                .unwrap_or(self.challenges_db_path),
+            usage_db_path: std::env::var("BMP_USAGE_DB")
+                .map(PathBuf::from)
+                .unwrap_or(self.usage_db_path),
+            disconnect_grace_secs: env_or("BMP_DISCONNECT_GRACE_SECS", self.disconnect_grace_secs),
+            idle_timeout_secs: env_or("BMP_IDLE_TIMEOUT_SECS", self.idle_timeout_secs),
+            federation: self.federation,
+            max_bad_frames: env_or("BMP_MAX_BAD_FRAMES", self.max_bad_frames),
+            scheduled_events: self.scheduled_events,
+            lobby_code_style: env_or("BMP_LOBBY_CODE_STYLE", self.lobby_code_style),
+            region: std::env::var("BMP_REGION").ok().or(self.region),
+            account_token_key: std::env::var("BMP_ACCOUNT_TOKEN_KEY")
+                .ok()
+                .or(self.account_token_key),
+        }
+    }
+}
+
+fn config_file_path() -> PathBuf {
+    std::env::var("BMP_CONFIG_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("server_config.json"))
+}
+
+/// `BMP_LISTEN` takes a comma separated list of TCP addresses, e.g. `0.0.0.0:8788,[::]:8788`
+fn env_listen_addrs() -> Option<Vec<SocketAddr>> {
+    let value = std::env::var("BMP_LISTEN").ok()?;
+    let addrs: Result<Vec<SocketAddr>, _> =
+        value.split(',').map(|addr| addr.trim().parse()).collect();
+    match addrs {
+        Ok(addrs) if !addrs.is_empty() => Some(addrs),
+        _ => {
+            warn!("Ignoring invalid BMP_LISTEN value '{}'", value);
+            None
+        }
+    }
+}
+
+/// `BMP_WEBHOOKS` takes a comma separated list of URLs that receive every event
+fn env_webhooks() -> Option<Vec<WebhookConfig>> {
+    let value = std::env::var("BMP_WEBHOOKS").ok()?;
+    let hooks = value
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(|url| WebhookConfig {
+            url: url.to_string(),
+            events: Vec::new(),
+        })
+        .collect();
+    Some(hooks)
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Shared handle to the live configuration, swapped wholesale on reload
+pub struct ConfigHandle {
+    current: RwLock<Arc<ServerConfig>>,
+}
+
+impl ConfigHandle {
+    fn new(config: ServerConfig) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    /// The configuration in effect right now; don't hold on to it across awaits
+    pub fn get(&self) -> Arc<ServerConfig> {
+        Arc::clone(&self.current.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Re-read the config; on error the running configuration is kept
+    pub fn reload(&self) -> Result<(), String> {
+        let config = ServerConfig::load()?;
+        info!("Configuration reloaded: {:?}", config);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+        Ok(())
+    }
+}
+
+pub static CONFIG: LazyLock<ConfigHandle> = LazyLock::new(|| {
+    let config = ServerConfig::load().unwrap_or_else(|e| {
+        warn!("{}, falling back to defaults", e);
+        ServerConfig::default().with_env_overrides()
+    });
+    ConfigHandle::new(config)
+});
+
+/// Reload the configuration whenever the process receives SIGHUP
+#[cfg(unix)]
+pub async fn reload_on_sighup() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            error!("Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        if let Err(e) = CONFIG.reload() {
+            error!("Config reload failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_file_keeps_defaults() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{ "slot_reservation_secs": 30 }"#).unwrap();
+        assert_eq!(config.slot_reservation_secs, 30);
+        assert_eq!(config.lobby_event_history, ServerConfig::default().lobby_event_history);
+    }
+
+    #[test]
+    fn test_listen_addresses_accept_ipv6() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{ "listen": ["0.0.0.0:8788", "[::]:8789"] }"#,
+        )
+        .unwrap();
+        assert_eq!(config.listen.len(), 2);
+        assert!(config.listen[1].is_ipv6());
+    }
+}
@@ -0,0 +1,172 @@
+//! Registry of every open client connection: where it comes from and when it
+//! last sent anything. Idle connections are reaped from here, and the operator
+//! console lists them.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+use tracing::info;
+
+use crate::config::CONFIG;
+use crate::metrics::{METRICS, Metrics};
+use crate::utils::now_millis;
+
+/// How often idle connections are looked for
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+pub enum ConnectionMessage {
+    Opened {
+        client_id: String,
+        addr: SocketAddr,
+        /// Millisecond timestamp of the last frame, bumped by the client handler
+        last_activity: Arc<AtomicU64>,
+        /// Fired to make the client handler hang up
+        close_tx: oneshot::Sender<()>,
+    },
+    Closed {
+        client_id: String,
+    },
+    List {
+        reply_tx: oneshot::Sender<Vec<ConnectionInfo>>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub client_id: String,
+    pub addr: SocketAddr,
+    pub connected_at: u64,
+    pub last_activity: u64,
+}
+
+struct Connection {
+    addr: SocketAddr,
+    connected_at: u64,
+    last_activity: Arc<AtomicU64>,
+    close_tx: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: HashMap<String, Connection>,
+}
+
+impl ConnectionRegistry {
+    fn handle(&mut self, message: ConnectionMessage) {
+        match message {
+            ConnectionMessage::Opened {
+                client_id,
+                addr,
+                last_activity,
+                close_tx,
+            } => {
+                let connection = Connection {
+                    addr,
+                    connected_at: now_millis(),
+                    last_activity,
+                    close_tx,
+                };
+                self.connections.insert(client_id, connection);
+            }
+            ConnectionMessage::Closed { client_id } => {
+                self.connections.remove(&client_id);
+            }
+            ConnectionMessage::List { reply_tx } => {
+                let _ = reply_tx.send(self.list());
+            }
+        }
+    }
+
+    /// Open connections, longest idle first
+    pub fn list(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<ConnectionInfo> = self
+            .connections
+            .iter()
+            .map(|(client_id, c)| ConnectionInfo {
+                client_id: client_id.clone(),
+                addr: c.addr,
+                connected_at: c.connected_at,
+                last_activity: c.last_activity.load(Ordering::Relaxed),
+            })
+            .collect();
+        connections.sort_by_key(|c| c.last_activity);
+        connections
+    }
+
+    /// Hang up on connections silent for longer than `idle_timeout`; returns their ids
+    pub fn reap(&mut self, now: u64, idle_timeout: Duration) -> Vec<String> {
+        let cutoff = now.saturating_sub(idle_timeout.as_millis() as u64);
+        let idle: Vec<String> = self
+            .connections
+            .iter()
+            .filter(|(_, c)| c.last_activity.load(Ordering::Relaxed) < cutoff)
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+        for client_id in &idle {
+            if let Some(connection) = self.connections.remove(client_id) {
+                info!("Reaping idle client {} from {}", client_id, connection.addr);
+                let _ = connection.close_tx.send(());
+                Metrics::incr(&METRICS.connections_reaped);
+            }
+        }
+        idle
+    }
+}
+
+pub async fn run_connection_registry(mut rx: mpsc::UnboundedReceiver<ConnectionMessage>) {
+    let mut registry = ConnectionRegistry::default();
+    let mut reap_tick = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(message) => registry.handle(message),
+                None => break,
+            },
+            _ = reap_tick.tick() => {
+                let idle_timeout_secs = CONFIG.get().idle_timeout_secs;
+                if idle_timeout_secs > 0 {
+                    registry.reap(now_millis(), Duration::from_secs(idle_timeout_secs));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reap_closes_only_idle_connections() {
+        let mut registry = ConnectionRegistry::default();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let mut closed = Vec::new();
+        for (client_id, last_activity) in [("idle", 1_000), ("busy", 50_000)] {
+            let (close_tx, close_rx) = oneshot::channel();
+            registry.handle(ConnectionMessage::Opened {
+                client_id: client_id.to_string(),
+                addr,
+                last_activity: Arc::new(AtomicU64::new(last_activity)),
+                close_tx,
+            });
+            closed.push(close_rx);
+        }
+        assert_eq!(registry.list()[0].client_id, "idle");
+
+        let reaped = registry.reap(60_000, Duration::from_secs(30));
+        assert_eq!(reaped, vec!["idle".to_string()]);
+        assert!(closed[0].try_recv().is_ok());
+        assert!(closed[1].try_recv().is_err());
+        assert_eq!(registry.list().len(), 1);
+
+        registry.handle(ConnectionMessage::Closed {
+            client_id: "busy".to_string(),
+        });
+        assert!(registry.list().is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+use crate::config::CONFIG;
+use crate::connections::ConnectionMessage;
+use crate::messages::CoordinatorMessage;
+use crate::utils::now_millis;
+
+const HELP: &str = "\
+Commands:
+  lobbies                 list running lobbies
+  connections             list open connections, longest idle first
+  lobby <code>            dump a lobby's state
+  kick <player> [reason]  remove a player from their lobby
+  audit <code> on|off     acknowledge every action a lobby processes, for desync hunts
+  reports                 list player reports waiting for review
+  report <id>             show a report with its lobby context
+  dismiss <id>            close a report without action
+  action <id> [reason]    kick the reported player and close the report
+  broadcast <message>     send a notice to every lobby
+  shutdown <secs>         warn every lobby, then stop the server
+  reload                  re-read the server configuration
+  drain <host> <port>     redirect clients to a new server, exit when games finish
+  help                    show this help";
+
+/// Operator console reading commands from stdin
+pub async fn run_console(
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+    connections_tx: mpsc::UnboundedSender<ConnectionMessage>,
+) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    info!("Operator console ready, type 'help' for commands");
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+
+        match command {
+            "help" => println!("{HELP}"),
+            "lobbies" => list_lobbies(&coordinator_tx).await,
+            "connections" => list_connections(&connections_tx).await,
+            "lobby" if !args.is_empty() => inspect_lobby(&coordinator_tx, args).await,
+            "kick" if !args.is_empty() => {
+                let (player_id, reason) = args.split_once(' ').unwrap_or((args, "Kicked by operator"));
+                kick_player(&coordinator_tx, player_id, reason).await;
+            }
+            "audit" => match args.split_once(' ').map(|(code, state)| (code, state.trim())) {
+                Some((code, "on")) => set_action_audit(&coordinator_tx, code, true).await,
+                Some((code, "off")) => set_action_audit(&coordinator_tx, code, false).await,
+                _ => println!("Usage: audit <code> on|off"),
+            },
+            "reports" => list_reports(&coordinator_tx).await,
+            "report" if !args.is_empty() => show_report(&coordinator_tx, args).await,
+            "dismiss" if !args.is_empty() => resolve_report(&coordinator_tx, args, None).await,
+            "action" if !args.is_empty() => {
+                let (report_id, reason) = args.split_once(' ').unwrap_or((args, "Reported by players"));
+                resolve_report(&coordinator_tx, report_id, Some(reason.to_string())).await;
+            }
+            "broadcast" if !args.is_empty() => {
+                let _ = coordinator_tx.send(CoordinatorMessage::BroadcastNotice {
+                    message: args.to_string(),
+                });
+                println!("Notice sent");
+            }
+            "drain" => match args.split_once(' ').map(|(h, p)| (h, p.trim().parse::<u16>())) {
+                Some((host, Ok(port))) => {
+                    let _ = coordinator_tx.send(CoordinatorMessage::Drain {
+                        host: host.to_string(),
+                        port,
+                    });
+                    println!("Draining to {host}:{port}");
+                }
+                _ => println!("Usage: drain <host> <port>"),
+            },
+            "reload" => match CONFIG.reload() {
+                Ok(()) => println!("Configuration reloaded"),
+                Err(e) => println!("Reload failed, keeping current config: {e}"),
+            },
+            "shutdown" => match args.parse::<u64>() {
+                Ok(secs) => shutdown(&coordinator_tx, secs).await,
+                Err(_) => println!("Usage: shutdown <secs>"),
+            },
+            _ => println!("Unknown command '{line}'\n{HELP}"),
+        }
+    }
+}
+
+async fn list_lobbies(coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::ListLobbies { reply_tx })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(lobbies) if lobbies.is_empty() => println!("No running lobbies"),
+        Ok(lobbies) => {
+            if let Some(region) = &CONFIG.get().region {
+                println!("Region: {}", region);
+            }
+            for lobby in lobbies {
+                println!("{}  players: {}", lobby.code, lobby.player_count);
+            }
+        }
+        Err(_) => warn!("Coordinator did not answer lobby listing"),
+    }
+}
+
+async fn list_connections(connections_tx: &mpsc::UnboundedSender<ConnectionMessage>) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if connections_tx
+        .send(ConnectionMessage::List { reply_tx })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(connections) if connections.is_empty() => println!("No open connections"),
+        Ok(connections) => {
+            let now = now_millis();
+            for c in connections {
+                println!(
+                    "{}  {}  idle {}s  connected {}s",
+                    c.client_id,
+                    c.addr,
+                    now.saturating_sub(c.last_activity) / 1000,
+                    now.saturating_sub(c.connected_at) / 1000
+                );
+            }
+        }
+        Err(_) => warn!("Connection registry did not answer"),
+    }
+}
+
+async fn inspect_lobby(coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>, code: &str) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::InspectLobby {
+            lobby_code: code.to_uppercase(),
+            reply_tx,
+        })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(Some(lobby)) => match serde_json::to_string_pretty(&lobby) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Failed to serialize lobby: {e}"),
+        },
+        _ => println!("Lobby {code} not found"),
+    }
+}
+
+async fn kick_player(
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+    player_id: &str,
+    reason: &str,
+) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::KickPlayer {
+            client_id: player_id.to_string(),
+            reason: reason.to_string(),
+            reply_tx,
+        })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(true) => println!("Kicked {player_id}"),
+        _ => println!("Player {player_id} is not in a lobby"),
+    }
+}
+
+async fn set_action_audit(
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+    lobby_code: &str,
+    enabled: bool,
+) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::SetActionAudit {
+            lobby_code: lobby_code.to_string(),
+            enabled,
+            reply_tx,
+        })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(true) => {
+            let state = if enabled { "on" } else { "off" };
+            println!("Action audit {state} for {lobby_code}");
+        }
+        _ => println!("No lobby {lobby_code}"),
+    }
+}
+
+async fn list_reports(coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::ListReports { reply_tx })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(reports) if reports.is_empty() => println!("No open reports"),
+        Ok(reports) => {
+            for report in reports {
+                println!(
+                    "{}  lobby {}  {} reported {} ({}): {}",
+                    report.report_id,
+                    report.lobby_code,
+                    report.reporter_id,
+                    report.reported_username,
+                    report.reported_id,
+                    report.reason
+                );
+            }
+        }
+        Err(_) => warn!("Coordinator did not answer report listing"),
+    }
+}
+
+async fn show_report(coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>, report_id: &str) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::GetReport {
+            report_id: report_id.to_string(),
+            reply_tx,
+        })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(Some(report)) => match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => println!("Failed to serialize report: {e}"),
+        },
+        _ => println!("Report {report_id} not found"),
+    }
+}
+
+async fn resolve_report(
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+    report_id: &str,
+    kick_reason: Option<String>,
+) {
+    let acting = kick_reason.is_some();
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::ResolveReport {
+            report_id: report_id.to_string(),
+            kick_reason,
+            reply_tx,
+        })
+        .is_err()
+    {
+        return;
+    }
+    match reply_rx.await {
+        Ok(Some(true)) => println!("Report {report_id} actioned, player kicked"),
+        Ok(Some(false)) if acting => {
+            println!("Report {report_id} actioned, player was no longer in a lobby")
+        }
+        Ok(Some(false)) => println!("Report {report_id} dismissed"),
+        _ => println!("Report {report_id} is not open"),
+    }
+}
+
+async fn shutdown(coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>, secs: u64) {
+    let _ = coordinator_tx.send(CoordinatorMessage::BroadcastNotice {
+        message: format!("Server is shutting down in {secs} seconds"),
+    });
+    println!("Shutting down in {secs}s");
+    tokio::time::sleep(Duration::from_secs(secs)).await;
+    info!("Shutdown requested from console");
+    std::process::exit(0);
+}
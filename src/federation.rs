@@ -0,0 +1,327 @@
+//! Reporting game results to a central stats service, so community-hosted servers
+//! can feed the global leaderboards.
+//!
+//! A server introduces itself with a signed handshake; the service answers with
+//! whether it is on its allowlist. Trusted servers then submit each finished game,
+//! signed with the same Ed25519 key. Refused servers keep playing as before and only
+//! ask again after a while. Results wait in a bounded queue on their own thread, so
+//! a slow or unreachable service never holds up a lobby.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use crate::config::CONFIG;
+use crate::game_mode::GameMode;
+use crate::utils::{decode_hex, encode_hex, now_millis};
+
+const FEDERATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Results waiting for the service; newer ones are dropped while it is this far behind
+const MAX_QUEUED_RESULTS: usize = 1024;
+const MAX_ATTEMPTS: u32 = 5;
+/// Wait before the first retry, doubled after each failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How long a refused server waits before asking to be trusted again
+const REFUSED_RECHECK: Duration = Duration::from_secs(60 * 60);
+
+const SERVER_HEADER: &str = "X-Bmp-Server";
+const TIMESTAMP_HEADER: &str = "X-Bmp-Timestamp";
+const SIGNATURE_HEADER: &str = "X-Bmp-Signature";
+
+static AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(FEDERATION_TIMEOUT))
+        .build()
+        .into()
+});
+
+/// Set for offline runs (e.g. `simulate`) whose games must not reach external services
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+static QUEUE: LazyLock<Mutex<SyncSender<FederatedResult>>> = LazyLock::new(|| {
+    let (tx, rx) = std::sync::mpsc::sync_channel(MAX_QUEUED_RESULTS);
+    std::thread::spawn(move || run_submitter(rx));
+    Mutex::new(tx)
+});
+
+#[derive(Clone, Deserialize)]
+pub struct FederationConfig {
+    /// Base URL of the stats service, `/handshake` and `/results` are posted below it
+    pub url: String,
+    /// Name this server is listed under on the service's allowlist
+    pub server_id: String,
+    /// Hex encoded 32 byte Ed25519 seed; the service knows the matching public key
+    pub signing_key: String,
+}
+
+/// Keeps the signing key out of logged configs
+impl fmt::Debug for FederationConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FederationConfig")
+            .field("url", &self.url)
+            .field("server_id", &self.server_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FederatedResult {
+    pub lobby_code: String,
+    pub game_mode: GameMode,
+    pub players: Vec<FederatedStanding>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FederatedStanding {
+    /// A verified account; players without one aren't reported at all, the service
+    /// can't tell who they are
+    pub account_id: String,
+    pub placement: u8,
+    pub lives: u8,
+    pub furthest_blind: u32,
+    pub points: u32,
+    /// False when the player's run checksums don't hold up
+    pub verified: bool,
+}
+
+#[derive(Serialize)]
+struct Handshake<'a> {
+    server_id: &'a str,
+    public_key: String,
+    version: &'static str,
+}
+
+struct Signer {
+    key: Ed25519KeyPair,
+}
+
+impl Signer {
+    fn new(config: &FederationConfig) -> Result<Self, &'static str> {
+        let seed = decode_hex(config.signing_key.trim()).ok_or("signing_key must be hex")?;
+        if seed.len() != 32 {
+            return Err("signing_key must be a 32 byte Ed25519 seed");
+        }
+        let key = Ed25519KeyPair::from_seed_unchecked(&seed).map_err(|_| "signing_key rejected")?;
+        Ok(Self { key })
+    }
+
+    fn public_key(&self) -> String {
+        encode_hex(self.key.public_key().as_ref())
+    }
+
+    /// Signature over the timestamp and body, so a captured request can't be replayed later
+    fn sign(&self, timestamp: u64, body: &[u8]) -> String {
+        let mut message = format!("{}\n", timestamp).into_bytes();
+        message.extend_from_slice(body);
+        encode_hex(self.key.sign(&message).as_ref())
+    }
+}
+
+/// What the service last said about this server
+struct Trust {
+    url: String,
+    server_id: String,
+    trusted: bool,
+    checked_at: Instant,
+}
+
+impl Trust {
+    fn still_holds(&self, config: &FederationConfig) -> bool {
+        self.url == config.url
+            && self.server_id == config.server_id
+            && (self.trusted || self.checked_at.elapsed() < REFUSED_RECHECK)
+    }
+}
+
+pub fn mute() {
+    MUTED.store(true, Ordering::Relaxed);
+}
+
+/// Queue a finished game for the stats service, if federation is configured
+pub fn submit(result: FederatedResult) {
+    if MUTED.load(Ordering::Relaxed) || CONFIG.get().federation.is_none() {
+        return;
+    }
+    // Nobody in the game had a verified account, there is nothing to rank
+    if result.players.is_empty() {
+        return;
+    }
+    let queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(TrySendError::Full(result)) = queue.try_send(result) {
+        warn!("Federation queue full, dropping result of lobby {}", result.lobby_code);
+    }
+}
+
+fn run_submitter(rx: Receiver<FederatedResult>) {
+    let mut trust: Option<Trust> = None;
+    for result in rx {
+        // Read per result so reloads take effect, including turning federation off
+        let Some(config) = CONFIG.get().federation.clone() else {
+            continue;
+        };
+        let signer = match Signer::new(&config) {
+            Ok(signer) => signer,
+            Err(e) => {
+                error!("Federation disabled: {}", e);
+                continue;
+            }
+        };
+        let Ok(body) = serde_json::to_vec(&result) else {
+            continue;
+        };
+        let mut delay = RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match try_submit(&mut trust, &config, &signer, &body) {
+                Ok(true) => {
+                    debug!("Submitted result of lobby {}", result.lobby_code);
+                    break;
+                }
+                Ok(false) => break,
+                Err(e) if attempt == MAX_ATTEMPTS => {
+                    warn!("Giving up on result of lobby {}: {}", result.lobby_code, e);
+                }
+                Err(e) => {
+                    debug!("Federation attempt {} failed: {}", attempt, e);
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+            }
+        }
+    }
+}
+
+/// Submit `body`, shaking hands first when needed; false when the service refused this server
+fn try_submit(
+    trust: &mut Option<Trust>,
+    config: &FederationConfig,
+    signer: &Signer,
+    body: &[u8],
+) -> Result<bool, ureq::Error> {
+    if !trust.as_ref().is_some_and(|t| t.still_holds(config)) {
+        let trusted = handshake(config, signer)?;
+        if trusted {
+            info!("Stats service at {} trusts this server", config.url);
+        } else {
+            warn!("Stats service at {} refused this server, results stay local", config.url);
+        }
+        *trust = Some(Trust {
+            url: config.url.clone(),
+            server_id: config.server_id.clone(),
+            trusted,
+            checked_at: Instant::now(),
+        });
+    }
+    if !trust.as_ref().is_some_and(|t| t.trusted) {
+        return Ok(false);
+    }
+    match post(config, signer, "results", body) {
+        // Dropped from the allowlist since the handshake, ask again on the next try
+        Err(ureq::Error::StatusCode(403)) => {
+            *trust = None;
+            Err(ureq::Error::StatusCode(403))
+        }
+        result => result.map(|()| true),
+    }
+}
+
+/// Whether the service trusts this server
+fn handshake(config: &FederationConfig, signer: &Signer) -> Result<bool, ureq::Error> {
+    let handshake = Handshake {
+        server_id: &config.server_id,
+        public_key: signer.public_key(),
+        version: env!("CARGO_PKG_VERSION"),
+    };
+    let Ok(body) = serde_json::to_vec(&handshake) else {
+        return Ok(false);
+    };
+    match post(config, signer, "handshake", &body) {
+        Ok(()) => Ok(true),
+        Err(ureq::Error::StatusCode(401 | 403)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn post(config: &FederationConfig, signer: &Signer, path: &str, body: &[u8]) -> Result<(), ureq::Error> {
+    let timestamp = now_millis();
+    AGENT
+        .post(format!("{}/{}", config.url.trim_end_matches('/'), path))
+        .header(SERVER_HEADER, &config.server_id)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, signer.sign(timestamp, body))
+        .content_type("application/json")
+        .send(body)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{ED25519, UnparsedPublicKey};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn config(url: String) -> FederationConfig {
+        FederationConfig {
+            url,
+            server_id: "community-eu".to_string(),
+            signing_key: "07".repeat(32),
+        }
+    }
+
+    #[test]
+    fn test_signatures_verify_with_the_public_key() {
+        let signer = Signer::new(&config(String::new())).unwrap();
+        let signature = decode_hex(&signer.sign(42, b"{}")).unwrap();
+        let public_key = decode_hex(&signer.public_key()).unwrap();
+        let verifier = UnparsedPublicKey::new(&ED25519, public_key);
+        assert!(verifier.verify(b"42\n{}", &signature).is_ok());
+        assert!(verifier.verify(b"43\n{}", &signature).is_err());
+
+        let short = FederationConfig {
+            signing_key: "07".repeat(16),
+            ..config(String::new())
+        };
+        assert!(Signer::new(&short).is_err());
+        assert!(!format!("{:?}", config(String::new())).contains("0707"));
+    }
+
+    #[test]
+    fn test_refused_servers_stop_submitting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let config = config(url);
+        let signer = Signer::new(&config).unwrap();
+        let mut trust = None;
+        assert!(!try_submit(&mut trust, &config, &signer, b"{}").unwrap());
+        // Only the handshake went out; the refusal is remembered, no second request
+        assert!(!try_submit(&mut trust, &config, &signer, b"{}").unwrap());
+
+        let request = server.join().unwrap().to_lowercase();
+        assert!(request.starts_with("post /handshake"));
+        assert!(request.contains("x-bmp-server: community-eu"));
+        assert!(request.contains(&signer.public_key()));
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::sync::LazyLock;
 
+use crate::challenges::{self, SHARED_CHALLENGE_PREFIX};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum GameMode {
     #[serde(rename = "gamemode_mp_attrition")]
@@ -15,6 +17,48 @@ pub enum GameMode {
     Clash,
 }
 
+/// What happens to players who don't ready up before the ready timeout expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ReadyTimeoutAction {
+    /// Clear everyone's ready state so the lobby stops waiting on a stale start
+    #[default]
+    #[serde(rename = "unready")]
+    Unready,
+    /// Remove the lagging players from the lobby
+    #[serde(rename = "kick")]
+    Kick,
+}
+
+/// What happens to a player whose connection drops mid-game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DisconnectPolicy {
+    /// Hold their seat for the server's reconnect grace period, they forfeit if not back in time
+    #[default]
+    #[serde(rename = "wait")]
+    Wait,
+    /// They lose on the spot
+    #[serde(rename = "forfeit")]
+    Forfeit,
+    /// A bot takes over their run
+    #[serde(rename = "bot")]
+    Bot,
+}
+
+/// When a co-op PvP blind starts while some players are not ready yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CoopReadyRule {
+    /// Everyone in the game has to be ready
+    #[default]
+    #[serde(rename = "all")]
+    All,
+    /// More than half of the players in the game being ready is enough
+    #[serde(rename = "majority")]
+    Majority,
+    /// The host may start the blind with `forceStartBlind`
+    #[serde(rename = "hostOverride")]
+    HostOverride,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LobbyOptions {
     pub back: String,
@@ -36,6 +80,65 @@ pub struct LobbyOptions {
     pub starting_lives: u8,
     pub timer_base_seconds: u32,
     pub timer_increment_seconds: i32,
+    /// Seconds a lagging player gets to ready up once others are ready (0 disables)
+    #[serde(default)]
+    pub ready_timeout_seconds: u32,
+    #[serde(default)]
+    pub ready_timeout_action: ReadyTimeoutAction,
+    /// Hold PvP evaluation until every player has finished the blind
+    #[serde(default)]
+    pub batch_round_scoring: bool,
+    /// CoopSurvival: a player who fails is revived if the team clears the next boss
+    #[serde(default)]
+    pub coop_revive: bool,
+    /// CoopSurvival: scale boss chips each ante by how comfortably the last boss was beaten
+    #[serde(default)]
+    pub dynamic_difficulty: bool,
+    /// Clash base damage per stage, stages past the end reuse the last entry
+    #[serde(default = "default_clash_damage_table")]
+    pub clash_damage_table: Vec<u8>,
+    /// Clash: points needed to win the league, 0 keeps lives as the only win condition
+    #[serde(default)]
+    pub clash_points_target: u32,
+    /// Clash: league points awarded per round placement, first place first
+    #[serde(default = "default_clash_placement_points")]
+    pub clash_placement_points: Vec<u32>,
+    /// Chips each skip since the last PvP blind hands every opponent as a head start (0 disables)
+    #[serde(default)]
+    pub skip_handicap_chips: u32,
+    /// Hide other players' usernames behind placeholders until the game ends
+    #[serde(default)]
+    pub anonymous_mode: bool,
+    /// Before each PvP blind every player bans a boss and the server picks one of the rest
+    #[serde(default)]
+    pub boss_ban_phase: bool,
+    #[serde(default)]
+    pub disconnect_policy: DisconnectPolicy,
+    /// Joker keys no run may hold, e.g. `j_blueprint`, sent to players at game start
+    #[serde(default)]
+    pub banned_jokers: Vec<String>,
+    /// Tarot, planet and spectral keys no run may hold, e.g. `c_soul`
+    #[serde(default)]
+    pub banned_consumables: Vec<String>,
+    /// CoopSurvival: who has to be ready before the PvP blind starts, other modes wait for everyone
+    #[serde(default)]
+    pub coop_ready_rule: CoopReadyRule,
+    /// Players can start the game by majority vote, for lobbies whose host may be away
+    #[serde(default)]
+    pub vote_to_start: bool,
+    /// Only the host knows the PvP boss until the blind starts
+    #[serde(default)]
+    pub hidden_boss: bool,
+    /// Blitz: seconds each PvP blind may run before the server fails unfinished players,
+    /// separate from the client-side ante timer (0 disables)
+    #[serde(default)]
+    pub blitz_blind_seconds: u32,
+    /// Blitz: seconds added to the limit for every PvP blind already played this game
+    #[serde(default)]
+    pub blitz_increment_seconds: u32,
+    /// Clients outside the lobby may watch it, checked when they start spectating
+    #[serde(default)]
+    pub allow_spectators: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,6 +195,26 @@ static ATTRITION_DATA: LazyLock<GameModeData> = LazyLock::new(|| GameModeData {
         starting_lives: 4,
         timer_base_seconds: 150,
         timer_increment_seconds: 60,
+        ready_timeout_seconds: 0,
+        ready_timeout_action: ReadyTimeoutAction::Unready,
+        batch_round_scoring: false,
+        coop_revive: false,
+        dynamic_difficulty: false,
+        clash_damage_table: CLASH_BASE_DAMAGE.to_vec(),
+        clash_points_target: 0,
+        clash_placement_points: CLASH_PLACEMENT_POINTS.to_vec(),
+        skip_handicap_chips: 0,
+        anonymous_mode: false,
+        boss_ban_phase: false,
+        disconnect_policy: DisconnectPolicy::Wait,
+        banned_jokers: Vec::new(),
+        banned_consumables: Vec::new(),
+        coop_ready_rule: CoopReadyRule::All,
+        vote_to_start: false,
+        hidden_boss: false,
+        blitz_blind_seconds: 0,
+        blitz_increment_seconds: 0,
+        allow_spectators: false,
     },
 });
 
@@ -118,6 +241,26 @@ static SHOWDOWN_DATA: LazyLock<GameModeData> = LazyLock::new(|| GameModeData {
         starting_lives: 4,
         timer_base_seconds: 150,
         timer_increment_seconds: 60,
+        ready_timeout_seconds: 0,
+        ready_timeout_action: ReadyTimeoutAction::Unready,
+        batch_round_scoring: false,
+        coop_revive: false,
+        dynamic_difficulty: false,
+        clash_damage_table: CLASH_BASE_DAMAGE.to_vec(),
+        clash_points_target: 0,
+        clash_placement_points: CLASH_PLACEMENT_POINTS.to_vec(),
+        skip_handicap_chips: 0,
+        anonymous_mode: false,
+        boss_ban_phase: false,
+        disconnect_policy: DisconnectPolicy::Wait,
+        banned_jokers: Vec::new(),
+        banned_consumables: Vec::new(),
+        coop_ready_rule: CoopReadyRule::All,
+        vote_to_start: false,
+        hidden_boss: false,
+        blitz_blind_seconds: 0,
+        blitz_increment_seconds: 0,
+        allow_spectators: false,
     },
 });
 
@@ -144,6 +287,26 @@ static SURVIVAL_DATA: LazyLock<GameModeData> = LazyLock::new(|| GameModeData {
         starting_lives: 4,
         timer_base_seconds: 150,
         timer_increment_seconds: 60,
+        ready_timeout_seconds: 0,
+        ready_timeout_action: ReadyTimeoutAction::Unready,
+        batch_round_scoring: false,
+        coop_revive: false,
+        dynamic_difficulty: false,
+        clash_damage_table: CLASH_BASE_DAMAGE.to_vec(),
+        clash_points_target: 0,
+        clash_placement_points: CLASH_PLACEMENT_POINTS.to_vec(),
+        skip_handicap_chips: 0,
+        anonymous_mode: false,
+        boss_ban_phase: false,
+        disconnect_policy: DisconnectPolicy::Wait,
+        banned_jokers: Vec::new(),
+        banned_consumables: Vec::new(),
+        coop_ready_rule: CoopReadyRule::All,
+        vote_to_start: false,
+        hidden_boss: false,
+        blitz_blind_seconds: 0,
+        blitz_increment_seconds: 0,
+        allow_spectators: false,
     },
 });
 
@@ -170,11 +333,126 @@ static COOP_SURVIVAL_DATA: LazyLock<GameModeData> = LazyLock::new(|| GameModeDat
         starting_lives: 2,
         timer_base_seconds: 150,
         timer_increment_seconds: 60,
+        ready_timeout_seconds: 0,
+        ready_timeout_action: ReadyTimeoutAction::Unready,
+        batch_round_scoring: false,
+        coop_revive: false,
+        dynamic_difficulty: false,
+        clash_damage_table: CLASH_BASE_DAMAGE.to_vec(),
+        clash_points_target: 0,
+        clash_placement_points: CLASH_PLACEMENT_POINTS.to_vec(),
+        skip_handicap_chips: 0,
+        anonymous_mode: false,
+        boss_ban_phase: false,
+        disconnect_policy: DisconnectPolicy::Wait,
+        banned_jokers: Vec::new(),
+        banned_consumables: Vec::new(),
+        coop_ready_rule: CoopReadyRule::All,
+        vote_to_start: false,
+        hidden_boss: false,
+        blitz_blind_seconds: 0,
+        blitz_increment_seconds: 0,
+        allow_spectators: false,
     },
 });
 
+/// Stakes a lobby can be played on, White through Gold
+pub const MIN_STAKE: u32 = 1;
+pub const MAX_STAKE: u32 = 8;
+
+/// Gold granted to a player each time they lose a life, when `gold_on_life_loss` is enabled
+pub const LIFE_LOSS_GOLD: u32 = 4;
+
+/// Seed spectators see in place of the lobby's
+pub const HIDDEN_SEED: &str = "hidden";
+
+/// Keys each banned card list may hold, enough for any tournament ruleset
+const MAX_BANNED_CARDS: usize = 128;
+const MAX_CARD_KEY_LEN: usize = 64;
+
+/// Shortest Blitz limit, time to at least look at the blind
+pub const MIN_BLITZ_SECONDS: u32 = 30;
+
 pub const CLASH_BASE_DAMAGE: [u8; 8] = [0, 2, 5, 8, 10, 12, 17, 100];
 
+pub const CLASH_PLACEMENT_POINTS: [u32; 4] = [5, 3, 2, 1];
+
+fn is_card_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_CARD_KEY_LEN
+        && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+fn default_clash_damage_table() -> Vec<u8> {
+    CLASH_BASE_DAMAGE.to_vec()
+}
+
+fn default_clash_placement_points() -> Vec<u32> {
+    CLASH_PLACEMENT_POINTS.to_vec()
+}
+
+impl LobbyOptions {
+    /// Put a placeholder over a chosen seed, for whoever may not see it
+    pub fn hide_seed(&mut self) {
+        if self.custom_seed != "random" {
+            self.custom_seed = HIDDEN_SEED.to_string();
+        }
+    }
+
+    /// Reject options the game can't be played with
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(MIN_STAKE..=MAX_STAKE).contains(&self.stake) {
+            return Err("Stake must be between 1 and 8");
+        }
+        if self.blitz_blind_seconds != 0 && self.blitz_blind_seconds < MIN_BLITZ_SECONDS {
+            return Err("Blitz blinds must last at least 30 seconds");
+        }
+        for banned in [&self.banned_jokers, &self.banned_consumables] {
+            if banned.len() > MAX_BANNED_CARDS {
+                return Err("Too many banned cards");
+            }
+            if banned.iter().any(|key| !is_card_key(key)) {
+                return Err("Banned cards must be card keys like j_joker");
+            }
+        }
+        let shared = self.challenge.starts_with(SHARED_CHALLENGE_PREFIX);
+        if shared && !challenges::is_known(&self.challenge) {
+            return Err("No shared challenge with that id");
+        }
+        Ok(())
+    }
+
+    /// The first banned card in a joker payload; clients choose its format, so any
+    /// key-shaped token counts
+    pub fn banned_card_in<'a>(&self, payload: &'a str) -> Option<&'a str> {
+        if self.banned_jokers.is_empty() && self.banned_consumables.is_empty() {
+            return None;
+        }
+        payload
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .find(|token| {
+                self.banned_jokers.iter().chain(&self.banned_consumables).any(|key| key == token)
+            })
+    }
+
+    /// Base Clash damage for a stage, clamped to the last entry of the table
+    pub fn clash_base_damage(&self, stage: usize) -> u8 {
+        self.clash_damage_table
+            .get(stage)
+            .or(self.clash_damage_table.last())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// League points for a 1-based placement, placements past the table score nothing
+    pub fn clash_placement_points(&self, placement: usize) -> u32 {
+        self.clash_placement_points
+            .get(placement.saturating_sub(1))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
 static CLASH_DATA: LazyLock<GameModeData> = LazyLock::new(|| GameModeData {
     max_players: 6,
     default_options: LobbyOptions {
@@ -197,6 +475,26 @@ static CLASH_DATA: LazyLock<GameModeData> = LazyLock::new(|| GameModeData {
         starting_lives: 50,
         timer_base_seconds: 150,
         timer_increment_seconds: 60,
+        ready_timeout_seconds: 0,
+        ready_timeout_action: ReadyTimeoutAction::Unready,
+        batch_round_scoring: false,
+        coop_revive: false,
+        dynamic_difficulty: false,
+        clash_damage_table: CLASH_BASE_DAMAGE.to_vec(),
+        clash_points_target: 0,
+        clash_placement_points: CLASH_PLACEMENT_POINTS.to_vec(),
+        skip_handicap_chips: 0,
+        anonymous_mode: false,
+        boss_ban_phase: false,
+        disconnect_policy: DisconnectPolicy::Wait,
+        banned_jokers: Vec::new(),
+        banned_consumables: Vec::new(),
+        coop_ready_rule: CoopReadyRule::All,
+        vote_to_start: false,
+        hidden_boss: false,
+        blitz_blind_seconds: 0,
+        blitz_increment_seconds: 0,
+        allow_spectators: false,
     },
 });
 
@@ -209,6 +507,7 @@ impl std::str::FromStr for GameMode {
             "Showdown" => Ok(GameMode::Showdown),
             "Survival" => Ok(GameMode::Survival),
             "CoopSurvival" => Ok(GameMode::CoopSurvival),
+            "Clash" => Ok(GameMode::Clash),
             _ => Err(format!("Unknown game mode: {}", s)),
         }
     }
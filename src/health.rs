@@ -0,0 +1,275 @@
+//! Plain HTTP status endpoints for orchestrators and uptime monitors, served on
+//! `metrics_listen` next to the metrics.
+//!
+//! `/healthz` is liveness: the coordinator still answers. `/readyz` is readiness:
+//! on top of that every configured listener is accepting and the server isn't
+//! draining. `/metrics` is the counter snapshot as JSON.
+//!
+//! Operators review player reports under `/admin`, with one of `admin_tokens` as
+//! `Authorization: Bearer <token>`: `GET /admin/reports`, `GET /admin/reports/<id>`,
+//! `POST /admin/reports/<id>/dismiss` and `POST /admin/reports/<id>/kick`.
+//! `GET /admin/stats` is the anonymous joker and game mode usage.
+
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+
+use crate::config::CONFIG;
+use crate::messages::{CoordinatorHealth, CoordinatorMessage};
+use crate::metrics::METRICS;
+use crate::usage_stats;
+use crate::utils::constant_time_eq;
+
+/// How long the coordinator gets to answer before it counts as stuck
+const COORDINATOR_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a prober gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Probes send a request line and a few headers, nothing more
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// What a player kicked from the admin endpoints is told
+const MODERATOR_KICK_REASON: &str = "Removed by a moderator";
+
+/// Serve status requests until the listener fails
+pub async fn run_status_server(
+    addr: SocketAddr,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Status endpoints listening on {}", addr);
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        tokio::spawn(serve_request(socket, peer, coordinator_tx.clone()));
+    }
+}
+
+async fn serve_request(
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+) {
+    let Ok(Some(head)) = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut socket)).await
+    else {
+        debug!("Status request from {} timed out", peer);
+        return;
+    };
+    let mut lines = head.lines();
+    let mut parts = lines.next().unwrap_or("").split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or(path);
+    let (status, body) = if let Some(admin_path) = path.strip_prefix("/admin/") {
+        if is_admin(&CONFIG.get().admin_tokens, lines) {
+            admin(method, admin_path, &coordinator_tx).await
+        } else {
+            (401, json!({ "error": "unauthorized" }))
+        }
+    } else if method != "GET" && method != "HEAD" {
+        (405, json!({ "error": "method not allowed" }))
+    } else {
+        let coordinator = match path {
+            "/healthz" | "/readyz" => probe_coordinator(&coordinator_tx).await,
+            _ => None,
+        };
+        respond(path, coordinator)
+    };
+    let body = if method == "HEAD" { String::new() } else { body.to_string() };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        body.len(),
+        body,
+    );
+    let _ = socket.write_all(response.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
+
+/// Read up to the blank line ending the request headers; the body, if any, is ignored
+async fn read_head(socket: &mut TcpStream) -> Option<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = socket.read(&mut buf).await.ok()?;
+        if read == 0 || head.len() + read > MAX_REQUEST_SIZE {
+            return None;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    String::from_utf8(head).ok()
+}
+
+async fn probe_coordinator(
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+) -> Option<CoordinatorHealth> {
+    ask_coordinator(coordinator_tx, |reply_tx| CoordinatorMessage::Health { reply_tx }).await
+}
+
+/// Send the coordinator a request and wait for its answer; `None` if it didn't answer
+async fn ask_coordinator<T>(
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+    request: impl FnOnce(oneshot::Sender<T>) -> CoordinatorMessage,
+) -> Option<T> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    coordinator_tx.send(request(reply_tx)).ok()?;
+    tokio::time::timeout(COORDINATOR_TIMEOUT, reply_rx).await.ok()?.ok()
+}
+
+/// Whether the request headers carry one of `tokens`; nobody is an admin without any
+fn is_admin<'a>(tokens: &[String], mut headers: impl Iterator<Item = &'a str>) -> bool {
+    let presented = headers.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("authorization").then(|| value.trim())
+    });
+    let Some(presented) = presented.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    tokens
+        .iter()
+        .filter(|token| !token.is_empty())
+        .any(|token| constant_time_eq(token.as_bytes(), presented.trim().as_bytes()))
+}
+
+/// Status code and body for an authorized request under `/admin/`
+async fn admin(
+    method: &str,
+    path: &str,
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+) -> (u16, Value) {
+    let unresponsive = (503, json!({ "error": "coordinator unresponsive" }));
+    let segments: Vec<&str> = path.split('/').collect();
+    match (method, segments.as_slice()) {
+        ("GET", ["reports"]) => {
+            let list = |reply_tx| CoordinatorMessage::ListReports { reply_tx };
+            let reports = ask_coordinator(coordinator_tx, list);
+            match reports.await {
+                Some(reports) => (200, json!(reports)),
+                None => unresponsive,
+            }
+        }
+        ("GET", ["reports", report_id]) => {
+            let report = ask_coordinator(coordinator_tx, |reply_tx| CoordinatorMessage::GetReport {
+                report_id: report_id.to_string(),
+                reply_tx,
+            });
+            match report.await {
+                Some(Some(report)) => (200, json!(report)),
+                Some(None) => (404, json!({ "error": "report not found" })),
+                None => unresponsive,
+            }
+        }
+        ("POST", ["reports", report_id, action @ ("dismiss" | "kick")]) => {
+            let kick_reason = (*action == "kick").then(|| MODERATOR_KICK_REASON.to_string());
+            let resolved = ask_coordinator(coordinator_tx, |reply_tx| {
+                CoordinatorMessage::ResolveReport {
+                    report_id: report_id.to_string(),
+                    kick_reason,
+                    reply_tx,
+                }
+            });
+            match resolved.await {
+                Some(Some(kicked)) => (200, json!({ "kicked": kicked })),
+                Some(None) => (404, json!({ "error": "no open report with that id" })),
+                None => unresponsive,
+            }
+        }
+        ("GET", ["stats"]) => (200, json!(usage_stats::snapshot())),
+        (_, ["reports", ..] | ["stats"]) => (405, json!({ "error": "method not allowed" })),
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+/// Status code and body for `path`, given what the coordinator answered (`None` if it didn't)
+fn respond(path: &str, coordinator: Option<CoordinatorHealth>) -> (u16, Value) {
+    match path {
+        "/healthz" => match coordinator {
+            Some(health) => (200, json!({ "status": "ok", "lobbies": health.lobbies })),
+            None => (503, json!({ "status": "coordinator unresponsive" })),
+        },
+        "/readyz" => {
+            let accept_loops = METRICS.accept_loops.load(Ordering::Relaxed);
+            let listeners = CONFIG.get().listen.len() as u64;
+            let ready = coordinator.as_ref().is_some_and(|health| !health.draining)
+                && accept_loops >= listeners;
+            let body = json!({
+                "ready": ready,
+                "coordinator": coordinator,
+                "accept_loops": accept_loops,
+                "listeners": listeners,
+            });
+            (if ready { 200 } else { 503 }, body)
+        }
+        "/metrics" => (200, json!(METRICS.snapshot())),
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probes_reflect_coordinator_state() {
+        let health = CoordinatorHealth {
+            lobbies: 3,
+            players_in_lobbies: 5,
+            draining: false,
+        };
+        let (status, body) = respond("/healthz", Some(health.clone()));
+        assert_eq!(status, 200);
+        assert_eq!(body["lobbies"], 3);
+        assert_eq!(respond("/healthz", None).0, 503);
+        assert_eq!(respond("/readyz", None).0, 503);
+
+        let draining = CoordinatorHealth {
+            draining: true,
+            ..health
+        };
+        let (status, body) = respond("/readyz", Some(draining));
+        assert_eq!(status, 503);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["coordinator"]["draining"], true);
+
+        assert_eq!(respond("/metrics", None).0, 200);
+        assert_eq!(respond("/stats", None).0, 404);
+        assert_eq!(respond("/nope", None).0, 404);
+    }
+
+    #[test]
+    fn test_admin_needs_a_configured_bearer_token() {
+        let tokens = vec!["s3cret".to_string()];
+        let headers = |auth: &'static str| ["Host: localhost", auth].into_iter();
+        assert!(is_admin(&tokens, headers("Authorization: Bearer s3cret")));
+        assert!(is_admin(&tokens, headers("authorization:Bearer s3cret ")));
+        assert!(!is_admin(&tokens, headers("Authorization: Bearer wrong")));
+        assert!(!is_admin(&tokens, headers("Authorization: s3cret")));
+        assert!(!is_admin(&tokens, headers("X-Token: s3cret")));
+        // No tokens, or only an empty one, lets nobody in
+        assert!(!is_admin(&[], headers("Authorization: Bearer s3cret")));
+        assert!(!is_admin(&[String::new()], headers("Authorization: Bearer ")));
+    }
+
+    #[tokio::test]
+    async fn test_usage_stats_are_served_to_admins() {
+        let (coordinator_tx, _rx) = mpsc::unbounded_channel();
+        let (status, body) = admin("GET", "stats", &coordinator_tx).await;
+        assert_eq!(status, 200);
+        assert!(body["jokers"].is_object());
+        assert_eq!(admin("POST", "stats", &coordinator_tx).await.0, 405);
+    }
+}
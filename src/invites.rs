@@ -0,0 +1,101 @@
+//! Invite links: a signed token naming a lobby, shared as a `balatro-mp://` link. The
+//! signature and expiry are checked when someone joins with one, and the lobby lets
+//! each token in only once. The signing key is made at startup, so invites stop
+//! working when the server restarts.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use ring::hmac;
+
+use crate::utils::{decode_hex, encode_hex};
+
+/// How long an invite stays usable
+pub const INVITE_TTL: Duration = Duration::from_secs(15 * 60);
+pub const INVITE_LINK_PREFIX: &str = "balatro-mp://join/";
+
+static KEY: LazyLock<hmac::Key> =
+    LazyLock::new(|| hmac::Key::new(hmac::HMAC_SHA256, &rand::random::<[u8; 32]>()));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invite {
+    pub lobby_code: String,
+    /// Lets the holder take a slot the host reserved for someone else
+    pub bypass_reservations: bool,
+    /// Unix seconds the invite stops working
+    pub expires_at: u64,
+    /// Tells invites apart, lobbies remember the ones they let in
+    pub nonce: String,
+}
+
+impl Invite {
+    pub fn new(lobby_code: String, bypass_reservations: bool, now: u64) -> Self {
+        Self {
+            lobby_code,
+            bypass_reservations,
+            expires_at: now + INVITE_TTL.as_secs(),
+            nonce: encode_hex(&rand::random::<[u8; 8]>()),
+        }
+    }
+
+    fn payload(&self) -> String {
+        let bypass = u8::from(self.bypass_reservations);
+        format!("{}.{}.{}.{}", self.lobby_code, self.expires_at, bypass, self.nonce)
+    }
+
+    /// `<code>.<expires_at>.<bypass>.<nonce>.<signature>`
+    pub fn token(&self) -> String {
+        let payload = self.payload();
+        let signature = hmac::sign(&KEY, payload.as_bytes());
+        format!("{}.{}", payload, encode_hex(signature.as_ref()))
+    }
+
+    pub fn link(&self) -> String {
+        format!("{}{}", INVITE_LINK_PREFIX, self.token())
+    }
+
+    /// The invite behind a token or link, when this server signed it and it hasn't expired
+    pub fn verify(token: &str, now: u64) -> Result<Self, &'static str> {
+        let token = token.strip_prefix(INVITE_LINK_PREFIX).unwrap_or(token);
+        let (payload, signature) = token.rsplit_once('.').ok_or("Invalid invite")?;
+        let signature = decode_hex(signature).ok_or("Invalid invite")?;
+        hmac::verify(&KEY, payload.as_bytes(), &signature).map_err(|_| "Invalid invite")?;
+
+        let fields: Vec<&str> = payload.split('.').collect();
+        let [lobby_code, expires_at, bypass, nonce] = fields[..] else {
+            return Err("Invalid invite");
+        };
+        let expires_at = expires_at.parse::<u64>().map_err(|_| "Invalid invite")?;
+        if now >= expires_at {
+            return Err("Invite has expired");
+        }
+        Ok(Self {
+            lobby_code: lobby_code.to_string(),
+            bypass_reservations: bypass == "1",
+            expires_at,
+            nonce: nonce.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_untampered_unexpired_invites_verify() {
+        let now = 1_700_000_000;
+        let invite = Invite::new("CRISP-JOKER".to_string(), true, now);
+        assert_eq!(Invite::verify(&invite.token(), now), Ok(invite.clone()));
+        assert_eq!(Invite::verify(&invite.link(), now), Ok(invite.clone()));
+
+        // Rewriting the lobby or the bypass breaks the signature
+        let forged = invite.token().replacen("CRISP-JOKER", "OTHER", 1);
+        assert_eq!(Invite::verify(&forged, now), Err("Invalid invite"));
+        let forged = invite.token().replacen(".1.", ".0.", 1);
+        assert_eq!(Invite::verify(&forged, now), Err("Invalid invite"));
+
+        let expired = now + INVITE_TTL.as_secs();
+        assert_eq!(Invite::verify(&invite.token(), expired), Err("Invite has expired"));
+    }
+}
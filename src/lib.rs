@@ -0,0 +1,169 @@
+//! The Balatro Multiplayer server. The binary runs [`serve`]; with the `client-sdk`
+//! feature the crate also offers [`client_sdk`], a typed client for bots, load tests
+//! and community tools.
+
+use anyhow::Context;
+use socket2::{Domain, SockRef, Socket, TcpKeepalive, Type};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+mod accounts;
+mod audit;
+mod client;
+#[cfg(any(test, feature = "client-sdk"))]
+pub mod client_sdk;
+mod challenges;
+mod config;
+mod connections;
+mod console;
+mod federation;
+mod game_mode;
+mod health;
+mod invites;
+mod lobby;
+mod lobby_codes;
+mod lobby_coordinator;
+mod lobby_limits;
+mod messages;
+mod metrics;
+mod moderation;
+mod presence;
+mod scheduled_events;
+mod simulate;
+mod sqlite_store;
+mod talisman_number;
+mod usage_stats;
+mod utils;
+mod vanity;
+mod webhooks;
+mod test_utils;
+
+use crate::client::handle_client;
+use crate::config::CONFIG;
+use crate::connections::{ConnectionMessage, run_connection_registry};
+use crate::lobby_coordinator::lobby_coordinator;
+use crate::metrics::{METRICS, Metrics};
+use crate::messages::CoordinatorMessage;
+
+pub use crate::simulate::run_cli as simulate;
+
+/// Bind the configured listeners and serve clients until one of them fails
+pub async fn serve() -> anyhow::Result<()> {
+    let mut listeners = Vec::new();
+    for &addr in &CONFIG.get().listen {
+        let listener =
+            bind_listener(addr).with_context(|| format!("Failed to bind {}", addr))?;
+        info!("Server listening on {}", addr);
+        listeners.push(listener);
+    }
+    if listeners.is_empty() {
+        anyhow::bail!("No listen addresses configured");
+    }
+
+    // Create the lobby coordinator
+    let (coordinator_tx, coordinator_rx) = mpsc::unbounded_channel::<CoordinatorMessage>();
+
+    // Spawn the lobby coordinator task
+    tokio::spawn(lobby_coordinator(coordinator_rx, coordinator_tx.clone()));
+
+    let (connections_tx, connections_rx) = mpsc::unbounded_channel::<ConnectionMessage>();
+    tokio::spawn(run_connection_registry(connections_rx));
+
+    #[cfg(unix)]
+    tokio::spawn(config::reload_on_sighup());
+
+    // Opt-in: needs both an address and at least one subscriber token
+    let config = CONFIG.get();
+    if let Some(addr) = config.presence_listen
+        && !config.presence_tokens.is_empty()
+    {
+        let coordinator_tx = coordinator_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = presence::run_presence_relay(addr, coordinator_tx).await {
+                error!("Presence relay stopped: {}", e);
+            }
+        });
+    }
+
+    if let Some(addr) = config.metrics_listen {
+        let coordinator_tx = coordinator_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = health::run_status_server(addr, coordinator_tx).await {
+                error!("Status server stopped: {}", e);
+            }
+        });
+    }
+
+    if config.console_enabled {
+        tokio::spawn(console::run_console(coordinator_tx.clone(), connections_tx.clone()));
+    }
+
+    // Every listener feeds the same coordinator; the first one to fail stops the server
+    let mut accept_loops = JoinSet::new();
+    for listener in listeners {
+        accept_loops.spawn(accept_loop(
+            listener,
+            coordinator_tx.clone(),
+            connections_tx.clone(),
+        ));
+    }
+    match accept_loops.join_next().await {
+        Some(Ok(result)) => result,
+        Some(Err(e)) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+/// Bind a listener; IPv6 sockets are v6-only so `[::]` and `0.0.0.0` can share a port
+fn bind_listener(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+    connections_tx: mpsc::UnboundedSender<ConnectionMessage>,
+) -> anyhow::Result<()> {
+    // Readiness probes count these against the configured listeners
+    Metrics::incr(&METRICS.accept_loops);
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                Metrics::decr(&METRICS.accept_loops);
+                return Err(e.into());
+            }
+        };
+
+        // Configure TCP keep-alive
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(10))
+            .with_interval(Duration::from_secs(1));
+        let sf = SockRef::from(&socket);
+        let _ = sf.set_tcp_keepalive(&keepalive);
+
+        // Split the socket for reading and writing
+        let (reader, writer) = socket.into_split();
+
+        // Spawn a client handler
+        tokio::spawn(handle_client(
+            reader,
+            writer,
+            addr,
+            coordinator_tx.clone(),
+            connections_tx.clone(),
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+//! Host announcements: short notes the host pins for the whole lobby, e.g. a
+//! co-op plan or a tournament ruling. Not chat, so only hosts send them and
+//! only a few at a time.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::emotes::allow_within;
+
+const MAX_ANNOUNCEMENT_CHARS: usize = 280;
+/// Announcements a host may make within `ANNOUNCEMENT_WINDOW`
+const ANNOUNCEMENT_BURST: usize = 2;
+const ANNOUNCEMENT_WINDOW: Duration = Duration::from_secs(30);
+
+/// The text as it goes out: trimmed, single-line and within the length limit
+pub fn clean_announcement(text: &str) -> Result<String, &'static str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Announcement is empty");
+    }
+    if text.chars().count() > MAX_ANNOUNCEMENT_CHARS {
+        return Err("Announcement is too long");
+    }
+    if text.chars().any(char::is_control) {
+        return Err("Announcement must be a single line");
+    }
+    Ok(text.to_string())
+}
+
+pub fn allow_announcement(recent: &mut VecDeque<Instant>, now: Instant) -> bool {
+    allow_within(recent, now, ANNOUNCEMENT_BURST, ANNOUNCEMENT_WINDOW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcements_are_checked_and_limited() {
+        assert_eq!(clean_announcement("  Save the Brainstorm  ").unwrap(), "Save the Brainstorm");
+        assert!(clean_announcement("   ").is_err());
+        assert!(clean_announcement("line\nbreak").is_err());
+        assert!(clean_announcement(&"a".repeat(MAX_ANNOUNCEMENT_CHARS + 1)).is_err());
+
+        let mut recent = VecDeque::new();
+        let start = Instant::now();
+        assert!(allow_announcement(&mut recent, start));
+        assert!(allow_announcement(&mut recent, start));
+        assert!(!allow_announcement(&mut recent, start + Duration::from_secs(1)));
+        assert!(allow_announcement(&mut recent, start + ANNOUNCEMENT_WINDOW));
+    }
+}
@@ -0,0 +1,116 @@
+//! End-of-game awards for the results screen, worked out from what the lobby tracked of
+//! each run: the biggest hand, the most discards, the fastest PvP blind won and the
+//! biggest shop bill.
+
+use serde::Serialize;
+
+use super::game_state::ClientGameState;
+use crate::talisman_number::TalismanNumber;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "award")]
+pub enum Award {
+    #[serde(rename = "biggestHand")]
+    BiggestHand { player_id: String, score: TalismanNumber },
+    #[serde(rename = "mostDiscards")]
+    MostDiscards { player_id: String, discards: u32 },
+    /// Milliseconds from a PvP blind starting to the winner finishing it
+    #[serde(rename = "fastestClear")]
+    FastestClear { player_id: String, ms: u64 },
+    #[serde(rename = "mostSpent")]
+    MostSpent { player_id: String, spent: u32 },
+}
+
+/// One award per stat, ties going to the lowest player id; a stat nobody scored on
+/// awards nothing
+pub fn awards<'a>(
+    players: impl IntoIterator<Item = (&'a String, &'a ClientGameState)>,
+) -> Vec<Award> {
+    let mut players: Vec<_> = players.into_iter().collect();
+    players.sort_by(|a, b| a.0.cmp(b.0));
+    let zero = TalismanNumber::Regular(0.0);
+
+    let mut awards = Vec::new();
+    if let Some((player_id, score)) =
+        best(&players, |run| run.biggest_hand.clone().filter(|score| *score > zero))
+    {
+        awards.push(Award::BiggestHand { player_id, score });
+    }
+    if let Some((player_id, discards)) =
+        best(&players, |run| Some(run.discards_used).filter(|&discards| discards > 0))
+    {
+        awards.push(Award::MostDiscards { player_id, discards });
+    }
+    if let Some((player_id, ms)) =
+        best(&players, |run| run.fastest_clear_ms.map(std::cmp::Reverse))
+    {
+        awards.push(Award::FastestClear { player_id, ms: ms.0 });
+    }
+    let spent = |run: &ClientGameState| {
+        let shops = run.spent_in_shop.iter();
+        Some(shops.fold(0u32, |total, &amount| total.saturating_add(amount))).filter(|&t| t > 0)
+    };
+    if let Some((player_id, spent)) = best(&players, spent) {
+        awards.push(Award::MostSpent { player_id, spent });
+    }
+    awards
+}
+
+/// The first player with the highest `stat`
+fn best<T: Ord>(
+    players: &[(&String, &ClientGameState)],
+    stat: impl Fn(&ClientGameState) -> Option<T>,
+) -> Option<(String, T)> {
+    let mut best: Option<(&String, T)> = None;
+    for (player_id, run) in players {
+        let Some(value) = stat(run) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|(_, top)| value > *top) {
+            best = Some((player_id, value));
+        }
+    }
+    best.map(|(player_id, value)| (player_id.clone(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_award_goes_to_the_best_run() {
+        let (alice, bob, carol) = ("alice".to_string(), "bob".to_string(), "carol".to_string());
+        let alice_run = ClientGameState {
+            biggest_hand: Some(TalismanNumber::Regular(12_000.0)),
+            discards_used: 4,
+            fastest_clear_ms: Some(40_000),
+            spent_in_shop: vec![10, 5],
+            ..ClientGameState::default()
+        };
+        let bob_run = ClientGameState {
+            biggest_hand: Some(TalismanNumber::Regular(9_000.0)),
+            discards_used: 4,
+            fastest_clear_ms: Some(25_000),
+            spent_in_shop: vec![30],
+            ..ClientGameState::default()
+        };
+        // Never played a hand, discarded or cleared a blind
+        let carol_run = ClientGameState::default();
+
+        let awards = awards([(&carol, &carol_run), (&bob, &bob_run), (&alice, &alice_run)]);
+        assert_eq!(
+            awards,
+            [
+                Award::BiggestHand {
+                    player_id: alice.clone(),
+                    score: TalismanNumber::Regular(12_000.0),
+                },
+                // A tie goes to the lowest player id
+                Award::MostDiscards { player_id: alice, discards: 4 },
+                Award::FastestClear { player_id: bob.clone(), ms: 25_000 },
+                Award::MostSpent { player_id: bob, spent: 30 },
+            ]
+        );
+        assert!(super::awards([(&carol, &carol_run)]).is_empty());
+    }
+}
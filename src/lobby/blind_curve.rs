@@ -0,0 +1,87 @@
+//! The chips Balatro asks for per ante and stake, used to sanity check boss
+//! chips hosts report and to pace the practice bot.
+
+use crate::talisman_number::TalismanNumber;
+
+/// Base chips of the small blind per ante (0 to 8), for each of the game's scaling
+/// levels: white stake, green stake and up, purple stake and up
+const ANTE_BASE_CHIPS: [[f64; 9]; 3] = [
+    [100.0, 300.0, 800.0, 2_000.0, 5_000.0, 11_000.0, 20_000.0, 35_000.0, 50_000.0],
+    [100.0, 300.0, 900.0, 2_600.0, 8_000.0, 20_000.0, 36_000.0, 60_000.0, 100_000.0],
+    [100.0, 300.0, 1_000.0, 3_200.0, 9_000.0, 25_000.0, 60_000.0, 110_000.0, 200_000.0],
+];
+/// Growth per ante past the end of the table
+const LATE_ANTE_GROWTH: f64 = 1.6;
+/// Stakes from which blinds scale faster
+const GREEN_STAKE: u32 = 3;
+const PURPLE_STAKE: u32 = 6;
+
+/// Smallest share of the curve a boss may ask for, The Needle asks for half
+const MIN_BOSS_FACTOR: f64 = 0.25;
+/// Largest multiple of the curve a boss may ask for: Violet Vessel on a Plasma deck
+/// is 6x, with room for challenges. Only checked within the table, late antes grow
+/// much faster than the curve here.
+const MAX_BOSS_FACTOR: f64 = 16.0;
+
+/// Chips a regular boss blind needs at `ante` on `stake`
+pub fn boss_blind_chips(ante: u32, stake: u32) -> f64 {
+    let scaling = match stake {
+        s if s >= PURPLE_STAKE => 2,
+        s if s >= GREEN_STAKE => 1,
+        _ => 0,
+    };
+    let table = &ANTE_BASE_CHIPS[scaling];
+    let last = table.len() - 1;
+    let index = (ante as usize).min(last);
+    let late_antes = (ante as usize).saturating_sub(last) as i32;
+    table[index] * LATE_ANTE_GROWTH.powi(late_antes) * 2.0
+}
+
+/// Whether `chips` could be the boss blind at `ante` on `stake`. An ante of 0 means the
+/// host hasn't reported one yet, then only the value itself is checked.
+pub fn check_boss_chips(chips: &TalismanNumber, ante: u32, stake: u32) -> Result<(), &'static str> {
+    // NaN orders below zero too
+    if *chips <= TalismanNumber::Regular(0.0) {
+        return Err("Boss chips must be a positive number");
+    }
+    if ante == 0 {
+        return Ok(());
+    }
+    let expected = boss_blind_chips(ante, stake);
+    if *chips < TalismanNumber::Regular(expected * MIN_BOSS_FACTOR) {
+        return Err("Boss chips are too low for this ante");
+    }
+    if (ante as usize) < ANTE_BASE_CHIPS[0].len()
+        && *chips > TalismanNumber::Regular(expected * MAX_BOSS_FACTOR)
+    {
+        return Err("Boss chips are too high for this ante");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boss_chips_follow_the_curve() {
+        assert_eq!(boss_blind_chips(1, 1), 600.0);
+        assert_eq!(boss_blind_chips(4, 3), 16_000.0);
+        assert_eq!(boss_blind_chips(8, 8), 400_000.0);
+
+        let chips = |n: f64| TalismanNumber::Regular(n);
+        assert!(check_boss_chips(&chips(4_000.0), 3, 1).is_ok());
+        // The Wall and The Needle
+        assert!(check_boss_chips(&chips(8_000.0), 3, 1).is_ok());
+        assert!(check_boss_chips(&chips(1_000.0), 3, 1).is_ok());
+        assert!(check_boss_chips(&chips(1.0), 3, 1).is_err());
+        assert!(check_boss_chips(&chips(1e12), 3, 1).is_err());
+        assert!(check_boss_chips(&chips(-5.0), 0, 1).is_err());
+        assert!(check_boss_chips(&chips(f64::NAN), 0, 1).is_err());
+
+        // Late antes outgrow any curve, Talisman values included
+        let late = TalismanNumber::Big { m: 1.0, e: 400.0 };
+        assert!(check_boss_chips(&late, 30, 8).is_ok());
+        assert!(check_boss_chips(&late, 2, 1).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Optional phase before a PvP blind: every player bans one boss from a pool
+//! dealt by the server, then the server picks the boss from what is left.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long players have to ban, missing bans are skipped
+pub const BOSS_BAN_TIMEOUT: Duration = Duration::from_secs(20);
+/// Bosses dealt per ban phase, raised when needed so one survives every ban
+pub const BOSS_BAN_POOL_SIZE: usize = 5;
+
+/// Boss blinds the pool is dealt from, the finisher bosses are left out
+pub const BOSS_BLINDS: &[&str] = &[
+    "bl_hook",
+    "bl_ox",
+    "bl_house",
+    "bl_wall",
+    "bl_wheel",
+    "bl_arm",
+    "bl_club",
+    "bl_fish",
+    "bl_psychic",
+    "bl_goad",
+    "bl_water",
+    "bl_window",
+    "bl_manacle",
+    "bl_eye",
+    "bl_mouth",
+    "bl_plant",
+    "bl_serpent",
+    "bl_pillar",
+    "bl_needle",
+    "bl_head",
+    "bl_tooth",
+    "bl_flint",
+    "bl_mark",
+];
+
+#[derive(Debug, Clone)]
+pub struct BossBanPhase {
+    pub pool: Vec<String>,
+    /// Banned boss per player
+    bans: HashMap<String, String>,
+    pub deadline: Instant,
+}
+
+impl BossBanPhase {
+    pub fn new(pool: Vec<String>, deadline: Instant) -> Self {
+        Self {
+            pool,
+            bans: HashMap::new(),
+            deadline,
+        }
+    }
+
+    pub fn ban(&mut self, player_id: &str, key: &str) -> Result<(), &'static str> {
+        if !self.pool.iter().any(|boss| boss == key) {
+            return Err("That boss is not in the ban pool");
+        }
+        if self.bans.contains_key(player_id) {
+            return Err("You already banned a boss");
+        }
+        if self.bans.values().any(|banned| banned == key) {
+            return Err("That boss is already banned");
+        }
+        self.bans.insert(player_id.to_string(), key.to_string());
+        Ok(())
+    }
+
+    pub fn has_banned(&self, player_id: &str) -> bool {
+        self.bans.contains_key(player_id)
+    }
+
+    /// Bosses nobody banned, in pool order
+    pub fn remaining(&self) -> Vec<String> {
+        self.pool
+            .iter()
+            .filter(|boss| !self.bans.values().any(|banned| banned == *boss))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_player_bans_one_distinct_boss_from_pool() {
+        let pool = vec!["bl_hook".to_string(), "bl_ox".to_string(), "bl_wall".to_string()];
+        let mut phase = BossBanPhase::new(pool, Instant::now() + BOSS_BAN_TIMEOUT);
+
+        assert!(phase.ban("p1", "bl_psychic").is_err());
+        assert!(phase.ban("p1", "bl_ox").is_ok());
+        assert!(phase.ban("p1", "bl_hook").is_err());
+        assert!(phase.ban("p2", "bl_ox").is_err());
+        assert!(phase.ban("p2", "bl_wall").is_ok());
+
+        assert!(phase.has_banned("p2"));
+        assert_eq!(phase.remaining(), vec!["bl_hook".to_string()]);
+    }
+}
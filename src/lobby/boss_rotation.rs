@@ -0,0 +1,80 @@
+//! Bosses a lobby played recently, so the boss the server suggests doesn't repeat
+//! them. Only kept when the host picks bosses (`normal_bosses` off); long co-op runs
+//! otherwise see the same boss back to back.
+
+use std::collections::VecDeque;
+
+use super::boss_ban::BOSS_BLINDS;
+use super::shared_rng::SharedRng;
+
+/// Bosses remembered, suggestions avoid all of them while any other is left
+pub const BOSS_MEMORY: usize = 8;
+const MAX_BOSS_KEY_LEN: usize = 64;
+
+#[derive(Debug, Clone, Default)]
+pub struct BossRotation {
+    /// Oldest first
+    recent: VecDeque<String>,
+    /// Bosses played this game, keys the suggestion rolls
+    played: u32,
+}
+
+impl BossRotation {
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.played = 0;
+    }
+
+    /// Remember a boss the lobby played, a repeat counts as the most recent again
+    pub fn record(&mut self, key: &str) {
+        if key.len() > MAX_BOSS_KEY_LEN {
+            return;
+        }
+        self.recent.retain(|boss| boss != key);
+        self.recent.push_back(key.to_string());
+        if self.recent.len() > BOSS_MEMORY {
+            self.recent.pop_front();
+        }
+        self.played += 1;
+    }
+
+    /// A boss the lobby hasn't seen lately, the same for everyone asking until the
+    /// next boss is played. With every boss seen lately, the one seen longest ago.
+    pub fn suggest(&self, rng: &SharedRng) -> String {
+        let mut pool: Vec<&str> = BOSS_BLINDS
+            .iter()
+            .copied()
+            .filter(|boss| !self.recent.iter().any(|seen| seen == boss))
+            .collect();
+        rng.shuffle(&format!("boss_suggestion:{}", self.played), &mut pool);
+        match pool.first() {
+            Some(boss) => boss.to_string(),
+            None => self.recent.front().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggestions_skip_recent_bosses() {
+        let rng = SharedRng::with_seed(7);
+        let mut rotation = BossRotation::default();
+        let first = rotation.suggest(&rng);
+        assert_eq!(rotation.suggest(&rng), first);
+
+        for _ in 0..BOSS_BLINDS.len() * 2 {
+            let boss = rotation.suggest(&rng);
+            assert!(!rotation.recent.contains(&boss));
+            rotation.record(&boss);
+        }
+        assert_eq!(rotation.recent.len(), BOSS_MEMORY);
+
+        rotation.record("bl_hook");
+        rotation.record("bl_hook");
+        assert_eq!(rotation.recent.iter().filter(|boss| *boss == "bl_hook").count(), 1);
+        assert_eq!(rotation.recent.back().map(String::as_str), Some("bl_hook"));
+    }
+}
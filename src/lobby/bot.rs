@@ -0,0 +1,284 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::lobby::blind_curve::boss_blind_chips;
+use crate::messages::{ClientToServer, LobbyMessage, ServerToClient};
+use crate::talisman_number::TalismanNumber;
+
+/// Hands a bot plays per PvP blind
+const BOT_HANDS: u8 = 4;
+/// Bots pace themselves against white stake blinds
+const BOT_STAKE: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotDifficulty {
+    #[serde(rename = "easy")]
+    Easy,
+    #[serde(rename = "medium")]
+    Medium,
+    #[serde(rename = "hard")]
+    Hard,
+}
+
+impl BotDifficulty {
+    /// Multiple of the boss blind the bot scores over a whole PvP blind
+    fn score_factor(self) -> f64 {
+        match self {
+            Self::Easy => 0.8,
+            Self::Medium => 1.3,
+            Self::Hard => 2.0,
+        }
+    }
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Easy => "Bot (Easy)",
+            Self::Medium => "Bot (Medium)",
+            Self::Hard => "Bot (Hard)",
+        }
+    }
+}
+
+impl std::str::FromStr for BotDifficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(format!("Unknown bot difficulty: {}", s)),
+        }
+    }
+}
+
+/// A practice opponent that reacts to the same messages a real client gets and
+/// answers with regular client actions, so the lobby evaluates it like anyone else.
+#[derive(Debug)]
+pub struct Bot {
+    id: String,
+    difficulty: BotDifficulty,
+    started: bool,
+    ante: u32,
+    pvp_rounds: u32,
+    hands_left: u8,
+    round_target: f64,
+    rng: StdRng,
+}
+
+impl Bot {
+    pub fn new(id: String, difficulty: BotDifficulty) -> Self {
+        Self::with_seed(id, difficulty, rand::rng().random())
+    }
+
+    /// A bot whose bans and scores replay exactly, for simulations and tests
+    pub fn with_seed(id: String, difficulty: BotDifficulty, seed: u64) -> Self {
+        Self {
+            id,
+            difficulty,
+            started: false,
+            ante: 1,
+            pvp_rounds: 0,
+            hands_left: 0,
+            round_target: 0.0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.hands_left > 0
+    }
+
+    pub fn handle(&mut self, msg: &ServerToClient) -> Vec<ClientToServer> {
+        match msg {
+            ServerToClient::JoinedLobby { .. } => vec![ClientToServer::SetReady { is_ready: true }],
+            ServerToClient::LobbyReady { ready_states }
+                if !self.started && ready_states.get(&self.id) == Some(&false) =>
+            {
+                vec![ClientToServer::SetReady { is_ready: true }]
+            }
+            ServerToClient::GameStarted { .. } => {
+                self.started = true;
+                self.ante = 1;
+                self.pvp_rounds = 0;
+                self.hands_left = 0;
+                // Always ready for the next PvP blind, it starts once the humans are
+                vec![ClientToServer::SetReady { is_ready: true }]
+            }
+            // Keep pace with the opponents' run
+            ServerToClient::GameStateUpdate {
+                player_id,
+                game_state,
+            } if *player_id != self.id => {
+                self.ante = self.ante.max(game_state.ante);
+                Vec::new()
+            }
+            ServerToClient::BossBanStarted { pool, .. } if self.started => pool
+                .choose(&mut self.rng)
+                .map(|key| ClientToServer::BanBoss { key: key.clone() })
+                .into_iter()
+                .collect(),
+            ServerToClient::StartBlind { .. } if self.started => {
+                let variance = self.rng.random_range(0.75..1.25);
+                self.round_target =
+                    boss_blind_chips(self.ante, BOT_STAKE) * self.difficulty.score_factor() * variance;
+                self.hands_left = BOT_HANDS;
+                Vec::new()
+            }
+            ServerToClient::EndPvp { .. } => {
+                self.hands_left = 0;
+                self.pvp_rounds += 1;
+                self.ante = self.ante.max(self.pvp_rounds + 1);
+                vec![
+                    ClientToServer::SetAnte { ante: self.ante },
+                    ClientToServer::SetReady { is_ready: true },
+                ]
+            }
+            // Leave the finished game only once the humans have, so nobody is
+            // handed a win for the bot walking out
+            ServerToClient::InGameStatuses { statuses, started }
+                if *started
+                    && statuses.get(&self.id) == Some(&true)
+                    && statuses.iter().all(|(id, in_game)| *id == self.id || !in_game) =>
+            {
+                self.started = false;
+                self.hands_left = 0;
+                vec![ClientToServer::ReturnToLobby {}]
+            }
+            ServerToClient::WinGame { .. } | ServerToClient::LoseGame { .. } => {
+                self.started = false;
+                self.hands_left = 0;
+                vec![ClientToServer::ReturnToLobby {}]
+            }
+            ServerToClient::GameStopped { .. } => {
+                self.started = false;
+                self.hands_left = 0;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Play the next hand of the current PvP blind
+    pub fn next_hand(&mut self) -> Option<ClientToServer> {
+        if self.hands_left == 0 {
+            return None;
+        }
+        self.hands_left -= 1;
+        let share = self.rng.random_range(0.6..1.4) / BOT_HANDS as f64;
+        Some(ClientToServer::PlayHand {
+            score: TalismanNumber::Regular((self.round_target * share).round()),
+            hands_left: self.hands_left,
+            breakdown: None,
+        })
+    }
+
+    fn think_time() -> Duration {
+        Duration::from_millis(rand::rng().random_range(1_500..5_000))
+    }
+}
+
+/// Drive a bot until the lobby drops its sender
+pub async fn run_bot(
+    mut bot: Bot,
+    mut events: mpsc::UnboundedReceiver<Arc<ServerToClient>>,
+    actions_tx: mpsc::UnboundedSender<LobbyMessage>,
+) {
+    let mut next_hand_at: Option<Instant> = None;
+    loop {
+        let actions = tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else { break };
+                bot.handle(&event)
+            }
+            _ = tokio::time::sleep_until(next_hand_at.unwrap_or_else(Instant::now)),
+                if next_hand_at.is_some() =>
+            {
+                next_hand_at = None;
+                bot.next_hand().into_iter().collect()
+            }
+        };
+        for action in actions {
+            if actions_tx
+                .send(LobbyMessage::client_action(bot.id.clone(), action, None, 0))
+                .is_err()
+            {
+                return;
+            }
+        }
+        if !bot.is_playing() {
+            next_hand_at = None;
+        } else if next_hand_at.is_none() {
+            next_hand_at = Some(Instant::now() + Bot::think_time());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lobby::ClientGameState;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_bot_plays_out_a_pvp_blind() {
+        let mut bot = Bot::new("bot".to_string(), BotDifficulty::Medium);
+        let readied = bot.handle(&ServerToClient::GameStarted {
+            seed: "seed".to_string(),
+            stake: 1,
+        });
+        assert!(matches!(readied[..], [ClientToServer::SetReady { is_ready: true }]));
+
+        bot.handle(&ServerToClient::GameStateUpdate {
+            player_id: "human".to_string(),
+            game_state: ClientGameState {
+                ante: 3,
+                ..ClientGameState::default()
+            },
+        });
+        bot.handle(&ServerToClient::StartBlind { server_time: 0 });
+
+        let mut total = 0.0;
+        let mut last_hands_left = None;
+        while let Some(action) = bot.next_hand() {
+            let ClientToServer::PlayHand { score, hands_left, .. } = action else {
+                panic!("bot should only play hands");
+            };
+            total += score.to_f64().unwrap();
+            last_hands_left = Some(hands_left);
+        }
+        assert_eq!(last_hands_left, Some(0));
+        // Medium scores 1.3x the boss blind, give or take the random spread
+        let boss = boss_blind_chips(3, BOT_STAKE);
+        assert!(total > boss * 0.5 && total < boss * 2.5, "total {total}");
+    }
+
+    #[test]
+    fn test_bot_waits_for_humans_before_returning_to_lobby() {
+        let mut bot = Bot::new("bot".to_string(), BotDifficulty::Easy);
+        bot.handle(&ServerToClient::GameStarted {
+            seed: "seed".to_string(),
+            stake: 1,
+        });
+        let statuses = |human: bool| ServerToClient::InGameStatuses {
+            statuses: HashMap::from([("bot".to_string(), true), ("human".to_string(), human)]),
+            started: true,
+        };
+        assert!(bot.handle(&statuses(true)).is_empty());
+        assert!(matches!(
+            bot.handle(&statuses(false))[..],
+            [ClientToServer::ReturnToLobby {}]
+        ));
+    }
+}
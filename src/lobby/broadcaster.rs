@@ -1,17 +1,229 @@
-use crate::messages::ServerToClient;
-use std::collections::HashMap;
-use std::sync::Arc;
+use crate::game_mode::HIDDEN_SEED;
+use crate::lobby::game_state::DEFAULT_TEAM;
+use crate::messages::{EventClass, ServerToClient, Subscriptions};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::error;
 
+/// Numbered messages kept per player for `resumeLobby`
+const RESUME_BUFFER_LEN: usize = 256;
+/// How far back a reconnecting client may resume from, older gaps need a full resync
+const RESUME_WINDOW: Duration = Duration::from_secs(10);
+
+/// State updates waiting to be merged with newer ones before going out
+#[derive(Default)]
+struct PendingUpdates {
+    flush_at: Option<Instant>,
+    per_player: HashMap<String, Vec<Arc<ServerToClient>>>,
+}
+
+/// Recent messages to a player who asked for numbered messages, so after a quick
+/// reconnect they are sent only what they missed instead of the whole lobby again
+#[derive(Default)]
+struct ResumeBuffer {
+    last_seq: u64,
+    recent: VecDeque<(u64, Instant, Arc<ServerToClient>)>,
+}
+
+impl ResumeBuffer {
+    /// Number `message` and remember it, returning its sequence number
+    fn record(&mut self, message: Arc<ServerToClient>, now: Instant) -> u64 {
+        self.last_seq += 1;
+        self.recent.push_back((self.last_seq, now, message));
+        while self.recent.len() > RESUME_BUFFER_LEN
+            || self.recent.front().is_some_and(|(_, at, _)| now.duration_since(*at) > RESUME_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.last_seq
+    }
+
+    /// Everything sent after `last_seq`, numbered again; None when part of it is gone
+    /// or too old, or the client claims messages that were never sent
+    fn after(&self, last_seq: u64, now: Instant) -> Option<Vec<Arc<ServerToClient>>> {
+        if last_seq > self.last_seq {
+            return None;
+        }
+        let mut missed = self.recent.iter().filter(|(seq, _, _)| *seq > last_seq).peekable();
+        if let Some((first, at, _)) = missed.peek()
+            && (*first != last_seq + 1 || now.duration_since(*at) > RESUME_WINDOW)
+        {
+            return None;
+        }
+        if missed.peek().is_none() && last_seq < self.last_seq {
+            return None;
+        }
+        Some(
+            missed
+                .map(|(seq, _, message)| sequenced(*seq, Arc::clone(message)))
+                .collect(),
+        )
+    }
+}
+
+fn sequenced(seq: u64, message: Arc<ServerToClient>) -> Arc<ServerToClient> {
+    Arc::new(ServerToClient::Sequenced { seq, message })
+}
+
+/// A client watching the lobby from outside it
+struct Spectator {
+    sender: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    /// Message classes it opted out of, as for players
+    subscriptions: Subscriptions,
+}
+
+/// `message` as spectators may see it: no seed they could pass on to a player, and
+/// runs only as far as the scoreboard goes. None when they don't get it at all.
+fn spectator_view(message: &Arc<ServerToClient>) -> Option<Arc<ServerToClient>> {
+    let redacted = match message.as_ref() {
+        ServerToClient::GameStarted { stake, .. } => ServerToClient::GameStarted {
+            seed: HIDDEN_SEED.to_string(),
+            stake: *stake,
+        },
+        ServerToClient::UpdateLobbyOptions {
+            options,
+            changed_by,
+            changes,
+        } => {
+            let mut options = options.clone();
+            options.hide_seed();
+            let mut changes = changes.clone();
+            if let Some(seed) = changes.get_mut("custom_seed") {
+                *seed = Value::from(options.custom_seed.clone());
+            }
+            ServerToClient::UpdateLobbyOptions {
+                options,
+                changed_by: changed_by.clone(),
+                changes,
+            }
+        }
+        ServerToClient::GameStateUpdate {
+            player_id,
+            game_state,
+        } => ServerToClient::GameStateUpdate {
+            player_id: player_id.clone(),
+            game_state: game_state.spectator_view(),
+        },
+        ServerToClient::ShopSpending { .. } => return None,
+        _ => return Some(Arc::clone(message)),
+    };
+    Some(Arc::new(redacted))
+}
+
 pub struct LobbyBroadcaster {
     player_senders: HashMap<String, mpsc::UnboundedSender<Arc<ServerToClient>>>,
+    /// Clients watching from other lobbies; they get what goes to every player, through
+    /// [`spectator_view`] and tagged with `lobby_code`
+    spectators: HashMap<String, Spectator>,
+    /// Players not on the default team, kept in step by `Lobby::assign_team`
+    teams: HashMap<String, u8>,
+    /// Players that opted out of some broadcasts
+    subscriptions: HashMap<String, Subscriptions>,
+    lobby_code: String,
+    /// Hold back coalescable updates this long, `None` sends everything at once
+    coalesce_window: Option<Duration>,
+    pending: Mutex<PendingUpdates>,
+    /// Players getting numbered messages
+    resume: Mutex<HashMap<String, ResumeBuffer>>,
+    /// Dropped players whose seat is held; their messages are only buffered
+    parked: HashSet<String>,
 }
 
 impl LobbyBroadcaster {
     pub fn new() -> Self {
         Self {
             player_senders: HashMap::new(),
+            spectators: HashMap::new(),
+            teams: HashMap::new(),
+            subscriptions: HashMap::new(),
+            lobby_code: String::new(),
+            coalesce_window: None,
+            pending: Mutex::new(PendingUpdates::default()),
+            resume: Mutex::new(HashMap::new()),
+            parked: HashSet::new(),
+        }
+    }
+
+    /// Merge bursts of `GameStateUpdate`/`LobbyReady` per player within `window`.
+    /// Anything else flushes a player's held updates first, so ordering is kept.
+    pub fn with_coalescing(window: Duration) -> Self {
+        Self {
+            coalesce_window: Some(window),
+            ..Self::new()
+        }
+    }
+
+    /// Code spectators see on this broadcaster's messages
+    pub fn for_lobby(mut self, lobby_code: String) -> Self {
+        self.lobby_code = lobby_code;
+        self
+    }
+
+    pub fn add_spectator(
+        &mut self,
+        client_id: String,
+        sender: mpsc::UnboundedSender<Arc<ServerToClient>>,
+        subscriptions: Subscriptions,
+    ) {
+        self.spectators.insert(client_id, Spectator { sender, subscriptions });
+    }
+
+    pub fn remove_spectator(&mut self, client_id: &str) -> bool {
+        self.spectators.remove(client_id).is_some()
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.spectators.len()
+    }
+
+    pub fn set_spectator_subscriptions(&mut self, client_id: &str, subscriptions: Subscriptions) {
+        if let Some(spectator) = self.spectators.get_mut(client_id) {
+            spectator.subscriptions = subscriptions;
+        }
+    }
+
+    /// Send straight to a spectator, redacted and tagged like everything else they get
+    /// from this lobby
+    pub fn send_to_spectator(&self, client_id: &str, response: ServerToClient) {
+        if let Some(spectator) = self.spectators.get(client_id)
+            && let Some(message) = spectator_view(&Arc::new(response))
+        {
+            let _ = spectator.sender.send(self.tagged(message));
+        }
+    }
+
+    /// Tell every spectator the lobby is closing
+    pub fn end_spectating(&mut self) {
+        self.send_to_spectators(Arc::new(ServerToClient::SpectatingEnded {}));
+        self.spectators.clear();
+    }
+
+    fn tagged(&self, message: Arc<ServerToClient>) -> Arc<ServerToClient> {
+        Arc::new(ServerToClient::ForLobby {
+            lobby_code: self.lobby_code.clone(),
+            message,
+        })
+    }
+
+    fn send_to_spectators(&self, message: Arc<ServerToClient>) {
+        let mut subscribed = self
+            .spectators
+            .values()
+            .filter(|spectator| spectator.subscriptions.wants(&message))
+            .peekable();
+        if subscribed.peek().is_none() {
+            return;
+        }
+        let Some(message) = spectator_view(&message) else {
+            return;
+        };
+        // One tagged message shared by all of them, so it is encoded once
+        let message = self.tagged(message);
+        for spectator in subscribed {
+            let _ = spectator.sender.send(Arc::clone(&message));
         }
     }
 
@@ -23,35 +235,193 @@ impl LobbyBroadcaster {
         self.player_senders.insert(player_id, sender);
     }
 
+    /// The channel a player is reached on, to hand them to another lobby
+    pub fn sender_for(&self, player_id: &str) -> Option<mpsc::UnboundedSender<Arc<ServerToClient>>> {
+        self.player_senders.get(player_id).cloned()
+    }
+
     pub fn remove_player(&mut self, player_id: &str) {
+        self.flush_player(player_id);
         self.player_senders.remove(player_id);
+        self.teams.remove(player_id);
+        self.subscriptions.remove(player_id);
+        self.parked.remove(player_id);
+        self.resume_buffers().remove(player_id);
     }
 
-    pub fn send_to(&self, player_id: &str, response: ServerToClient) {
+    /// Number every message to this player from now on, so they can resume later
+    pub fn enable_resume(&mut self, player_id: &str) {
+        self.resume_buffers().entry(player_id.to_string()).or_default();
+    }
+
+    /// The player's connection dropped but their seat is held: keep buffering what
+    /// they would have been sent if they can resume, otherwise forget them
+    pub fn park_player(&mut self, player_id: &str) {
+        if !self.resume_buffers().contains_key(player_id) {
+            self.remove_player(player_id);
+            return;
+        }
+        self.flush_player(player_id);
+        self.player_senders.remove(player_id);
+        self.parked.insert(player_id.to_string());
+    }
+
+    /// Hand a parked player's buffer to their new connection and return what they
+    /// missed since `last_seq`. None when that can't be told, the caller resyncs them.
+    pub fn resume_player(
+        &mut self,
+        old_id: &str,
+        new_id: &str,
+        last_seq: u64,
+    ) -> Option<Vec<Arc<ServerToClient>>> {
+        self.parked.remove(old_id);
+        let mut buffers = self.resume_buffers();
+        let buffer = buffers.remove(old_id)?;
+        let missed = buffer.after(last_seq, Instant::now())?;
+        buffers.insert(new_id.to_string(), buffer);
+        Some(missed)
+    }
+
+    /// Send messages that were numbered already, e.g. replayed from a resume buffer
+    pub fn replay_to(&self, player_id: &str, messages: Vec<Arc<ServerToClient>>) {
+        if let Some(sender) = self.player_senders.get(player_id) {
+            for message in messages {
+                let _ = sender.send(message);
+            }
+        }
+    }
+
+    fn resume_buffers(&self) -> std::sync::MutexGuard<'_, HashMap<String, ResumeBuffer>> {
+        self.resume.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_subscriptions(&mut self, player_id: &str, subscriptions: Subscriptions) {
+        if subscriptions == Subscriptions::default() {
+            self.subscriptions.remove(player_id);
+        } else {
+            self.subscriptions.insert(player_id.to_string(), subscriptions);
+        }
+    }
+
+    /// Whether broadcasts of `class` reach the player
+    pub fn subscribed_to(&self, player_id: &str, class: EventClass) -> bool {
+        self.subscriptions
+            .get(player_id)
+            .is_none_or(|subscriptions| subscriptions.accepts(class))
+    }
+
+    pub fn update_subscriptions(
+        &mut self,
+        player_id: &str,
+        subscribe: &[EventClass],
+        unsubscribe: &[EventClass],
+    ) {
+        let mut subscriptions = self.subscriptions.get(player_id).copied().unwrap_or_default();
+        subscriptions.update(subscribe, unsubscribe);
+        self.set_subscriptions(player_id, subscriptions);
+    }
+
+    pub fn set_team(&mut self, player_id: &str, team: u8) {
+        if team == DEFAULT_TEAM {
+            self.teams.remove(player_id);
+        } else {
+            self.teams.insert(player_id.to_string(), team);
+        }
+    }
+
+    fn team_of(&self, player_id: &str) -> u8 {
+        self.teams.get(player_id).copied().unwrap_or(DEFAULT_TEAM)
+    }
+
+    /// When held updates are due, if there are any
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).flush_at
+    }
+
+    /// Send every held update now
+    pub fn flush(&self) {
+        let per_player = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending.flush_at = None;
+            std::mem::take(&mut pending.per_player)
+        };
+        for (player_id, messages) in per_player {
+            for message in messages {
+                self.send_now(&player_id, message);
+            }
+        }
+    }
+
+    fn flush_player(&self, player_id: &str) {
+        let held = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .per_player
+            .remove(player_id);
+        for message in held.into_iter().flatten() {
+            self.send_now(player_id, message);
+        }
+    }
+
+    fn send_now(&self, player_id: &str, message: Arc<ServerToClient>) {
+        let message = match self.resume_buffers().get_mut(player_id) {
+            Some(buffer) => sequenced(buffer.record(Arc::clone(&message), Instant::now()), message),
+            None => message,
+        };
         if let Some(sender) = self.player_senders.get(player_id) {
-            if let Err(e) = sender.send(Arc::new(response)) {
+            if let Err(e) = sender.send(message) {
                 error!("Failed to send message to {}: {}", player_id, e);
             }
         }
     }
 
-    // DRY: Single broadcast implementation with filter
-    fn broadcast_to_filtered<F>(&self, response: ServerToClient, filter: F)
+    fn deliver(&self, player_id: &str, message: Arc<ServerToClient>) {
+        let Some(window) = self.coalesce_window else {
+            self.send_now(player_id, message);
+            return;
+        };
+        let Some(key) = coalesce_key(&message) else {
+            self.flush_player(player_id);
+            self.send_now(player_id, message);
+            return;
+        };
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.flush_at.get_or_insert_with(|| Instant::now() + window);
+        let held = pending.per_player.entry(player_id.to_string()).or_default();
+        // A newer update for the same subject replaces the held one where it stands
+        match held.iter_mut().find(|m| coalesce_key(m) == Some(key)) {
+            Some(older) => *older = message,
+            None => held.push(message),
+        }
+    }
+
+    pub fn send_to(&self, player_id: &str, response: ServerToClient) {
+        self.deliver(player_id, Arc::new(response));
+    }
+
+    // DRY: Single broadcast implementation with filter. Players who unsubscribed from the
+    // message's class are skipped; what is sent to one player on request always goes out.
+    fn broadcast_to_filtered<F>(&self, response: ServerToClient, filter: F) -> Arc<ServerToClient>
     where
         F: Fn(&str) -> bool,
     {
         let message = Arc::new(response);
-        for (player_id, sender) in self.player_senders.iter() {
-            if filter(player_id) {
-                if let Err(e) = sender.send(Arc::clone(&message)) {
-                    error!("Failed to send message to {}: {}", player_id, e);
-                }
+        for player_id in self.player_senders.keys().chain(&self.parked) {
+            let subscribed = self
+                .subscriptions
+                .get(player_id)
+                .is_none_or(|subscriptions| subscriptions.wants(&message));
+            if subscribed && filter(player_id) {
+                self.deliver(player_id, Arc::clone(&message));
             }
         }
+        message
     }
 
     pub fn broadcast(&self, response: ServerToClient) {
-        self.broadcast_to_filtered(response, |_| true);
+        let message = self.broadcast_to_filtered(response, |_| true);
+        self.send_to_spectators(message);
     }
 
     pub fn broadcast_to(&self, player_ids: &[String], response: ServerToClient) {
@@ -60,7 +430,224 @@ impl LobbyBroadcaster {
         self.broadcast_to_filtered(response, |id| id_set.contains(id));
     }
 
+    /// Only the players on `team`; spectators watch the whole lobby and don't get these
+    pub fn broadcast_to_team(&self, team: u8, response: ServerToClient) {
+        self.broadcast_to_filtered(response, |id| self.team_of(id) == team);
+    }
+
+    /// Spectators get these too, the excluded player already knows what happened
     pub fn broadcast_except(&self, except: &str, response: ServerToClient) {
-        self.broadcast_to_filtered(response, |id| id != except);
+        let message = self.broadcast_to_filtered(response, |id| id != except);
+        self.send_to_spectators(message);
+    }
+
+    /// Send each player their own version of a message, skipping those `build` returns None for
+    pub fn broadcast_per_player<F>(&self, build: F)
+    where
+        F: Fn(&str) -> Option<ServerToClient>,
+    {
+        for player_id in self.player_senders.keys().chain(&self.parked) {
+            if let Some(response) = build(player_id) {
+                self.send_to(player_id, response);
+            }
+        }
+    }
+}
+
+/// Updates where only the newest one matters: one per player's game state, one for ready states
+fn coalesce_key(message: &ServerToClient) -> Option<(u8, &str)> {
+    match message {
+        ServerToClient::GameStateUpdate { player_id, .. } => Some((0, player_id)),
+        ServerToClient::LobbyReady { .. } => Some((1, "")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lobby::ClientGameState;
+    use crate::messages::StopReason;
+
+    fn game_state_update(player_id: &str, lives: u8) -> ServerToClient {
+        ServerToClient::GameStateUpdate {
+            player_id: player_id.to_string(),
+            game_state: ClientGameState {
+                lives,
+                ..ClientGameState::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_spectators_get_lobby_wide_messages_tagged() {
+        let (player_tx, mut player_rx) = mpsc::unbounded_channel();
+        let (spectator_tx, mut spectator_rx) = mpsc::unbounded_channel();
+        let mut broadcaster = LobbyBroadcaster::new().for_lobby("ABCDE".to_string());
+        broadcaster.add_player("p1".to_string(), player_tx);
+        broadcaster.add_spectator("s1".to_string(), spectator_tx, Subscriptions::default());
+
+        broadcaster.broadcast(ServerToClient::GameStopped { reason: StopReason::Host });
+        broadcaster.send_to("p1", ServerToClient::error("only for p1"));
+        broadcaster.broadcast_except("p1", ServerToClient::GameStopped { reason: StopReason::Host });
+
+        assert_eq!(std::iter::from_fn(|| player_rx.try_recv().ok()).count(), 2);
+        let watched: Vec<_> = std::iter::from_fn(|| spectator_rx.try_recv().ok()).collect();
+        assert_eq!(watched.len(), 2);
+        assert!(watched.iter().all(|m| matches!(
+            &**m,
+            ServerToClient::ForLobby { lobby_code, message }
+                if lobby_code == "ABCDE" && matches!(**message, ServerToClient::GameStopped { .. })
+        )));
+
+        assert!(broadcaster.remove_spectator("s1"));
+        broadcaster.broadcast(ServerToClient::GameStopped { reason: StopReason::Host });
+        assert!(spectator_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_spectators_get_redacted_messages_they_subscribed_to() {
+        let (spectator_tx, mut spectator_rx) = mpsc::unbounded_channel();
+        let mut broadcaster = LobbyBroadcaster::new().for_lobby("ABCDE".to_string());
+        let mut subscriptions = Subscriptions::default();
+        subscriptions.update(&[], &[EventClass::OpponentLocations]);
+        broadcaster.add_spectator("s1".to_string(), spectator_tx, subscriptions);
+
+        broadcaster.broadcast(ServerToClient::GameStarted {
+            seed: "ABCD1234".to_string(),
+            stake: 1,
+        });
+        let game_state = ClientGameState {
+            money: 25,
+            hands_left: 1,
+            ..ClientGameState::default()
+        };
+        broadcaster.broadcast(ServerToClient::GameStateUpdate {
+            player_id: "p1".to_string(),
+            game_state,
+        });
+        broadcaster.broadcast(ServerToClient::PlayerLocation {
+            player_id: "p1".to_string(),
+            location: "loc_shop".to_string(),
+        });
+
+        let watched: Vec<_> = std::iter::from_fn(|| spectator_rx.try_recv().ok())
+            .map(|m| match &*m {
+                ServerToClient::ForLobby { message, .. } => message.clone(),
+                other => panic!("untagged {:?}", other),
+            })
+            .collect();
+        assert_eq!(watched.len(), 2);
+        assert!(matches!(
+            &*watched[0],
+            ServerToClient::GameStarted { seed, .. } if seed == HIDDEN_SEED
+        ));
+        assert!(matches!(
+            &*watched[1],
+            ServerToClient::GameStateUpdate { game_state, .. }
+                if game_state.money == 0 && game_state.hands_left == game_state.hands_max
+        ));
+    }
+
+    #[test]
+    fn test_coalescing_keeps_latest_update_and_order() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut broadcaster = LobbyBroadcaster::with_coalescing(Duration::from_millis(30));
+        broadcaster.add_player("p1".to_string(), tx);
+
+        broadcaster.broadcast(game_state_update("p2", 3));
+        broadcaster.broadcast(ServerToClient::LobbyReady {
+            ready_states: HashMap::new(),
+        });
+        broadcaster.broadcast(game_state_update("p2", 2));
+        assert!(rx.try_recv().is_err(), "updates are held");
+        assert!(broadcaster.flush_deadline().is_some());
+
+        // Anything else pushes the held updates out ahead of it
+        broadcaster.broadcast(ServerToClient::GameStopped { reason: StopReason::Host });
+        let sent: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(sent.len(), 3);
+        assert!(matches!(
+            &*sent[0],
+            ServerToClient::GameStateUpdate { game_state, .. } if game_state.lives == 2
+        ));
+        assert!(matches!(&*sent[1], ServerToClient::LobbyReady { .. }));
+        assert!(matches!(&*sent[2], ServerToClient::GameStopped { .. }));
+
+        broadcaster.broadcast(game_state_update("p2", 1));
+        broadcaster.flush();
+        assert!(rx.try_recv().is_ok());
+        assert!(broadcaster.flush_deadline().is_none());
+    }
+
+    #[test]
+    fn test_unsubscribed_classes_are_not_broadcast() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut broadcaster = LobbyBroadcaster::new();
+        broadcaster.add_player("p1".to_string(), tx);
+        let action: crate::messages::ClientToServer = serde_json::from_str(
+            r#"{ "action": "setSubscriptions", "unsubscribe": ["opponentLocations", "jokerPreviews"] }"#,
+        )
+        .unwrap();
+        let crate::messages::ClientToServer::SetSubscriptions { subscribe, unsubscribe } = action else {
+            panic!("not parsed as setSubscriptions");
+        };
+        broadcaster.update_subscriptions("p1", &subscribe, &unsubscribe);
+
+        let location = || ServerToClient::PlayerLocation {
+            player_id: "p2".to_string(),
+            location: "loc_shop".to_string(),
+        };
+        broadcaster.broadcast(location());
+        broadcaster.broadcast_except("p2", ServerToClient::ReceivePlayerJokers {
+            player_id: "p2".to_string(),
+            jokers: "j_joker".to_string(),
+        });
+        broadcaster.broadcast(ServerToClient::GameStopped { reason: StopReason::Host });
+        // Asked for directly, so it goes out anyway
+        broadcaster.send_to("p1", location());
+        let sent: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(sent.len(), 2);
+        assert!(matches!(&*sent[0], ServerToClient::GameStopped { .. }));
+
+        broadcaster.update_subscriptions("p1", &[EventClass::OpponentLocations], &[]);
+        broadcaster.broadcast(location());
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_parked_players_resume_from_their_last_message() {
+        let notice = |message: &str| ServerToClient::ServerNotice {
+            message: message.to_string(),
+        };
+        let seq_of = |message: &ServerToClient| match message {
+            ServerToClient::Sequenced { seq, .. } => *seq,
+            _ => panic!("not numbered"),
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut broadcaster = LobbyBroadcaster::new();
+        broadcaster.add_player("p1".to_string(), tx);
+        broadcaster.enable_resume("p1");
+        broadcaster.broadcast(notice("one"));
+        broadcaster.broadcast(notice("two"));
+        assert_eq!(seq_of(&rx.try_recv().unwrap()), 1);
+
+        // Claiming messages that were never sent means a full resync
+        broadcaster.park_player("p1");
+        assert!(broadcaster.resume_player("p1", "p1b", 5).is_none());
+
+        broadcaster.add_player("p1".to_string(), mpsc::unbounded_channel().0);
+        broadcaster.enable_resume("p1");
+        broadcaster.broadcast(notice("one"));
+        broadcaster.park_player("p1");
+        broadcaster.broadcast(notice("two"));
+        broadcaster.broadcast(notice("three"));
+        let missed = broadcaster.resume_player("p1", "p1b", 1).unwrap();
+        assert_eq!(missed.iter().map(|m| seq_of(m)).collect::<Vec<_>>(), [2, 3]);
+        // Numbering carries on under the new id
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        broadcaster.add_player("p1b".to_string(), tx);
+        broadcaster.broadcast(notice("four"));
+        assert_eq!(seq_of(&rx.try_recv().unwrap()), 4);
     }
 }
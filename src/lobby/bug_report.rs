@@ -0,0 +1,117 @@
+use serde::Serialize;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::{event_log::LobbyEvent, lobby::Lobby, options_history::OptionsChange};
+use crate::{config::CONFIG, utils::now_millis};
+
+/// Longest player-provided description accepted in a bug report
+pub const MAX_BUG_DESCRIPTION: usize = 2000;
+/// Largest report written to disk; bigger ones drop the lobby snapshot, then the report
+pub const MAX_BUG_REPORT_BYTES: usize = 256 * 1024;
+/// Reports one player may file in a lobby
+pub const MAX_BUG_REPORTS_PER_PLAYER: u32 = 3;
+/// Reports a lobby may file over its lifetime
+pub const MAX_BUG_REPORTS_PER_LOBBY: u32 = 20;
+
+#[derive(Debug, Serialize)]
+pub struct BugReport {
+    pub report_id: String,
+    pub created_at: u64,
+    pub reporter_id: String,
+    pub description: String,
+    /// Left out when it would take the report past `MAX_BUG_REPORT_BYTES`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lobby: Option<Lobby>,
+    pub events: Vec<LobbyEvent>,
+    pub options_changes: Vec<OptionsChange>,
+}
+
+impl BugReport {
+    pub fn new(lobby: &Lobby, reporter_id: &str, mut description: String) -> Self {
+        if description.len() > MAX_BUG_DESCRIPTION {
+            let mut cut = MAX_BUG_DESCRIPTION;
+            while !description.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            description.truncate(cut);
+        }
+        Self {
+            report_id: Uuid::new_v4().to_string(),
+            created_at: now_millis(),
+            reporter_id: reporter_id.to_string(),
+            description,
+            lobby: Some(lobby.clone()),
+            events: lobby.event_log().events().cloned().collect(),
+            options_changes: lobby.options_history().changes().cloned().collect(),
+        }
+    }
+
+    /// Write the report to the configured directory without blocking the lobby task
+    pub fn persist(mut self) {
+        tokio::spawn(async move {
+            let dir = CONFIG.get().bug_report_dir.clone();
+            let path = dir.join(format!("{}.json", self.report_id));
+            let json = match self.capped_json() {
+                Ok(Some(json)) => json,
+                Ok(None) => {
+                    warn!("Dropping bug report {}: over the size cap", self.report_id);
+                    return;
+                }
+                Err(e) => {
+                    error!("Failed to serialize bug report {}: {}", self.report_id, e);
+                    return;
+                }
+            };
+            if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+                error!("Failed to create bug report dir {:?}: {}", dir, e);
+                return;
+            }
+            match tokio::fs::write(&path, json).await {
+                Ok(()) => info!("Bug report {} written to {:?}", self.report_id, path),
+                Err(e) => error!("Failed to write bug report {}: {}", self.report_id, e),
+            }
+        });
+    }
+
+    /// The report as JSON within `MAX_BUG_REPORT_BYTES`, `None` when even the report
+    /// without its lobby snapshot is too big
+    fn capped_json(&mut self) -> serde_json::Result<Option<Vec<u8>>> {
+        let json = serde_json::to_vec_pretty(self)?;
+        if json.len() <= MAX_BUG_REPORT_BYTES {
+            return Ok(Some(json));
+        }
+        self.lobby = None;
+        let json = serde_json::to_vec_pretty(self)?;
+        Ok((json.len() <= MAX_BUG_REPORT_BYTES).then_some(json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientProfile;
+    use crate::game_mode::GameMode;
+
+    #[test]
+    fn test_bug_reports_are_capped_in_count_and_size() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        for _ in 0..MAX_BUG_REPORTS_PER_PLAYER {
+            assert!(lobby.take_bug_report_slot("p1").is_ok());
+        }
+        assert!(lobby.take_bug_report_slot("p1").is_err());
+        for player in 0..MAX_BUG_REPORTS_PER_LOBBY {
+            let _ = lobby.take_bug_report_slot(&format!("p{}", player + 2));
+        }
+        assert!(lobby.take_bug_report_slot("latecomer").is_err());
+
+        // An oversized lobby snapshot is left out rather than written
+        lobby.lobby_options.banned_jokers = vec!["j_joker".to_string(); MAX_BUG_REPORT_BYTES / 8];
+        let mut report = BugReport::new(&lobby, "p1", "x".repeat(MAX_BUG_DESCRIPTION * 2));
+        assert_eq!(report.description.len(), MAX_BUG_DESCRIPTION);
+        let json = report.capped_json().unwrap().unwrap();
+        assert!(json.len() <= MAX_BUG_REPORT_BYTES);
+        assert!(report.lobby.is_none());
+    }
+}
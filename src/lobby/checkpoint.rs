@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::{debug, error, info, warn};
+
+use super::ClientGameState;
+use super::phase::LobbyPhase;
+use super::shared_rng::SharedRng;
+use crate::{config::CONFIG, game_mode::LobbyOptions, talisman_number::TalismanNumber};
+
+/// On-disk snapshot of a running game, restored after a server restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LobbyCheckpoint {
+    pub code: String,
+    pub options: LobbyOptions,
+    pub started: bool,
+    /// Older checkpoints have none, their games resume in `InRound`
+    #[serde(default)]
+    pub phase: Option<LobbyPhase>,
+    pub stage: i32,
+    pub boss_chips: TalismanNumber,
+    pub players: Vec<CheckpointPlayer>,
+    /// Older checkpoints have none, their games continue with a fresh seed
+    #[serde(default)]
+    pub rng: SharedRng,
+}
+
+/// Only players with a verified account can be recognised when they reconnect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointPlayer {
+    pub account_id: String,
+    pub game_state: ClientGameState,
+}
+
+fn checkpoint_path(code: &str) -> PathBuf {
+    CONFIG.get().checkpoint_dir.join(format!("{}.json", code))
+}
+
+impl LobbyCheckpoint {
+    /// Write the checkpoint without blocking the lobby task
+    pub fn persist(self) {
+        tokio::spawn(async move {
+            let json = match serde_json::to_vec(&self) {
+                Ok(json) => json,
+                Err(e) => {
+                    error!("Failed to serialize checkpoint for lobby {}: {}", self.code, e);
+                    return;
+                }
+            };
+            let dir = CONFIG.get().checkpoint_dir.clone();
+            if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+                error!("Failed to create checkpoint dir {:?}: {}", dir, e);
+                return;
+            }
+            // Write then rename so a crash mid-write never leaves a torn checkpoint
+            let path = checkpoint_path(&self.code);
+            let tmp_path = path.with_extension("json.tmp");
+            let result = match tokio::fs::write(&tmp_path, json).await {
+                Ok(()) => tokio::fs::rename(&tmp_path, &path).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => debug!("Checkpointed lobby {}", self.code),
+                Err(e) => error!("Failed to write checkpoint for lobby {}: {}", self.code, e),
+            }
+        });
+    }
+
+    /// Drop a lobby's checkpoint once there is no game left to restore
+    pub fn remove(code: &str) {
+        let path = checkpoint_path(code);
+        tokio::spawn(async move {
+            if let Err(e) = tokio::fs::remove_file(&path).await
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                error!("Failed to remove checkpoint {:?}: {}", path, e);
+            }
+        });
+    }
+
+    /// Read every checkpoint left behind by the previous run
+    pub fn load_all() -> Vec<LobbyCheckpoint> {
+        let dir = CONFIG.get().checkpoint_dir.clone();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                error!("Failed to read checkpoint dir {:?}: {}", dir, e);
+                return Vec::new();
+            }
+        };
+
+        let mut checkpoints = Vec::new();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let parsed = std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(checkpoint) => checkpoints.push(checkpoint),
+                Err(e) => warn!("Skipping unreadable checkpoint {:?}: {}", path, e),
+            }
+        }
+        info!("Loaded {} lobby checkpoints", checkpoints.len());
+        checkpoints
+    }
+}
@@ -0,0 +1,36 @@
+//! Deck checks for lobbies where everyone must play the host's back.
+
+/// The back a `sendPlayerDeck` payload was built from: the client puts its key
+/// (e.g. `b_red`) in front of the first `;`. None when the payload doesn't say.
+pub fn deck_back(deck: &str) -> Option<&str> {
+    let (back, _) = deck.split_once(';')?;
+    let back = back.trim();
+    (!back.is_empty()).then_some(back)
+}
+
+/// Whether two backs are the same, however they are named: the options hold the
+/// display name (`Red Deck`) while deck payloads carry the key (`b_red`)
+pub fn same_back(a: &str, b: &str) -> bool {
+    normalize_back(a) == normalize_back(b)
+}
+
+fn normalize_back(back: &str) -> String {
+    let back = back.trim().to_ascii_lowercase();
+    let back = back.strip_prefix("b_").unwrap_or(&back);
+    let back = back.strip_suffix(" deck").unwrap_or(back);
+    back.chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_back_keys_match_display_names() {
+        assert_eq!(deck_back("b_ghost;H_2;S_K"), Some("b_ghost"));
+        assert_eq!(deck_back("H_2,S_K"), None);
+        assert!(same_back("Red Deck", "b_red"));
+        assert!(same_back("Checkered Deck", "B_CHECKERED"));
+        assert!(!same_back("Red Deck", "b_blue"));
+    }
+}
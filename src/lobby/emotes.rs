@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Emotes clients know how to render; anything else is rejected
+pub const KNOWN_EMOTES: &[&str] = &[
+    "gg",
+    "good_luck",
+    "nice_hand",
+    "well_played",
+    "thanks",
+    "oops",
+    "thinking",
+    "wow",
+    "jimbo_laugh",
+];
+
+/// Emotes a player may send within `EMOTE_WINDOW`
+const EMOTE_BURST: usize = 3;
+const EMOTE_WINDOW: Duration = Duration::from_secs(5);
+
+pub fn is_known_emote(emote_id: &str) -> bool {
+    KNOWN_EMOTES.contains(&emote_id)
+}
+
+/// Sliding-window limiter over a player's recent emote times; records the emote when allowed
+pub fn allow_emote(recent: &mut VecDeque<Instant>, now: Instant) -> bool {
+    allow_within(recent, now, EMOTE_BURST, EMOTE_WINDOW)
+}
+
+/// At most `burst` of `recent` within `window`; records `now` when allowed
+pub fn allow_within(
+    recent: &mut VecDeque<Instant>,
+    now: Instant,
+    burst: usize,
+    window: Duration,
+) -> bool {
+    while recent
+        .front()
+        .is_some_and(|sent| now.duration_since(*sent) >= window)
+    {
+        recent.pop_front();
+    }
+    if recent.len() >= burst {
+        return false;
+    }
+    recent.push_back(now);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emotes_are_rate_limited_per_window() {
+        let mut recent = VecDeque::new();
+        let start = Instant::now();
+        for _ in 0..EMOTE_BURST {
+            assert!(allow_emote(&mut recent, start));
+        }
+        assert!(!allow_emote(&mut recent, start + EMOTE_WINDOW / 2));
+        assert!(allow_emote(&mut recent, start + EMOTE_WINDOW));
+        assert!(is_known_emote("gg"));
+        assert!(!is_known_emote("<script>"));
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::utils::now_millis;
+
+/// Longest description kept per event, so deck/joker payloads don't bloat the log
+const MAX_EVENT_DESCRIPTION: usize = 512;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LobbyEvent {
+    pub timestamp: u64,
+    pub player_id: Option<String>,
+    pub description: String,
+}
+
+/// Fixed-size ring buffer of the most recent lobby events
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyEventLog {
+    capacity: usize,
+    events: VecDeque<LobbyEvent>,
+}
+
+impl LobbyEventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, player_id: Option<&str>, description: impl Into<String>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut description = description.into();
+        if description.len() > MAX_EVENT_DESCRIPTION {
+            let mut cut = MAX_EVENT_DESCRIPTION;
+            while !description.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            description.truncate(cut);
+            description.push('…');
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(LobbyEvent {
+            timestamp: now_millis(),
+            player_id: player_id.map(str::to_string),
+            description,
+        });
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &LobbyEvent> {
+        self.events.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_log_keeps_only_latest_events() {
+        let mut log = LobbyEventLog::new(2);
+        log.record(Some("p1"), "first");
+        log.record(Some("p1"), "second");
+        log.record(None, "third");
+        let descriptions: Vec<_> = log.events().map(|e| e.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["second", "third"]);
+    }
+}
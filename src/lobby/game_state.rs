@@ -1,5 +1,43 @@
 use crate::{client::ClientProfile, talisman_number::TalismanNumber};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Most lives a handicap adds
+pub const MAX_HANDICAP_LIVES: u8 = 3;
+pub const MIN_HANDICAP_MULTIPLIER: f64 = 0.5;
+pub const MAX_HANDICAP_MULTIPLIER: f64 = 2.0;
+
+/// Evens out a game between players of different skill
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Handicap {
+    /// Lives on top of the lobby's starting lives
+    #[serde(default)]
+    pub extra_lives: u8,
+    /// Applied to the player's PvP score when the server evaluates a round
+    pub score_multiplier: f64,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            extra_lives: 0,
+            score_multiplier: 1.0,
+        }
+    }
+}
+
+impl Handicap {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.extra_lives > MAX_HANDICAP_LIVES {
+            return Err("A handicap adds at most 3 lives");
+        }
+        if !(MIN_HANDICAP_MULTIPLIER..=MAX_HANDICAP_MULTIPLIER).contains(&self.score_multiplier) {
+            return Err("Handicap score multiplier must be between 0.5 and 2");
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ClientLobbyState {
@@ -9,8 +47,34 @@ pub struct ClientLobbyState {
     pub first_ready: bool,
     pub is_cached: bool,
     pub is_host: bool,
+    pub latency_ms: Option<u32>,
+    /// Set by the host before the game, shown to everyone
+    pub handicap: Handicap,
+    /// Player declared the current blind finished (batched scoring)
+    pub round_complete: bool,
+    /// Highest client sequence id applied, used to drop replayed actions
+    #[serde(skip)]
+    pub last_action_seq: Option<u64>,
+    /// Generation of the client's current stay, actions from older ones are dropped
+    #[serde(skip)]
+    pub lobby_generation: u64,
+    /// When the player's recent emotes were sent, for rate limiting
+    #[serde(skip)]
+    pub recent_emotes: VecDeque<Instant>,
+    /// Connection dropped mid-game, the seat is held until this deadline
+    #[serde(skip)]
+    pub disconnected_until: Option<Instant>,
+    /// Jokers last relayed to the other players
+    #[serde(skip)]
+    pub last_jokers: Option<String>,
+    /// Deck last relayed to the other players
+    #[serde(skip)]
+    pub last_deck: Option<String>,
 }
 
+/// Team players are on until a team mode sorts them
+pub const DEFAULT_TEAM: u8 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientGameState {
     pub ante: u32,
@@ -26,8 +90,46 @@ pub struct ClientGameState {
     pub skips: u8,
     pub score: TalismanNumber,
     pub highest_score: TalismanNumber,
+    /// Spent in the shops of each ante this run, first ante first
     pub spent_in_shop: Vec<u32>,
-    pub team: u8
+    pub team: u8,
+    /// Gold granted by the server this run (e.g. for losing a life)
+    pub money: u32,
+    /// Survival: blind the player was knocked out on, they wait for the others
+    #[serde(default)]
+    pub eliminated_at: Option<u32>,
+    /// Clash league points earned from round placements
+    #[serde(default)]
+    pub points: u32,
+    /// Best single hand this run, for the end-of-game awards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub biggest_hand: Option<TalismanNumber>,
+    #[serde(default)]
+    pub discards_used: u32,
+    /// Quickest PvP blind the player won, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fastest_clear_ms: Option<u64>,
+}
+
+impl ClientGameState {
+    /// What spectators see of a run: where it stands and its scores, not the hands,
+    /// discards, money and spending a player could use against the others
+    pub fn spectator_view(&self) -> Self {
+        Self {
+            ante: self.ante,
+            round: self.round,
+            furthest_blind: self.furthest_blind,
+            lives: self.lives,
+            lives_blocker: self.lives_blocker,
+            location: self.location.clone(),
+            score: self.score.clone(),
+            highest_score: self.highest_score.clone(),
+            team: self.team,
+            eliminated_at: self.eliminated_at,
+            points: self.points,
+            ..Self::default()
+        }
+    }
 }
 
 impl Default for ClientGameState {
@@ -47,7 +149,13 @@ impl Default for ClientGameState {
             score: TalismanNumber::Regular(0.0),
             highest_score: TalismanNumber::Regular(0.0),
             spent_in_shop: Vec::new(),
-            team: 1,
+            team: DEFAULT_TEAM,
+            money: 0,
+            eliminated_at: None,
+            points: 0,
+            biggest_hand: None,
+            discards_used: 0,
+            fastest_clear_ms: None,
         }
     }
 }
@@ -74,14 +182,47 @@ impl ClientLobbyEntry {
                 first_ready: false,
                 is_cached: false,
                 is_host,
+                latency_ms: None,
+                handicap: Handicap::default(),
+                round_complete: false,
+                last_action_seq: None,
+                lobby_generation: 0,
+                recent_emotes: VecDeque::new(),
+                disconnected_until: None,
+                last_jokers: None,
+                last_deck: None,
             },
             game_state,
         }
     }
 
+    /// The entry as a spectator may see it: no account or mod details, and only the
+    /// scoreboard part of the run
+    pub fn spectator_view(&self) -> Self {
+        let mut entry = self.clone();
+        entry.profile.account_id = None;
+        entry.profile.mod_hash.clear();
+        entry.game_state = self.game_state.spectator_view();
+        entry
+    }
+
+    /// Fresh run state for a new game; teams are picked in the lobby and carry over
     pub fn reset_for_game(&mut self, starting_lives: u8) {
         self.lobby_state.is_ready = false;
+        self.lobby_state.last_jokers = None;
+        self.lobby_state.last_deck = None;
+        let team = self.game_state.team;
         self.game_state = ClientGameState::default();
-        self.game_state.lives = starting_lives;
+        self.game_state.lives = starting_lives.saturating_add(self.lobby_state.handicap.extra_lives);
+        self.game_state.team = team;
+    }
+
+    /// PvP score the server ranks the player by, with their handicap applied
+    pub fn handicapped_score(&self) -> TalismanNumber {
+        let multiplier = self.lobby_state.handicap.score_multiplier;
+        if multiplier == 1.0 {
+            return self.game_state.score.clone();
+        }
+        self.game_state.score.scale(multiplier)
     }
 }
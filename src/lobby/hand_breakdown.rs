@@ -0,0 +1,64 @@
+//! What made up a played hand, sent along with `playHand` so opponents can
+//! follow each other's big hands as they happen.
+
+use serde::{Deserialize, Serialize};
+
+use crate::talisman_number::TalismanNumber;
+
+const MAX_HAND_TYPE_LEN: usize = 32;
+/// Jokers listed per hand, a full joker row with room for negatives
+const MAX_TRIGGERED_JOKERS: usize = 16;
+const MAX_JOKER_KEY_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandBreakdown {
+    /// Poker hand key, e.g. `Flush`
+    pub hand_type: String,
+    pub chips: TalismanNumber,
+    pub mult: TalismanNumber,
+    /// Keys of the jokers that triggered, in order
+    #[serde(default)]
+    pub jokers: Vec<String>,
+}
+
+impl HandBreakdown {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.hand_type.trim().is_empty() || self.hand_type.len() > MAX_HAND_TYPE_LEN {
+            return Err("Hand type must be 1 to 32 characters");
+        }
+        if self.jokers.len() > MAX_TRIGGERED_JOKERS {
+            return Err("Too many jokers in hand breakdown");
+        }
+        if self
+            .jokers
+            .iter()
+            .any(|key| key.is_empty() || key.len() > MAX_JOKER_KEY_LEN)
+        {
+            return Err("Invalid joker key in hand breakdown");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakdown_size_is_limited() {
+        let mut breakdown = HandBreakdown {
+            hand_type: "Flush".to_string(),
+            chips: TalismanNumber::Regular(90.0),
+            mult: TalismanNumber::Regular(500.0),
+            jokers: vec!["j_droll".to_string(), "j_blueprint".to_string()],
+        };
+        assert!(breakdown.validate().is_ok());
+
+        breakdown.jokers = vec!["j_joker".to_string(); MAX_TRIGGERED_JOKERS + 1];
+        assert!(breakdown.validate().is_err());
+
+        breakdown.jokers.clear();
+        breakdown.hand_type = "x".repeat(MAX_HAND_TYPE_LEN + 1);
+        assert!(breakdown.validate().is_err());
+    }
+}
@@ -1,8 +1,25 @@
-use super::{broadcaster::LobbyBroadcaster, lobby::Lobby};
+use super::{broadcaster::LobbyBroadcaster, bug_report::BugReport, lobby::Lobby};
+use crate::audit::{self, AuditEvent};
+use crate::lobby::announcements::clean_announcement;
+use crate::lobby::blind_curve::check_boss_chips;
+use crate::lobby::emotes::{allow_emote, is_known_emote};
+use crate::lobby::game_state::Handicap;
+use crate::lobby::hand_breakdown::HandBreakdown;
 use crate::lobby::lobby::RoundResult;
-use crate::messages::{ClientToServer, ServerToClient};
+use crate::game_mode::{GameMode, LobbyOptions};
+use crate::invites::Invite;
+use crate::lobby::options_history::{OptionsDiff, invalidates_run};
+use crate::lobby::phase::LobbyPhase;
+use crate::lobby::preview::PreviewKind;
+use crate::lobby::shared_rng::{BOSS_ROLL_PREFIX, MAX_ROLL_KEY};
+use crate::messages::{
+    ClientToServer, OptionsRevertTarget, OutcomeReason, ServerToClient, StopReason,
+};
 use crate::talisman_number::TalismanNumber;
-use tracing::{debug, error};
+use crate::utils::now_millis;
+use crate::webhooks::{self, WebhookPayload};
+use std::time::Instant;
+use tracing::{debug, error, warn};
 
 // KISS: Group related handlers
 pub struct LobbyHandlers;
@@ -30,7 +47,16 @@ impl LobbyHandlers {
         player_id: &str,
         score: TalismanNumber,
         hands_left: u8,
+        breakdown: Option<HandBreakdown>,
     ) {
+        // A bad breakdown only loses the live feed, the score still counts
+        let breakdown = breakdown.filter(|b| match b.validate() {
+            Ok(()) => true,
+            Err(msg) => {
+                broadcaster.send_to(player_id, ServerToClient::error(msg));
+                false
+            }
+        });
         if let Some(player) = lobby.get_player_mut(player_id) {
             debug!(
                 "Player {} played hand with score {} and hands left {}",
@@ -40,6 +66,7 @@ impl LobbyHandlers {
             );
 
             // Update player state
+            let hand_score = score.clone();
             player.game_state.score = match player.game_state.score.add(&score) {
                 Ok(val) => val,
                 Err(e) => {
@@ -48,22 +75,56 @@ impl LobbyHandlers {
                 }
             };
             player.game_state.hands_left = hands_left;
+            lobby.record_hand(player_id, hand_score.clone(), hands_left, breakdown.clone());
 
-            // Broadcast and evaluate
-            lobby.broadcast_game_state_update(broadcaster, player_id, true);
+            // In batched mode opponents only see scores once the round is evaluated
+            if !lobby.lobby_options.batch_round_scoring {
+                lobby.broadcast_game_state_update(broadcaster, player_id, true);
+                if let Some(breakdown) = breakdown {
+                    broadcaster.broadcast_except(
+                        player_id,
+                        ServerToClient::HandPlayed {
+                            player_id: player_id.to_string(),
+                            score: hand_score,
+                            breakdown,
+                        },
+                    );
+                }
+            }
             lobby.evaluate_online_round(broadcaster);
         }
     }
 
+    fn handle_round_complete(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        debug!("Player {} declared their round complete", player_id);
+        lobby.mark_round_complete(player_id);
+        lobby.evaluate_online_round(broadcaster);
+    }
+
     fn handle_set_location(
         lobby: &mut Lobby,
         broadcaster: &LobbyBroadcaster,
         player_id: &str,
         location: String,
     ) {
-        Self::update_player_and_broadcast(lobby, broadcaster, player_id, false, |player| {
-            player.game_state.location = location;
-        });
+        let Some(player) = lobby.get_player_mut(player_id) else {
+            return;
+        };
+        if player.game_state.location == location {
+            return;
+        }
+        player.game_state.location = location.clone();
+        if player.lobby_state.in_game {
+            lobby.note_location(&location);
+        }
+        // Presence only needs the location, not the whole game state
+        broadcaster.broadcast_except(
+            player_id,
+            ServerToClient::PlayerLocation {
+                player_id: player_id.to_string(),
+                location,
+            },
+        );
     }
 
     fn handle_skip(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, player_id: &str, blind: u32) {
@@ -104,6 +165,197 @@ impl LobbyHandlers {
         broadcaster.broadcast_except(player_id, ServerToClient::RemovePhantom { key });
     }
 
+    fn handle_send_emote(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        emote_id: String,
+        team_only: bool,
+    ) {
+        if !is_known_emote(&emote_id) {
+            broadcaster.send_to(player_id, ServerToClient::error("Unknown emote"));
+            return;
+        }
+        let Some(player) = lobby.get_player_mut(player_id) else {
+            return;
+        };
+        if !allow_emote(&mut player.lobby_state.recent_emotes, Instant::now()) {
+            debug!("Dropping emote from {}, sending too fast", player_id);
+            return;
+        }
+        let emote = ServerToClient::Emote {
+            player_id: player_id.to_string(),
+            emote_id,
+        };
+        if !team_only {
+            broadcaster.broadcast_except(player_id, emote);
+            return;
+        }
+        match lobby.team_of(player_id) {
+            Some(team) if !lobby.teammates(player_id).is_empty() => {
+                broadcaster.broadcast_to_team(team, emote);
+            }
+            _ => broadcaster.send_to(player_id, ServerToClient::error("No teammates to send to")),
+        }
+    }
+
+    fn handle_announce(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        text: String,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            broadcaster.send_to(player_id, ServerToClient::error("Only the host can make announcements"));
+            return;
+        }
+        let text = match clean_announcement(&text) {
+            Ok(text) => text,
+            Err(message) => {
+                broadcaster.send_to(player_id, ServerToClient::error(message));
+                return;
+            }
+        };
+        if !lobby.allow_announcement(Instant::now()) {
+            broadcaster.send_to(player_id, ServerToClient::error("Announcing too often"));
+            return;
+        }
+        lobby.record_event(Some(player_id), format!("announced: {}", text));
+        broadcaster.broadcast(ServerToClient::Announcement {
+            player_id: player_id.to_string(),
+            text,
+        });
+    }
+
+    fn handle_force_start_blind(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        if !lobby.is_player_host(player_id) {
+            broadcaster.send_to(player_id, ServerToClient::error("Only the host can start the blind"));
+            return;
+        }
+        if !matches!(lobby.phase(), LobbyPhase::InRound | LobbyPhase::ShopPhase) {
+            broadcaster.send_to(player_id, ServerToClient::error("No blind to start"));
+            return;
+        }
+        match lobby.blind_start_overrides(true) {
+            Some(overridden) => {
+                lobby.record_event(Some(player_id), format!("forced the blind past {:?}", overridden));
+                lobby.begin_pvp_blind_overriding(broadcaster, overridden);
+            }
+            None => broadcaster.send_to(
+                player_id,
+                ServerToClient::error("This lobby waits for everyone to be ready"),
+            ),
+        }
+    }
+
+    fn handle_request_roll(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        key: String,
+        sides: u32,
+    ) {
+        if key.is_empty() || key.len() > MAX_ROLL_KEY || sides == 0 {
+            broadcaster.send_to(player_id, ServerToClient::error("Invalid roll request"));
+            return;
+        }
+        if key.starts_with(BOSS_ROLL_PREFIX) && lobby.lobby_options.normal_bosses {
+            broadcaster.send_to(player_id, ServerToClient::error("This lobby uses normal bosses"));
+            return;
+        }
+        let (value, first) = lobby.shared_roll(&key, sides);
+        let roll = ServerToClient::SharedRoll { key, value };
+        // Only the first request goes to everyone, later ones were already answered by it
+        if first {
+            broadcaster.broadcast(roll);
+        } else {
+            broadcaster.send_to(player_id, roll);
+        }
+    }
+
+    /// Reject boss chips no boss at the host's ante could ask for, flagging them in the audit log
+    fn validate_boss_chips(
+        lobby: &mut Lobby,
+        player_id: &str,
+        key: &str,
+        chips: &TalismanNumber,
+    ) -> Result<(), &'static str> {
+        let ante = lobby.boss_ante();
+        let Err(e) = check_boss_chips(chips, ante, lobby.lobby_options.stake) else {
+            return Ok(());
+        };
+        warn!(
+            "Host {} of lobby {} sent boss {} with {} chips at ante {}: {}",
+            player_id, lobby.code, key, chips, ante, e
+        );
+        let detail = format!("{} with {} chips at ante {}", key, chips, ante);
+        lobby.record_event(Some(player_id), format!("rejected boss chips: {}", detail));
+        audit::record(
+            &lobby.code,
+            AuditEvent::RulesViolation {
+                player_id: player_id.to_string(),
+                rule: "boss_chips".to_string(),
+                detail,
+            },
+        );
+        Err(e)
+    }
+
+    fn handle_send_player_deck(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        deck: String,
+    ) {
+        if let Some(message) = lobby.deck_violation(&deck) {
+            debug!("Rejected deck from {} in lobby {}: {}", player_id, lobby.code, message);
+            lobby.record_event(Some(player_id), format!("rules violation: {}", message));
+            audit::record(
+                &lobby.code,
+                AuditEvent::RulesViolation {
+                    player_id: player_id.to_string(),
+                    rule: "different_decks".to_string(),
+                    detail: message.clone(),
+                },
+            );
+            broadcaster.broadcast(ServerToClient::RulesViolation {
+                player_id: player_id.to_string(),
+                rule: "different_decks".to_string(),
+                message,
+            });
+            return;
+        }
+        lobby.relay_preview(broadcaster, player_id, PreviewKind::Deck, deck);
+    }
+
+    fn handle_send_player_jokers(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        jokers: String,
+    ) {
+        if let Some(card) = lobby.check_banned_cards(player_id, &jokers) {
+            debug!("Player {} holds banned {} in lobby {}", player_id, card, lobby.code);
+            let message = format!("Holding {} which this lobby bans", card);
+            lobby.record_event(Some(player_id), format!("rules violation: {}", message));
+            audit::record(
+                &lobby.code,
+                AuditEvent::RulesViolation {
+                    player_id: player_id.to_string(),
+                    rule: "banned_cards".to_string(),
+                    detail: message.clone(),
+                },
+            );
+            broadcaster.broadcast(ServerToClient::RulesViolation {
+                player_id: player_id.to_string(),
+                rule: "banned_cards".to_string(),
+                message,
+            });
+            return;
+        }
+        lobby.relay_preview(broadcaster, player_id, PreviewKind::Jokers, jokers);
+    }
+
     fn handle_asteroid(broadcaster: &LobbyBroadcaster, player_id: &str, target: &str) {
         debug!("Player {} sent asteroid to {}", player_id, target);
         broadcaster.send_to(
@@ -135,8 +387,8 @@ impl LobbyHandlers {
             lobby.broadcast_game_state_update(broadcaster, player_id, false);
 
             // Check for survival mode game end condition
-            if lobby.lobby_options.gamemode == crate::game_mode::GameMode::Survival {
-                lobby.check_and_handle_game_over(broadcaster);
+            if lobby.lobby_options.gamemode == GameMode::Survival {
+                lobby.check_and_handle_game_over(broadcaster, None);
             }
         }
     }
@@ -157,36 +409,345 @@ impl LobbyHandlers {
         broadcaster.broadcast_except(player_id, crate::messages::ServerToClient::SoldJoker {});
     }
 
-    fn handle_spent_last_shop(broadcaster: &LobbyBroadcaster, player_id: &str, amount: u32) {
-        //TODO fix the vector handling here
+    fn handle_spent_last_shop(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        amount: u32,
+    ) {
         debug!("Player {} spent {} in shop", player_id, amount);
-        broadcaster.broadcast(ServerToClient::SpentLastShop {
-            player_id: player_id.to_string(),
-            amount,
+        if let Err(message) = lobby.record_shop_spend(player_id, amount) {
+            broadcaster.send_to(player_id, ServerToClient::error(message));
+            return;
+        }
+        broadcaster.broadcast(ServerToClient::ShopSpending {
+            spending: lobby.shop_spending(),
         });
     }
 
-    fn handle_magnet(broadcaster: &LobbyBroadcaster, player_id: &str) {
-        debug!("Player {} triggered magnet", player_id);
-        broadcaster.broadcast_except(player_id, ServerToClient::Magnet {});
+    fn handle_magnet(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        target: Option<String>,
+    ) {
+        debug!("Player {} triggered magnet (target: {:?})", player_id, target);
+        lobby.start_magnet(broadcaster, player_id, target);
     }
 
-    fn handle_magnet_response(broadcaster: &LobbyBroadcaster, player_id: &str, key: String) {
+    fn handle_magnet_response(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        key: String,
+    ) {
         debug!("Player {} responding to magnet with: {}", player_id, key);
-        broadcaster.broadcast_except(player_id, ServerToClient::MagnetResponse { key });
+        lobby.resolve_magnet(broadcaster, player_id, key);
     }
 
     fn handle_fail_timer(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, player_id: &str) {
         debug!("Player {} failed timer", player_id);
-        lobby.process_round_outcome(&vec![RoundResult {
+        let rewards = lobby.process_round_outcome(&[RoundResult {
             player_id: player_id.to_string(),
             won: true,
         }]);
+        lobby.broadcast_round_rewards(broadcaster, &rewards);
         lobby.broadcast_life_updates(broadcaster, player_id);
-        lobby.check_and_handle_game_over(broadcaster);
+        lobby.check_and_handle_game_over(broadcaster, Some(OutcomeReason::TimerExpired));
+        lobby.ante_timer_stopped();
         broadcaster.broadcast(ServerToClient::PauseAnteTimer {
-            time: (lobby.lobby_options.timer_base_seconds),
+            time: lobby.lobby_options.timer_base_seconds,
+            server_time: now_millis(),
+        });
+    }
+
+    fn handle_update_lobby_options(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        mut options: LobbyOptions,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            debug!(
+                "Player {} attempted to update lobby options but is not host",
+                player_id
+            );
+            return;
+        }
+        if let Err(message) = options.validate() {
+            broadcaster.send_to(player_id, ServerToClient::error(message));
+            return;
+        }
+        // The host's client never learns the seed rolled for a random seed game and
+        // sends "random" back, which is no change
+        let rolled_seed = lobby.started() && options.custom_seed == "random";
+        if rolled_seed {
+            options.custom_seed = lobby.lobby_options.custom_seed.clone();
+        }
+
+        let changes = lobby.apply_options(options, player_id);
+        lobby.audit_options_change(player_id, &changes);
+        if lobby.started() && invalidates_run(&changes) {
+            if rolled_seed {
+                lobby.lobby_options.custom_seed = String::from("random");
+            }
+            Self::stop_game(lobby, broadcaster, StopReason::OptionsChanged);
+        }
+        lobby.reset_ready_states_to_host_only();
+        lobby.broadcast_ready_states_except(broadcaster, player_id);
+        broadcaster.broadcast_except(
+            player_id,
+            ServerToClient::UpdateLobbyOptions {
+                options: lobby.lobby_options.clone(),
+                changed_by: player_id.to_string(),
+                changes,
+            },
+        );
+    }
+
+    /// Start a game, `player_id` is whoever started it and hears why if it can't
+    fn start_game(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        if lobby.started() {
+            broadcaster.send_to(player_id, ServerToClient::error("The game has already started"));
+            return;
+        }
+        if lobby.merge_pending() {
+            broadcaster.send_to(
+                player_id,
+                ServerToClient::error("Players from another lobby are still joining"),
+            );
+            return;
+        }
+        lobby.start_game();
+        audit::record(
+            &lobby.code,
+            AuditEvent::GameStarted {
+                players: lobby.players().keys().cloned().collect(),
+            },
+        );
+        webhooks::emit(WebhookPayload::GameStarted {
+            lobby_code: lobby.code.clone(),
+            game_mode: lobby.lobby_options.gamemode,
+            players: lobby.players().keys().cloned().collect(),
+        });
+        lobby.broadcast_players(broadcaster);
+        lobby.broadcast_game_started(broadcaster);
+        if lobby.lobby_options.gamemode == GameMode::Clash {
+            lobby.broadcast_clash_stage(broadcaster);
+        }
+        lobby.broadcast_ready_states(broadcaster);
+        broadcaster.broadcast(ServerToClient::InGameStatuses {
+            statuses: lobby.get_in_game_statuses(),
+            started: lobby.started(),
+        });
+    }
+
+    fn handle_set_handicap(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        target: &str,
+        handicap: Handicap,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            broadcaster.send_to(player_id, ServerToClient::error("Only the host can set handicaps"));
+            return;
+        }
+        if let Err(e) = lobby.set_handicap(target, handicap) {
+            broadcaster.send_to(player_id, ServerToClient::error(e));
+            return;
+        }
+        lobby.record_event(Some(player_id), format!("set handicap of {}: {:?}", target, handicap));
+        lobby.broadcast_players(broadcaster);
+    }
+
+    fn handle_vote_start(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        if !lobby.lobby_options.vote_to_start {
+            broadcaster.send_to(player_id, ServerToClient::error("This lobby doesn't vote to start"));
+            return;
+        }
+        if lobby.started() {
+            broadcaster.send_to(player_id, ServerToClient::error("The game has already started"));
+            return;
+        }
+        let carried = lobby.vote_to_start(player_id);
+        broadcaster.broadcast(ServerToClient::StartVotes {
+            player_ids: lobby.start_votes(),
+            needed: lobby.start_votes_needed() as u32,
+        });
+        if carried {
+            lobby.record_event(Some(player_id), "started the game by vote".to_string());
+            Self::start_game(lobby, broadcaster, player_id);
+        }
+    }
+
+    /// End the running game early; ready states are left to the caller
+    fn stop_game(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, reason: StopReason) {
+        lobby.stop_game();
+        broadcaster.broadcast(ServerToClient::GameStopped { reason });
+        broadcaster.broadcast(ServerToClient::InGameStatuses {
+            statuses: lobby.get_in_game_statuses(),
+            started: lobby.started(),
+        });
+    }
+
+    fn handle_revert_lobby_options(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        target: OptionsRevertTarget,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            debug!(
+                "Player {} attempted to revert lobby options but is not host",
+                player_id
+            );
+            return;
+        }
+
+        let changes: Option<OptionsDiff> = match target {
+            OptionsRevertTarget::Previous => lobby.revert_to_previous_options(),
+            OptionsRevertTarget::Default => Some(lobby.revert_to_default_options(player_id)),
+        };
+        let Some(changes) = changes else {
+            broadcaster.send_to(player_id, ServerToClient::error("No previous options to revert to"));
+            return;
+        };
+
+        lobby.audit_options_change(player_id, &changes);
+        if lobby.started() && invalidates_run(&changes) {
+            Self::stop_game(lobby, broadcaster, StopReason::OptionsChanged);
+        }
+        lobby.reset_ready_states_to_host_only();
+        lobby.broadcast_ready_states(broadcaster);
+        // The host didn't send these options, so everyone gets the update
+        broadcaster.broadcast(ServerToClient::UpdateLobbyOptions {
+            options: lobby.lobby_options.clone(),
+            changed_by: player_id.to_string(),
+            changes,
+        });
+    }
+
+    fn handle_reserve_slot(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        account_id: String,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            broadcaster.send_to(player_id, ServerToClient::error("Only the host can reserve slots"));
+            return;
+        }
+        debug!("Host {} reserving slot for {}", player_id, account_id);
+        match lobby.reserve_slot(account_id) {
+            Ok(()) => lobby.broadcast_reservations(broadcaster),
+            Err(message) => broadcaster.send_to(player_id, ServerToClient::error(message)),
+        }
+    }
+
+    fn handle_cancel_reservation(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        account_id: &str,
+    ) {
+        if lobby.is_player_host(player_id) && lobby.cancel_reservation(account_id) {
+            lobby.broadcast_reservations(broadcaster);
+        }
+    }
+
+    fn handle_create_invite(
+        lobby: &Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        bypass_reservations: bool,
+    ) {
+        if bypass_reservations && !lobby.is_player_host(player_id) {
+            broadcaster.send_to(
+                player_id,
+                ServerToClient::error("Only the host can invite past reserved slots"),
+            );
+            return;
+        }
+        let invite = Invite::new(lobby.code.clone(), bypass_reservations, now_millis() / 1000);
+        debug!("Player {} created an invite to lobby {}", player_id, lobby.code);
+        broadcaster.send_to(
+            player_id,
+            ServerToClient::InviteCreated {
+                token: invite.token(),
+                link: invite.link(),
+                expires_at: invite.expires_at * 1000,
+            },
+        );
+    }
+
+    fn handle_offer_lobby_merge(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        open: bool,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            broadcaster.send_to(player_id, ServerToClient::error("Only the host can offer a merge"));
+            return;
+        }
+        if open && lobby.started() {
+            broadcaster.send_to(player_id, ServerToClient::error("Game already started"));
+            return;
+        }
+        debug!("Host {} set lobby {} open to merging: {}", player_id, lobby.code, open);
+        lobby.set_merge_offered(open);
+        broadcaster.broadcast(ServerToClient::MergeOfferChanged { open });
+    }
+
+    fn handle_set_public_listing(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        listed: bool,
+    ) {
+        if !lobby.is_player_host(player_id) {
+            let message = ServerToClient::error("Only the host can list the lobby");
+            broadcaster.send_to(player_id, message);
+            return;
+        }
+        if listed && lobby.started() {
+            broadcaster.send_to(player_id, ServerToClient::error("Game already started"));
+            return;
+        }
+        debug!("Host {} set lobby {} publicly listed: {}", player_id, lobby.code, listed);
+        lobby.set_publicly_listed(listed);
+        broadcaster.broadcast(ServerToClient::PublicListingChanged { listed });
+    }
+
+    fn handle_report_bug(
+        lobby: &mut Lobby,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        description: String,
+    ) {
+        if let Err(e) = lobby.take_bug_report_slot(player_id) {
+            broadcaster.send_to(player_id, ServerToClient::error(e));
+            return;
+        }
+        let report = BugReport::new(lobby, player_id, description);
+        debug!(
+            "Player {} filed bug report {} in lobby {}",
+            player_id, report.report_id, lobby.code
+        );
+        broadcaster.send_to(
+            player_id,
+            ServerToClient::BugReported {
+                report_id: report.report_id.clone(),
+            },
+        );
+        webhooks::emit(WebhookPayload::BugReported {
+            lobby_code: lobby.code.clone(),
+            player_id: player_id.to_string(),
+            report_id: report.report_id.clone(),
+            description: report.description.clone(),
         });
+        report.persist();
     }
 
     pub fn handle_player_action(
@@ -196,9 +757,40 @@ impl LobbyHandlers {
         action: ClientToServer,
     ) {
         debug!("Player {} performed action: {:?}", player_id, action);
+        if !lobby.players().contains_key(&player_id) {
+            debug!(
+                "Ignoring action from {} who is not in lobby {}",
+                player_id, lobby.code
+            );
+            return;
+        }
+        if !lobby.phase().allows(&action) {
+            debug!(
+                "Ignoring {:?} from {} in phase {:?}",
+                action,
+                player_id,
+                lobby.phase()
+            );
+            broadcaster.send_to(&player_id, ServerToClient::error("Not allowed right now"));
+            return;
+        }
         match action {
-            ClientToServer::PlayHand { score, hands_left } => {
-                Self::handle_play_hand(&mut lobby, &broadcaster, &player_id, score, hands_left);
+            ClientToServer::PlayHand {
+                score,
+                hands_left,
+                breakdown,
+            } => {
+                Self::handle_play_hand(
+                    &mut lobby,
+                    &broadcaster,
+                    &player_id,
+                    score,
+                    hands_left,
+                    breakdown,
+                );
+            }
+            ClientToServer::RoundComplete {} => {
+                Self::handle_round_complete(lobby, broadcaster, &player_id);
             }
             ClientToServer::SetLocation { location } => {
                 Self::handle_set_location(&mut lobby, &broadcaster, &player_id, location);
@@ -222,69 +814,57 @@ impl LobbyHandlers {
                 lobby.handle_player_fail_round(&player_id, &broadcaster);
             }
             ClientToServer::UpdateLobbyOptions { options } => {
-                if !lobby.is_player_host(&player_id) {
-                    debug!(
-                        "Player {} attempted to update lobby options but is not host",
-                        player_id
-                    );
-                    return;
-                }
-
-                lobby.lobby_options = options;
-                lobby.reset_ready_states_to_host_only();
-                lobby.broadcast_ready_states_except(&broadcaster, &player_id);
-                broadcaster.broadcast_except(
-                    &player_id,
-                    ServerToClient::UpdateLobbyOptions {
-                        options: lobby.lobby_options.clone(),
-                    },
-                );
+                Self::handle_update_lobby_options(lobby, broadcaster, &player_id, options);
             }
-            ClientToServer::StartGame { seed: _, stake } => {
+            ClientToServer::RevertLobbyOptions { target } => {
+                Self::handle_revert_lobby_options(lobby, broadcaster, &player_id, target);
+            }
+            // The lobby options decide the stake, whatever the host's client asks for
+            ClientToServer::StartGame { .. } => {
                 if lobby.is_player_host(&player_id) {
-                    lobby.start_game();
-                    broadcaster.broadcast(ServerToClient::ResetPlayers {
-                        players: lobby.players().values().cloned().collect(),
-                    });
-                    broadcaster.broadcast(ServerToClient::GameStarted {
-                        seed: lobby.lobby_options.custom_seed.clone(),
-                        stake,
-                    });
-                    lobby.broadcast_ready_states(&broadcaster);
-                    broadcaster.broadcast(ServerToClient::InGameStatuses {
-                        statuses: lobby.get_in_game_statuses(),
-                        started: lobby.started,
-                    });
+                    Self::start_game(lobby, broadcaster, &player_id);
                 }
             }
+            ClientToServer::SetHandicap {
+                player_id: target,
+                handicap,
+            } => {
+                Self::handle_set_handicap(lobby, broadcaster, &player_id, &target, handicap);
+            }
+            ClientToServer::VoteStart {} => {
+                Self::handle_vote_start(lobby, broadcaster, &player_id);
+            }
             ClientToServer::StopGame {} => {
-                lobby.started = false;
-                lobby.reset_game_states(false);
                 lobby.lobby_options.custom_seed = String::from("random");
-
-                broadcaster.broadcast(ServerToClient::GameStopped {});
+                Self::stop_game(lobby, broadcaster, StopReason::Host);
                 lobby.reset_ready_states_to_host_only();
                 lobby.broadcast_ready_states(&broadcaster);
-                broadcaster.broadcast(ServerToClient::InGameStatuses {
-                    statuses: lobby.get_in_game_statuses(),
-                    started: lobby.started,
-                });
             }
             ClientToServer::SetReady { is_ready } => {
                 lobby.set_player_ready(&player_id, is_ready);
-                if lobby.started {
-                    let all_ready = lobby
-                        .players()
-                        .values()
-                        .filter(|p| p.lobby_state.in_game)
-                        .all(|p| p.lobby_state.is_ready);
-                    if all_ready {
-                        lobby.start_online_blind(&broadcaster);
+                if lobby.started() {
+                    if let Some(overridden) = lobby.blind_start_overrides(false) {
+                        lobby.begin_pvp_blind_overriding(broadcaster, overridden);
                     }
                 } else {
                     lobby.broadcast_ready_states_except(&broadcaster, &player_id);
                 }
             }
+            ClientToServer::GetStandings {} => {
+                if lobby.lobby_options.gamemode != GameMode::Survival {
+                    broadcaster.send_to(&player_id, ServerToClient::error("Standings are only kept in Survival"));
+                } else {
+                    broadcaster.send_to(
+                        &player_id,
+                        ServerToClient::SurvivalStandings {
+                            standings: lobby.survival_standings(),
+                        },
+                    );
+                }
+            }
+            ClientToServer::ForceStartBlind {} => {
+                Self::handle_force_start_blind(lobby, broadcaster, &player_id);
+            }
             ClientToServer::SetBossBlind { key, chips } => {
                 if lobby.is_player_host(&player_id) {
                     debug!(
@@ -292,15 +872,25 @@ impl LobbyHandlers {
                         key,
                         chips.to_string()
                     );
-                    lobby.boss_chips = chips;
-                    broadcaster.broadcast_except(&player_id, ServerToClient::SetBossBlind { key });
+                    if let Err(e) = Self::validate_boss_chips(lobby, &player_id, &key, &chips) {
+                        broadcaster.send_to(&player_id, ServerToClient::error(e));
+                        return;
+                    }
+                    lobby.record_boss(&key);
+                    lobby.announce_boss(broadcaster, &player_id, key);
+                    lobby.set_boss_chips(chips, broadcaster);
                 }
             }
+            ClientToServer::SuggestBoss {} => match lobby.suggest_boss() {
+                Ok(key) => broadcaster.send_to(&player_id, ServerToClient::SuggestedBoss { key }),
+                Err(e) => broadcaster.send_to(&player_id, ServerToClient::error(e)),
+            },
+            ClientToServer::BanBoss { key } => {
+                debug!("Player {} banning boss {}", player_id, key);
+                lobby.ban_boss(broadcaster, &player_id, key);
+            }
             ClientToServer::SendPlayerDeck { deck } => {
-                broadcaster.broadcast(ServerToClient::ReceivePlayerDeck {
-                    player_id: player_id.clone(),
-                    deck,
-                });
+                Self::handle_send_player_deck(lobby, broadcaster, &player_id, deck);
             }
             ClientToServer::SendPhantom { key } => {
                 Self::handle_send_phantom(&broadcaster, &player_id, key);
@@ -321,13 +911,13 @@ impl LobbyHandlers {
                 Self::handle_sold_joker(&broadcaster, &player_id);
             }
             ClientToServer::SpentLastShop { amount } => {
-                Self::handle_spent_last_shop(&broadcaster, &player_id, amount);
+                Self::handle_spent_last_shop(lobby, broadcaster, &player_id, amount);
             }
-            ClientToServer::Magnet {} => {
-                Self::handle_magnet(&broadcaster, &player_id);
+            ClientToServer::Magnet { target } => {
+                Self::handle_magnet(lobby, broadcaster, &player_id, target);
             }
             ClientToServer::MagnetResponse { key } => {
-                Self::handle_magnet_response(&broadcaster, &player_id, key);
+                Self::handle_magnet_response(lobby, broadcaster, &player_id, key);
             }
             ClientToServer::SetFurthestBlind { blind } => {
                 Self::set_furthest_blind(&mut lobby, &broadcaster, &player_id, blind);
@@ -337,27 +927,47 @@ impl LobbyHandlers {
                     "Starting ante timer in lobby {} with time: {}",
                     lobby.code, time
                 );
-                broadcaster.broadcast_except(&player_id, ServerToClient::StartAnteTimer { time });
+                lobby.ante_timer_started(time, Instant::now());
+                broadcaster.broadcast_except(
+                    &player_id,
+                    ServerToClient::StartAnteTimer {
+                        time,
+                        server_time: now_millis(),
+                    },
+                );
             }
             ClientToServer::PauseAnteTimer { time } => {
                 debug!(
                     "Pausing ante timer in lobby {} with time: {}",
                     lobby.code, time
                 );
-                broadcaster.broadcast_except(&player_id, ServerToClient::PauseAnteTimer { time });
+                lobby.ante_timer_stopped();
+                broadcaster.broadcast_except(
+                    &player_id,
+                    ServerToClient::PauseAnteTimer {
+                        time,
+                        server_time: now_millis(),
+                    },
+                );
             }
             ClientToServer::FailTimer {} => {
                 LobbyHandlers::handle_fail_timer(&mut lobby, &broadcaster, &player_id);
             }
             ClientToServer::SendPlayerJokers { jokers } => {
                 debug!("Sending jokers for player {}: {}", player_id, jokers);
-                broadcaster.broadcast_except(
-                    &player_id,
-                    ServerToClient::ReceivePlayerJokers {
-                        player_id: player_id.clone(),
-                        jokers,
-                    },
-                );
+                Self::handle_send_player_jokers(lobby, broadcaster, &player_id, jokers);
+            }
+            ClientToServer::RequestOpponentJokers { player_id: opponent } => {
+                if let Some(jokers) = lobby.last_preview(&opponent, PreviewKind::Jokers) {
+                    lobby.note_preview_base(&player_id, &opponent, PreviewKind::Jokers);
+                    broadcaster.send_to(
+                        &player_id,
+                        ServerToClient::ReceivePlayerJokers {
+                            player_id: opponent,
+                            jokers,
+                        },
+                    );
+                }
             }
             ClientToServer::ReturnToLobby {} => {
                 // Mark player as not ready and not in game
@@ -369,30 +979,42 @@ impl LobbyHandlers {
                 let in_game_count = lobby.get_player_count_in_game();
 
                 // Handle game end conditions
-                if lobby.started {
+                if lobby.started() {
                     match in_game_count {
                         1 => {
                             if let Some((winner_id, _)) =
                                 lobby.players().iter().find(|(_, p)| p.lobby_state.in_game)
                             {
-                                broadcaster.send_to(winner_id, ServerToClient::WinGame {});
+                                broadcaster.send_to(
+                                    winner_id,
+                                    ServerToClient::WinGame {
+                                        reason: OutcomeReason::OpponentDisconnected,
+                                        standings: lobby.compute_standings(),
+                                    },
+                                );
                             }
+                            lobby.finish_game(broadcaster);
                         }
                         0 => {
-                            lobby.started = false;
+                            lobby.set_phase(LobbyPhase::Waiting);
                             lobby.reset_game_states(false);
-                            broadcaster.broadcast(ServerToClient::GameStopped {});
+                            broadcaster.broadcast(ServerToClient::GameStopped {
+                                reason: StopReason::PlayersLeft,
+                            });
                             lobby.reset_ready_states_to_host_only();
                         }
                         _ => {}
                     }
+                } else if in_game_count == 0 {
+                    // Everyone is back from the results screen
+                    lobby.set_phase(LobbyPhase::Waiting);
                 }
 
                 // Broadcast updated ready states and in-game statuses
                 lobby.broadcast_ready_states(&broadcaster);
                 broadcaster.broadcast(ServerToClient::InGameStatuses {
                     statuses: lobby.get_in_game_statuses(),
-                    started: lobby.started,
+                    started: lobby.started(),
                 });
             }
             ClientToServer::SendMoney {
@@ -400,7 +1022,67 @@ impl LobbyHandlers {
             } => {
                 broadcaster.send_to(&target_player_id, ServerToClient::ReceivedMoney {});
             }
-            ClientToServer::Discard {} => todo!(),
+            ClientToServer::ReserveSlot { account_id } => {
+                Self::handle_reserve_slot(lobby, broadcaster, &player_id, account_id);
+            }
+            ClientToServer::CancelReservation { account_id } => {
+                Self::handle_cancel_reservation(lobby, broadcaster, &player_id, &account_id);
+            }
+            ClientToServer::OfferLobbyMerge { open } => {
+                Self::handle_offer_lobby_merge(lobby, broadcaster, &player_id, open);
+            }
+            ClientToServer::SetPublicListing { listed } => {
+                Self::handle_set_public_listing(lobby, broadcaster, &player_id, listed);
+            }
+            ClientToServer::CreateInvite { bypass_reservations } => {
+                Self::handle_create_invite(lobby, broadcaster, &player_id, bypass_reservations);
+            }
+            ClientToServer::SetActionAudit { enabled } => {
+                if lobby.is_player_host(&player_id) {
+                    debug!("Host {} set action audit in {}: {}", player_id, lobby.code, enabled);
+                    lobby.set_action_audit(enabled, broadcaster);
+                } else {
+                    broadcaster.send_to(
+                        &player_id,
+                        ServerToClient::error("Only the host can switch action audit"),
+                    );
+                }
+            }
+            ClientToServer::Announce { text } => {
+                Self::handle_announce(lobby, broadcaster, &player_id, text);
+            }
+            ClientToServer::SendEmote { emote_id, team_only } => {
+                Self::handle_send_emote(lobby, broadcaster, &player_id, emote_id, team_only);
+            }
+            ClientToServer::ReportBug { description } => {
+                Self::handle_report_bug(lobby, broadcaster, &player_id, description);
+            }
+            ClientToServer::SetAnte { ante } => {
+                // Kept for lobby stats, opponents see it with the next game state update
+                lobby.set_ante(&player_id, ante);
+            }
+            ClientToServer::RunChecksum { hash } => {
+                if let Err(message) = lobby.record_run_checksum(&player_id, hash) {
+                    broadcaster.send_to(&player_id, ServerToClient::error(message));
+                }
+            }
+            ClientToServer::GetPlayerLocations {} => {
+                broadcaster.send_to(
+                    &player_id,
+                    ServerToClient::PlayerLocations {
+                        locations: lobby.collect_locations(),
+                    },
+                );
+            }
+            ClientToServer::Forfeit {} => {
+                lobby.forfeit(&player_id, broadcaster);
+            }
+            ClientToServer::RequestRoll { key, sides } => {
+                Self::handle_request_roll(lobby, broadcaster, &player_id, key, sides);
+            }
+            ClientToServer::Discard {} => {
+                lobby.record_discard(&player_id);
+            }
             other => {
                 debug!("Unhandled action from player {}: {:?}", player_id, other);
             }
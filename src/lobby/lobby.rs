@@ -1,530 +1,4002 @@
-use super::{broadcaster::LobbyBroadcaster, game_state::ClientLobbyEntry};
+use super::{
+    announcements,
+    awards,
+    bot::BotDifficulty,
+    boss_ban::{BOSS_BAN_POOL_SIZE, BOSS_BAN_TIMEOUT, BOSS_BLINDS, BossBanPhase},
+    boss_rotation::BossRotation,
+    bug_report::{MAX_BUG_REPORTS_PER_LOBBY, MAX_BUG_REPORTS_PER_PLAYER},
+    broadcaster::LobbyBroadcaster,
+    checkpoint::{CheckpointPlayer, LobbyCheckpoint},
+    decks::{deck_back, same_back},
+    event_log::LobbyEventLog,
+    game_state::{ClientGameState, ClientLobbyEntry, DEFAULT_TEAM, Handicap},
+    hand_breakdown::HandBreakdown,
+    options_history::{OptionsDiff, OptionsHistory, diff_options},
+    phase::{LOBBY_LOCATION, LobbyPhase, SHOP_LOCATION},
+    preview::{PreviewKind, PreviewPatch},
+    run_integrity::RunChecksums,
+    shared_rng::SharedRng,
+    stats::MatchStats,
+    tutorial::{Tutorial, WELCOME, tutorial_announcement},
+};
 use crate::{
+    audit::{self, AuditEvent},
     client::ClientProfile,
-    game_mode::{CLASH_BASE_DAMAGE, GameMode, LobbyOptions},
-    messages::ServerToClient,
+    config::CONFIG,
+    federation::{self, FederatedResult, FederatedStanding},
+    invites::Invite,
+    game_mode::{CoopReadyRule, GameMode, LIFE_LOSS_GOLD, LobbyOptions, ReadyTimeoutAction},
+    messages::{
+        LobbyListing, MergeOffer, OutcomeReason, ServerToClient, ShopSpend, Standing,
+        SurvivalStanding,
+    },
+    scheduled_events,
     talisman_number::TalismanNumber,
-    utils::time_based_string,
+    usage_stats,
+    utils::{constant_time_eq, encode_hex, now_millis, random_seed_string, time_based_string},
+    webhooks::{self, WebhookPayload},
 };
-use rand::rng;
-use rand::seq::SliceRandom;
 use serde::Serialize;
-use std::{collections::HashMap};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
 use tracing::{debug, error};
 
+/// How long the targeted player has to answer a magnet request
+pub const MAGNET_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often player latencies are broadcast to the lobby
+pub const LATENCY_BROADCAST_INTERVAL: Duration = Duration::from_secs(5);
+/// Survival standings go out this often while a game runs
+pub const SURVIVAL_STANDINGS_INTERVAL: Duration = Duration::from_secs(10);
+/// How often match statistics are broadcast while a game is running
+pub const LOBBY_STATS_INTERVAL: Duration = Duration::from_secs(10);
+/// Dynamic difficulty: multiplier change per boss and its bounds
+pub const DIFFICULTY_STEP: f64 = 0.25;
+pub const DIFFICULTY_MIN_MULTIPLIER: f64 = 0.5;
+pub const DIFFICULTY_MAX_MULTIPLIER: f64 = 3.0;
+/// Beating the boss by at least this many orders of magnitude counts as comfortable
+pub const DIFFICULTY_COMFORTABLE_MARGIN: f64 = 0.3;
+/// How long room is held for a lobby merging into this one
+const MERGE_RESERVATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Antes spending is kept apart for, well past where runs end; later antes add to the last
+const MAX_SPEND_ANTES: usize = 100;
+
 #[derive(Debug)]
 pub struct RoundResult {
     pub player_id: String,
     pub won: bool,
 }
 
+/// One played hand, recorded for the round's scoring timeline
+#[derive(Debug, Clone, Serialize)]
+pub struct HandScore {
+    pub player_id: String,
+    pub score: TalismanNumber,
+    pub hands_left: u8,
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<HandBreakdown>,
+}
+
+/// How a PvP blind played out, sent with its results
+#[derive(Debug)]
+pub struct RoundTimeline {
+    pub started_at: Option<u64>,
+    /// Milliseconds from the blind starting to each player being done with it
+    pub finish_ms: HashMap<String, u64>,
+    pub hands: Vec<HandScore>,
+}
+
+/// Gold a player receives after a round, decided by the lobby options
+#[derive(Debug)]
+pub struct RoundReward {
+    pub player_id: String,
+    pub gold: u32,
+    pub blind_reward: bool,
+}
+
+/// A magnet request waiting for the targeted player's response
+#[derive(Debug, Clone)]
+pub struct MagnetTransaction {
+    pub requester_id: String,
+    pub target_id: String,
+    pub deadline: Instant,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Lobby {
     pub code: String,
-    pub started: bool,
+    phase: LobbyPhase,
+    /// Mirrors `phase.is_started()` for clients that predate phases
+    started: bool,
+    /// Last phase clients were told about
+    #[serde(skip)]
+    announced_phase: LobbyPhase,
     pub boss_chips: TalismanNumber,
+    /// Dynamic difficulty factor applied to the boss chips set by the host
+    pub boss_chip_multiplier: f64,
     pub lobby_options: LobbyOptions,
     stage: i32,
+    /// Furthest ante any player reported this game, boss chips are checked against it
+    #[serde(skip)]
+    highest_ante: u32,
     players: HashMap<String, ClientLobbyEntry>,
     max_players: u8,
+    #[serde(skip)]
+    magnet: Option<MagnetTransaction>,
+    #[serde(skip)]
+    boss_ban: Option<BossBanPhase>,
+    /// Boss set by the host with `hidden_boss` on, and who set it, revealed when the blind starts
+    #[serde(skip)]
+    withheld_boss: Option<(String, String)>,
+    /// Bosses the host set lately, when they pick them
+    #[serde(skip)]
+    boss_rotation: BossRotation,
+    /// Set for single-player tutorial lobbies, closed to anyone but the bot
+    #[serde(skip)]
+    tutorial: Option<Tutorial>,
+    /// Back every player must use this game, set when `different_decks` is off
+    #[serde(skip)]
+    required_back: Option<String>,
+    #[serde(skip)]
+    last_latency_broadcast: Option<Instant>,
+    #[serde(skip)]
+    last_standings_broadcast: Option<Instant>,
+    #[serde(skip)]
+    event_log: LobbyEventLog,
+    #[serde(skip)]
+    reservations: HashMap<String, Instant>,
+    /// Invites already used to join, by nonce, until they expire
+    #[serde(skip)]
+    redeemed_invites: HashMap<String, u64>,
+    /// Bug reports filed in this lobby, by player
+    #[serde(skip)]
+    bug_reports: HashMap<String, u32>,
+    /// The host agreed to merge this lobby with another waiting one
+    #[serde(skip)]
+    merge_offered: bool,
+    /// Room held for a lobby merging in: its code, how many players, until when
+    #[serde(skip)]
+    merge_reservation: Option<(String, usize, Instant)>,
+    /// How hard each bot seated here plays, so a merge can take them along
+    #[serde(skip)]
+    bot_difficulties: HashMap<String, BotDifficulty>,
+    /// Previews each player was sent in full, by recipient, sender and kind; patches
+    /// only go on top of those
+    #[serde(skip)]
+    preview_bases: HashSet<(String, String, PreviewKind)>,
+    /// The host listed this lobby for clients browsing open lobbies
+    #[serde(skip)]
+    publicly_listed: bool,
+    #[serde(skip)]
+    options_history: OptionsHistory,
+    /// Debug mode: every processed action is acknowledged to its sender
+    #[serde(skip)]
+    action_audit: bool,
+    /// Actions processed so far, reported with audit acknowledgements
+    #[serde(skip)]
+    state_version: u64,
+    /// Team draws so far, keys the shuffle of the next one
+    #[serde(skip)]
+    team_draws: u32,
+    /// Players who voted to start the game, see `vote_to_start`
+    #[serde(skip)]
+    start_votes: HashSet<String>,
+    /// When the host's recent announcements went out, whoever was host at the time
+    #[serde(skip)]
+    recent_announcements: VecDeque<Instant>,
+    #[serde(skip)]
+    ready_deadline: Option<Instant>,
+    /// Secret each player got on joining, proves a reconnecting client owns a held seat
+    #[serde(skip)]
+    reconnect_tokens: HashMap<String, String>,
+    /// Seconds on the ante timer a client last started, and when
+    #[serde(skip)]
+    ante_timer: Option<(u32, Instant)>,
+    /// Seconds left on the ante timer paused while a dropped player's seat is held
+    #[serde(skip)]
+    held_ante_timer: Option<u32>,
+    #[serde(skip)]
+    ready_countdown_announced: Option<u32>,
+    /// Blitz: when the running PvP blind fails whoever hasn't finished
+    #[serde(skip)]
+    blitz_deadline: Option<Instant>,
+    /// Blitz: PvP blinds started this game, each adds an increment to the next limit
+    #[serde(skip)]
+    blitz_blinds: u32,
+    #[serde(skip)]
+    round_timeline: Vec<HandScore>,
+    /// Server time the current PvP blind started
+    #[serde(skip)]
+    round_started_at: Option<u64>,
+    /// Server time each player ran out of hands or completed the current PvP blind
+    #[serde(skip)]
+    round_finished_at: HashMap<String, u64>,
+    /// Players knocked out this game, grouped by the round they fell in
+    #[serde(skip)]
+    eliminations: Vec<Vec<String>>,
+    /// CoopSurvival players out of the run, waiting for the team to clear a boss
+    #[serde(skip)]
+    awaiting_revive: Vec<String>,
+    /// Skip counts already turned into a PvP handicap
+    #[serde(skip)]
+    skips_at_last_pvp: HashMap<String, u8>,
+    /// Anonymous mode placeholders, stable for as long as the lobby lives
+    #[serde(skip)]
+    aliases: HashMap<String, String>,
+    #[serde(skip)]
+    names_revealed: bool,
+    /// Each player's own seed this game when `different_seeds` is on, never shown to
+    /// the others
+    #[serde(skip)]
+    player_seeds: HashMap<String, String>,
+    #[serde(skip)]
+    run_checksums: RunChecksums,
+    /// Players caught with a banned card this game, their results are unverified
+    #[serde(skip)]
+    rule_breakers: HashSet<String>,
+    /// Game states from a checkpoint, keyed by account id, waiting for their owner to rejoin
+    #[serde(skip)]
+    restored_players: HashMap<String, ClientGameState>,
+    #[serde(skip)]
+    stats: MatchStats,
+    /// Rolls for random events all players share, reseeded every game
+    #[serde(skip)]
+    rng: SharedRng,
 }
 
 impl Lobby {
     pub fn new(code: String, ruleset: String, game_mode: GameMode) -> Self {
-        let mut new_gamemode = game_mode.get_default_options();
+        let mut new_gamemode = scheduled_events::event_defaults(game_mode);
         new_gamemode.ruleset = ruleset;
         Self {
             code,
+            phase: LobbyPhase::Waiting,
             started: false,
+            announced_phase: LobbyPhase::Waiting,
             boss_chips: TalismanNumber::Regular(0.0),
+            boss_chip_multiplier: 1.0,
             lobby_options: new_gamemode,
             players: HashMap::new(),
             stage: 0,
+            highest_ante: 0,
             max_players: game_mode.get_max_players(),
+            magnet: None,
+            boss_ban: None,
+            withheld_boss: None,
+            boss_rotation: BossRotation::default(),
+            tutorial: None,
+            team_draws: 0,
+            start_votes: HashSet::new(),
+            required_back: None,
+            last_latency_broadcast: None,
+            last_standings_broadcast: None,
+            event_log: LobbyEventLog::new(CONFIG.get().lobby_event_history),
+            reservations: HashMap::new(),
+            redeemed_invites: HashMap::new(),
+            bug_reports: HashMap::new(),
+            merge_offered: false,
+            merge_reservation: None,
+            bot_difficulties: HashMap::new(),
+            preview_bases: HashSet::new(),
+            publicly_listed: false,
+            options_history: OptionsHistory::default(),
+            action_audit: false,
+            state_version: 0,
+            recent_announcements: VecDeque::new(),
+            ready_deadline: None,
+            reconnect_tokens: HashMap::new(),
+            ante_timer: None,
+            held_ante_timer: None,
+            ready_countdown_announced: None,
+            blitz_deadline: None,
+            blitz_blinds: 0,
+            round_timeline: Vec::new(),
+            round_started_at: None,
+            round_finished_at: HashMap::new(),
+            eliminations: Vec::new(),
+            awaiting_revive: Vec::new(),
+            skips_at_last_pvp: HashMap::new(),
+            aliases: HashMap::new(),
+            names_revealed: false,
+            player_seeds: HashMap::new(),
+            run_checksums: RunChecksums::default(),
+            rule_breakers: HashSet::new(),
+            restored_players: HashMap::new(),
+            stats: MatchStats::default(),
+            rng: SharedRng::default(),
         }
     }
 
-    pub fn get_player_mut(&mut self, player_id: &str) -> Option<&mut ClientLobbyEntry> {
-        self.players.get_mut(player_id)
+    pub fn phase(&self) -> LobbyPhase {
+        self.phase
     }
 
-    pub fn players(&self) -> &HashMap<String, ClientLobbyEntry> {
-        &self.players
+    /// A game is running, see [`LobbyPhase::is_started`]
+    pub fn started(&self) -> bool {
+        self.phase.is_started()
     }
 
-    pub fn is_full(&self) -> bool {
-        self.players.len() >= self.max_players as usize
+    /// Move to `next` if the current phase allows it; clients hear about it from the lobby task
+    pub fn set_phase(&mut self, next: LobbyPhase) -> bool {
+        if self.phase == next {
+            return true;
+        }
+        if !self.phase.can_transition_to(next) {
+            debug!(
+                "Lobby {} refused phase change {:?} -> {:?}",
+                self.code, self.phase, next
+            );
+            return false;
+        }
+        debug!("Lobby {} phase {:?} -> {:?}", self.code, self.phase, next);
+        self.phase = next;
+        self.started = next.is_started();
+        true
     }
 
-    pub fn randomize_teams(&mut self, team_size: u8) {
-        let mut rng = rng();
-        let mut player_ids: Vec<String> = self.players.keys().cloned().collect();
-        player_ids.shuffle(&mut rng);
-
-        let mut team = 1;
-        for (i, player_id) in player_ids.iter().enumerate() {
-            if i > 0 && i % team_size as usize == 0 {
-                team += 1;
-            }
-            if let Some(player) = self.players.get_mut(player_id) {
-                player.game_state.team = team;
+    pub fn broadcast_phase_if_changed(&mut self, broadcaster: &LobbyBroadcaster) {
+        if self.announced_phase != self.phase {
+            self.announced_phase = self.phase;
+            broadcaster.broadcast(ServerToClient::LobbyPhase { phase: self.phase });
+            if let Some(text) = self.tutorial.as_mut().and_then(|t| t.narrate(self.phase)) {
+                broadcaster.broadcast(tutorial_announcement(text));
             }
         }
     }
 
-    pub fn add_player(
-        &mut self,
-        player_id: String,
-        client_profile: ClientProfile,
-    ) -> ClientLobbyEntry {
-        let is_host = self.players.is_empty();
-        let entry = ClientLobbyEntry::new(
-            client_profile,
-            self.code.clone(),
-            is_host,
-            self.lobby_options.starting_lives,
-        );
-        self.players.insert(player_id, entry.clone());
-        entry
+    /// Turn the lobby into a tutorial once its bot has joined, and greet the player
+    pub fn start_tutorial(&mut self, broadcaster: &LobbyBroadcaster) {
+        self.tutorial = Some(Tutorial::default());
+        broadcaster.broadcast(tutorial_announcement(WELCOME));
     }
 
-    pub fn remove_player(&mut self, player_id: &str) -> Option<ClientLobbyEntry> {
-        self.players.remove(player_id)
+    pub fn is_tutorial(&self) -> bool {
+        self.tutorial.is_some()
     }
 
-    pub fn promote_new_host(&mut self) -> Option<String> {
-        if let Some((new_host_id, new_host_entry)) = self.players.iter_mut().next() {
-            new_host_entry.lobby_state.is_host = true;
-            new_host_entry.lobby_state.is_ready = true;
-            Some(new_host_id.clone())
-        } else {
-            None
+    /// Runs leave the lobby screen once loaded, and the shop once players move on
+    pub fn note_location(&mut self, location: &str) {
+        let advances = match self.phase {
+            LobbyPhase::Starting => location != LOBBY_LOCATION,
+            LobbyPhase::ShopPhase => location != SHOP_LOCATION,
+            _ => false,
+        };
+        if advances {
+            self.set_phase(LobbyPhase::InRound);
         }
     }
 
-    pub fn get_alive_player_count(&self) -> usize {
-        self.players
-            .values()
-            .filter(|p| p.game_state.lives > 0 && p.lobby_state.in_game)
-            .count()
+    pub fn get_player_mut(&mut self, player_id: &str) -> Option<&mut ClientLobbyEntry> {
+        self.players.get_mut(player_id)
     }
 
-    pub fn is_player_host(&self, player_id: &str) -> bool {
+    pub fn players(&self) -> &HashMap<String, ClientLobbyEntry> {
+        &self.players
+    }
+
+    pub fn event_log(&self) -> &LobbyEventLog {
+        &self.event_log
+    }
+
+    pub fn record_event(&mut self, player_id: Option<&str>, description: impl Into<String>) {
+        self.event_log.record(player_id, description);
+    }
+
+    /// Start a new stay for a player that (re)joined under `generation`
+    pub fn set_lobby_generation(&mut self, player_id: &str, generation: u64) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.lobby_state.lobby_generation = generation;
+        }
+    }
+
+    /// Whether an action belongs to the player's current stay; false for clients that
+    /// already left and for actions still in flight from an earlier stay
+    pub fn accepts_lobby_generation(&self, player_id: &str, generation: u64) -> bool {
         self.players
             .get(player_id)
-            .map(|p| p.lobby_state.is_host)
-            .unwrap_or(false)
+            .is_some_and(|p| p.lobby_state.lobby_generation == generation)
     }
 
-    // DRY: Consolidated ready state operations
-    pub fn reset_ready_states(&mut self) {
-        for player in self.players.values_mut() {
-            player.lobby_state.is_ready = false;
+    /// Record a client sequence id; returns false if the action was already applied
+    pub fn accept_action_seq(&mut self, player_id: &str, seq: u64) -> bool {
+        let Some(player) = self.players.get_mut(player_id) else {
+            return false;
+        };
+        if player.lobby_state.last_action_seq.is_some_and(|last| seq <= last) {
+            return false;
         }
+        player.lobby_state.last_action_seq = Some(seq);
+        true
     }
 
-    pub fn reset_ready_states_to_host_only(&mut self) {
-        for player in self.players.values_mut() {
-            player.lobby_state.is_ready = player.lobby_state.is_host;
+    pub fn is_full(&self) -> bool {
+        self.players.len() + self.merge_reserved() >= self.max_players as usize
+    }
+
+    // Lobby options history
+    pub fn options_history(&self) -> &OptionsHistory {
+        &self.options_history
+    }
+
+    /// Replace the lobby options, remembering the previous set; returns the changed fields
+    pub fn apply_options(&mut self, options: LobbyOptions, changed_by: &str) -> OptionsDiff {
+        let diff = diff_options(&self.lobby_options, &options);
+        if !diff.is_empty() {
+            let previous = std::mem::replace(&mut self.lobby_options, options);
+            self.options_history.push(changed_by, previous, diff.clone());
         }
+        diff
     }
 
-    pub fn set_player_ready(&mut self, player_id: &str, is_ready: bool) {
-        if let Some(player) = self.players.get_mut(player_id) {
-            player.lobby_state.is_ready = is_ready;
+    /// Log an options change to the audit trail; no-op when nothing changed
+    pub fn audit_options_change(&self, changed_by: &str, changes: &OptionsDiff) {
+        if changes.is_empty() {
+            return;
         }
+        audit::record(
+            &self.code,
+            AuditEvent::OptionsChanged {
+                changed_by: changed_by.to_string(),
+                changes: changes.clone(),
+                during_game: self.started(),
+            },
+        );
     }
 
-    pub fn collect_ready_states(&self) -> HashMap<String, bool> {
-        self.players
-            .iter()
-            .map(|(id, entry)| (id.clone(), entry.lobby_state.is_ready))
-            .collect()
+    /// Roll back the most recent options change
+    pub fn revert_to_previous_options(&mut self) -> Option<OptionsDiff> {
+        let change = self.options_history.pop()?;
+        let diff = diff_options(&self.lobby_options, &change.previous);
+        self.lobby_options = change.previous;
+        Some(diff)
     }
 
-    // Game state management
-    pub fn reset_game_states(&mut self, in_game: bool) {
-        for player in self.players.values_mut() {
-            player.reset_for_game(self.lobby_options.starting_lives);
-            player.lobby_state.in_game = in_game;
+    /// Reset options to the game mode defaults, keeping the lobby's ruleset
+    pub fn revert_to_default_options(&mut self, changed_by: &str) -> OptionsDiff {
+        let mut defaults = scheduled_events::event_defaults(self.lobby_options.gamemode);
+        defaults.ruleset = self.lobby_options.ruleset.clone();
+        self.apply_options(defaults, changed_by)
+    }
+
+    // Slot reservations
+    fn free_slots(&self) -> usize {
+        (self.max_players as usize).saturating_sub(self.players.len() + self.merge_reserved())
+    }
+
+    /// Check whether a profile may take a slot, honouring active reservations
+    /// `bypass_reservations` lets the joiner take a reserved slot, for host invites
+    pub fn check_can_join(
+        &self,
+        profile: &ClientProfile,
+        bypass_reservations: bool,
+    ) -> Result<(), &'static str> {
+        if self.is_tutorial() {
+            return Err("Tutorial lobbies are single-player");
+        }
+        if self.is_full() {
+            return Err("Lobby is full");
+        }
+        // Reserved ids are broadcast, only a verified account can claim one
+        let holds_reservation = profile
+            .verified_account()
+            .is_some_and(|id| self.reservations.contains_key(id));
+        if !holds_reservation
+            && !bypass_reservations
+            && self.free_slots() <= self.reservations.len()
+        {
+            return Err("Remaining slots are reserved");
         }
+        Ok(())
     }
 
-    pub fn start_game(&mut self) {
-        self.started = true;
-        self.stage = 0;
-        if !self.lobby_options.different_seeds
-            && self.lobby_options.custom_seed == String::from("random")
+    pub fn reserve_slot(&mut self, account_id: String) -> Result<(), &'static str> {
+        if !self.reservations.contains_key(&account_id)
+            && self.reservations.len() >= self.free_slots()
         {
-            self.lobby_options.custom_seed = time_based_string(8);
-            debug!(
-                "Generating time-based seed for lobby {} seed: {}",
-                self.code, self.lobby_options.custom_seed
-            );
+            return Err("No free slots to reserve");
         }
-        self.reset_game_states(true);
+        let expires_at =
+            Instant::now() + Duration::from_secs(CONFIG.get().slot_reservation_secs);
+        self.reservations.insert(account_id, expires_at);
+        Ok(())
     }
 
-    pub fn stop_game(&mut self) {
-        self.started = false;
-        self.reset_game_states(false);
-        self.stage = 0;
-        self.boss_chips = TalismanNumber::Regular(0.0);
+    pub fn cancel_reservation(&mut self, account_id: &str) -> bool {
+        self.reservations.remove(account_id).is_some()
     }
 
-    pub fn reset_scores(&mut self) {
-        for player in self.players.values_mut() {
-            player.game_state.score = TalismanNumber::Regular(0.0);
-            player.game_state.hands_left = player.game_state.hands_max;
-            player.game_state.discards_left = player.game_state.discards_max;
-        }
+    /// Consume the reservation held by a joining profile's verified account, if any
+    pub fn claim_reservation(&mut self, profile: &ClientProfile) -> bool {
+        profile
+            .verified_account()
+            .is_some_and(|id| self.reservations.remove(id).is_some())
     }
 
-    pub fn get_total_score(&self) -> TalismanNumber {
-        let mut acc = TalismanNumber::Regular(0.0);
-        for player in self.players.values() {
-            acc = acc.add(&player.game_state.score).unwrap_or(acc.clone());
-        }
-        acc
+    pub fn reserved_account_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.reservations.keys().cloned().collect();
+        ids.sort();
+        ids
     }
 
-    pub fn all_players_done(&self) -> bool {
-        self.players
-            .values()
-            .filter(|p| p.lobby_state.in_game)
-            .all(|p| p.game_state.hands_left == 0)
+    pub fn broadcast_reservations(&self, broadcaster: &LobbyBroadcaster) {
+        broadcaster.broadcast(ServerToClient::ReservationsUpdated {
+            account_ids: self.reserved_account_ids(),
+        });
     }
 
-    pub fn is_someone_dead(&self) -> bool {
-        self.players
-            .values()
-            .any(|p| p.game_state.lives == 0 && p.lobby_state.in_game)
+    // Action audit
+    pub fn action_audit(&self) -> bool {
+        self.action_audit
     }
 
-    pub fn handle_player_fail_round(&mut self, player_id: &str, broadcaster: &LobbyBroadcaster) {
-        debug!("Player {} failed a round in lobby {}", player_id, self.code);
+    pub fn set_action_audit(&mut self, enabled: bool, broadcaster: &LobbyBroadcaster) {
+        self.action_audit = enabled;
+        broadcaster.broadcast(ServerToClient::ActionAuditChanged { enabled });
+    }
 
-        if self.lobby_options.death_on_round_loss {
-            self.process_round_outcome(&vec![RoundResult {
-                player_id: player_id.to_string(),
-                won: false,
-            }]);
+    /// Count a processed action; `audited` names it when the sender is owed an acknowledgement
+    pub fn action_processed(
+        &mut self,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        audited: Option<&'static str>,
+        seq: Option<u64>,
+    ) {
+        self.state_version += 1;
+        if let Some(processed) = audited {
+            broadcaster.send_to(
+                player_id,
+                ServerToClient::ActionAck {
+                    processed: processed.to_string(),
+                    seq,
+                    state_version: self.state_version,
+                },
+            );
         }
-        self.broadcast_life_updates(broadcaster, player_id);
+    }
 
-        // Use unified game over check
-        self.check_and_handle_game_over(broadcaster);
+    // Invites
+    /// Whether an invite, already verified as signed and unexpired, may still let someone in
+    pub fn check_invite(&self, invite: &Invite) -> Result<(), &'static str> {
+        if invite.lobby_code != self.code {
+            return Err("Invite is for another lobby");
+        }
+        if self.redeemed_invites.contains_key(&invite.nonce) {
+            return Err("Invite was already used");
+        }
+        Ok(())
     }
 
-    // Game logic - kept in lobby for now but could be moved to game_logic module
-    pub fn evaluate_online_round(&mut self, broadcaster: &LobbyBroadcaster) {
-        if !self.all_players_done() {
-            return;
+    /// Use up an invite; expired ones are forgotten as their tokens no longer verify
+    pub fn redeem_invite(&mut self, invite: &Invite, now: u64) {
+        self.redeemed_invites.retain(|_, expires_at| *expires_at > now);
+        self.redeemed_invites.insert(invite.nonce.clone(), invite.expires_at);
+    }
+
+    /// Count a bug report against the player's and the lobby's allowance
+    pub fn take_bug_report_slot(&mut self, player_id: &str) -> Result<(), &'static str> {
+        if self.bug_reports.values().sum::<u32>() >= MAX_BUG_REPORTS_PER_LOBBY {
+            return Err("This lobby has filed too many bug reports");
+        }
+        let filed = self.bug_reports.entry(player_id.to_string()).or_default();
+        if *filed >= MAX_BUG_REPORTS_PER_PLAYER {
+            return Err("You have filed too many bug reports in this lobby");
         }
+        *filed += 1;
+        Ok(())
+    }
 
-        debug!("Evaluating online battle for lobby {}", self.code);
+    // Lobby merging
+    pub fn set_merge_offered(&mut self, open: bool) {
+        self.merge_offered = open;
+    }
 
-        let result = self.determine_round_outcome();
-        self.process_round_outcome(&result);
+    /// Hold room for all `incoming` players of `from_code` until they arrive; refused
+    /// when not every one of them fits
+    pub fn reserve_merge(
+        &mut self,
+        from_code: &str,
+        incoming: usize,
+        now: Instant,
+    ) -> Result<(), &'static str> {
+        if self.is_tutorial() {
+            return Err("Tutorial lobbies are single-player");
+        }
+        if self.started() {
+            return Err("Game already started");
+        }
+        if self.merge_pending() {
+            return Err("Another lobby is already merging in");
+        }
+        if incoming + self.reservations.len() > self.free_slots() {
+            return Err("Not enough room for everyone");
+        }
+        self.merge_reservation =
+            Some((from_code.to_string(), incoming, now + MERGE_RESERVATION_TIMEOUT));
+        Ok(())
+    }
 
-        // Use unified game over check
-        let game_over = self.check_and_handle_game_over(broadcaster);
-        if game_over {
-            self.started = false;
-            self.reset_ready_states_to_host_only();
-        } else {
-            self.reset_scores();
-            self.reset_ready_states();
-            self.broadcast_end_round_results(broadcaster, &result);
+    /// Release the room held for `from_code` to its arriving players; false when none
+    /// is held for it
+    pub fn take_merge_reservation(&mut self, from_code: &str) -> bool {
+        if self.merge_reservation.as_ref().is_some_and(|(code, _, _)| code == from_code) {
+            self.merge_reservation = None;
+            return true;
         }
-        self.broadcast_ready_states(broadcaster);
-        self.broadcast_all_game_states(broadcaster);
-        broadcaster.broadcast(ServerToClient::InGameStatuses {
-            statuses: self.get_in_game_statuses(),
-            started: self.started,
-        });
+        false
     }
 
-    fn determine_round_outcome(&self) -> Vec<RoundResult> {
-        match self.lobby_options.gamemode {
-            GameMode::CoopSurvival => {
-                let mut results = Vec::new();
-                let won = self.get_total_score() > self.boss_chips;
-                for (id, _) in &self.players {
-                    results.push(RoundResult {
-                        player_id: id.clone(),
-                        won,
-                    });
-                }
-                return results;
-            }
-            GameMode::Clash => {
-                let mut sorted_players = self
-                    .players
-                    .iter()
-                    .filter(|(_, p)| p.lobby_state.in_game)
-                    .collect::<Vec<(&String, &ClientLobbyEntry)>>();
-                sorted_players.sort_by(|a, b| b.1.game_state.score.cmp(&a.1.game_state.score));
-                let top_score = sorted_players[0].1.game_state.score.clone();
+    /// Players of another lobby are on their way in
+    pub fn merge_pending(&self) -> bool {
+        self.merge_reservation.is_some()
+    }
 
-                let mut results = Vec::new();
-                for (id, player) in sorted_players {
-                    results.push(RoundResult {
-                        player_id: id.clone(),
-                        won: player.game_state.score == top_score,
-                    });
-                }
-                return results;
-            }
+    fn merge_reserved(&self) -> usize {
+        self.merge_reservation.as_ref().map_or(0, |(_, incoming, _)| *incoming)
+    }
 
-            _ => {
-                if self.players.len() < 2 {
-                    error!("Not enough players to evaluate round");
-                    return vec![RoundResult {
-                        player_id: String::new(),
-                        won: false,
-                    }];
-                }
+    fn expire_merge_reservation(&mut self, now: Instant) {
+        if self.merge_reservation.as_ref().is_some_and(|(_, _, until)| now >= *until) {
+            debug!("Merge reservation in lobby {} expired", self.code);
+            self.merge_reservation = None;
+        }
+    }
 
-                let mut result = vec![];
-                // Find the actual highest score
-                let top_score = self
-                    .players
-                    .values()
-                    .map(|p| &p.game_state.score)
-                    .max()
-                    .unwrap(); // Safe because we checked players.len() >= 2
+    pub fn set_bot_difficulty(&mut self, bot_id: &str, difficulty: BotDifficulty) {
+        self.bot_difficulties.insert(bot_id.to_string(), difficulty);
+    }
 
-                for (id, player) in &self.players {
-                    result.push(RoundResult {
-                        player_id: id.clone(),
-                        won: &player.game_state.score == top_score,
-                    });
-                }
+    pub fn bot_difficulty(&self, bot_id: &str) -> Option<BotDifficulty> {
+        self.bot_difficulties.get(bot_id).copied()
+    }
 
-                result
+    /// What to tell the coordinator while the lobby is open to merging: only waiting
+    /// lobbies with room left take part
+    pub fn merge_offer(&self) -> Option<MergeOffer> {
+        let open = self.merge_offered && !self.is_tutorial() && !self.merge_pending();
+        (open && self.phase == LobbyPhase::Waiting && !self.is_full()).then(|| {
+            MergeOffer {
+                lobby_code: self.code.clone(),
+                game_mode: self.lobby_options.gamemode,
+                ruleset: self.lobby_options.ruleset.clone(),
+                players: self.players.len(),
+                max_players: self.max_players as usize,
             }
+        })
+    }
+
+    // Public listing
+    pub fn set_publicly_listed(&mut self, listed: bool) {
+        self.publicly_listed = listed;
+    }
+
+    /// What to tell the coordinator while the lobby is listed: like merge offers, only
+    /// waiting lobbies with room left show up
+    pub fn public_listing(&self) -> Option<LobbyListing> {
+        let listed = self.publicly_listed && !self.is_tutorial();
+        (listed && self.phase == LobbyPhase::Waiting && !self.is_full()).then(|| LobbyListing {
+            lobby_code: self.code.clone(),
+            game_mode: self.lobby_options.gamemode,
+            players: self.players.len(),
+            max_players: self.max_players as usize,
+        })
+    }
+
+    fn expire_reservations(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        let before = self.reservations.len();
+        self.reservations.retain(|_, expires_at| *expires_at > now);
+        if self.reservations.len() != before {
+            debug!("Expired slot reservations in lobby {}", self.code);
+            self.broadcast_reservations(broadcaster);
         }
     }
 
-    fn broadcast_end_round_results(&self, broadcaster: &LobbyBroadcaster, results: &[RoundResult]) {
-        for r in results {
-            broadcaster.send_to(&r.player_id, ServerToClient::EndPvp { won: r.won });
+    /// Roll for a random event every player shares, see [`SharedRng::roll`]
+    pub fn shared_roll(&mut self, key: &str, sides: u32) -> (u32, bool) {
+        self.rng.roll(key, sides)
+    }
+
+    pub fn set_handicap(&mut self, player_id: &str, handicap: Handicap) -> Result<(), &'static str> {
+        if self.started() {
+            return Err("Handicaps are set before the game starts");
         }
+        handicap.validate()?;
+        let player = self.players.get_mut(player_id).ok_or("Player not found")?;
+        player.lobby_state.handicap = handicap;
+        Ok(())
     }
-    pub fn process_round_outcome(&mut self, result: &[RoundResult]) {
-        match self.lobby_options.gamemode {
-            GameMode::CoopSurvival => {
-                if result.is_empty() || result.iter().all(|r| r.won) {
-                    return;
-                }
-                for player in self.players.values_mut() {
-                    player.game_state.lives = player.game_state.lives.saturating_sub(1);
-                }
-            }
-            GameMode::Clash => {
-                let mut i = 0;
-                for r in result {
-                    if !r.won {
-                        if let Some(player) = self.players.get_mut(&r.player_id) {
-                            let damage = CLASH_BASE_DAMAGE[self.stage as usize] + (i as u8) + 1;
-                            player.game_state.lives =
-                                player.game_state.lives.saturating_sub(damage);
-                            i += 1;
-                        }
-                    }
-                }
-                self.stage += 1;
-            }
-            _ => {
-                for r in result {
-                    if !r.won {
-                        if let Some(player) = self.players.get_mut(&r.player_id) {
-                            player.game_state.lives = player.game_state.lives.saturating_sub(1);
-                        }
-                    }
-                }
+
+    /// Deal everyone into random teams of `team_size`, which has to split the lobby evenly
+    pub fn randomize_teams(
+        &mut self,
+        broadcaster: &mut LobbyBroadcaster,
+        team_size: u8,
+    ) -> Result<(), &'static str> {
+        if team_size == 0 || !self.players.len().is_multiple_of(team_size as usize) {
+            return Err("Players can't be split into teams of that size");
+        }
+        let mut player_ids: Vec<String> = self.players.keys().cloned().collect();
+        player_ids.sort();
+        // Every draw shuffles under its own key, or re-drawing would deal the same teams
+        self.rng
+            .shuffle(&format!("teams:{}", self.team_draws), &mut player_ids);
+        self.team_draws += 1;
+
+        let mut team = DEFAULT_TEAM;
+        for (i, player_id) in player_ids.iter().enumerate() {
+            if i > 0 && i % team_size as usize == 0 {
+                team += 1;
             }
+            self.assign_team(broadcaster, player_id, team);
         }
+        Ok(())
     }
 
-    pub fn check_and_handle_game_over(&mut self, broadcaster: &LobbyBroadcaster) -> bool {
-        match self.lobby_options.gamemode {
-            GameMode::Survival => {
-                if self.get_alive_player_count() > 1 {
-                    return false;
-                }
+    /// Move a player to `team`, here and in the broadcaster's team messages
+    pub fn assign_team(&mut self, broadcaster: &mut LobbyBroadcaster, player_id: &str, team: u8) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.game_state.team = team;
+            broadcaster.set_team(player_id, team);
+        }
+    }
 
-                let (winner_id, _) = self.get_max_furthest_blind();
-                let winner_alive = self
-                    .players
-                    .get(&winner_id)
-                    .map_or(false, |p| p.game_state.lives > 0);
+    pub fn team_of(&self, player_id: &str) -> Option<u8> {
+        self.players.get(player_id).map(|p| p.game_state.team)
+    }
 
-                if winner_alive || self.is_all_players_dead() {
-                    broadcaster.broadcast_to(&[winner_id.clone()], ServerToClient::WinGame {});
-                    broadcaster.broadcast_except(&winner_id, ServerToClient::LoseGame {});
-                    return true;
-                }
+    /// Every team's members, sorted
+    pub fn teams(&self) -> BTreeMap<u8, Vec<String>> {
+        let mut teams: BTreeMap<u8, Vec<String>> = BTreeMap::new();
+        for (id, player) in &self.players {
+            teams.entry(player.game_state.team).or_default().push(id.clone());
+        }
+        for members in teams.values_mut() {
+            members.sort();
+        }
+        teams
+    }
 
-                false
-            }
-            GameMode::CoopSurvival => {
-                // Game over if any player is dead (everyone loses together)
-                if self.is_someone_dead() {
-                    broadcaster.broadcast(ServerToClient::LoseGame {});
-                    true
-                } else {
-                    false
-                }
-            }
-            GameMode::Clash => {
-                if !self.is_someone_dead() {
-                    return false;
-                }
+    pub fn team_members(&self, team: u8) -> Vec<String> {
+        let mut members: Vec<String> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.game_state.team == team)
+            .map(|(id, _)| id.clone())
+            .collect();
+        members.sort();
+        members
+    }
 
-                let mut dead_players = Vec::new();
-                let mut alive_players = Vec::new();
+    /// The rest of `player_id`'s team
+    pub fn teammates(&self, player_id: &str) -> Vec<String> {
+        let Some(team) = self.team_of(player_id) else {
+            return Vec::new();
+        };
+        let mut members = self.team_members(team);
+        members.retain(|id| id != player_id);
+        members
+    }
 
-                for (id, player) in self.players.iter_mut() {
-                    if player.game_state.lives <= 0 {
-                        dead_players.push(id.clone());
-                        player.lobby_state.in_game = false;
-                    } else {
-                        alive_players.push(id.clone())
-                    }
-                }
+    pub fn add_player(
+        &mut self,
+        player_id: String,
+        client_profile: ClientProfile,
+    ) -> ClientLobbyEntry {
+        let is_host = self.players.is_empty();
+        let entry = ClientLobbyEntry::new(
+            client_profile,
+            self.code.clone(),
+            is_host,
+            self.lobby_options.starting_lives,
+        );
+        let alias = format!("Player {}", self.aliases.len() + 1);
+        self.aliases.entry(player_id.clone()).or_insert(alias);
+        self.players.insert(player_id, entry.clone());
+        entry
+    }
 
-                broadcaster.broadcast_to(&dead_players, ServerToClient::LoseGame {});
+    pub fn checkpoint(&self) -> LobbyCheckpoint {
+        LobbyCheckpoint {
+            code: self.code.clone(),
+            options: self.lobby_options.clone(),
+            started: self.started(),
+            phase: Some(self.phase),
+            stage: self.stage,
+            boss_chips: self.boss_chips.clone(),
+            players: self
+                .players
+                .values()
+                // Only a verified account can prove it is the same player after a restart
+                .filter_map(|p| {
+                    Some(CheckpointPlayer {
+                        account_id: p.profile.verified_account()?.to_string(),
+                        game_state: p.game_state.clone(),
+                    })
+                })
+                .collect(),
+            rng: self.rng.clone(),
+        }
+    }
 
-                if alive_players.len() == 1 {
-                    broadcaster.send_to(&alive_players[0], ServerToClient::WinGame {});
-                    return true;
-                }
+    /// Rebuild a lobby from a checkpoint, holding a slot for each player until they rejoin
+    pub fn restore(checkpoint: LobbyCheckpoint) -> Self {
+        let game_mode = checkpoint.options.gamemode;
+        let mut lobby = Lobby::new(checkpoint.code, checkpoint.options.ruleset.clone(), game_mode);
+        lobby.lobby_options = checkpoint.options;
+        // Resume in the phase the game was checkpointed in; the players' own states
+        // carry their progress through it
+        if checkpoint.started {
+            lobby.phase = checkpoint
+                .phase
+                .filter(|phase| phase.is_started())
+                .unwrap_or(LobbyPhase::InRound);
+            lobby.started = true;
+        }
+        lobby.stage = checkpoint.stage;
+        lobby.boss_chips = checkpoint.boss_chips;
+        lobby.rng = checkpoint.rng;
 
-                return false;
-            }
-            _ => {
-                if !self.is_someone_dead() {
-                    return false;
-                }
+        let expires_at =
+            Instant::now() + Duration::from_secs(CONFIG.get().slot_reservation_secs);
+        for player in checkpoint.players {
+            lobby.reservations.insert(player.account_id.clone(), expires_at);
+            lobby.restored_players.insert(player.account_id, player.game_state);
+        }
+        lobby
+    }
 
-                let mut winners = Vec::new();
-                let mut losers = Vec::new();
+    /// Hand a rejoining player back their checkpointed game state
+    pub fn claim_restored_state(&mut self, player_id: &str) -> bool {
+        let started = self.started();
+        let Some(player) = self.players.get_mut(player_id) else {
+            return false;
+        };
+        let Some(game_state) = player
+            .profile
+            .verified_account()
+            .and_then(|id| self.restored_players.remove(id))
+        else {
+            return false;
+        };
+        player.game_state = game_state;
+        player.lobby_state.in_game = started;
+        true
+    }
 
-                for (id, player) in self.players.iter() {
-                    if player.game_state.lives > 0 {
-                        winners.push(id.clone());
-                    } else {
-                        losers.push(id.clone());
-                    }
-                }
+    /// Keep a dropped player's seat until `until` so they can reconnect mid-game.
+    /// Only players holding a reconnect token can be recognised when they come back.
+    pub fn hold_seat(&mut self, player_id: &str, until: Instant) -> bool {
+        if !self.started() || !self.reconnect_tokens.contains_key(player_id) {
+            return false;
+        }
+        let Some(player) = self.players.get_mut(player_id) else {
+            return false;
+        };
+        if player.profile.is_bot
+            || !player.lobby_state.in_game
+            || player.game_state.lives == 0
+        {
+            return false;
+        }
+        player.lobby_state.disconnected_until = Some(until);
+        true
+    }
 
-                broadcaster.broadcast_to(&winners, ServerToClient::WinGame {});
-                broadcaster.broadcast_to(&losers, ServerToClient::LoseGame {});
-                true
+    /// New reconnect token for `player_id`, replacing the one it had
+    pub fn issue_reconnect_token(&mut self, player_id: &str) -> String {
+        let token = encode_hex(&rand::random::<[u8; 16]>());
+        self.reconnect_tokens.insert(player_id.to_string(), token.clone());
+        token
+    }
+
+    /// Id of the held seat `reconnect_token` was issued for, if any
+    pub fn held_seat_for(&self, reconnect_token: &str) -> Option<String> {
+        self.players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.disconnected_until.is_some())
+            .find(|(id, _)| {
+                self.reconnect_tokens.get(*id).is_some_and(|token| {
+                    constant_time_eq(token.as_bytes(), reconnect_token.as_bytes())
+                })
+            })
+            .map(|(id, _)| id.clone())
+    }
+
+    /// Move a held seat over to the reconnected client's new id, the old reconnect token
+    /// stops working
+    pub fn reclaim_seat(&mut self, old_id: &str, new_id: String, profile: ClientProfile) -> bool {
+        let Some(mut player) = self.players.remove(old_id) else {
+            return false;
+        };
+        self.reconnect_tokens.remove(old_id);
+        self.bot_difficulties.remove(old_id);
+        // The new connection holds no preview payloads, nor does anyone under its new id
+        self.preview_bases.retain(|(to, from, _)| to != old_id && from != old_id);
+        // A person who voted keeps their vote, a bot taking the seat doesn't vote
+        if self.start_votes.remove(old_id) && !profile.is_bot {
+            self.start_votes.insert(new_id.clone());
+        }
+        player.profile = profile;
+        player.lobby_state.disconnected_until = None;
+        player.lobby_state.last_action_seq = None;
+        self.players.insert(new_id.clone(), player);
+
+        if let Some(alias) = self.aliases.remove(old_id) {
+            self.aliases.insert(new_id.clone(), alias);
+        }
+        if let Some(skips) = self.skips_at_last_pvp.remove(old_id) {
+            self.skips_at_last_pvp.insert(new_id.clone(), skips);
+        }
+        if let Some(seed) = self.player_seeds.remove(old_id) {
+            self.player_seeds.insert(new_id.clone(), seed);
+        }
+        self.run_checksums.rename(old_id, &new_id);
+        if self.rule_breakers.remove(old_id) {
+            self.rule_breakers.insert(new_id.clone());
+        }
+        for id in self.awaiting_revive.iter_mut().chain(self.eliminations.iter_mut().flatten()) {
+            if id == old_id {
+                *id = new_id.clone();
             }
         }
+        for hand in self.round_timeline.iter_mut().filter(|h| h.player_id == old_id) {
+            hand.player_id = new_id.clone();
+        }
+        if let Some(at) = self.round_finished_at.remove(old_id) {
+            self.round_finished_at.insert(new_id.clone(), at);
+        }
+        true
     }
 
-    // Broadcasting helpers
-    pub fn broadcast_all_game_states(&self, broadcaster: &LobbyBroadcaster) {
-        for player in self.players.values() {
-            self.broadcast_game_state_update(broadcaster, &player.profile.id, false);
+    /// Held seats whose owner did not come back in time
+    pub fn expired_seats(&self, now: Instant) -> Vec<String> {
+        self.players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.disconnected_until.is_some_and(|until| now >= until))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// A client started the ante timer with `time` seconds on it
+    pub fn ante_timer_started(&mut self, time: u32, now: Instant) {
+        self.ante_timer = Some((time, now));
+    }
+
+    pub fn ante_timer_stopped(&mut self) {
+        self.ante_timer = None;
+    }
+
+    /// Pause the ante timer while a dropped player's seat is held, returns the seconds
+    /// left on it
+    pub fn hold_ante_timer(&mut self, now: Instant) -> u32 {
+        if let Some((time, started)) = self.ante_timer.take() {
+            let elapsed = now.duration_since(started).as_secs() as u32;
+            self.held_ante_timer = Some(time.saturating_sub(elapsed));
         }
+        self.held_ante_timer.unwrap_or(self.lobby_options.timer_base_seconds)
     }
 
-    pub fn broadcast_life_updates(&self, broadcaster: &LobbyBroadcaster, player_id: &str) {
-        if self.lobby_options.gamemode == GameMode::CoopSurvival {
-            self.broadcast_all_game_states(broadcaster);
-        } else {
-            self.broadcast_game_state_update(broadcaster, player_id, false);
+    /// Seconds to restart a held ante timer with, once no seat is held anymore
+    pub fn release_ante_timer(&mut self, now: Instant) -> Option<u32> {
+        if self.players.values().any(|p| p.lobby_state.disconnected_until.is_some()) {
+            return None;
         }
+        let time = self.held_ante_timer.take()?;
+        self.ante_timer = Some((time, now));
+        Some(time)
     }
 
-    pub fn broadcast_game_state_update(
-        &self,
-        broadcaster: &LobbyBroadcaster,
-        player_id: &str,
-        exclude_player: bool,
-    ) {
-        if let Some(player) = self.players.get(player_id) {
-            let update = ServerToClient::GameStateUpdate {
-                player_id: player_id.to_string(),
-                game_state: player.game_state.clone(),
-            };
+    /// A restored lobby nobody came back to
+    pub fn is_abandoned(&self) -> bool {
+        self.players.is_empty() && self.reservations.is_empty()
+    }
 
-            if exclude_player {
-                broadcaster.broadcast_except(player_id, update);
-            } else {
-                broadcaster.broadcast(update);
+    fn hides_names(&self) -> bool {
+        self.lobby_options.anonymous_mode && !self.names_revealed
+    }
+
+    /// A player's entry as `viewer` may see it, with the username and account redacted
+    /// in anonymous mode
+    pub fn entry_view(&self, player_id: &str, viewer: &str) -> Option<ClientLobbyEntry> {
+        let mut entry = self.players.get(player_id)?.clone();
+        if self.hides_names()
+            && player_id != viewer
+            && let Some(alias) = self.aliases.get(player_id)
+        {
+            entry.profile.username = alias.clone();
+            entry.profile.account_id = None;
+        }
+        Some(entry)
+    }
+
+    /// The lobby as `viewer` may see it: everyone's entry through [`Self::entry_view`],
+    /// trimmed to the scoreboard for spectators, the seed only for players, so
+    /// spectators can't pass it on, and a hidden boss's chips only for the host
+    pub fn snapshot_for(&self, viewer: &str) -> Lobby {
+        let mut lobby = self.clone();
+        let spectator = !self.players.contains_key(viewer);
+        for (id, entry) in lobby.players.iter_mut() {
+            if let Some(view) = self.entry_view(id, viewer) {
+                *entry = if spectator { view.spectator_view() } else { view };
             }
         }
+        if spectator {
+            lobby.lobby_options.hide_seed();
+        }
+        // The chips give a hidden boss away
+        if self.withheld_boss.is_some() && !self.is_player_host(viewer) {
+            lobby.boss_chips = TalismanNumber::Regular(0.0);
+        }
+        lobby
     }
 
-    pub fn broadcast_ready_states(&self, broadcaster: &LobbyBroadcaster) {
-        let ready_states = self
-            .collect_ready_states()
-            .into_iter()
-            .map(|(id, ready)| (id, ready))
-            .collect::<HashMap<String, bool>>();
-        broadcaster.broadcast(ServerToClient::LobbyReady { ready_states });
+    pub fn broadcast_player_joined(&self, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        broadcaster.broadcast_per_player(|viewer| {
+            if viewer == player_id {
+                return None;
+            }
+            self.entry_view(player_id, viewer).map(ServerToClient::player_joined_lobby)
+        });
     }
 
-    pub fn broadcast_ready_states_except(
-        &self,
-        broadcaster: &LobbyBroadcaster,
-        except_player: &str,
-    ) {
-        let ready_states = self
-            .collect_ready_states()
-            .into_iter()
-            .map(|(id, ready)| (id, ready))
-            .collect::<HashMap<String, bool>>();
-        broadcaster.broadcast_except(except_player, ServerToClient::LobbyReady { ready_states });
+    pub fn broadcast_players(&self, broadcaster: &LobbyBroadcaster) {
+        broadcaster.broadcast_per_player(|viewer| {
+            Some(ServerToClient::ResetPlayers {
+                players: self
+                    .players
+                    .keys()
+                    .filter_map(|id| self.entry_view(id, viewer))
+                    .collect(),
+            })
+        });
     }
 
-    pub fn start_online_blind(&mut self, broadcaster: &LobbyBroadcaster) {
-        self.reset_ready_states();
-        self.reset_scores();
-        let in_game_player_ids = self
-            .players
-            .iter()
-            .filter(|(_, p)| p.lobby_state.in_game)
-            .map(|(id, _)| id.clone())
-            .collect::<Vec<String>>();
-        broadcaster.broadcast_to(&in_game_player_ids, ServerToClient::StartBlind {});
-        self.broadcast_ready_states(broadcaster);
+    /// Anonymous mode ends with the game, tell everyone who they were playing
+    fn reveal_names(&mut self, broadcaster: &LobbyBroadcaster) {
+        if !self.hides_names() {
+            return;
+        }
+        self.names_revealed = true;
+        broadcaster.broadcast(ServerToClient::PlayerNamesRevealed {
+            usernames: self
+                .players
+                .iter()
+                .map(|(id, p)| (id.clone(), p.profile.username.clone()))
+                .collect(),
+        });
     }
 
-    // Survival mode helper methods
-    fn is_all_players_dead(&self) -> bool {
-        let all_dead = self.players.values().all(|p| p.game_state.lives == 0);
-        for (id, player) in &self.players {
-            debug!("Player {} has {} lives", id, player.game_state.lives);
+    pub fn remove_player(&mut self, player_id: &str) -> Option<ClientLobbyEntry> {
+        self.start_votes.remove(player_id);
+        self.reconnect_tokens.remove(player_id);
+        self.bot_difficulties.remove(player_id);
+        self.preview_bases.retain(|(to, from, _)| to != player_id && from != player_id);
+        self.players.remove(player_id)
+    }
+
+    /// Count a player's vote to start; true once more than half of the people in the
+    /// lobby voted. Bots don't vote.
+    pub fn vote_to_start(&mut self, player_id: &str) -> bool {
+        if self.players.get(player_id).is_some_and(|p| !p.profile.is_bot) {
+            self.start_votes.insert(player_id.to_string());
         }
-        return all_dead;
+        self.start_votes.len() >= self.start_votes_needed()
     }
 
-    fn get_max_furthest_blind(&self) -> (String, u32) {
-        self.players
-            .iter()
-            .map(|(id, p)| (id.clone(), p.game_state.furthest_blind))
-            .max_by_key(|(_, furthest_blind)| *furthest_blind)
-            .unwrap_or((String::new(), 0))
+    /// Who voted to start so far, sorted
+    pub fn start_votes(&self) -> Vec<String> {
+        let mut voters: Vec<String> = self.start_votes.iter().cloned().collect();
+        voters.sort();
+        voters
     }
 
-    pub fn get_in_game_statuses(&self) -> HashMap<String, bool> {
-        self.players
-            .iter()
-            .map(|(id, entry)| (id.clone(), entry.lobby_state.in_game))
-            .collect()
+    pub fn start_votes_needed(&self) -> usize {
+        self.players.values().filter(|p| !p.profile.is_bot).count() / 2 + 1
     }
 
-    pub fn get_player_count_in_game(&self) -> usize {
+    pub fn promote_new_host(&mut self) -> Option<String> {
+        // Bots can't run a lobby, hand it to a person while there is one
+        let new_host = self
+            .players
+            .iter_mut()
+            .min_by_key(|(_, entry)| entry.profile.is_bot);
+        if let Some((new_host_id, new_host_entry)) = new_host {
+            new_host_entry.lobby_state.is_host = true;
+            new_host_entry.lobby_state.is_ready = true;
+            Some(new_host_id.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn has_human_players(&self) -> bool {
+        self.players.values().any(|p| !p.profile.is_bot)
+    }
+
+    pub fn is_player_host(&self, player_id: &str) -> bool {
         self.players
-            .values()
-            .filter(|p| p.lobby_state.in_game)
-            .count()
+            .get(player_id)
+            .map(|p| p.lobby_state.is_host)
+            .unwrap_or(false)
+    }
+
+    // DRY: Consolidated ready state operations
+    pub fn reset_ready_states(&mut self) {
+        for player in self.players.values_mut() {
+            player.lobby_state.is_ready = false;
+        }
+    }
+
+    pub fn reset_ready_states_to_host_only(&mut self) {
+        for player in self.players.values_mut() {
+            player.lobby_state.is_ready = player.lobby_state.is_host;
+        }
+    }
+
+    pub fn set_player_ready(&mut self, player_id: &str, is_ready: bool) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.lobby_state.is_ready = is_ready;
+        }
+    }
+
+    pub fn collect_ready_states(&self) -> HashMap<String, bool> {
+        self.players
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.lobby_state.is_ready))
+            .collect()
+    }
+
+    // Game state management
+    pub fn reset_game_states(&mut self, in_game: bool) {
+        for player in self.players.values_mut() {
+            player.reset_for_game(self.lobby_options.starting_lives);
+            player.lobby_state.in_game = in_game;
+        }
+    }
+
+    pub fn start_game(&mut self) {
+        self.set_phase(LobbyPhase::Starting);
+        self.stage = 0;
+        self.highest_ante = 0;
+        self.blitz_blinds = 0;
+        self.eliminations.clear();
+        self.boss_chip_multiplier = 1.0;
+        self.awaiting_revive.clear();
+        self.skips_at_last_pvp.clear();
+        self.names_revealed = false;
+        self.stats = MatchStats::default();
+        self.stats.game_started(Instant::now());
+        self.boss_ban = None;
+        self.withheld_boss = None;
+        self.boss_rotation.clear();
+        self.start_votes.clear();
+        self.ante_timer = None;
+        self.held_ante_timer = None;
+        self.preview_bases.clear();
+        self.required_back =
+            (!self.lobby_options.different_decks).then(|| self.lobby_options.back.clone());
+        self.rng.reseed();
+        if !self.lobby_options.different_seeds
+            && self.lobby_options.custom_seed == String::from("random")
+        {
+            self.lobby_options.custom_seed = time_based_string(8);
+            debug!(
+                "Generating time-based seed for lobby {} seed: {}",
+                self.code, self.lobby_options.custom_seed
+            );
+        }
+        self.player_seeds.clear();
+        self.run_checksums.clear();
+        self.rule_breakers.clear();
+        if self.lobby_options.different_seeds {
+            let player_ids: Vec<String> = self.players.keys().cloned().collect();
+            for player_id in player_ids {
+                self.assign_player_seed(player_id);
+            }
+        }
+        self.reset_game_states(true);
+    }
+
+    fn assign_player_seed(&mut self, player_id: String) {
+        let seed = loop {
+            let seed = random_seed_string(8);
+            if !self.player_seeds.values().any(|taken| *taken == seed) {
+                break seed;
+            }
+        };
+        self.player_seeds.insert(player_id, seed);
+    }
+
+    /// The seed `player_id` plays this game with: their own with `different_seeds`,
+    /// otherwise the lobby's
+    pub fn seed_for(&self, player_id: &str) -> String {
+        self.player_seeds
+            .get(player_id)
+            .cloned()
+            .unwrap_or_else(|| self.lobby_options.custom_seed.clone())
+    }
+
+    /// Tell players the game started, each with only their own seed when seeds differ
+    pub fn broadcast_game_started(&self, broadcaster: &LobbyBroadcaster) {
+        let stake = self.lobby_options.stake as i32;
+        if let Some(banned) = self.banned_cards() {
+            broadcaster.broadcast(banned);
+        }
+        if self.player_seeds.is_empty() {
+            broadcaster.broadcast(ServerToClient::GameStarted {
+                seed: self.lobby_options.custom_seed.clone(),
+                stake,
+            });
+            return;
+        }
+        broadcaster.broadcast_per_player(|player_id| {
+            Some(ServerToClient::GameStarted {
+                seed: self.seed_for(player_id),
+                stake,
+            })
+        });
+    }
+
+    pub fn allow_announcement(&mut self, now: Instant) -> bool {
+        announcements::allow_announcement(&mut self.recent_announcements, now)
+    }
+
+    /// The lobby's banned cards for clients to enforce, `None` when nothing is banned
+    pub fn banned_cards(&self) -> Option<ServerToClient> {
+        let options = &self.lobby_options;
+        if options.banned_jokers.is_empty() && options.banned_consumables.is_empty() {
+            return None;
+        }
+        Some(ServerToClient::BannedCards {
+            jokers: options.banned_jokers.clone(),
+            consumables: options.banned_consumables.clone(),
+        })
+    }
+
+    /// A banned card in the jokers `player_id` sent; the player's result is flagged
+    /// unverified when there is one
+    pub fn check_banned_cards<'a>(&mut self, player_id: &str, jokers: &'a str) -> Option<&'a str> {
+        if !self.started() {
+            return None;
+        }
+        let banned = self.lobby_options.banned_card_in(jokers)?;
+        self.rule_breakers.insert(player_id.to_string());
+        Some(banned)
+    }
+
+    /// Modes without a fixed seat count can take in a player whose join raced the start,
+    /// as long as nobody's run has loaded yet
+    pub fn accepts_late_join(&self) -> bool {
+        self.phase == LobbyPhase::Starting
+            && matches!(self.lobby_options.gamemode, GameMode::Clash | GameMode::CoopSurvival)
+    }
+
+    /// Put a player who joined late into the game that is starting
+    pub fn join_starting_game(&mut self, player_id: &str) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.reset_for_game(self.lobby_options.starting_lives);
+            player.lobby_state.in_game = true;
+        }
+        if self.lobby_options.different_seeds {
+            self.assign_player_seed(player_id.to_string());
+        }
+    }
+
+    pub fn stop_game(&mut self) {
+        self.set_phase(LobbyPhase::Waiting);
+        self.reset_game_states(false);
+        self.stage = 0;
+        self.boss_chips = TalismanNumber::Regular(0.0);
+        self.magnet = None;
+        self.boss_ban = None;
+    }
+
+    pub fn reset_scores(&mut self) {
+        self.round_timeline.clear();
+        self.round_started_at = None;
+        self.blitz_deadline = None;
+        self.round_finished_at.clear();
+        for player in self.players.values_mut() {
+            player.lobby_state.round_complete = false;
+            player.game_state.score = TalismanNumber::Regular(0.0);
+            player.game_state.hands_left = player.game_state.hands_max;
+            player.game_state.discards_left = player.game_state.discards_max;
+        }
+    }
+
+    pub fn get_total_score(&self) -> TalismanNumber {
+        let mut acc = TalismanNumber::Regular(0.0);
+        for player in self.players.values() {
+            acc = acc.add(&player.handicapped_score()).unwrap_or(acc.clone());
+        }
+        acc
+    }
+
+    pub fn all_players_done(&self) -> bool {
+        self.players
+            .values()
+            .filter(|p| p.lobby_state.in_game)
+            .all(|p| p.game_state.hands_left == 0 || p.lobby_state.round_complete)
+    }
+
+    pub fn record_hand(
+        &mut self,
+        player_id: &str,
+        score: TalismanNumber,
+        hands_left: u8,
+        breakdown: Option<HandBreakdown>,
+    ) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            let best = &mut player.game_state.biggest_hand;
+            if best.as_ref().is_none_or(|best| score > *best) {
+                *best = Some(score.clone());
+            }
+        }
+        self.round_timeline.push(HandScore {
+            player_id: player_id.to_string(),
+            score,
+            hands_left,
+            timestamp: now_millis(),
+            breakdown,
+        });
+        if hands_left == 0 {
+            self.record_finish(player_id);
+        }
+    }
+
+    pub fn record_discard(&mut self, player_id: &str) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.game_state.discards_used = player.game_state.discards_used.saturating_add(1);
+        }
+    }
+
+    /// Keep each winner's quickest PvP blind for the awards
+    fn record_clear_times(&mut self, result: &[RoundResult], finish_ms: &HashMap<String, u64>) {
+        for won in result.iter().filter(|r| r.won) {
+            let (Some(player), Some(&ms)) =
+                (self.players.get_mut(&won.player_id), finish_ms.get(&won.player_id))
+            else {
+                continue;
+            };
+            let fastest = &mut player.game_state.fastest_clear_ms;
+            *fastest = Some(fastest.map_or(ms, |fastest| fastest.min(ms)));
+        }
+    }
+
+    pub fn mark_round_complete(&mut self, player_id: &str) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.lobby_state.round_complete = true;
+            self.record_finish(player_id);
+        }
+    }
+
+    /// Remember when a player was done with the PvP blind, only the first time counts
+    fn record_finish(&mut self, player_id: &str) {
+        let Some(started_at) = self.round_started_at else {
+            return;
+        };
+        if self.round_finished_at.contains_key(player_id) {
+            return;
+        }
+        let at = now_millis();
+        self.round_finished_at.insert(player_id.to_string(), at);
+        self.record_event(
+            Some(player_id),
+            format!("finished the PvP blind after {}ms", at.saturating_sub(started_at)),
+        );
+    }
+
+    /// Milliseconds each player took to finish the PvP blind
+    fn finish_times(&self) -> HashMap<String, u64> {
+        let Some(started_at) = self.round_started_at else {
+            return HashMap::new();
+        };
+        self.round_finished_at
+            .iter()
+            .map(|(id, at)| (id.clone(), at.saturating_sub(started_at)))
+            .collect()
+    }
+
+    pub fn is_someone_dead(&self) -> bool {
+        self.players
+            .values()
+            .any(|p| p.game_state.lives == 0 && p.lobby_state.in_game)
+    }
+
+    pub fn handle_player_fail_round(&mut self, player_id: &str, broadcaster: &LobbyBroadcaster) {
+        debug!("Player {} failed a round in lobby {}", player_id, self.code);
+
+        // Survival runs end on the first failed blind, the player then waits for the others
+        if self.lobby_options.gamemode == GameMode::Survival {
+            if let Some(player) = self.players.get_mut(player_id)
+                && player.game_state.eliminated_at.is_none()
+            {
+                player.game_state.eliminated_at = Some(player.game_state.furthest_blind);
+                player.game_state.lives = 0;
+            }
+        } else if self.lobby_options.gamemode == GameMode::CoopSurvival
+            && self.lobby_options.coop_revive
+        {
+            self.down_coop_player(player_id);
+        } else if self.lobby_options.death_on_round_loss {
+            let rewards = self.process_round_outcome(&[RoundResult {
+                player_id: player_id.to_string(),
+                won: false,
+            }]);
+            self.broadcast_round_rewards(broadcaster, &rewards);
+        }
+        self.broadcast_life_updates(broadcaster, player_id);
+
+        // Use unified game over check
+        self.check_and_handle_game_over(broadcaster, None);
+    }
+
+    /// Take a failed co-op player out of the run until the team clears the next boss
+    fn down_coop_player(&mut self, player_id: &str) {
+        let Some(player) = self.players.get_mut(player_id) else {
+            return;
+        };
+        if !player.lobby_state.in_game {
+            return;
+        }
+        debug!("Player {} is down in lobby {}, awaiting revive", player_id, self.code);
+        player.game_state.lives = 0;
+        player.lobby_state.in_game = false;
+        self.awaiting_revive.push(player_id.to_string());
+    }
+
+    /// Bring every downed co-op player back with a single life
+    fn revive_coop_players(&mut self, broadcaster: &LobbyBroadcaster) {
+        for player_id in std::mem::take(&mut self.awaiting_revive) {
+            let Some(player) = self.players.get_mut(&player_id) else {
+                continue;
+            };
+            player.game_state.lives = 1;
+            player.lobby_state.in_game = true;
+            debug!("Player {} revived in lobby {}", player_id, self.code);
+            broadcaster.broadcast(ServerToClient::PlayerRevived {
+                player_id,
+                lives: 1,
+            });
+        }
+    }
+
+    /// Tell the other players which boss `setter_id` chose, or keep it to the host until
+    /// the blind starts when the lobby hides bosses
+    pub fn announce_boss(&mut self, broadcaster: &LobbyBroadcaster, setter_id: &str, key: String) {
+        if self.lobby_options.hidden_boss && self.phase != LobbyPhase::PvpBlind {
+            self.withheld_boss = Some((setter_id.to_string(), key));
+            return;
+        }
+        broadcaster.broadcast_except(setter_id, ServerToClient::SetBossBlind { key });
+    }
+
+    fn dynamic_difficulty(&self) -> bool {
+        self.lobby_options.dynamic_difficulty
+            && self.lobby_options.gamemode == GameMode::CoopSurvival
+    }
+
+    /// Store the boss chips reported by the host, scaled by the dynamic difficulty.
+    /// Announce the boss first: the scaled chips of a withheld boss go out with it
+    pub fn set_boss_chips(&mut self, chips: TalismanNumber, broadcaster: &LobbyBroadcaster) {
+        if !self.dynamic_difficulty() {
+            self.boss_chips = chips;
+            return;
+        }
+        self.boss_chips = chips.scale(self.boss_chip_multiplier);
+        if self.withheld_boss.is_none() {
+            self.broadcast_boss_difficulty(broadcaster);
+        }
+    }
+
+    fn broadcast_boss_difficulty(&self, broadcaster: &LobbyBroadcaster) {
+        broadcaster.broadcast(ServerToClient::BossDifficulty {
+            multiplier: self.boss_chip_multiplier,
+            boss_chips: self.boss_chips.clone(),
+        });
+    }
+
+    /// Rubber-band the boss multiplier on the team's margin against the last boss
+    fn adjust_difficulty(&mut self, won: bool) {
+        // Margin in orders of magnitude, positive when the team beat the boss
+        let margin =
+            self.get_total_score().estimate_magnitude() - self.boss_chips.estimate_magnitude();
+        let step = if !won {
+            -DIFFICULTY_STEP
+        } else if margin >= DIFFICULTY_COMFORTABLE_MARGIN {
+            DIFFICULTY_STEP
+        } else {
+            0.0
+        };
+        self.boss_chip_multiplier = (self.boss_chip_multiplier + step)
+            .clamp(DIFFICULTY_MIN_MULTIPLIER, DIFFICULTY_MAX_MULTIPLIER);
+        debug!(
+            "Lobby {} boss multiplier now {} (margin {:.2})",
+            self.code, self.boss_chip_multiplier, margin
+        );
+    }
+
+    /// Concede the game: the player drops to zero lives and game over is evaluated
+    pub fn forfeit(&mut self, player_id: &str, broadcaster: &LobbyBroadcaster) -> bool {
+        if !self.started() {
+            return false;
+        }
+        let Some(player) = self.players.get_mut(player_id) else {
+            return false;
+        };
+        if !player.lobby_state.in_game || player.game_state.lives == 0 {
+            return false;
+        }
+        debug!("Player {} forfeited in lobby {}", player_id, self.code);
+        player.game_state.lives = 0;
+        self.broadcast_life_updates(broadcaster, player_id);
+        self.check_and_handle_game_over(broadcaster, Some(OutcomeReason::Forfeit));
+        true
+    }
+
+    // Game logic - kept in lobby for now but could be moved to game_logic module
+    pub fn evaluate_online_round(&mut self, broadcaster: &LobbyBroadcaster) {
+        if !self.all_players_done() {
+            return;
+        }
+
+        debug!("Evaluating online battle for lobby {}", self.code);
+        self.stats.round_finished(Instant::now());
+
+        let timeline = RoundTimeline {
+            started_at: self.round_started_at,
+            finish_ms: self.finish_times(),
+            hands: std::mem::take(&mut self.round_timeline),
+        };
+        let result = self.determine_round_outcome();
+        self.record_clear_times(&result, &timeline.finish_ms);
+        if self.lobby_options.gamemode == GameMode::Clash
+            && self.lobby_options.clash_points_target > 0
+        {
+            self.award_clash_points(broadcaster);
+        }
+        let rewards = self.process_round_outcome(&result);
+        self.broadcast_round_rewards(broadcaster, &rewards);
+
+        let boss_cleared = !result.is_empty() && result.iter().all(|r| r.won);
+        if self.lobby_options.gamemode == GameMode::CoopSurvival && boss_cleared {
+            self.revive_coop_players(broadcaster);
+        }
+        if self.lobby_options.gamemode == GameMode::CoopSurvival
+            && self.lobby_options.dynamic_difficulty
+        {
+            self.adjust_difficulty(boss_cleared);
+        }
+
+        // Use unified game over check
+        let game_over = self.check_and_handle_game_over(broadcaster, None);
+        if game_over {
+            self.reset_ready_states_to_host_only();
+        } else {
+            self.set_phase(LobbyPhase::ShopPhase);
+            self.reset_scores();
+            self.reset_ready_states();
+            self.broadcast_end_round_results(broadcaster, &result, &timeline);
+            if self.lobby_options.gamemode == GameMode::Clash {
+                self.broadcast_clash_stage(broadcaster);
+            }
+        }
+        self.broadcast_ready_states(broadcaster);
+        self.broadcast_all_game_states(broadcaster);
+        broadcaster.broadcast(ServerToClient::InGameStatuses {
+            statuses: self.get_in_game_statuses(),
+            started: self.started(),
+        });
+    }
+
+    fn determine_round_outcome(&self) -> Vec<RoundResult> {
+        match self.lobby_options.gamemode {
+            GameMode::CoopSurvival => {
+                let mut results = Vec::new();
+                let won = self.get_total_score() > self.boss_chips;
+                for (id, _) in &self.players {
+                    results.push(RoundResult {
+                        player_id: id.clone(),
+                        won,
+                    });
+                }
+                return results;
+            }
+            GameMode::Clash => {
+                let mut sorted_players = self
+                    .players
+                    .iter()
+                    .filter(|(_, p)| p.lobby_state.in_game)
+                    .collect::<Vec<(&String, &ClientLobbyEntry)>>();
+                sorted_players.sort_by_key(|(_, p)| std::cmp::Reverse(p.handicapped_score()));
+                let top_score = sorted_players[0].1.handicapped_score();
+
+                let mut results = Vec::new();
+                for (id, player) in sorted_players {
+                    results.push(RoundResult {
+                        player_id: id.clone(),
+                        won: player.handicapped_score() == top_score,
+                    });
+                }
+                return results;
+            }
+
+            _ => {
+                if self.players.len() < 2 {
+                    error!("Not enough players to evaluate round");
+                    return vec![RoundResult {
+                        player_id: String::new(),
+                        won: false,
+                    }];
+                }
+
+                let mut result = vec![];
+                // Find the actual highest score
+                let top_score = self
+                    .players
+                    .values()
+                    .map(|p| p.handicapped_score())
+                    .max()
+                    .unwrap(); // Safe because we checked players.len() >= 2
+
+                for (id, player) in &self.players {
+                    result.push(RoundResult {
+                        player_id: id.clone(),
+                        won: player.handicapped_score() == top_score,
+                    });
+                }
+
+                result
+            }
+        }
+    }
+
+    fn broadcast_end_round_results(
+        &self,
+        broadcaster: &LobbyBroadcaster,
+        results: &[RoundResult],
+        timeline: &RoundTimeline,
+    ) {
+        for r in results {
+            let reason = match self.lobby_options.gamemode {
+                GameMode::CoopSurvival if r.won => OutcomeReason::BossDefeated,
+                GameMode::CoopSurvival => OutcomeReason::BossNotDefeated,
+                _ => OutcomeReason::Score,
+            };
+            broadcaster.send_to(
+                &r.player_id,
+                ServerToClient::EndPvp {
+                    won: r.won,
+                    reason,
+                    timeline: timeline.hands.clone(),
+                    started_at: timeline.started_at,
+                    finish_ms: timeline.finish_ms.clone(),
+                },
+            );
+        }
+    }
+
+    pub fn broadcast_round_rewards(&self, broadcaster: &LobbyBroadcaster, rewards: &[RoundReward]) {
+        for r in rewards {
+            broadcaster.send_to(
+                &r.player_id,
+                ServerToClient::RoundRewards {
+                    gold: r.gold,
+                    blind_reward: r.blind_reward,
+                },
+            );
+        }
+    }
+
+    /// Apply life loss for a round and work out the gold each player is owed
+    pub fn process_round_outcome(&mut self, result: &[RoundResult]) -> Vec<RoundReward> {
+        let lives_before: HashMap<String, u8> = self
+            .players
+            .iter()
+            .map(|(id, p)| (id.clone(), p.game_state.lives))
+            .collect();
+
+        self.apply_round_damage(result);
+
+        let mut rewards = Vec::new();
+        for r in result {
+            let Some(player) = self.players.get_mut(&r.player_id) else {
+                continue;
+            };
+            let lost_life = lives_before
+                .get(&r.player_id)
+                .is_some_and(|before| player.game_state.lives < *before);
+            let gold = if lost_life && self.lobby_options.gold_on_life_loss {
+                LIFE_LOSS_GOLD
+            } else {
+                0
+            };
+            player.game_state.money += gold;
+            rewards.push(RoundReward {
+                player_id: r.player_id.clone(),
+                gold,
+                blind_reward: r.won || !self.lobby_options.no_gold_on_round_loss,
+            });
+        }
+        rewards
+    }
+
+    fn apply_round_damage(&mut self, result: &[RoundResult]) {
+        match self.lobby_options.gamemode {
+            GameMode::CoopSurvival => {
+                if result.is_empty() || result.iter().all(|r| r.won) {
+                    return;
+                }
+                for player in self.players.values_mut() {
+                    player.game_state.lives = player.game_state.lives.saturating_sub(1);
+                }
+            }
+            GameMode::Clash => {
+                let mut i = 0;
+                for r in result {
+                    if !r.won {
+                        if let Some(player) = self.players.get_mut(&r.player_id) {
+                            let damage = self
+                                .lobby_options
+                                .clash_base_damage(self.stage as usize)
+                                .saturating_add(i + 1);
+                            player.game_state.lives =
+                                player.game_state.lives.saturating_sub(damage);
+                            i += 1;
+                        }
+                    }
+                }
+                self.stage += 1;
+            }
+            _ => {
+                for r in result {
+                    if !r.won {
+                        if let Some(player) = self.players.get_mut(&r.player_id) {
+                            player.game_state.lives = player.game_state.lives.saturating_sub(1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Award league points by this round's score placement, ties share a placement
+    fn award_clash_points(&mut self, broadcaster: &LobbyBroadcaster) {
+        let scores: Vec<(String, TalismanNumber)> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .map(|(id, p)| (id.clone(), p.handicapped_score()))
+            .collect();
+        for (id, score) in &scores {
+            let placement = 1 + scores.iter().filter(|(_, other)| other > score).count();
+            let points = self.lobby_options.clash_placement_points(placement);
+            if let Some(player) = self.players.get_mut(id) {
+                player.game_state.points += points;
+            }
+        }
+        broadcaster.broadcast(ServerToClient::LeagueTable {
+            points: self
+                .players
+                .iter()
+                .map(|(id, p)| (id.clone(), p.game_state.points))
+                .collect(),
+        });
+    }
+
+    /// Players at or past the league target with the most points, if anyone got there
+    fn clash_league_leaders(&self) -> Option<Vec<String>> {
+        let target = self.lobby_options.clash_points_target;
+        if target == 0 {
+            return None;
+        }
+        let best = self
+            .players
+            .values()
+            .filter(|p| p.lobby_state.in_game)
+            .map(|p| p.game_state.points)
+            .max()
+            .filter(|best| *best >= target)?;
+        Some(
+            self.players
+                .iter()
+                .filter(|(_, p)| p.lobby_state.in_game && p.game_state.points == best)
+                .map(|(id, _)| id.clone())
+                .collect(),
+        )
+    }
+
+    /// Tell clients which Clash stage is next and the base damage of the stages ahead
+    pub fn broadcast_clash_stage(&self, broadcaster: &LobbyBroadcaster) {
+        let stage = self.stage.max(0) as usize;
+        let table = &self.lobby_options.clash_damage_table;
+        let upcoming_damage = match table.get(stage..) {
+            Some(rest) if !rest.is_empty() => rest.to_vec(),
+            _ => vec![self.lobby_options.clash_base_damage(stage)],
+        };
+        broadcaster.broadcast(ServerToClient::ClashStage {
+            stage: stage as u32,
+            upcoming_damage,
+        });
+    }
+
+    /// Final placements, only meaningful (and only sent) with more than two players
+    pub fn compute_standings(&self) -> Vec<Standing> {
+        if self.players.len() <= 2 {
+            return Vec::new();
+        }
+        let rank_key = |id: &String, p: &ClientLobbyEntry| {
+            (
+                p.game_state.lives > 0,
+                self.eliminations.iter().position(|group| group.contains(id)),
+                p.game_state.points,
+                p.game_state.lives,
+                p.game_state.furthest_blind,
+            )
+        };
+        let keys: Vec<_> = self
+            .players
+            .iter()
+            .map(|(id, p)| (id, p, rank_key(id, p)))
+            .collect();
+
+        let mut standings: Vec<Standing> = keys
+            .iter()
+            .map(|(id, p, key)| Standing {
+                player_id: (*id).clone(),
+                account_id: p.profile.verified_account().map(str::to_string),
+                placement: 1 + keys.iter().filter(|(_, _, other)| other > key).count() as u8,
+                lives: p.game_state.lives,
+                furthest_blind: p.game_state.furthest_blind,
+                points: p.game_state.points,
+                verified: self.run_problem(id, p).is_none(),
+            })
+            .collect();
+        standings.sort_by_key(|s| s.placement);
+        standings
+    }
+
+    fn send_game_results(
+        &self,
+        broadcaster: &LobbyBroadcaster,
+        winners: &[String],
+        losers: &[String],
+        win_reason: OutcomeReason,
+        lose_reason: OutcomeReason,
+    ) {
+        let standings = self.compute_standings();
+        if !winners.is_empty() {
+            broadcaster.broadcast_to(
+                winners,
+                ServerToClient::WinGame {
+                    reason: win_reason,
+                    standings: standings.clone(),
+                },
+            );
+        }
+        if !losers.is_empty() {
+            broadcaster.broadcast_to(
+                losers,
+                ServerToClient::LoseGame {
+                    reason: lose_reason,
+                    standings,
+                },
+            );
+        }
+    }
+
+    /// Check whether the game has ended and notify players. `cause` overrides the
+    /// default reason when the caller knows why lives were lost (e.g. the timer).
+    pub fn check_and_handle_game_over(
+        &mut self,
+        broadcaster: &LobbyBroadcaster,
+        cause: Option<OutcomeReason>,
+    ) -> bool {
+        // Results already went out
+        if !self.started() {
+            return false;
+        }
+        let game_over = self.evaluate_game_over(broadcaster, cause);
+        if game_over {
+            self.finish_game(broadcaster);
+        }
+        game_over
+    }
+
+    /// Everything that follows once the results are out, however the game ended
+    pub fn finish_game(&mut self, broadcaster: &LobbyBroadcaster) {
+        self.set_phase(LobbyPhase::GameOver);
+        self.reveal_names(broadcaster);
+        broadcaster.broadcast(ServerToClient::Awards {
+            awards: awards::awards(self.players.iter().map(|(id, p)| (id, &p.game_state))),
+        });
+        let standings = self.compute_standings();
+        let unverified = self.unverified_accounts();
+        audit::record(
+            &self.code,
+            AuditEvent::GameEnded {
+                standings: standings.clone(),
+                unverified: unverified.clone(),
+            },
+        );
+        federation::submit(self.federated_result(&standings));
+        webhooks::emit(WebhookPayload::GameEnded {
+            lobby_code: self.code.clone(),
+            game_mode: self.lobby_options.gamemode,
+            standings,
+            unverified,
+        });
+        // Bots don't send joker previews, only players' verified runs count
+        usage_stats::record_game(
+            self.lobby_options.gamemode,
+            self.stats.game_length(Instant::now()),
+            self.players
+                .iter()
+                .filter(|(id, p)| self.run_problem(id, p).is_none())
+                .filter_map(|(_, p)| p.lobby_state.last_jokers.as_deref()),
+        );
+    }
+
+    /// Standings keyed by verified account instead of this server's player ids, for the
+    /// stats service. An account the client only claimed could be anyone's, so players
+    /// without a verified one are left out
+    fn federated_result(&self, standings: &[Standing]) -> FederatedResult {
+        let players = standings
+            .iter()
+            .filter_map(|standing| {
+                Some(FederatedStanding {
+                    account_id: standing.account_id.clone()?,
+                    placement: standing.placement,
+                    lives: standing.lives,
+                    furthest_blind: standing.furthest_blind,
+                    points: standing.points,
+                    verified: standing.verified,
+                })
+            })
+            .collect();
+        FederatedResult {
+            lobby_code: self.code.clone(),
+            game_mode: self.lobby_options.gamemode,
+            players,
+        }
+    }
+
+    pub fn record_run_checksum(&mut self, player_id: &str, hash: String) -> Result<(), &'static str> {
+        if !self.started() {
+            return Err("No game running");
+        }
+        let Some(player) = self.players.get(player_id) else {
+            return Err("Not in this lobby");
+        };
+        let ante = player.game_state.ante;
+        self.run_checksums.record(player_id, ante, hash)
+    }
+
+    /// Why a player's run can't be vouched for, `None` when it can. Bots play on the
+    /// server and don't send checksums
+    fn run_problem(&self, id: &str, p: &ClientLobbyEntry) -> Option<&'static str> {
+        if p.profile.is_bot {
+            None
+        } else if self.rule_breakers.contains(id) {
+            Some("played a banned card")
+        } else {
+            self.run_checksums.problem(id, p.game_state.ante)
+        }
+    }
+
+    /// Accounts whose runs this game can't be vouched for. Results are kept per account,
+    /// players without a verified one are only marked in their standing
+    fn unverified_accounts(&self) -> Vec<String> {
+        let mut unverified: Vec<String> = self
+            .players
+            .iter()
+            .filter_map(|(id, p)| {
+                let problem = self.run_problem(id, p)?;
+                debug!("Run of {} in lobby {} is unverified: {}", id, self.code, problem);
+                p.profile.verified_account().map(str::to_string)
+            })
+            .collect();
+        unverified.sort();
+        unverified.dedup();
+        unverified
+    }
+
+    fn evaluate_game_over(
+        &mut self,
+        broadcaster: &LobbyBroadcaster,
+        cause: Option<OutcomeReason>,
+    ) -> bool {
+        match self.lobby_options.gamemode {
+            GameMode::Survival => {
+                let Some(winners) = self.survival_winners() else {
+                    self.broadcast_survival_wait_status(broadcaster);
+                    return false;
+                };
+                let losers: Vec<String> = self
+                    .players
+                    .keys()
+                    .filter(|id| !winners.contains(id))
+                    .cloned()
+                    .collect();
+                let reason = cause.unwrap_or(OutcomeReason::FurthestBlind);
+                self.send_game_results(broadcaster, &winners, &losers, reason, reason);
+                true
+            }
+            GameMode::CoopSurvival => {
+                // Game over if any player is dead (everyone loses together), with revives
+                // enabled downed players are out of the game so only a full wipe ends it
+                if self.is_someone_dead() || self.get_player_count_in_game() == 0 {
+                    let everyone: Vec<String> = self.players.keys().cloned().collect();
+                    let reason = cause.unwrap_or(OutcomeReason::OutOfLives);
+                    self.send_game_results(broadcaster, &[], &everyone, reason, reason);
+                    true
+                } else {
+                    false
+                }
+            }
+            GameMode::Clash => {
+                if let Some(leaders) = self.clash_league_leaders() {
+                    let others: Vec<String> = self
+                        .players
+                        .keys()
+                        .filter(|id| !leaders.contains(id))
+                        .cloned()
+                        .collect();
+                    self.send_game_results(
+                        broadcaster,
+                        &leaders,
+                        &others,
+                        OutcomeReason::PointsTarget,
+                        OutcomeReason::PointsTarget,
+                    );
+                    return true;
+                }
+                if !self.is_someone_dead() {
+                    return false;
+                }
+
+                let mut dead_players = Vec::new();
+                let mut alive_players = Vec::new();
+
+                for (id, player) in self.players.iter_mut() {
+                    if !player.lobby_state.in_game {
+                        continue;
+                    }
+                    if player.game_state.lives == 0 {
+                        dead_players.push(id.clone());
+                        player.lobby_state.in_game = false;
+                    } else {
+                        alive_players.push(id.clone())
+                    }
+                }
+                dead_players.sort();
+                self.eliminations.push(dead_players.clone());
+
+                let game_over = alive_players.len() == 1;
+                let winners = if game_over { alive_players } else { Vec::new() };
+                self.send_game_results(
+                    broadcaster,
+                    &winners,
+                    &dead_players,
+                    cause.unwrap_or(OutcomeReason::OpponentOutOfLives),
+                    cause.unwrap_or(OutcomeReason::OutOfLives),
+                );
+                game_over
+            }
+            _ => {
+                if !self.is_someone_dead() {
+                    return false;
+                }
+
+                let mut winners = Vec::new();
+                let mut losers = Vec::new();
+
+                for (id, player) in self.players.iter() {
+                    if player.game_state.lives > 0 {
+                        winners.push(id.clone());
+                    } else {
+                        losers.push(id.clone());
+                    }
+                }
+
+                self.send_game_results(
+                    broadcaster,
+                    &winners,
+                    &losers,
+                    cause.unwrap_or(OutcomeReason::OpponentOutOfLives),
+                    cause.unwrap_or(OutcomeReason::OutOfLives),
+                );
+                true
+            }
+        }
+    }
+
+    // Broadcasting helpers
+    pub fn broadcast_all_game_states(&self, broadcaster: &LobbyBroadcaster) {
+        for player in self.players.values() {
+            self.broadcast_game_state_update(broadcaster, &player.profile.id, false);
+        }
+    }
+
+    pub fn broadcast_life_updates(&self, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        if self.lobby_options.gamemode == GameMode::CoopSurvival {
+            self.broadcast_all_game_states(broadcaster);
+        } else {
+            self.broadcast_game_state_update(broadcaster, player_id, false);
+        }
+    }
+
+    pub fn broadcast_game_state_update(
+        &self,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        exclude_player: bool,
+    ) {
+        if let Some(player) = self.players.get(player_id) {
+            let update = ServerToClient::GameStateUpdate {
+                player_id: player_id.to_string(),
+                game_state: player.game_state.clone(),
+            };
+
+            if exclude_player {
+                broadcaster.broadcast_except(player_id, update);
+            } else {
+                broadcaster.broadcast(update);
+            }
+        }
+    }
+
+    pub fn broadcast_ready_states(&self, broadcaster: &LobbyBroadcaster) {
+        let ready_states = self
+            .collect_ready_states()
+            .into_iter()
+            .map(|(id, ready)| (id, ready))
+            .collect::<HashMap<String, bool>>();
+        broadcaster.broadcast(ServerToClient::LobbyReady { ready_states });
+    }
+
+    pub fn broadcast_ready_states_except(
+        &self,
+        broadcaster: &LobbyBroadcaster,
+        except_player: &str,
+    ) {
+        let ready_states = self
+            .collect_ready_states()
+            .into_iter()
+            .map(|(id, ready)| (id, ready))
+            .collect::<HashMap<String, bool>>();
+        broadcaster.broadcast_except(except_player, ServerToClient::LobbyReady { ready_states });
+    }
+
+    /// Turn skips since the last PvP blind into head-start chips for each opponent
+    fn apply_skip_handicaps(&mut self, broadcaster: &LobbyBroadcaster) {
+        let chips_per_skip = self.lobby_options.skip_handicap_chips;
+        if chips_per_skip == 0 {
+            return;
+        }
+        let new_skips: HashMap<String, u32> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .map(|(id, p)| {
+                let counted = self.skips_at_last_pvp.get(id).copied().unwrap_or(0);
+                (id.clone(), p.game_state.skips.saturating_sub(counted) as u32)
+            })
+            .collect();
+
+        let mut handicaps = HashMap::new();
+        for id in new_skips.keys() {
+            let opponent_skips: u32 = new_skips
+                .iter()
+                .filter(|(other, _)| *other != id)
+                .fold(0u32, |total, (_, skips)| total.saturating_add(*skips));
+            let chips = opponent_skips.saturating_mul(chips_per_skip) as f64;
+            if let Some(player) = self.players.get_mut(id) {
+                player.game_state.score = TalismanNumber::Regular(chips);
+                self.skips_at_last_pvp.insert(id.clone(), player.game_state.skips);
+            }
+            handicaps.insert(id.clone(), chips);
+        }
+        broadcaster.broadcast(ServerToClient::PvpHandicaps { handicaps });
+    }
+
+    pub fn start_online_blind(&mut self, broadcaster: &LobbyBroadcaster) {
+        self.set_phase(LobbyPhase::PvpBlind);
+        if let Some((setter_id, key)) = self.withheld_boss.take() {
+            broadcaster.broadcast_except(&setter_id, ServerToClient::SetBossBlind { key });
+            if self.dynamic_difficulty() {
+                self.broadcast_boss_difficulty(broadcaster);
+            }
+        }
+        self.reset_ready_states();
+        self.reset_scores();
+        self.apply_skip_handicaps(broadcaster);
+        self.stats.round_started(Instant::now());
+        let started_at = now_millis();
+        self.round_started_at = Some(started_at);
+        let in_game_player_ids = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<String>>();
+        broadcaster.broadcast_to(
+            &in_game_player_ids,
+            ServerToClient::StartBlind {
+                server_time: started_at,
+            },
+        );
+        self.start_blitz_timer(broadcaster, &in_game_player_ids, started_at);
+        self.broadcast_ready_states(broadcaster);
+    }
+
+    /// Blitz: start the clock on the PvP blind, longer by one increment per blind played
+    fn start_blitz_timer(
+        &mut self,
+        broadcaster: &LobbyBroadcaster,
+        player_ids: &[String],
+        started_at: u64,
+    ) {
+        let options = &self.lobby_options;
+        if options.blitz_blind_seconds == 0 {
+            return;
+        }
+        let increments = options.blitz_increment_seconds.saturating_mul(self.blitz_blinds);
+        let seconds = options.blitz_blind_seconds.saturating_add(increments);
+        self.blitz_blinds += 1;
+        self.blitz_deadline = Some(Instant::now() + Duration::from_secs(seconds as u64));
+        broadcaster.broadcast_to(
+            player_ids,
+            ServerToClient::BlitzTimer {
+                seconds,
+                ends_at: started_at + seconds as u64 * 1000,
+            },
+        );
+    }
+
+    /// Blitz: once the PvP blind is out of time, players still playing it fail it with a
+    /// score of zero and the round is settled without them
+    fn expire_blitz_blind(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        if self.phase != LobbyPhase::PvpBlind || self.blitz_deadline.is_none_or(|at| now < at) {
+            return;
+        }
+        self.blitz_deadline = None;
+        let mut expired: Vec<String> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .filter(|(_, p)| p.game_state.hands_left > 0 && !p.lobby_state.round_complete)
+            .map(|(id, _)| id.clone())
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        expired.sort();
+        debug!("Blitz time ran out in lobby {} for {:?}", self.code, expired);
+        for player_id in &expired {
+            if let Some(player) = self.players.get_mut(player_id) {
+                player.game_state.score = TalismanNumber::Regular(0.0);
+            }
+            self.mark_round_complete(player_id);
+        }
+        broadcaster.broadcast(ServerToClient::BlitzExpired { player_ids: expired });
+        self.evaluate_online_round(broadcaster);
+    }
+
+    /// Pass a player's jokers or deck on, dropping repeats; clients that asked for
+    /// patches and hold an earlier payload only get what changed since
+    pub fn relay_preview(
+        &mut self,
+        broadcaster: &LobbyBroadcaster,
+        player_id: &str,
+        kind: PreviewKind,
+        payload: String,
+    ) {
+        let Some(player) = self.players.get_mut(player_id) else {
+            return;
+        };
+        let last = kind.last_sent(&mut player.lobby_state);
+        if last.as_deref() == Some(payload.as_str()) {
+            debug!("Dropping repeated {:?} preview from {}", kind, player_id);
+            return;
+        }
+        let patch = last
+            .replace(payload.clone())
+            .map(|previous| PreviewPatch::between(&previous, &payload))
+            .filter(|patch| patch.is_worth_sending(&payload));
+
+        let mut patched = Vec::new();
+        let mut full = Vec::new();
+        for (id, entry) in &self.players {
+            if id == player_id && !kind.echoes_sender() {
+                continue;
+            }
+            // Whoever gets this one holds it as the base for the next; someone who opted
+            // out misses it, and needs the whole payload again once they opt back in
+            let base = (id.clone(), player_id.to_string(), kind);
+            if !broadcaster.subscribed_to(id, kind.event_class()) {
+                self.preview_bases.remove(&base);
+                continue;
+            }
+            let has_base = !self.preview_bases.insert(base);
+            if patch.is_some() && entry.profile.preview_patches && has_base {
+                patched.push(id.clone());
+            } else {
+                full.push(id.clone());
+            }
+        }
+        if let Some(patch) = patch {
+            broadcaster.broadcast_to(&patched, kind.patched(player_id.to_string(), patch));
+        }
+        broadcaster.broadcast_to(&full, kind.full(player_id.to_string(), payload));
+    }
+
+    /// `recipient` was sent `sender`'s whole payload outside of a relay
+    pub fn note_preview_base(&mut self, recipient: &str, sender: &str, kind: PreviewKind) {
+        if self.players.contains_key(recipient) {
+            self.preview_bases.insert((recipient.to_string(), sender.to_string(), kind));
+        }
+    }
+
+    pub fn last_preview(&self, player_id: &str, kind: PreviewKind) -> Option<String> {
+        let state = &self.players.get(player_id)?.lobby_state;
+        match kind {
+            PreviewKind::Jokers => state.last_jokers.clone(),
+            PreviewKind::Deck => state.last_deck.clone(),
+        }
+    }
+
+    /// Why a player's deck breaks the lobby's required back, if it does. A deck whose
+    /// back can't be read counts as breaking it
+    pub fn deck_violation(&self, deck: &str) -> Option<String> {
+        let required = self.required_back.as_ref().filter(|_| self.started())?;
+        match deck_back(deck) {
+            Some(back) if same_back(back, required) => None,
+            Some(back) => Some(format!("Playing {} but this lobby requires {}", back, required)),
+            None => Some(format!("Sent a deck with no back but this lobby requires {}", required)),
+        }
+    }
+
+    /// Everyone is ready for the PvP blind, run the boss ban phase first if the lobby wants one
+    pub fn begin_pvp_blind(&mut self, broadcaster: &LobbyBroadcaster) {
+        if !self.lobby_options.boss_ban_phase {
+            self.start_online_blind(broadcaster);
+        } else if self.boss_ban.is_none() {
+            self.start_boss_ban(broadcaster);
+        }
+    }
+
+    /// Players in the game who aren't ready, when the PvP blind may start without them:
+    /// empty once everyone is ready, otherwise only as far as the co-op ready rule allows.
+    /// `None` while the blind has to keep waiting
+    pub fn blind_start_overrides(&self, forced_by_host: bool) -> Option<Vec<String>> {
+        let in_game: Vec<(&String, &ClientLobbyEntry)> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .collect();
+        let mut not_ready: Vec<String> = in_game
+            .iter()
+            .filter(|(_, p)| !p.lobby_state.is_ready)
+            .map(|(id, _)| id.to_string())
+            .collect();
+        if not_ready.is_empty() {
+            return Some(not_ready);
+        }
+        if self.lobby_options.gamemode != GameMode::CoopSurvival {
+            return None;
+        }
+        let allowed = match self.lobby_options.coop_ready_rule {
+            CoopReadyRule::All => false,
+            CoopReadyRule::Majority => (in_game.len() - not_ready.len()) * 2 > in_game.len(),
+            CoopReadyRule::HostOverride => forced_by_host,
+        };
+        if !allowed {
+            return None;
+        }
+        not_ready.sort();
+        Some(not_ready)
+    }
+
+    /// Start the PvP blind, first telling everyone who it starts without
+    pub fn begin_pvp_blind_overriding(&mut self, broadcaster: &LobbyBroadcaster, overridden: Vec<String>) {
+        if self.boss_ban.is_some() {
+            return;
+        }
+        if !overridden.is_empty() {
+            debug!(
+                "Lobby {} starts the blind without {:?} ready",
+                self.code, overridden
+            );
+            broadcaster.broadcast(ServerToClient::ReadyOverridden {
+                player_ids: overridden,
+                rule: self.lobby_options.coop_ready_rule,
+            });
+        }
+        self.begin_pvp_blind(broadcaster);
+    }
+
+    /// Remember a boss the host set, only tracked when the host picks the bosses
+    pub fn record_boss(&mut self, key: &str) {
+        if !self.lobby_options.normal_bosses {
+            self.boss_rotation.record(key);
+        }
+    }
+
+    pub fn suggest_boss(&self) -> Result<String, &'static str> {
+        if self.lobby_options.normal_bosses {
+            return Err("This lobby plays the game's own bosses");
+        }
+        Ok(self.boss_rotation.suggest(&self.rng))
+    }
+
+    /// Record the ante a player's run reached
+    pub fn set_ante(&mut self, player_id: &str, ante: u32) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.game_state.ante = ante;
+            self.highest_ante = self.highest_ante.max(ante);
+        }
+    }
+
+    /// Ante the host's boss chips are checked against. It only grows with what any player
+    /// reports and is never below one ante per PvP blind played, so the host can't lower it
+    pub fn boss_ante(&self) -> u32 {
+        let played = self.stats.rounds_played() + 1;
+        self.highest_ante.max(self.current_ante()).max(played)
+    }
+
+    /// Highest ante in the game, keys the ban rolls so a restored game deals the same pool
+    fn current_ante(&self) -> u32 {
+        self.players
+            .values()
+            .filter(|p| p.lobby_state.in_game)
+            .map(|p| p.game_state.ante)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn start_boss_ban(&mut self, broadcaster: &LobbyBroadcaster) {
+        let mut pool: Vec<String> = BOSS_BLINDS.iter().map(|boss| boss.to_string()).collect();
+        self.rng
+            .shuffle(&format!("boss_pool:{}", self.current_ante()), &mut pool);
+        pool.truncate(BOSS_BAN_POOL_SIZE.max(self.get_player_count_in_game() + 1));
+
+        debug!("Starting boss ban in lobby {} with pool {:?}", self.code, pool);
+        broadcaster.broadcast(ServerToClient::BossBanStarted {
+            pool: pool.clone(),
+            seconds: BOSS_BAN_TIMEOUT.as_secs(),
+        });
+        self.boss_ban = Some(BossBanPhase::new(pool, Instant::now() + BOSS_BAN_TIMEOUT));
+    }
+
+    pub fn ban_boss(&mut self, broadcaster: &LobbyBroadcaster, player_id: &str, key: String) {
+        let in_game = self
+            .players
+            .get(player_id)
+            .is_some_and(|p| p.lobby_state.in_game);
+        let Some(phase) = self.boss_ban.as_mut().filter(|_| in_game) else {
+            broadcaster.send_to(player_id, ServerToClient::error("No boss ban in progress"));
+            return;
+        };
+        if let Err(message) = phase.ban(player_id, &key) {
+            broadcaster.send_to(player_id, ServerToClient::error(message));
+            return;
+        }
+        broadcaster.broadcast(ServerToClient::BossBanned {
+            player_id: player_id.to_string(),
+            key,
+        });
+        self.finish_boss_ban_if_done(broadcaster, Instant::now());
+    }
+
+    /// Pick the boss once every player in the game has banned or time ran out
+    fn finish_boss_ban_if_done(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        let Some(phase) = self.boss_ban.as_ref() else {
+            return;
+        };
+        let all_banned = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .all(|(id, _)| phase.has_banned(id));
+        if !all_banned && now < phase.deadline {
+            return;
+        }
+        let Some(phase) = self.boss_ban.take() else {
+            return;
+        };
+        if !self.started() {
+            return;
+        }
+
+        let remaining = phase.remaining();
+        let (index, _) = self.rng.roll(
+            &format!("boss_pick:{}", self.current_ante()),
+            remaining.len() as u32,
+        );
+        let Some(key) = remaining.get(index as usize).cloned() else {
+            error!("Boss ban in lobby {} left no boss to pick", self.code);
+            self.start_online_blind(broadcaster);
+            return;
+        };
+        debug!("Boss ban in lobby {} picked {}", self.code, key);
+        broadcaster.broadcast(ServerToClient::SetBossBlind { key });
+        self.start_online_blind(broadcaster);
+    }
+
+    // Magnet transaction handling
+    pub fn start_magnet(
+        &mut self,
+        broadcaster: &LobbyBroadcaster,
+        requester_id: &str,
+        target: Option<String>,
+    ) {
+        if self.magnet.is_some() {
+            debug!(
+                "Player {} triggered magnet while another is in flight in lobby {}",
+                requester_id, self.code
+            );
+            broadcaster.send_to(requester_id, ServerToClient::MagnetFailed {});
+            return;
+        }
+
+        let is_valid_target = |id: &str| {
+            id != requester_id
+                && self
+                    .players
+                    .get(id)
+                    .is_some_and(|p| p.lobby_state.in_game)
+        };
+        let target_id = match target {
+            Some(id) if is_valid_target(&id) => Some(id),
+            Some(_) => None,
+            None => self.players.keys().find(|id| is_valid_target(id)).cloned(),
+        };
+
+        let Some(target_id) = target_id else {
+            debug!("No valid magnet target for player {}", requester_id);
+            broadcaster.send_to(requester_id, ServerToClient::MagnetFailed {});
+            return;
+        };
+
+        broadcaster.send_to(&target_id, ServerToClient::Magnet {});
+        self.magnet = Some(MagnetTransaction {
+            requester_id: requester_id.to_string(),
+            target_id,
+            deadline: Instant::now() + MAGNET_TIMEOUT,
+        });
+    }
+
+    pub fn resolve_magnet(&mut self, broadcaster: &LobbyBroadcaster, responder_id: &str, key: String) {
+        match self.magnet.take() {
+            Some(magnet) if magnet.target_id == responder_id => {
+                broadcaster.send_to(&magnet.requester_id, ServerToClient::MagnetResponse { key });
+            }
+            other => {
+                debug!(
+                    "Ignoring magnet response from {} in lobby {}: not the targeted player",
+                    responder_id, self.code
+                );
+                self.magnet = other;
+            }
+        }
+    }
+
+    pub fn expire_magnet(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        if self.magnet.as_ref().is_some_and(|m| now >= m.deadline)
+            && let Some(magnet) = self.magnet.take()
+        {
+            debug!(
+                "Magnet from {} to {} timed out in lobby {}",
+                magnet.requester_id, magnet.target_id, self.code
+            );
+            broadcaster.send_to(&magnet.requester_id, ServerToClient::MagnetFailed {});
+        }
+    }
+
+    /// Drop any magnet transaction involving a player who is leaving
+    pub fn cancel_magnet_for(&mut self, broadcaster: &LobbyBroadcaster, player_id: &str) {
+        let involved = self
+            .magnet
+            .as_ref()
+            .is_some_and(|m| m.requester_id == player_id || m.target_id == player_id);
+        if involved && let Some(magnet) = self.magnet.take() {
+            broadcaster.send_to(&magnet.requester_id, ServerToClient::MagnetFailed {});
+        }
+    }
+
+    // Latency tracking
+    pub fn set_player_latency(&mut self, player_id: &str, rtt_ms: u32) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.lobby_state.latency_ms = Some(rtt_ms);
+        }
+    }
+
+    pub fn collect_latencies(&self) -> HashMap<String, u32> {
+        self.players
+            .iter()
+            .filter_map(|(id, entry)| entry.lobby_state.latency_ms.map(|ms| (id.clone(), ms)))
+            .collect()
+    }
+
+    pub fn collect_locations(&self) -> HashMap<String, String> {
+        self.players
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.game_state.location.clone()))
+            .collect()
+    }
+
+    fn broadcast_latencies_if_due(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        let due = self
+            .last_latency_broadcast
+            .is_none_or(|last| now.duration_since(last) >= LATENCY_BROADCAST_INTERVAL);
+        if !due {
+            return;
+        }
+        self.last_latency_broadcast = Some(now);
+
+        let latencies = self.collect_latencies();
+        if !latencies.is_empty() {
+            broadcaster.broadcast(ServerToClient::LobbyLatency { latencies });
+        }
+    }
+
+    fn broadcast_stats_if_due(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        if !self.started() || !self.stats.broadcast_due(now, LOBBY_STATS_INTERVAL) {
+            return;
+        }
+        broadcaster.broadcast(ServerToClient::LobbyStats {
+            rounds_played: self.stats.rounds_played(),
+            antes: self
+                .players
+                .iter()
+                .filter(|(_, p)| p.lobby_state.in_game)
+                .map(|(id, p)| (id.clone(), p.game_state.ante))
+                .collect(),
+            average_round_secs: self.stats.average_round_secs(),
+        });
+    }
+
+    fn broadcast_survival_standings_if_due(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) {
+        if !self.started() || self.lobby_options.gamemode != GameMode::Survival {
+            return;
+        }
+        let due = self
+            .last_standings_broadcast
+            .is_none_or(|last| now.duration_since(last) >= SURVIVAL_STANDINGS_INTERVAL);
+        if !due {
+            return;
+        }
+        self.last_standings_broadcast = Some(now);
+        broadcaster.broadcast(ServerToClient::SurvivalStandings {
+            standings: self.survival_standings(),
+        });
+    }
+
+    // Ready timeout
+    fn players_not_ready(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .players
+            .iter()
+            .filter(|(_, p)| !p.lobby_state.is_ready)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Someone joined before the game got going, whoever readied up did so without
+    /// them: stop the countdown and make everyone ready up again. False when no
+    /// countdown was running.
+    pub fn restart_ready_check(&mut self, broadcaster: &LobbyBroadcaster) -> bool {
+        if self.started() || self.ready_deadline.is_none() {
+            return false;
+        }
+        self.cancel_ready_countdown(broadcaster);
+        self.reset_ready_states_to_host_only();
+        true
+    }
+
+    fn cancel_ready_countdown(&mut self, broadcaster: &LobbyBroadcaster) {
+        if self.ready_deadline.take().is_some() {
+            self.ready_countdown_announced = None;
+            broadcaster.broadcast(ServerToClient::ReadyCountdownCancelled {});
+        }
+    }
+
+    /// Count down on players holding up a lobby where everyone else is ready.
+    /// Returns the players that should be kicked once the countdown expires.
+    pub fn check_ready_timeout(&mut self, broadcaster: &LobbyBroadcaster, now: Instant) -> Vec<String> {
+        let timeout = self.lobby_options.ready_timeout_seconds;
+        let laggards = self.players_not_ready();
+        // The host counts as ready from the start, only a guest readying up starts the clock
+        let guest_ready = self
+            .players
+            .values()
+            .any(|p| p.lobby_state.is_ready && !p.lobby_state.is_host);
+        if timeout == 0 || self.started() || laggards.is_empty() || !guest_ready {
+            self.cancel_ready_countdown(broadcaster);
+            return Vec::new();
+        }
+
+        let deadline = *self
+            .ready_deadline
+            .get_or_insert(now + Duration::from_secs(timeout as u64));
+
+        if now >= deadline {
+            self.ready_deadline = None;
+            self.ready_countdown_announced = None;
+            debug!(
+                "Ready timeout expired in lobby {} for {:?}",
+                self.code, laggards
+            );
+            return match self.lobby_options.ready_timeout_action {
+                ReadyTimeoutAction::Kick => laggards,
+                ReadyTimeoutAction::Unready => {
+                    self.reset_ready_states_to_host_only();
+                    self.broadcast_ready_states(broadcaster);
+                    Vec::new()
+                }
+            };
+        }
+
+        let seconds_left = deadline.duration_since(now).as_secs_f32().ceil() as u32;
+        if self.ready_countdown_announced != Some(seconds_left) {
+            self.ready_countdown_announced = Some(seconds_left);
+            broadcaster.broadcast(ServerToClient::ReadyCountdown {
+                player_ids: laggards,
+                seconds_left,
+            });
+        }
+        Vec::new()
+    }
+
+    /// Periodic housekeeping driven by the lobby task.
+    /// Returns players the lobby decided to kick.
+    pub fn handle_tick(&mut self, broadcaster: &LobbyBroadcaster) -> Vec<String> {
+        let now = Instant::now();
+        self.expire_magnet(broadcaster, now);
+        self.expire_blitz_blind(broadcaster, now);
+        self.finish_boss_ban_if_done(broadcaster, now);
+        self.expire_reservations(broadcaster, now);
+        self.expire_merge_reservation(now);
+        self.broadcast_latencies_if_due(broadcaster, now);
+        self.broadcast_stats_if_due(broadcaster, now);
+        self.broadcast_survival_standings_if_due(broadcaster, now);
+        self.check_ready_timeout(broadcaster, now)
+    }
+
+    // Survival mode helper methods
+    fn survival_eliminated_blind(player: &ClientLobbyEntry) -> Option<u32> {
+        if player.game_state.lives > 0 {
+            return None;
+        }
+        Some(player.game_state.eliminated_at.unwrap_or(player.game_state.furthest_blind))
+    }
+
+    /// Survival is decided once every run ended, or the last runner passed every eliminated blind
+    fn survival_winners(&self) -> Option<Vec<String>> {
+        let in_game = self.players.iter().filter(|(_, p)| p.lobby_state.in_game);
+        let (eliminated, alive): (Vec<_>, Vec<_>) =
+            in_game.partition(|(_, p)| Self::survival_eliminated_blind(p).is_some());
+        let best_eliminated = eliminated
+            .iter()
+            .filter_map(|(_, p)| Self::survival_eliminated_blind(p))
+            .max()?;
+
+        match alive.as_slice() {
+            [] => Some(
+                eliminated
+                    .iter()
+                    .filter(|(_, p)| Self::survival_eliminated_blind(p) == Some(best_eliminated))
+                    .map(|(id, _)| (*id).clone())
+                    .collect(),
+            ),
+            [(id, p)] if p.game_state.furthest_blind > best_eliminated => Some(vec![(*id).clone()]),
+            _ => None,
+        }
+    }
+
+    /// Players in the game ranked by how far they got, runs still going ahead of
+    /// players knocked out on the same blind
+    pub fn survival_standings(&self) -> Vec<SurvivalStanding> {
+        let in_game: Vec<(&String, &ClientLobbyEntry, (u32, bool))> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .map(|(id, p)| {
+                let eliminated = Self::survival_eliminated_blind(p);
+                let key = (eliminated.unwrap_or(p.game_state.furthest_blind), eliminated.is_none());
+                (id, p, key)
+            })
+            .collect();
+
+        let mut standings: Vec<SurvivalStanding> = in_game
+            .iter()
+            .map(|(id, p, key)| SurvivalStanding {
+                player_id: (*id).clone(),
+                placement: 1 + in_game.iter().filter(|(_, _, other)| other > key).count() as u8,
+                furthest_blind: key.0,
+                ante: p.game_state.ante,
+                lives: p.game_state.lives,
+                eliminated: !key.1,
+            })
+            .collect();
+        standings.sort_by(|a, b| (a.placement, &a.player_id).cmp(&(b.placement, &b.player_id)));
+        standings
+    }
+
+    fn broadcast_survival_wait_status(&self, broadcaster: &LobbyBroadcaster) {
+        let mut remaining_players: Vec<String> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game && p.game_state.lives > 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+        remaining_players.sort();
+        let leading_blind = self
+            .players
+            .values()
+            .map(|p| p.game_state.furthest_blind)
+            .max()
+            .unwrap_or(0);
+
+        for (id, player) in &self.players {
+            if let Some(eliminated_at) = Self::survival_eliminated_blind(player) {
+                broadcaster.send_to(
+                    id,
+                    ServerToClient::SurvivalWaiting {
+                        eliminated_at,
+                        leading_blind,
+                        remaining_players: remaining_players.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn record_shop_spend(&mut self, player_id: &str, amount: u32) -> Result<(), &'static str> {
+        if !self.started() {
+            return Err("No game running");
+        }
+        let Some(player) = self.players.get_mut(player_id) else {
+            return Err("Not in this lobby");
+        };
+        let ante = (player.game_state.ante.max(1) as usize).min(MAX_SPEND_ANTES);
+        let spent = &mut player.game_state.spent_in_shop;
+        if spent.len() < ante {
+            spent.resize(ante, 0);
+        }
+        spent[ante - 1] = spent[ante - 1].saturating_add(amount);
+        Ok(())
+    }
+
+    pub fn shop_spending(&self) -> Vec<ShopSpend> {
+        let mut spending: Vec<ShopSpend> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.lobby_state.in_game)
+            .map(|(id, p)| {
+                let spent = &p.game_state.spent_in_shop;
+                ShopSpend {
+                    player_id: id.clone(),
+                    total: spent.iter().fold(0u32, |total, &amount| total.saturating_add(amount)),
+                    per_ante: spent.clone(),
+                }
+            })
+            .collect();
+        spending.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.player_id.cmp(&b.player_id)));
+        spending
+    }
+
+    pub fn get_in_game_statuses(&self) -> HashMap<String, bool> {
+        self.players
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.lobby_state.in_game))
+            .collect()
+    }
+
+    pub fn get_player_count_in_game(&self) -> usize {
+        self.players
+            .values()
+            .filter(|p| p.lobby_state.in_game)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{EventClass, ServerToClient};
+    use crate::test_utils::contains_response_of_type;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    fn drain(rx: &mut mpsc::UnboundedReceiver<Arc<ServerToClient>>) -> Vec<Arc<ServerToClient>> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn test_magnet_only_target_can_respond_and_times_out() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx1);
+        broadcaster.add_player("p2".to_string(), tx2);
+        lobby.start_game();
+
+        lobby.start_magnet(&broadcaster, "p1", None);
+        assert!(contains_response_of_type(&drain(&mut rx2), &ServerToClient::Magnet {}));
+
+        // The requester cannot answer its own magnet
+        lobby.resolve_magnet(&broadcaster, "p1", "j_joker".to_string());
+        assert!(drain(&mut rx1).is_empty());
+
+        lobby.expire_magnet(&broadcaster, Instant::now() + MAGNET_TIMEOUT);
+        assert!(contains_response_of_type(&drain(&mut rx1), &ServerToClient::MagnetFailed {}));
+
+        // Late responses after expiry are ignored
+        lobby.resolve_magnet(&broadcaster, "p2", "j_joker".to_string());
+        assert!(drain(&mut rx1).is_empty());
+    }
+
+    #[test]
+    fn test_actions_from_an_earlier_stay_are_dropped() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.set_lobby_generation("p1", 1);
+        assert!(lobby.accepts_lobby_generation("p1", 1));
+
+        // Left and came back: whatever was still queued from the first stay is stale
+        lobby.remove_player("p1");
+        assert!(!lobby.accepts_lobby_generation("p1", 1));
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.set_lobby_generation("p1", 3);
+        assert!(!lobby.accepts_lobby_generation("p1", 1));
+        assert!(lobby.accepts_lobby_generation("p1", 3));
+    }
+
+    #[test]
+    fn test_gold_on_life_loss_rewards_loser_only() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        lobby.start_game();
+        lobby.lobby_options.no_gold_on_round_loss = true;
+
+        let rewards = lobby.process_round_outcome(&[
+            RoundResult { player_id: "p1".to_string(), won: true },
+            RoundResult { player_id: "p2".to_string(), won: false },
+        ]);
+
+        let winner = rewards.iter().find(|r| r.player_id == "p1").unwrap();
+        let loser = rewards.iter().find(|r| r.player_id == "p2").unwrap();
+        assert_eq!((winner.gold, winner.blind_reward), (0, true));
+        assert_eq!((loser.gold, loser.blind_reward), (LIFE_LOSS_GOLD, false));
+        assert_eq!(lobby.players()["p2"].game_state.money, LIFE_LOSS_GOLD);
+    }
+
+    #[test]
+    fn test_forfeit_ends_duel_with_forfeit_reason() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx1);
+        broadcaster.add_player("p2".to_string(), tx2);
+
+        // Conceding before the game starts does nothing
+        assert!(!lobby.forfeit("p1", &broadcaster));
+
+        lobby.start_game();
+        assert!(lobby.forfeit("p1", &broadcaster));
+        assert!(!lobby.forfeit("p1", &broadcaster));
+
+        assert!(drain(&mut rx1).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::LoseGame { reason: OutcomeReason::Forfeit, .. }
+        )));
+        assert!(drain(&mut rx2).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::WinGame { reason: OutcomeReason::Forfeit, .. }
+        )));
+    }
+
+    #[test]
+    fn test_round_results_carry_finish_times() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx1);
+        lobby.start_game();
+        lobby.start_online_blind(&broadcaster);
+
+        lobby.get_player_mut("p1").unwrap().game_state.hands_left = 0;
+        lobby.record_hand("p1", TalismanNumber::Regular(300.0), 0, None);
+        lobby.mark_round_complete("p2");
+        drain(&mut rx1);
+        lobby.evaluate_online_round(&broadcaster);
+
+        let end = drain(&mut rx1)
+            .into_iter()
+            .find(|m| matches!(m.as_ref(), ServerToClient::EndPvp { .. }))
+            .expect("round results");
+        let ServerToClient::EndPvp { timeline, started_at, finish_ms, .. } = end.as_ref() else {
+            unreachable!();
+        };
+        assert!(started_at.is_some());
+        assert_eq!(timeline.len(), 1);
+        let mut finished: Vec<_> = finish_ms.keys().cloned().collect();
+        finished.sort();
+        assert_eq!(finished, vec!["p1".to_string(), "p2".to_string()]);
+    }
+
+    #[test]
+    fn test_joker_previews_skip_repeats_and_patch_for_opted_in_clients() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        let (tx3, mut rx3) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player(
+            "p2".to_string(),
+            ClientProfile {
+                preview_patches: true,
+                ..ClientProfile::default()
+            },
+        );
+        lobby.add_player("p3".to_string(), ClientProfile::default());
+        broadcaster.add_player("p2".to_string(), tx2);
+        broadcaster.add_player("p3".to_string(), tx3);
+
+        let jokers = "j_joker;j_droll;j_blueprint;j_brainstorm".to_string();
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers.clone());
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers);
+        // Nobody has a payload to patch yet, and the repeat goes nowhere
+        assert_eq!(drain(&mut rx2).len(), 1);
+        assert_eq!(drain(&mut rx3).len(), 1);
+
+        let jokers = "j_joker;j_droll;j_baron;j_blueprint;j_brainstorm".to_string();
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers.clone());
+        assert!(matches!(
+            drain(&mut rx2)[..],
+            [ref m] if matches!(m.as_ref(), ServerToClient::PatchPlayerJokers { .. })
+        ));
+        assert!(matches!(
+            drain(&mut rx3)[..],
+            [ref m] if matches!(m.as_ref(), ServerToClient::ReceivePlayerJokers { .. })
+        ));
+        assert_eq!(lobby.last_preview("p1", PreviewKind::Jokers), Some(jokers));
+
+        // A late joiner has nothing to patch until it got the whole payload once
+        let (tx4, mut rx4) = mpsc::unbounded_channel();
+        let patches = ClientProfile {
+            preview_patches: true,
+            ..ClientProfile::default()
+        };
+        lobby.add_player("p4".to_string(), patches);
+        broadcaster.add_player("p4".to_string(), tx4);
+        let jokers = "j_joker;j_droll;j_baron;j_blueprint;j_brainstorm;j_mime".to_string();
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers);
+        assert!(matches!(
+            drain(&mut rx4)[..],
+            [ref m] if matches!(m.as_ref(), ServerToClient::ReceivePlayerJokers { .. })
+        ));
+        let jokers = "j_joker;j_baron;j_blueprint;j_brainstorm;j_mime".to_string();
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers);
+        assert!(matches!(
+            drain(&mut rx4)[..],
+            [ref m] if matches!(m.as_ref(), ServerToClient::PatchPlayerJokers { .. })
+        ));
+
+        // Updates missed while unsubscribed leave nothing to patch against
+        broadcaster.update_subscriptions("p4", &[], &[EventClass::JokerPreviews]);
+        let jokers = "j_joker;j_baron;j_blueprint;j_mime".to_string();
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers);
+        assert!(drain(&mut rx4).is_empty());
+        broadcaster.update_subscriptions("p4", &[EventClass::JokerPreviews], &[]);
+        let jokers = "j_joker;j_baron;j_blueprint;j_mime;j_droll".to_string();
+        lobby.relay_preview(&broadcaster, "p1", PreviewKind::Jokers, jokers);
+        assert!(matches!(
+            drain(&mut rx4)[..],
+            [ref m] if matches!(m.as_ref(), ServerToClient::ReceivePlayerJokers { .. })
+        ));
+    }
+
+    #[test]
+    fn test_survival_waits_until_last_runner_passes_eliminated_blind() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Survival);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx1);
+        broadcaster.add_player("p2".to_string(), tx2);
+        lobby.start_game();
+
+        lobby.get_player_mut("p1").unwrap().game_state.furthest_blind = 5;
+        lobby.get_player_mut("p2").unwrap().game_state.furthest_blind = 3;
+        lobby.handle_player_fail_round("p1", &broadcaster);
+
+        assert_eq!(lobby.players()["p1"].game_state.eliminated_at, Some(5));
+        assert!(drain(&mut rx1).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::SurvivalWaiting { eliminated_at: 5, .. }
+        )));
+        assert!(!drain(&mut rx2).iter().any(|m| matches!(m.as_ref(), ServerToClient::WinGame { .. })));
+
+        // Tying the eliminated blind is not enough, passing it wins
+        lobby.get_player_mut("p2").unwrap().game_state.furthest_blind = 5;
+        assert!(!lobby.check_and_handle_game_over(&broadcaster, None));
+        lobby.get_player_mut("p2").unwrap().game_state.furthest_blind = 6;
+        assert!(lobby.check_and_handle_game_over(&broadcaster, None));
+        assert!(drain(&mut rx2).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::WinGame { reason: OutcomeReason::FurthestBlind, .. }
+        )));
+    }
+
+    #[test]
+    fn test_survival_standings_rank_runs_ahead_of_knocked_out_players() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Survival);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for id in ["p1", "p2", "p3"] {
+            lobby.add_player(id.to_string(), ClientProfile::default());
+        }
+        broadcaster.add_player("p3".to_string(), tx);
+        lobby.start_game();
+
+        lobby.get_player_mut("p1").unwrap().game_state.furthest_blind = 5;
+        lobby.get_player_mut("p2").unwrap().game_state.furthest_blind = 5;
+        lobby.get_player_mut("p2").unwrap().game_state.ante = 2;
+        lobby.get_player_mut("p3").unwrap().game_state.furthest_blind = 3;
+        lobby.handle_player_fail_round("p1", &broadcaster);
+        lobby.handle_player_fail_round("p1", &broadcaster);
+        drain(&mut rx);
+
+        let standings = lobby.survival_standings();
+        let order: Vec<_> = standings
+            .iter()
+            .map(|s| (s.player_id.as_str(), s.placement, s.eliminated))
+            .collect();
+        assert_eq!(order, [("p2", 1, false), ("p1", 2, true), ("p3", 3, false)]);
+        assert_eq!(standings[0].ante, 2);
+
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "p3".to_string(), ClientToServer::GetStandings {});
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::SurvivalStandings { standings: sent } if sent == &standings
+        )));
+        lobby.handle_tick(&broadcaster);
+        assert!(contains_response_of_type(
+            &drain(&mut rx),
+            &ServerToClient::SurvivalStandings { standings: Vec::new() }
+        ));
+    }
+
+    #[test]
+    fn test_coop_revive_after_boss_cleared() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::CoopSurvival);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx);
+        lobby.lobby_options.coop_revive = true;
+        lobby.start_game();
+
+        lobby.handle_player_fail_round("p2", &broadcaster);
+        assert_eq!(lobby.players()["p2"].game_state.lives, 0);
+        assert!(!drain(&mut rx).iter().any(|m| matches!(m.as_ref(), ServerToClient::LoseGame { .. })));
+
+        lobby.boss_chips = TalismanNumber::Regular(100.0);
+        let p1 = lobby.get_player_mut("p1").unwrap();
+        p1.game_state.score = TalismanNumber::Regular(200.0);
+        p1.game_state.hands_left = 0;
+        lobby.evaluate_online_round(&broadcaster);
+
+        assert_eq!(lobby.players()["p2"].game_state.lives, 1);
+        assert!(lobby.players()["p2"].lobby_state.in_game);
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::PlayerRevived { player_id, lives: 1 } if player_id == "p2"
+        )));
+    }
+
+    #[test]
+    fn test_dynamic_difficulty_rubber_bands_boss_chips() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::CoopSurvival);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx);
+        lobby.lobby_options.dynamic_difficulty = true;
+        lobby.start_game();
+
+        // Crushing the boss raises the next one
+        lobby.set_boss_chips(TalismanNumber::Regular(100.0), &broadcaster);
+        let p1 = lobby.get_player_mut("p1").unwrap();
+        p1.game_state.score = TalismanNumber::Regular(1000.0);
+        p1.game_state.hands_left = 0;
+        lobby.evaluate_online_round(&broadcaster);
+        assert_eq!(lobby.boss_chip_multiplier, 1.0 + DIFFICULTY_STEP);
+
+        drain(&mut rx);
+        lobby.set_boss_chips(TalismanNumber::Regular(100.0), &broadcaster);
+        assert_eq!(lobby.boss_chips, TalismanNumber::Regular(100.0 * (1.0 + DIFFICULTY_STEP)));
+        assert!(drain(&mut rx)
+            .iter()
+            .any(|m| matches!(m.as_ref(), ServerToClient::BossDifficulty { .. })));
+
+        // A hidden boss keeps its chips back until the blind reveals it
+        lobby.lobby_options.hidden_boss = true;
+        lobby.announce_boss(&broadcaster, "p1", "bl_wall".to_string());
+        lobby.set_boss_chips(TalismanNumber::Regular(100.0), &broadcaster);
+        assert!(!drain(&mut rx)
+            .iter()
+            .any(|m| matches!(m.as_ref(), ServerToClient::BossDifficulty { .. })));
+        lobby.start_online_blind(&broadcaster);
+        assert!(drain(&mut rx)
+            .iter()
+            .any(|m| matches!(m.as_ref(), ServerToClient::BossDifficulty { .. })));
+    }
+
+    #[test]
+    fn test_clash_damage_past_table_end_reuses_last_stage() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Clash);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        lobby.lobby_options.clash_damage_table = vec![1, 3];
+        lobby.lobby_options.starting_lives = 100;
+        lobby.start_game();
+
+        let round = [
+            RoundResult { player_id: "p1".to_string(), won: true },
+            RoundResult { player_id: "p2".to_string(), won: false },
+        ];
+        for _ in 0..4 {
+            lobby.process_round_outcome(&round);
+        }
+        // Stages 0..4 deal 1, 3, 3, 3 plus one for being the first loser
+        assert_eq!(lobby.players()["p2"].game_state.lives, 100 - 14);
+    }
+
+    #[test]
+    fn test_clash_league_reaching_target_ends_game() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Clash);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for id in ["p1", "p2", "p3"] {
+            lobby.add_player(id.to_string(), ClientProfile::default());
+        }
+        broadcaster.add_player("p1".to_string(), tx);
+        lobby.lobby_options.clash_points_target = 8;
+        lobby.lobby_options.starting_lives = 100;
+        lobby.start_game();
+
+        let play_round = |lobby: &mut Lobby, scores: [f64; 3]| {
+            for (id, score) in ["p1", "p2", "p3"].into_iter().zip(scores) {
+                let player = lobby.get_player_mut(id).unwrap();
+                player.game_state.score = TalismanNumber::Regular(score);
+                player.game_state.hands_left = 0;
+            }
+            lobby.evaluate_online_round(&broadcaster);
+        };
+
+        play_round(&mut lobby, [300.0, 200.0, 200.0]);
+        assert_eq!(lobby.players()["p1"].game_state.points, 5);
+        assert_eq!(lobby.players()["p2"].game_state.points, 3);
+        assert!(lobby.started());
+
+        play_round(&mut lobby, [300.0, 200.0, 100.0]);
+        assert!(!lobby.started());
+        assert_eq!(lobby.phase(), LobbyPhase::GameOver);
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::WinGame { reason: OutcomeReason::PointsTarget, standings }
+                if standings[0].player_id == "p1" && standings[0].points == 10
+        )));
+    }
+
+    #[test]
+    fn test_skips_grant_opponent_handicap_once() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let broadcaster = LobbyBroadcaster::new();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        lobby.lobby_options.skip_handicap_chips = 50;
+        lobby.start_game();
+
+        lobby.get_player_mut("p1").unwrap().game_state.skips = 2;
+        lobby.start_online_blind(&broadcaster);
+        assert_eq!(lobby.players()["p1"].game_state.score, TalismanNumber::Regular(0.0));
+        assert_eq!(lobby.players()["p2"].game_state.score, TalismanNumber::Regular(100.0));
+
+        // Skips already paid out don't count again
+        lobby.start_online_blind(&broadcaster);
+        assert_eq!(lobby.players()["p2"].game_state.score, TalismanNumber::Regular(0.0));
+    }
+
+    #[test]
+    fn test_boss_ban_picks_unbanned_boss_before_blind() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx);
+        lobby.lobby_options.boss_ban_phase = true;
+        lobby.start_game();
+
+        lobby.begin_pvp_blind(&broadcaster);
+        let Some(ServerToClient::BossBanStarted { pool, .. }) =
+            drain(&mut rx).first().map(|m| m.as_ref().clone())
+        else {
+            panic!("ban phase should start before the blind");
+        };
+        assert_eq!(pool.len(), BOSS_BAN_POOL_SIZE);
+
+        lobby.ban_boss(&broadcaster, "p1", pool[0].clone());
+        assert!(!contains_response_of_type(
+            &drain(&mut rx),
+            &ServerToClient::StartBlind { server_time: 0 }
+        ));
+
+        lobby.ban_boss(&broadcaster, "p2", pool[1].clone());
+        let messages = drain(&mut rx);
+        let picked = messages.iter().find_map(|m| match m.as_ref() {
+            ServerToClient::SetBossBlind { key } => Some(key.clone()),
+            _ => None,
+        });
+        assert!(picked.is_some_and(|key| pool[2..].contains(&key)));
+        assert!(contains_response_of_type(&messages, &ServerToClient::StartBlind { server_time: 0 }));
+    }
+
+    #[test]
+    fn test_deck_must_use_host_back_without_different_decks() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.lobby_options.back = "Red Deck".to_string();
+        lobby.start_game();
+        // Options changed mid-game don't move the requirement
+        lobby.lobby_options.back = "Blue Deck".to_string();
+
+        assert_eq!(lobby.deck_violation("b_red;H_2;S_K"), None);
+        assert_eq!(
+            lobby.deck_violation("b_blue;H_2;S_K").as_deref(),
+            Some("Playing b_blue but this lobby requires Red Deck")
+        );
+        assert!(lobby.deck_violation("b_red").is_some());
+
+        lobby.lobby_options.different_decks = true;
+        lobby.start_game();
+        assert_eq!(lobby.deck_violation("b_red;H_2;S_K"), None);
+    }
+
+    #[test]
+    fn test_team_messages_reach_only_that_team() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut receivers = HashMap::new();
+        for id in ["p1", "p2", "p3", "p4"] {
+            let (tx, rx) = mpsc::unbounded_channel();
+            lobby.add_player(id.to_string(), ClientProfile::default());
+            broadcaster.add_player(id.to_string(), tx);
+            receivers.insert(id, rx);
+        }
+        lobby.randomize_teams(&mut broadcaster, 2).unwrap();
+        lobby.start_game();
+
+        let team = lobby.team_of("p1").unwrap();
+        let mate = lobby.teammates("p1");
+        assert_eq!(mate.len(), 1);
+        assert_eq!(lobby.teammates(&mate[0]), vec!["p1".to_string()]);
+        assert_eq!(lobby.team_members(team).len(), 2);
+
+        let emote = ClientToServer::SendEmote {
+            emote_id: "gg".to_string(),
+            team_only: true,
+        };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "p1".to_string(), emote);
+        for (id, rx) in receivers.iter_mut() {
+            let on_team = *id == "p1" || *id == mate[0];
+            let got_emote = drain(rx)
+                .iter()
+                .any(|m| matches!(m.as_ref(), ServerToClient::Emote { .. }));
+            assert_eq!(got_emote, on_team, "{id}");
+        }
+    }
+
+    #[test]
+    fn test_shop_spending_adds_up_per_game() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        assert!(lobby.record_shop_spend("p1", 5).is_err());
+
+        lobby.start_game();
+        // Shops within an ante add up, an ante without a shop counts as nothing spent
+        for (player, ante, amount) in [("p1", 1, 12), ("p2", 1, 20), ("p1", 1, 3), ("p1", 3, 15)] {
+            lobby.set_ante(player, ante);
+            lobby.record_shop_spend(player, amount).unwrap();
+        }
+        let spending = lobby.shop_spending();
+        assert_eq!(spending[0].player_id, "p1");
+        assert_eq!((spending[0].total, &spending[0].per_ante[..]), (30, &[15, 0, 15][..]));
+        assert_eq!((spending[1].total, &spending[1].per_ante[..]), (20, &[20][..]));
+
+        lobby.start_game();
+        assert!(lobby.shop_spending().iter().all(|spend| spend.per_ante.is_empty()));
+    }
+
+    #[test]
+    fn test_banned_cards_are_sent_and_flag_the_run() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        let verified = ClientProfile {
+            account_id: Some("acc1".to_string()),
+            account_verified: true,
+            ..ClientProfile::default()
+        };
+        lobby.add_player("p1".to_string(), verified);
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        lobby.add_player("p3".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx1);
+        broadcaster.add_player("p2".to_string(), tx2);
+        lobby.lobby_options.banned_jokers = vec!["j_blueprint".to_string()];
+        lobby.lobby_options.banned_consumables = vec!["c_soul".to_string()];
+        assert!(lobby.lobby_options.validate().is_ok());
+
+        lobby.start_game();
+        lobby.broadcast_game_started(&broadcaster);
+        assert!(drain(&mut rx2).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::BannedCards { jokers, .. } if jokers[0] == "j_blueprint"
+        )));
+
+        let mut send = |jokers: &str| {
+            let action = ClientToServer::SendPlayerJokers { jokers: jokers.to_string() };
+            LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "p1".to_string(), action);
+        };
+        send("j_joker;j_blueprint_x");
+        assert!(drain(&mut rx2)
+            .iter()
+            .any(|m| matches!(m.as_ref(), ServerToClient::ReceivePlayerJokers { .. })));
+        send("j_joker;c_soul");
+        let responses = drain(&mut rx2);
+        assert!(responses.iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::RulesViolation { rule, .. } if rule == "banned_cards"
+        )));
+        assert!(!responses.iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::PatchPlayerJokers { .. } | ServerToClient::ReceivePlayerJokers { .. }
+        )));
+        // The flag is kept per account and carried into the standings
+        assert!(lobby.unverified_accounts().contains(&"acc1".to_string()));
+        let standings = lobby.compute_standings();
+        let p1 = standings.iter().find(|s| s.player_id == "p1").unwrap();
+        assert_eq!((p1.account_id.as_deref(), p1.verified), (Some("acc1"), false));
+        // Only the verified account is reported to the stats service
+        let federated = lobby.federated_result(&standings);
+        assert_eq!(federated.players.len(), 1);
+        assert_eq!(federated.players[0].account_id, "acc1");
+        drain(&mut rx1);
+
+        lobby.lobby_options.banned_jokers = vec!["not a key".to_string()];
+        assert!(lobby.lobby_options.validate().is_err());
+    }
+
+    #[test]
+    fn test_coop_blind_starts_without_everyone_under_the_ready_rule() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+        const START_BLIND: ServerToClient = ServerToClient::StartBlind { server_time: 0 };
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::CoopSurvival);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for id in ["p1", "p2", "p3"] {
+            lobby.add_player(id.to_string(), ClientProfile::default());
+        }
+        broadcaster.add_player("p3".to_string(), tx);
+        lobby.lobby_options.coop_ready_rule = CoopReadyRule::Majority;
+        lobby.start_game();
+        lobby.note_location("loc_selecting");
+        drain(&mut rx);
+
+        let act = |lobby: &mut Lobby, id: &str, action: ClientToServer| {
+            LobbyHandlers::handle_player_action(lobby, &broadcaster, id.to_string(), action);
+        };
+        act(&mut lobby, "p1", ClientToServer::SetReady { is_ready: true });
+        assert!(!contains_response_of_type(&drain(&mut rx), &START_BLIND));
+        act(&mut lobby, "p2", ClientToServer::SetReady { is_ready: true });
+        let responses = drain(&mut rx);
+        assert!(responses.iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::ReadyOverridden { player_ids, rule: CoopReadyRule::Majority }
+                if player_ids == &["p3".to_string()]
+        )));
+        assert!(contains_response_of_type(&responses, &START_BLIND));
+
+        lobby.lobby_options.coop_ready_rule = CoopReadyRule::HostOverride;
+        lobby.set_phase(LobbyPhase::ShopPhase);
+        act(&mut lobby, "p2", ClientToServer::SetReady { is_ready: true });
+        act(&mut lobby, "p3", ClientToServer::ForceStartBlind {});
+        assert!(!contains_response_of_type(&drain(&mut rx), &START_BLIND));
+        act(&mut lobby, "p1", ClientToServer::ForceStartBlind {});
+        let responses = drain(&mut rx);
+        assert!(responses.iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::ReadyOverridden { player_ids, .. }
+                if player_ids == &["p1".to_string(), "p3".to_string()]
+        )));
+        assert!(contains_response_of_type(&responses, &START_BLIND));
+
+        lobby.lobby_options.coop_ready_rule = CoopReadyRule::All;
+        assert_eq!(lobby.blind_start_overrides(true), None);
+    }
+
+    #[test]
+    fn test_started_games_ignore_repeat_starts_and_votes_can_start_one() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for id in ["host", "p2", "p3"] {
+            lobby.add_player(id.to_string(), ClientProfile::default());
+        }
+        broadcaster.add_player("p2".to_string(), tx);
+        lobby.get_player_mut("host").unwrap().lobby_state.is_host = true;
+        let act = |lobby: &mut Lobby, id: &str, action: ClientToServer| {
+            LobbyHandlers::handle_player_action(lobby, &broadcaster, id.to_string(), action);
+        };
+
+        act(&mut lobby, "p2", ClientToServer::VoteStart {});
+        assert!(!lobby.started());
+        lobby.lobby_options.vote_to_start = true;
+        act(&mut lobby, "p2", ClientToServer::VoteStart {});
+        act(&mut lobby, "p2", ClientToServer::VoteStart {});
+        assert!(!lobby.started());
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::StartVotes { player_ids, needed: 2 } if player_ids == &["p2".to_string()]
+        )));
+        act(&mut lobby, "p3", ClientToServer::VoteStart {});
+        assert!(lobby.started());
+        drain(&mut rx);
+
+        lobby.get_player_mut("p2").unwrap().game_state.furthest_blind = 4;
+        let start = ClientToServer::StartGame {
+            seed: String::new(),
+            stake: 1,
+        };
+        act(&mut lobby, "host", start);
+        assert_eq!(lobby.players()["p2"].game_state.furthest_blind, 4);
+        assert!(!contains_response_of_type(
+            &drain(&mut rx),
+            &ServerToClient::GameStarted {
+                seed: String::new(),
+                stake: 0
+            }
+        ));
+    }
+
+    #[test]
+    fn test_stake_comes_from_validated_lobby_options() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        broadcaster.add_player("host".to_string(), tx);
+
+        let mut options = lobby.lobby_options.clone();
+        options.stake = 9;
+        let update = ClientToServer::UpdateLobbyOptions { options: options.clone() };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), update);
+        assert_eq!(lobby.lobby_options.stake, 1);
+
+        options.stake = 4;
+        let update = ClientToServer::UpdateLobbyOptions { options };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), update);
+        drain(&mut rx);
+
+        let start = ClientToServer::StartGame { seed: "random".to_string(), stake: 8 };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), start);
+        assert!(drain(&mut rx)
+            .iter()
+            .any(|m| matches!(m.as_ref(), ServerToClient::GameStarted { stake: 4, .. })));
+    }
+
+    #[test]
+    fn test_different_seeds_are_sent_to_their_player_only() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx1);
+        broadcaster.add_player("p2".to_string(), tx2);
+        lobby.lobby_options.different_seeds = true;
+        lobby.start_game();
+        lobby.broadcast_game_started(&broadcaster);
+
+        let seeds = |rx: &mut mpsc::UnboundedReceiver<Arc<ServerToClient>>| -> Vec<String> {
+            drain(rx)
+                .iter()
+                .filter_map(|m| match m.as_ref() {
+                    ServerToClient::GameStarted { seed, .. } => Some(seed.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let (seeds1, seeds2) = (seeds(&mut rx1), seeds(&mut rx2));
+        assert_eq!(seeds1, vec![lobby.seed_for("p1")]);
+        assert_eq!(seeds2, vec![lobby.seed_for("p2")]);
+        assert_ne!(seeds1, seeds2);
+        assert_eq!(lobby.lobby_options.custom_seed, "random");
+    }
+
+    #[test]
+    fn test_changing_run_options_mid_game_stops_it() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::{ClientToServer, StopReason};
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        broadcaster.add_player("host".to_string(), tx);
+        let options = lobby.lobby_options.clone();
+        lobby.start_game();
+
+        // Still "random" on the host's side, the rolled seed stays
+        let mut timer = options.clone();
+        timer.timer_base_seconds += 30;
+        let update = ClientToServer::UpdateLobbyOptions { options: timer };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), update);
+        assert!(lobby.started());
+        assert_ne!(lobby.lobby_options.custom_seed, "random");
+        drain(&mut rx);
+
+        let mut lives = options;
+        lives.starting_lives += 1;
+        let update = ClientToServer::UpdateLobbyOptions { options: lives };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), update);
+        assert!(!lobby.started());
+        assert_eq!(lobby.lobby_options.custom_seed, "random");
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::GameStopped { reason: StopReason::OptionsChanged }
+        )));
+    }
+
+    #[test]
+    fn test_anonymous_mode_hides_other_usernames_until_game_end() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let broadcaster = LobbyBroadcaster::new();
+        let profile = |name: &str| ClientProfile {
+            username: name.to_string(),
+            account_id: Some(format!("acc-{}", name)),
+            ..ClientProfile::default()
+        };
+        lobby.add_player("p1".to_string(), profile("alice"));
+        lobby.add_player("p2".to_string(), profile("bob"));
+        lobby.lobby_options.anonymous_mode = true;
+
+        let view = lobby.snapshot_for("p1");
+        assert_eq!(view.players()["p1"].profile.username, "alice");
+        assert_eq!(view.players()["p2"].profile.username, "Player 2");
+        assert_eq!(view.players()["p2"].profile.account_id, None);
+
+        lobby.start_game();
+        lobby.forfeit("p1", &broadcaster);
+        assert_eq!(lobby.entry_view("p2", "p1").unwrap().profile.username, "bob");
+    }
+
+    #[test]
+    fn test_snapshots_keep_the_seed_to_players() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        assert_eq!(lobby.snapshot_for("watcher").lobby_options.custom_seed, "random");
+
+        lobby.lobby_options.custom_seed = "ABCD1234".to_string();
+        assert_eq!(lobby.snapshot_for("p1").lobby_options.custom_seed, "ABCD1234");
+        let json = serde_json::to_string(&lobby.snapshot_for("watcher")).unwrap();
+        assert!(json.contains(crate::game_mode::HIDDEN_SEED));
+        assert!(!json.contains("ABCD1234"));
+
+        // Spectators see the scoreboard, not what a run has to spend
+        lobby.get_player_mut("p1").unwrap().game_state.money = 25;
+        assert_eq!(lobby.snapshot_for("p1").players()["p1"].game_state.money, 25);
+        assert_eq!(lobby.snapshot_for("watcher").players()["p1"].game_state.money, 0);
+    }
+
+    #[test]
+    fn test_hidden_boss_is_revealed_when_the_blind_starts() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("host".to_string(), host_tx);
+        broadcaster.add_player("p2".to_string(), tx);
+        lobby.lobby_options.hidden_boss = true;
+        lobby.start_game();
+        drain(&mut rx);
+
+        let set_boss = ClientToServer::SetBossBlind {
+            key: "bl_wall".to_string(),
+            chips: TalismanNumber::Regular(600.0),
+        };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), set_boss);
+        assert!(drain(&mut rx).is_empty());
+        assert_eq!(lobby.snapshot_for("p2").boss_chips, TalismanNumber::Regular(0.0));
+        assert_eq!(lobby.snapshot_for("host").boss_chips, TalismanNumber::Regular(600.0));
+
+        lobby.start_online_blind(&broadcaster);
+        let responses = drain(&mut rx);
+        assert!(matches!(
+            responses[0].as_ref(),
+            ServerToClient::SetBossBlind { key } if key == "bl_wall"
+        ));
+        assert!(contains_response_of_type(&responses, &ServerToClient::StartBlind { server_time: 0 }));
+        assert!(!drain(&mut host_rx)
+            .iter()
+            .any(|m| matches!(m.as_ref(), ServerToClient::SetBossBlind { .. })));
+        assert_eq!(lobby.snapshot_for("p2").boss_chips, TalismanNumber::Regular(600.0));
+    }
+
+    #[test]
+    fn test_last_player_standing_gets_awards_when_the_rest_leave() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p1".to_string(), tx);
+        lobby.start_game();
+        lobby.get_player_mut("p1").unwrap().game_state.discards_used = 2;
+        drain(&mut rx);
+
+        let leave = ClientToServer::ReturnToLobby {};
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "p2".to_string(), leave);
+        assert_eq!(lobby.phase(), LobbyPhase::GameOver);
+        let responses = drain(&mut rx);
+        assert!(responses.iter().any(|m| matches!(m.as_ref(), ServerToClient::WinGame { .. })));
+        assert!(responses.iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::Awards { awards } if !awards.is_empty()
+        )));
+    }
+
+    #[test]
+    fn test_boss_chips_are_checked_against_the_server_ante() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let broadcaster = LobbyBroadcaster::new();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        lobby.start_game();
+        lobby.set_ante("p2", 4);
+        // The host claiming an earlier ante doesn't make ante 1 chips acceptable
+        lobby.set_ante("host", 1);
+        assert_eq!(lobby.boss_ante(), 4);
+
+        let set_boss = ClientToServer::SetBossBlind {
+            key: "bl_wall".to_string(),
+            chips: TalismanNumber::Regular(600.0),
+        };
+        LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, "host".to_string(), set_boss);
+        assert_eq!(lobby.boss_chips, TalismanNumber::Regular(0.0));
+    }
+
+    #[test]
+    fn test_handicaps_add_lives_and_scale_scores() {
+        use crate::lobby::handlers::LobbyHandlers;
+        use crate::messages::ClientToServer;
+
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let broadcaster = LobbyBroadcaster::new();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        let handicap = Handicap {
+            extra_lives: 1,
+            score_multiplier: 2.0,
+        };
+        let set = |lobby: &mut Lobby, by: &str, handicap: Handicap| {
+            let action = ClientToServer::SetHandicap {
+                player_id: "p2".to_string(),
+                handicap,
+            };
+            LobbyHandlers::handle_player_action(lobby, &broadcaster, by.to_string(), action);
+        };
+        set(&mut lobby, "p2", handicap);
+        set(&mut lobby, "host", Handicap { extra_lives: 9, ..handicap });
+        assert_eq!(lobby.players()["p2"].lobby_state.handicap, Handicap::default());
+        set(&mut lobby, "host", handicap);
+
+        lobby.start_game();
+        let starting_lives = lobby.lobby_options.starting_lives;
+        assert_eq!(lobby.players()["p2"].game_state.lives, starting_lives + 1);
+        assert_eq!(lobby.players()["host"].game_state.lives, starting_lives);
+        assert!(lobby.set_handicap("p2", Handicap::default()).is_err());
+
+        lobby.get_player_mut("host").unwrap().game_state.score = TalismanNumber::Regular(1000.0);
+        lobby.get_player_mut("p2").unwrap().game_state.score = TalismanNumber::Regular(600.0);
+        let winners: Vec<_> = lobby
+            .determine_round_outcome()
+            .into_iter()
+            .filter(|r| r.won)
+            .map(|r| r.player_id)
+            .collect();
+        assert_eq!(winners, ["p2"]);
+    }
+
+    #[test]
+    fn test_reclaimed_seat_moves_votes_and_drops_stale_bases() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Survival);
+        for id in ["p1", "p2", "p3"] {
+            lobby.add_player(id.to_string(), ClientProfile::default());
+        }
+        lobby.vote_to_start("p1");
+        lobby.note_preview_base("p1", "p2", PreviewKind::Jokers);
+        lobby.note_preview_base("p2", "p1", PreviewKind::Jokers);
+        lobby.note_preview_base("p3", "p2", PreviewKind::Deck);
+
+        assert!(lobby.reclaim_seat("p1", "p1-again".to_string(), ClientProfile::default()));
+        assert_eq!(lobby.start_votes(), vec!["p1-again".to_string()]);
+        assert_eq!(lobby.preview_bases.len(), 1);
+
+        // A bot taking the seat over doesn't inherit the vote
+        let bot = ClientProfile {
+            is_bot: true,
+            ..ClientProfile::default()
+        };
+        assert!(lobby.reclaim_seat("p1-again", "bot-1".to_string(), bot));
+        assert!(lobby.start_votes().is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint_restores_game_state_on_rejoin() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let profile = |account: &str, verified: bool| ClientProfile {
+            account_id: Some(account.to_string()),
+            account_verified: verified,
+            ..ClientProfile::default()
+        };
+        lobby.add_player("p1".to_string(), profile("acc1", true));
+        // Declared but unverified accounts aren't checkpointed
+        lobby.add_player("p2".to_string(), profile("acc2", false));
+        lobby.start_game();
+        lobby.get_player_mut("p1").unwrap().game_state.lives = 1;
+        assert!(lobby.set_phase(LobbyPhase::PvpBlind));
+
+        let checkpoint = lobby.checkpoint();
+        assert_eq!(checkpoint.players.len(), 1);
+
+        let mut restored = Lobby::restore(checkpoint);
+        assert!(restored.started());
+        assert_eq!(restored.phase(), LobbyPhase::PvpBlind);
+        assert_eq!(restored.reserved_account_ids(), vec!["acc1".to_string()]);
+
+        // Naming the account without its token doesn't hand over the run
+        restored.add_player("impostor".to_string(), profile("acc1", false));
+        assert!(!restored.claim_restored_state("impostor"));
+        restored.add_player("new-id".to_string(), profile("acc1", true));
+        assert!(restored.claim_restored_state("new-id"));
+        let player = &restored.players()["new-id"];
+        assert_eq!(player.game_state.lives, 1);
+        assert!(player.lobby_state.in_game);
+    }
+
+    #[test]
+    fn test_ready_timeout_kicks_laggards_after_countdown() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        lobby.add_player("guest".to_string(), ClientProfile::default());
+        lobby.add_player("eager".to_string(), ClientProfile::default());
+        broadcaster.add_player("guest".to_string(), tx);
+        lobby.lobby_options.ready_timeout_seconds = 5;
+        lobby.lobby_options.ready_timeout_action = ReadyTimeoutAction::Kick;
+        lobby.reset_ready_states_to_host_only();
+
+        // Only the host being ready doesn't start the clock
+        let now = Instant::now();
+        assert!(lobby.check_ready_timeout(&broadcaster, now).is_empty());
+        assert!(lobby.check_ready_timeout(&broadcaster, now + Duration::from_secs(5)).is_empty());
+        assert!(drain(&mut rx).is_empty());
+
+        lobby.set_player_ready("eager", true);
+        assert!(lobby.check_ready_timeout(&broadcaster, now).is_empty());
+        let countdown = ServerToClient::ReadyCountdown {
+            player_ids: vec![],
+            seconds_left: 0,
+        };
+        assert!(contains_response_of_type(&drain(&mut rx), &countdown));
+
+        let kicked = lobby.check_ready_timeout(&broadcaster, now + Duration::from_secs(5));
+        assert_eq!(kicked, vec!["guest".to_string()]);
+    }
+
+    #[test]
+    fn test_blitz_fails_players_still_in_the_blind() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        lobby.add_player("p1".to_string(), ClientProfile::default());
+        lobby.add_player("p2".to_string(), ClientProfile::default());
+        broadcaster.add_player("p2".to_string(), tx);
+        lobby.lobby_options.blitz_blind_seconds = 60;
+        lobby.lobby_options.blitz_increment_seconds = 15;
+        lobby.start_game();
+        let lives = lobby.players()["p2"].game_state.lives;
+
+        lobby.start_online_blind(&broadcaster);
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::BlitzTimer { seconds: 60, .. }
+        )));
+        let p1 = lobby.get_player_mut("p1").unwrap();
+        p1.game_state.score = TalismanNumber::Regular(100.0);
+        p1.game_state.hands_left = 0;
+        lobby.get_player_mut("p2").unwrap().game_state.score = TalismanNumber::Regular(500.0);
+
+        let now = Instant::now();
+        lobby.expire_blitz_blind(&broadcaster, now);
+        assert_eq!(lobby.phase(), LobbyPhase::PvpBlind);
+        lobby.expire_blitz_blind(&broadcaster, now + Duration::from_secs(61));
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::BlitzExpired { player_ids } if player_ids == &["p2".to_string()]
+        )));
+        // Whatever p2 had scored, running out of time lost them the blind
+        assert_eq!(lobby.phase(), LobbyPhase::ShopPhase);
+        assert_eq!(lobby.players()["p2"].game_state.lives, lives - 1);
+
+        lobby.start_online_blind(&broadcaster);
+        assert!(drain(&mut rx).iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::BlitzTimer { seconds: 75, .. }
+        )));
     }
 }
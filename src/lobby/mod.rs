@@ -1,9 +1,29 @@
+pub mod announcements;
+pub mod awards;
+pub mod blind_curve;
+pub mod boss_ban;
+pub mod boss_rotation;
+pub mod bot;
 pub mod broadcaster;
+pub mod bug_report;
+pub mod checkpoint;
+pub mod decks;
+pub mod emotes;
+pub mod event_log;
 pub mod game_state;
+pub mod hand_breakdown;
 pub mod handlers;
 pub mod lobby;
+pub mod options_history;
+pub mod phase;
+pub mod preview;
+pub mod run_integrity;
+pub mod shared_rng;
+pub mod stats;
 pub mod task;
+pub mod tutorial;
 
 // Re-export the main types for easy access
+pub use bot::BotDifficulty;
 pub use game_state::{ClientGameState, ClientLobbyEntry};
-pub use task::lobby_task;
\ No newline at end of file
+pub use task::{lobby_task, pooled_lobby_task, restored_lobby_task};
\ No newline at end of file
@@ -0,0 +1,83 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{game_mode::LobbyOptions, utils::now_millis};
+
+/// How many option changes a lobby remembers
+const MAX_OPTIONS_HISTORY: usize = 50;
+
+/// Changed option fields, keyed by their wire name
+pub type OptionsDiff = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionsChange {
+    pub changed_by: String,
+    pub timestamp: u64,
+    pub diff: OptionsDiff,
+    #[serde(skip)]
+    pub previous: LobbyOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OptionsHistory {
+    changes: VecDeque<OptionsChange>,
+}
+
+impl OptionsHistory {
+    pub fn push(&mut self, changed_by: &str, previous: LobbyOptions, diff: OptionsDiff) {
+        if self.changes.len() == MAX_OPTIONS_HISTORY {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(OptionsChange {
+            changed_by: changed_by.to_string(),
+            timestamp: now_millis(),
+            diff,
+            previous,
+        });
+    }
+
+    pub fn pop(&mut self) -> Option<OptionsChange> {
+        self.changes.pop_back()
+    }
+
+    pub fn changes(&self) -> impl Iterator<Item = &OptionsChange> {
+        self.changes.iter()
+    }
+}
+
+/// Options a running game was started with: changing one leaves players in a run
+/// that no longer matches the lobby
+const RUN_DEFINING_OPTIONS: [&str; 3] = ["custom_seed", "gamemode", "starting_lives"];
+
+pub fn invalidates_run(diff: &OptionsDiff) -> bool {
+    RUN_DEFINING_OPTIONS.iter().any(|key| diff.contains_key(*key))
+}
+
+/// Field-level diff between two option sets, containing the new values
+pub fn diff_options(old: &LobbyOptions, new: &LobbyOptions) -> OptionsDiff {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return OptionsDiff::new();
+    };
+    new.into_iter()
+        .filter(|(key, value)| old.get(key) != Some(value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_mode::GameMode;
+
+    #[test]
+    fn test_diff_options_only_reports_changed_fields() {
+        let old = GameMode::Attrition.get_default_options();
+        let mut new = old.clone();
+        new.starting_lives = 7;
+        let diff = diff_options(&old, &new);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff.get("starting_lives"), Some(&Value::from(7)));
+    }
+}
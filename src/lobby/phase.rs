@@ -0,0 +1,95 @@
+//! Where a lobby is in its game, shared with clients so both sides agree on
+//! which actions make sense at any moment.
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::ClientToServer;
+
+/// Location clients report while browsing the shop
+pub const SHOP_LOCATION: &str = "loc_shop";
+/// Location clients report before their run has loaded
+pub const LOBBY_LOCATION: &str = "loc_waiting_in_lobby";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LobbyPhase {
+    /// No game, players are gathering and changing options
+    #[default]
+    #[serde(rename = "waiting")]
+    Waiting,
+    /// The game started and clients are loading into their runs
+    #[serde(rename = "starting")]
+    Starting,
+    /// Players are working through their own blinds
+    #[serde(rename = "inRound")]
+    InRound,
+    /// A PvP blind just ended and players are shopping
+    #[serde(rename = "shop")]
+    ShopPhase,
+    /// Everyone is playing the shared PvP blind
+    #[serde(rename = "pvpBlind")]
+    PvpBlind,
+    /// Results are out, players head back to the lobby
+    #[serde(rename = "gameOver")]
+    GameOver,
+}
+
+impl LobbyPhase {
+    /// A game is running and players are still in it
+    pub fn is_started(self) -> bool {
+        matches!(
+            self,
+            Self::Starting | Self::InRound | Self::ShopPhase | Self::PvpBlind
+        )
+    }
+
+    pub fn can_transition_to(self, next: LobbyPhase) -> bool {
+        use LobbyPhase::*;
+        match next {
+            // Stopping works from anywhere
+            Waiting => true,
+            Starting => !self.is_started(),
+            InRound => matches!(self, Starting | ShopPhase),
+            // Some modes open with a PvP blind straight away
+            PvpBlind => matches!(self, Starting | InRound | ShopPhase),
+            ShopPhase => self == PvpBlind,
+            GameOver => self.is_started(),
+        }
+    }
+
+    /// Whether a client action makes sense in this phase; everything not listed always does
+    pub fn allows(self, action: &ClientToServer) -> bool {
+        match action {
+            ClientToServer::StartGame { .. } => !self.is_started(),
+            ClientToServer::StopGame {} => self != Self::Waiting,
+            ClientToServer::PlayHand { .. } | ClientToServer::RoundComplete {} => {
+                self == Self::PvpBlind
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases_follow_the_game() {
+        use LobbyPhase::*;
+        let game = [Waiting, Starting, InRound, PvpBlind, ShopPhase, PvpBlind, GameOver, Starting];
+        for pair in game.windows(2) {
+            assert!(pair[0].can_transition_to(pair[1]), "{:?} -> {:?}", pair[0], pair[1]);
+        }
+        assert!(!Waiting.can_transition_to(PvpBlind));
+        assert!(!InRound.can_transition_to(ShopPhase));
+        assert!(!GameOver.can_transition_to(InRound));
+
+        let hand = ClientToServer::PlayHand {
+            score: crate::talisman_number::TalismanNumber::Regular(1.0),
+            hands_left: 1,
+            breakdown: None,
+        };
+        assert!(PvpBlind.allows(&hand));
+        assert!(!InRound.allows(&hand));
+    }
+}
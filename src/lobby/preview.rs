@@ -0,0 +1,136 @@
+//! Opponent joker and deck previews. Clients resend the whole payload whenever
+//! anything changes, mostly while shopping, so the lobby drops repeats and can
+//! send clients that ask for it just the part that changed.
+
+use serde::Serialize;
+
+use crate::lobby::game_state::ClientLobbyState;
+use crate::messages::{EventClass, ServerToClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreviewKind {
+    Jokers,
+    Deck,
+}
+
+impl PreviewKind {
+    /// The payload last relayed for this player
+    pub fn last_sent(self, state: &mut ClientLobbyState) -> &mut Option<String> {
+        match self {
+            Self::Jokers => &mut state.last_jokers,
+            Self::Deck => &mut state.last_deck,
+        }
+    }
+
+    /// What clients opt out of to stop getting these
+    pub fn event_class(self) -> EventClass {
+        match self {
+            Self::Jokers => EventClass::JokerPreviews,
+            Self::Deck => EventClass::DeckPreviews,
+        }
+    }
+
+    /// Decks have always gone back to their sender too, jokers only to the others
+    pub fn echoes_sender(self) -> bool {
+        self == Self::Deck
+    }
+
+    pub fn full(self, player_id: String, payload: String) -> ServerToClient {
+        match self {
+            Self::Jokers => ServerToClient::ReceivePlayerJokers {
+                player_id,
+                jokers: payload,
+            },
+            Self::Deck => ServerToClient::ReceivePlayerDeck {
+                player_id,
+                deck: payload,
+            },
+        }
+    }
+
+    pub fn patched(self, player_id: String, patch: PreviewPatch) -> ServerToClient {
+        match self {
+            Self::Jokers => ServerToClient::PatchPlayerJokers { player_id, patch },
+            Self::Deck => ServerToClient::PatchPlayerDeck { player_id, patch },
+        }
+    }
+}
+
+/// Turns `previous` into the new payload: keep `prefix` bytes from its start and
+/// `suffix` bytes from its end, with `insert` in between
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreviewPatch {
+    pub prefix: usize,
+    pub suffix: usize,
+    pub insert: String,
+}
+
+impl PreviewPatch {
+    pub fn between(previous: &str, next: &str) -> Self {
+        let mut prefix = previous
+            .bytes()
+            .zip(next.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !next.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+        let max_suffix = previous.len().min(next.len()) - prefix;
+        let mut suffix = previous
+            .bytes()
+            .rev()
+            .zip(next.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !next.is_char_boundary(next.len() - suffix) {
+            suffix -= 1;
+        }
+        Self {
+            prefix,
+            suffix,
+            insert: next[prefix..next.len() - suffix].to_string(),
+        }
+    }
+
+    /// Only worth sending when it is well under the full payload, otherwise send `next` whole
+    pub fn is_worth_sending(&self, next: &str) -> bool {
+        self.insert.len() < next.len() / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What clients do with a patch
+    fn apply(patch: &PreviewPatch, previous: &str) -> Option<String> {
+        if patch.prefix + patch.suffix > previous.len() {
+            return None;
+        }
+        let head = previous.get(..patch.prefix)?;
+        let tail = previous.get(previous.len() - patch.suffix..)?;
+        Some(format!("{}{}{}", head, patch.insert, tail))
+    }
+
+    #[test]
+    fn test_patch_rebuilds_next_payload() {
+        let cases = [
+            ("j_joker;j_droll;j_blueprint", "j_joker;j_blueprint"),
+            ("j_joker", "j_joker;j_brainstorm"),
+            ("aaaa", "aa"),
+            ("", "j_joker"),
+            ("j_jökér", "j_jokér"),
+        ];
+        for (previous, next) in cases {
+            let patch = PreviewPatch::between(previous, next);
+            assert_eq!(apply(&patch, previous).as_deref(), Some(next), "{previous} -> {next}");
+        }
+
+        let previous = "j_joker;j_droll;j_blueprint;j_brainstorm";
+        let next = "j_joker;j_droll;j_baron;j_blueprint;j_brainstorm";
+        let patch = PreviewPatch::between(previous, next);
+        assert_eq!(patch.insert, "aron;j_b");
+        assert!(patch.is_worth_sending(next));
+    }
+}
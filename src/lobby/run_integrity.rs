@@ -0,0 +1,89 @@
+//! Run checksums clients send while they play, a hash of their deck, jokers and
+//! money. The server can't recompute them; it checks that one arrives for every ante
+//! a player reaches and that they change as the run does, and marks results from
+//! runs that don't as unverified.
+
+use std::collections::HashMap;
+
+const MAX_HASH_LEN: usize = 64;
+/// Checksums kept per player and game, far more than a run sends
+const MAX_CHECKSUMS_PER_RUN: usize = 512;
+
+#[derive(Debug, Clone, Default)]
+pub struct RunChecksums {
+    /// (ante, hash) per player, in the order they came in
+    submitted: HashMap<String, Vec<(u32, String)>>,
+}
+
+impl RunChecksums {
+    pub fn clear(&mut self) {
+        self.submitted.clear();
+    }
+
+    pub fn record(&mut self, player_id: &str, ante: u32, hash: String) -> Result<(), &'static str> {
+        if hash.is_empty()
+            || hash.len() > MAX_HASH_LEN
+            || !hash.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            return Err("Run checksum must be 1 to 64 letters or digits");
+        }
+        let checksums = self.submitted.entry(player_id.to_string()).or_default();
+        if checksums.len() >= MAX_CHECKSUMS_PER_RUN {
+            return Err("Too many run checksums");
+        }
+        checksums.push((ante, hash));
+        Ok(())
+    }
+
+    /// A reconnecting player keeps the checksums sent under their old id
+    pub fn rename(&mut self, old_id: &str, new_id: &str) {
+        if let Some(checksums) = self.submitted.remove(old_id) {
+            self.submitted.insert(new_id.to_string(), checksums);
+        }
+    }
+
+    /// Why a run that got to `reached_ante` can't be vouched for, `None` when it can
+    pub fn problem(&self, player_id: &str, reached_ante: u32) -> Option<&'static str> {
+        let checksums = self.submitted.get(player_id).map(Vec::as_slice).unwrap_or_default();
+        if (1..=reached_ante).any(|ante| !checksums.iter().any(|(a, _)| *a == ante)) {
+            return Some("missing checksums");
+        }
+        if checksums.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+            return Some("checksums went back an ante");
+        }
+        // A run that changed antes changed its deck, jokers or money along the way
+        let repeated = checksums.iter().enumerate().any(|(i, (ante, hash))| {
+            checksums[..i].iter().any(|(a, h)| a != ante && h == hash)
+        });
+        if repeated {
+            return Some("same checksum in different antes");
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_need_changing_checksums_for_every_ante() {
+        let mut checksums = RunChecksums::default();
+        for (ante, hash) in [(1, "a1"), (1, "a2"), (2, "b1"), (3, "c1")] {
+            checksums.record("p1", ante, hash.to_string()).unwrap();
+        }
+        assert_eq!(checksums.problem("p1", 3), None);
+        assert_eq!(checksums.problem("p1", 4), Some("missing checksums"));
+        assert_eq!(checksums.problem("p2", 1), Some("missing checksums"));
+        assert_eq!(checksums.problem("p2", 0), None);
+
+        checksums.record("p1", 4, "a1".to_string()).unwrap();
+        assert_eq!(checksums.problem("p1", 4), Some("same checksum in different antes"));
+        checksums.rename("p1", "p3");
+        checksums.record("p3", 2, "d1".to_string()).unwrap();
+        assert_eq!(checksums.problem("p3", 4), Some("checksums went back an ante"));
+
+        assert!(checksums.record("p1", 1, "not hex!".to_string()).is_err());
+        assert!(checksums.record("p1", 1, String::new()).is_err());
+    }
+}
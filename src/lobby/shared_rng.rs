@@ -0,0 +1,92 @@
+//! Server-side randomness for events every player must see the same way, such
+//! as random bosses, draft offers and team shuffles. Each roll is made once per
+//! game under a key and handed to everyone, instead of each client rolling its own.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Longest key a client may roll under
+pub const MAX_ROLL_KEY: usize = 64;
+/// Prefix of keys picking the boss blind, e.g. `boss:3` for ante 3
+pub const BOSS_ROLL_PREFIX: &str = "boss:";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedRng {
+    seed: u64,
+    /// Rolls made this game; later requests for a key get the same value
+    rolls: HashMap<String, u32>,
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+}
+
+impl SharedRng {
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rolls: HashMap::new(),
+        }
+    }
+
+    /// Fresh seed and no rolls, for a new game
+    pub fn reseed(&mut self) {
+        *self = Self::default();
+    }
+
+    /// A generator for `key`, derived from the seed so the same key always gets the same stream
+    fn stream(&self, key: &str) -> StdRng {
+        // FNV-1a, stable across builds unlike the std hasher, so checkpoints restore the same rolls
+        let key_hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        StdRng::seed_from_u64(self.seed ^ key_hash)
+    }
+
+    /// Value in `0..sides` for `key`, and whether this call made the roll.
+    /// The first request for a key fixes its value, whatever `sides` later requests ask for.
+    pub fn roll(&mut self, key: &str, sides: u32) -> (u32, bool) {
+        if let Some(&value) = self.rolls.get(key) {
+            return (value, false);
+        }
+        let value = self.stream(key).random_range(0..sides.max(1));
+        self.rolls.insert(key.to_string(), value);
+        (value, true)
+    }
+
+    pub fn shuffle<T>(&self, key: &str, items: &mut [T]) {
+        items.shuffle(&mut self.stream(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolls_are_made_once_per_key() {
+        let mut rng = SharedRng::with_seed(42);
+        let (boss, first) = rng.roll("boss:1", 20);
+        assert!(first);
+        assert!(boss < 20);
+        assert_eq!(rng.roll("boss:1", 5), (boss, false));
+
+        // Same seed, same rolls, e.g. after restoring a checkpoint
+        let mut restored = SharedRng::with_seed(42);
+        assert_eq!(restored.roll("boss:1", 20).0, boss);
+
+        let mut teams = vec!["a", "b", "c", "d", "e"];
+        let mut restored_teams = teams.clone();
+        rng.shuffle("teams", &mut teams);
+        restored.shuffle("teams", &mut restored_teams);
+        assert_eq!(teams, restored_teams);
+
+        rng.reseed();
+        assert!(rng.roll("boss:1", 20).1);
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+/// Running totals behind the periodic `LobbyStats` broadcast
+#[derive(Debug, Clone, Default)]
+pub struct MatchStats {
+    rounds_played: u32,
+    total_round_time: Duration,
+    round_started_at: Option<Instant>,
+    game_started_at: Option<Instant>,
+    last_broadcast: Option<Instant>,
+}
+
+impl MatchStats {
+    pub fn game_started(&mut self, now: Instant) {
+        self.game_started_at = Some(now);
+    }
+
+    pub fn game_length(&self, now: Instant) -> Duration {
+        self.game_started_at
+            .map_or(Duration::ZERO, |started_at| now.duration_since(started_at))
+    }
+
+    pub fn round_started(&mut self, now: Instant) {
+        self.round_started_at = Some(now);
+    }
+
+    pub fn round_finished(&mut self, now: Instant) {
+        if let Some(started_at) = self.round_started_at.take() {
+            self.rounds_played += 1;
+            self.total_round_time += now.duration_since(started_at);
+        }
+    }
+
+    pub fn rounds_played(&self) -> u32 {
+        self.rounds_played
+    }
+
+    pub fn average_round_secs(&self) -> f64 {
+        if self.rounds_played == 0 {
+            return 0.0;
+        }
+        self.total_round_time.as_secs_f64() / self.rounds_played as f64
+    }
+
+    /// True at most once per `interval`, recording the broadcast time
+    pub fn broadcast_due(&mut self, now: Instant, interval: Duration) -> bool {
+        let due = self
+            .last_broadcast
+            .is_none_or(|last| now.duration_since(last) >= interval);
+        if due {
+            self.last_broadcast = Some(now);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_counts_only_finished_rounds() {
+        let mut stats = MatchStats::default();
+        let start = Instant::now();
+        stats.round_started(start);
+        stats.round_finished(start + Duration::from_secs(30));
+        stats.round_started(start + Duration::from_secs(40));
+        stats.round_finished(start + Duration::from_secs(90));
+        // A finish without a start is ignored
+        stats.round_finished(start + Duration::from_secs(100));
+
+        assert_eq!(stats.rounds_played(), 2);
+        assert_eq!(stats.average_round_secs(), 40.0);
+    }
+}
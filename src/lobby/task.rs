@@ -1,52 +1,328 @@
 use std::sync::Arc;
 
-use super::{broadcaster::LobbyBroadcaster, handlers::LobbyHandlers, lobby::Lobby};
+use super::{
+    bot::{Bot, BotDifficulty, run_bot},
+    tutorial::TUTORIAL_BOT,
+    broadcaster::LobbyBroadcaster, checkpoint::LobbyCheckpoint, game_state::ClientLobbyEntry,
+    handlers::LobbyHandlers, lobby::Lobby,
+};
 use crate::{
+    audit::{self, AuditEvent},
     client::ClientProfile,
-    game_mode::GameMode,
-    messages::{CoordinatorMessage, LobbyMessage, ServerToClient},
+    config::CONFIG,
+    game_mode::{DisconnectPolicy, GameMode},
+    invites::Invite,
+    messages::{
+        ClientToServer, CoordinatorMessage, LobbyChannel, LobbyListing, LobbyMessage,
+        LobbyReceiver, MergeOffer, MergingPlayer, ServerToClient, StopReason, lobby_channel,
+    },
+    moderation::PlayerReport,
+    utils::now_millis,
+    webhooks::{self, WebhookPayload},
 };
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, info};
+use uuid::Uuid;
+
+/// How often the lobby task runs its timer housekeeping
+const LOBBY_TICK_INTERVAL: Duration = Duration::from_millis(500);
+/// How well a bot standing in for a dropped player plays
+const REPLACEMENT_BOT_DIFFICULTY: BotDifficulty = BotDifficulty::Medium;
+/// Bursts of game state and ready updates within this window go out as one
+const BROADCAST_COALESCE_WINDOW: Duration = Duration::from_millis(30);
+/// Most clients watching one lobby at a time
+const MAX_SPECTATORS: usize = 16;
+/// How long a merging lobby waits for the other to hold room for its players
+const MERGE_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub async fn lobby_task(
     lobby_code: String,
-    mut rx: mpsc::UnboundedReceiver<LobbyMessage>,
+    rx: LobbyReceiver,
     ruleset: String,
     game_mode: GameMode,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
 ) {
-    let mut lobby = Lobby::new(lobby_code.clone(), ruleset.clone(), game_mode);
-    let mut broadcaster = LobbyBroadcaster::new();
-    let mut host_id = String::new();
-
+    let lobby = Lobby::new(lobby_code.clone(), ruleset.clone(), game_mode);
     info!(
         "Lobby {} started (ruleset: {}, mode: {})",
         lobby_code, ruleset, game_mode
     );
+    run_lobby(lobby, rx, coordinator_tx, false).await;
+}
+
+/// A lobby task started ahead of demand: it waits in the coordinator's pool until it is
+/// handed a lobby, and offers itself again once that lobby closes. It ends when the
+/// coordinator turns it away because the pool is full.
+pub async fn pooled_lobby_task(coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>) {
+    loop {
+        // Every lobby gets a fresh channel so stale senders from the last one go nowhere
+        let (lobby_tx, lobby_rx) = lobby_channel();
+        let (assign_tx, assign_rx) = oneshot::channel();
+        if coordinator_tx
+            .send(CoordinatorMessage::LobbyPooled { lobby_tx, assign_tx })
+            .is_err()
+        {
+            return;
+        }
+        let Ok(assignment) = assign_rx.await else {
+            return;
+        };
+        lobby_task(
+            assignment.lobby_code,
+            lobby_rx,
+            assignment.ruleset,
+            assignment.game_mode,
+            coordinator_tx.clone(),
+        )
+        .await;
+    }
+}
+
+/// Run a lobby rebuilt from a checkpoint; it closes itself if nobody rejoins in time
+pub async fn restored_lobby_task(
+    checkpoint: LobbyCheckpoint,
+    rx: LobbyReceiver,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+) {
+    let lobby = Lobby::restore(checkpoint);
+    info!(
+        "Lobby {} restored from checkpoint (started: {})",
+        lobby.code, lobby.started()
+    );
+    run_lobby(lobby, rx, coordinator_tx, true).await;
+}
+
+async fn run_lobby(
+    mut lobby: Lobby,
+    mut rx: LobbyReceiver,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+    restored: bool,
+) {
+    let lobby_code = lobby.code.clone();
+    let mut broadcaster =
+        LobbyBroadcaster::with_coalescing(BROADCAST_COALESCE_WINDOW).for_lobby(lobby_code.clone());
+    let mut host_id = String::new();
+
+    let mut tick = tokio::time::interval(LOBBY_TICK_INTERVAL);
+    // The period is fixed per lobby, a reload can still switch checkpointing off
+    let checkpoint_period = Duration::from_secs(CONFIG.get().checkpoint_interval_secs.max(1));
+    let mut checkpoint_tick =
+        tokio::time::interval_at(tokio::time::Instant::now() + checkpoint_period, checkpoint_period);
+    let mut checkpointed = false;
+    // Redirect and coordinator handle once the server is draining
+    let mut draining: Option<(ServerToClient, mpsc::UnboundedSender<CoordinatorMessage>)> = None;
+    // Actions from this lobby's bots, which play through the same handlers as clients
+    let (bot_tx, mut bot_rx) = mpsc::unbounded_channel::<LobbyMessage>();
+    let mut reported_started = false;
+    let mut reported_merge: Option<MergeOffer> = None;
+    let mut reported_listing: Option<LobbyListing> = None;
+
+    loop {
+        lobby.broadcast_phase_if_changed(&broadcaster);
+        // Keep the coordinator's presence view in step with games starting and ending
+        if lobby.started() != reported_started {
+            reported_started = lobby.started();
+            let _ = coordinator_tx.send(CoordinatorMessage::LobbyGameState {
+                lobby_code: lobby_code.clone(),
+                started: reported_started,
+            });
+        }
+        let merge_offer = lobby.merge_offer();
+        if merge_offer != reported_merge {
+            let _ = coordinator_tx.send(match merge_offer.clone() {
+                Some(offer) => CoordinatorMessage::OfferMerge { offer },
+                None => CoordinatorMessage::WithdrawMerge {
+                    lobby_code: lobby_code.clone(),
+                },
+            });
+            reported_merge = merge_offer;
+        }
+        let listing = lobby.public_listing();
+        if listing != reported_listing {
+            let _ = coordinator_tx.send(match listing.clone() {
+                Some(listing) => CoordinatorMessage::ListLobby { listing },
+                None => CoordinatorMessage::UnlistLobby {
+                    lobby_code: lobby_code.clone(),
+                },
+            });
+            reported_listing = listing;
+        }
+        let flush_at = broadcaster.flush_deadline();
+        let msg = tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = tokio::time::sleep_until(flush_at.unwrap_or_else(Instant::now).into()),
+                if flush_at.is_some() =>
+            {
+                broadcaster.flush();
+                continue;
+            }
+            Some(msg) = bot_rx.recv() => msg,
+            _ = checkpoint_tick.tick(), if CONFIG.get().checkpoint_interval_secs > 0 => {
+                if lobby.started() {
+                    lobby.checkpoint().persist();
+                    checkpointed = true;
+                } else if checkpointed {
+                    LobbyCheckpoint::remove(&lobby_code);
+                    checkpointed = false;
+                }
+                continue;
+            }
+            _ = tick.tick() => {
+                let mut shutdown = false;
+                for client_id in lobby.handle_tick(&broadcaster) {
+                    broadcaster.send_to(
+                        &client_id,
+                        ServerToClient::Kicked {
+                            reason: "Did not ready up in time".to_string(),
+                        },
+                    );
+                    shutdown |= handle_client_leave(
+                        &mut lobby,
+                        &mut broadcaster,
+                        client_id,
+                        coordinator_tx.clone(),
+                        &mut host_id,
+                    );
+                }
+                // Dropped players who didn't make it back forfeit and leave for good
+                for client_id in lobby.expired_seats(Instant::now()) {
+                    info!("Player {} did not reconnect to lobby {}", client_id, lobby_code);
+                    lobby.forfeit(&client_id, &broadcaster);
+                    shutdown |= handle_client_leave(
+                        &mut lobby,
+                        &mut broadcaster,
+                        client_id,
+                        coordinator_tx.clone(),
+                        &mut host_id,
+                    );
+                }
+                if shutdown {
+                    break;
+                }
+                resume_ante_timer(&mut lobby, &broadcaster);
+                // Players still in a game finish it before being sent on
+                if let Some((redirect, coordinator_tx)) = draining.take() {
+                    if !lobby.started() {
+                        broadcaster.broadcast(redirect);
+                        let _ = coordinator_tx.send(CoordinatorMessage::LobbyShutdown {
+                            lobby_code: lobby_code.clone(),
+                        });
+                        break;
+                    }
+                    draining = Some((redirect, coordinator_tx));
+                }
+                if restored && lobby.is_abandoned() {
+                    info!("Restored lobby {} was not rejoined, closing", lobby_code);
+                    let _ = coordinator_tx.send(CoordinatorMessage::LobbyShutdown {
+                        lobby_code: lobby_code.clone(),
+                    });
+                    break;
+                }
+                continue;
+            }
+        };
 
-    while let Some(msg) = rx.recv().await {
         match msg {
-            LobbyMessage::ClientAction { client_id, action } => {
-                LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, client_id, action);
+            LobbyMessage::ClientAction {
+                client_id,
+                action,
+                seq,
+                lobby_generation,
+            } => {
+                if !lobby.accepts_lobby_generation(&client_id, lobby_generation) {
+                    debug!("Dropping stale action from {}: {:?}", client_id, action);
+                    continue;
+                }
+                if let Some(seq) = seq
+                    && !lobby.accept_action_seq(&client_id, seq)
+                {
+                    debug!("Dropping replayed action #{} from {}", seq, client_id);
+                    continue;
+                }
+                lobby.record_event(Some(&client_id), format!("{:?}", action));
+                let audited = lobby.action_audit().then(|| action.name());
+                match action {
+                    ClientToServer::AddBot { difficulty } => {
+                        handle_add_bot(
+                            &mut lobby,
+                            &mut broadcaster,
+                            &client_id,
+                            difficulty,
+                            &bot_tx,
+                            &mut host_id,
+                        );
+                    }
+                    ClientToServer::ShuffleTeams { team_size } => {
+                        handle_shuffle_teams(&mut lobby, &mut broadcaster, &client_id, team_size);
+                    }
+                    ClientToServer::SetSubscriptions { subscribe, unsubscribe } => {
+                        broadcaster.update_subscriptions(&client_id, &subscribe, &unsubscribe);
+                    }
+                    ClientToServer::ReportPlayer { player_id, reason } => {
+                        handle_report_player(
+                            &lobby,
+                            &broadcaster,
+                            &client_id,
+                            &player_id,
+                            reason,
+                            &coordinator_tx,
+                        );
+                    }
+                    action => {
+                        LobbyHandlers::handle_player_action(
+                            &mut lobby,
+                            &broadcaster,
+                            client_id.clone(),
+                            action,
+                        );
+                    }
+                }
+                lobby.action_processed(&broadcaster, &client_id, audited, seq);
             }
             LobbyMessage::ClientJoin {
                 client_id,
                 client_profile,
                 client_response_tx,
+                lobby_generation,
+                resume_after,
+                invite,
+                reconnect_token,
             } => {
-                handle_client_join(
+                join_client(
                     &mut lobby,
                     &mut broadcaster,
-                    client_id,
+                    client_id.clone(),
                     client_profile,
                     client_response_tx,
                     &mut host_id,
+                    JoinRequest {
+                        resume_after,
+                        invite,
+                        reconnect_token,
+                    },
                 );
+                lobby.set_lobby_generation(&client_id, lobby_generation);
             }
             LobbyMessage::ClientLeave {
                 client_id,
                 coordinator_tx,
+                connection_lost,
             } => {
+                if connection_lost
+                    && handle_mid_game_disconnect(
+                        &mut lobby,
+                        &mut broadcaster,
+                        &client_id,
+                        &bot_tx,
+                        &mut host_id,
+                    )
+                {
+                    continue;
+                }
                 let shutdown = handle_client_leave(
                     &mut lobby,
                     &mut broadcaster,
@@ -58,8 +334,126 @@ pub async fn lobby_task(
                     break;
                 }
             }
+            LobbyMessage::SpectatorJoin {
+                client_id,
+                client_response_tx,
+                subscriptions,
+                request_tx,
+                join_data,
+            } => {
+                let refusal = if !lobby.lobby_options.allow_spectators {
+                    Some("This lobby can't be spectated")
+                } else if broadcaster.spectator_count() >= MAX_SPECTATORS {
+                    Some("This lobby has too many spectators")
+                } else {
+                    None
+                };
+                if let Some(message) = refusal {
+                    let _ = client_response_tx.send(Arc::new(ServerToClient::error(message)));
+                    continue;
+                }
+                broadcaster.add_spectator(client_id.clone(), client_response_tx, subscriptions);
+                let _ = request_tx.send(join_data);
+                broadcaster.send_to_spectator(
+                    &client_id,
+                    ServerToClient::SpectatingLobby {
+                        lobby_data: Box::new(lobby.snapshot_for(&client_id)),
+                    },
+                );
+                debug!("Client {} spectating lobby {}", client_id, lobby_code);
+            }
+            LobbyMessage::SpectatorSubscriptions {
+                client_id,
+                subscriptions,
+            } => {
+                broadcaster.set_spectator_subscriptions(&client_id, subscriptions);
+            }
+            LobbyMessage::SpectatorLeave { client_id } => {
+                if broadcaster.remove_spectator(&client_id) {
+                    debug!("Client {} stopped spectating lobby {}", client_id, lobby_code);
+                }
+            }
+            LobbyMessage::LatencyUpdate { client_id, rtt_ms } => {
+                lobby.set_player_latency(&client_id, rtt_ms);
+            }
+            LobbyMessage::Snapshot { reply_tx } => {
+                let _ = reply_tx.send(lobby.clone());
+            }
+            LobbyMessage::Kick { client_id, reason } => {
+                handle_client_kick(&mut lobby, &mut broadcaster, client_id, &reason, &mut host_id);
+            }
+            LobbyMessage::ServerNotice { message } => {
+                broadcaster.broadcast(ServerToClient::ServerNotice { message });
+            }
+            LobbyMessage::SetActionAudit { enabled } => {
+                lobby.set_action_audit(enabled, &broadcaster);
+            }
+            LobbyMessage::StartTutorial { client_id } => {
+                handle_start_tutorial(
+                    &mut lobby,
+                    &mut broadcaster,
+                    &client_id,
+                    &bot_tx,
+                    &mut host_id,
+                );
+            }
+            LobbyMessage::MergeInto {
+                into_code,
+                into_tx,
+                coordinator_tx,
+            } => {
+                // The coordinator dropped our offer, make it again if the merge is off
+                reported_merge = None;
+                if handle_merge_into(
+                    &mut lobby,
+                    &mut broadcaster,
+                    &into_code,
+                    &into_tx,
+                    &coordinator_tx,
+                    &host_id,
+                )
+                .await
+                {
+                    break;
+                }
+            }
+            LobbyMessage::ReserveMerge {
+                from_code,
+                players,
+                reply_tx,
+            } => {
+                let _ = reply_tx.send(lobby.reserve_merge(&from_code, players, Instant::now()));
+            }
+            LobbyMessage::MergeIn {
+                from_code,
+                players,
+                coordinator_tx,
+            } => {
+                reported_merge = None;
+                handle_merge_in(
+                    &mut lobby,
+                    &mut broadcaster,
+                    &from_code,
+                    players,
+                    &coordinator_tx,
+                    &bot_tx,
+                    &mut host_id,
+                );
+            }
+            LobbyMessage::Drain {
+                host,
+                port,
+                coordinator_tx,
+            } => {
+                draining = Some((ServerToClient::ServerRedirect { host, port }, coordinator_tx));
+            }
         }
     }
+    broadcaster.flush();
+    broadcaster.end_spectating();
+    if checkpointed || restored {
+        LobbyCheckpoint::remove(&lobby_code);
+    }
     info!("Lobby {} task ended", lobby_code);
 }
 
@@ -72,27 +466,472 @@ pub fn handle_client_join(
     client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
     host_id: &mut String,
 ) {
-    if lobby.is_full() {
-        let _ = client_response_tx.send(Arc::new(ServerToClient::Error {
-            message: String::from("Lobby is full"),
-        }));
+    let response_tx = client_response_tx;
+    let join = JoinRequest::default();
+    join_client(lobby, broadcaster, client_id, client_profile, response_tx, host_id, join);
+}
+
+/// What a client joins with besides its profile
+#[derive(Debug, Default)]
+struct JoinRequest {
+    /// Resuming a held seat: the last numbered message the client got, so it skips the resync
+    resume_after: Option<u64>,
+    invite: Option<Invite>,
+    /// Token the client got for its seat before its connection dropped
+    reconnect_token: Option<String>,
+}
+
+fn join_client(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: String,
+    client_profile: ClientProfile,
+    client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    host_id: &mut String,
+    join: JoinRequest,
+) {
+    if let Some(held_id) = join.reconnect_token.as_deref().and_then(|t| lobby.held_seat_for(t)) {
+        let missed = join
+            .resume_after
+            .and_then(|last_seq| broadcaster.resume_player(&held_id, &client_id, last_seq));
+        if *host_id == held_id {
+            *host_id = client_id.clone();
+        }
+        let response_tx = client_response_tx;
+        reclaim_seat(lobby, broadcaster, held_id, client_id, client_profile, response_tx, missed);
         return;
     }
-    let lobby_entry = lobby.add_player(client_id.clone(), client_profile.clone());
+    let bypass_reservations = join.invite.as_ref().is_some_and(|i| i.bypass_reservations);
+    let checked = match &join.invite {
+        Some(invite) => lobby.check_invite(invite),
+        None => Ok(()),
+    };
+    if let Err(message) =
+        checked.and_then(|()| lobby.check_can_join(&client_profile, bypass_reservations))
+    {
+        let _ = client_response_tx.send(Arc::new(ServerToClient::error(message)));
+        return;
+    }
+    if let Some(invite) = &join.invite {
+        lobby.redeem_invite(invite, now_millis() / 1000);
+    }
+    let claimed_reservation = lobby.claim_reservation(&client_profile);
+    lobby.add_player(client_id.clone(), client_profile.clone());
+    if lobby.claim_restored_state(&client_id) {
+        lobby.record_event(Some(&client_id), "restored from checkpoint");
+    }
+    lobby.record_event(Some(&client_id), "joined");
+    audit::record(
+        &lobby.code,
+        AuditEvent::PlayerJoined {
+            player_id: client_id.clone(),
+            username: client_profile.username.clone(),
+            account_id: client_profile.verified_account().map(str::to_string),
+            unverified_account_id: client_profile
+                .account_id
+                .clone()
+                .filter(|_| !client_profile.account_verified),
+        },
+    );
     broadcaster.add_player(client_id.clone(), client_response_tx);
+    broadcaster.set_subscriptions(&client_id, client_profile.subscriptions);
+    if client_profile.resumable {
+        broadcaster.enable_resume(&client_id);
+    }
 
     if lobby.players().len() == 1 {
         *host_id = client_id.clone();
     }
+    let late_join = lobby.accepts_late_join();
+    if late_join {
+        lobby.join_starting_game(&client_id);
+    }
+    let ready_check_restarted = lobby.restart_ready_check(broadcaster);
 
-    let player_joined_response = ServerToClient::player_joined_lobby(lobby_entry);
-    let joined_response = ServerToClient::joined_lobby(client_id.clone(), lobby.clone());
+    // Built after the ready reset so the joiner starts from the same state as everyone
+    let joined_response =
+        ServerToClient::joined_lobby(client_id.clone(), lobby.snapshot_for(&client_id));
 
     broadcaster.send_to(&client_id, joined_response);
-    broadcaster.broadcast_except(&client_id, player_joined_response);
+    send_reconnect_token(lobby, broadcaster, &client_id);
+    lobby.broadcast_player_joined(broadcaster, &client_id);
+    if claimed_reservation {
+        lobby.broadcast_reservations(broadcaster);
+    }
+    if ready_check_restarted {
+        lobby.broadcast_ready_states(broadcaster);
+    }
+    if late_join {
+        send_game_start_to(lobby, broadcaster, &client_id);
+    }
     debug!("Player {} joined lobby {}", client_id, lobby.code);
 }
 
+fn send_reconnect_token(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster, client_id: &str) {
+    if lobby.players().get(client_id).is_some_and(|p| !p.profile.is_bot) {
+        let token = lobby.issue_reconnect_token(client_id);
+        broadcaster.send_to(client_id, ServerToClient::ReconnectToken { token });
+    }
+}
+
+/// Hand every player, bots included, to `into_code` and close. The other lobby has to
+/// hold room for all of them first; false when it won't or this lobby is no longer open
+/// to merging, everyone then stays and it carries on as before
+async fn handle_merge_into(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    into_code: &str,
+    into_tx: &LobbyChannel,
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+    host_id: &str,
+) -> bool {
+    if lobby.merge_offer().is_none() {
+        debug!("Lobby {} no longer open to merging into {}", lobby.code, into_code);
+        return false;
+    }
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let reserve = LobbyMessage::ReserveMerge {
+        from_code: lobby.code.clone(),
+        players: lobby.players().len(),
+        reply_tx,
+    };
+    let reply = match into_tx.send_control(reserve) {
+        Ok(()) => {
+            let answer = tokio::time::timeout(MERGE_REPLY_TIMEOUT, reply_rx).await;
+            answer.ok().and_then(Result::ok)
+        }
+        Err(_) => None,
+    };
+    if let Err(reason) = reply.unwrap_or(Err("The other lobby didn't answer")) {
+        info!("Lobby {} could not merge into {}: {}", lobby.code, into_code, reason);
+        // The host offers again if they still want to merge
+        lobby.set_merge_offered(false);
+        broadcaster.broadcast(ServerToClient::MergeOfferChanged { open: false });
+        broadcaster.send_to(
+            host_id,
+            ServerToClient::error(format!("Lobby merge failed: {}", reason)),
+        );
+        return false;
+    }
+
+    let mut players = Vec::new();
+    let player_ids: Vec<String> = lobby.players().keys().cloned().collect();
+    for client_id in player_ids {
+        let Some(client_response_tx) = broadcaster.sender_for(&client_id) else {
+            continue;
+        };
+        let bot = lobby.bot_difficulty(&client_id);
+        let Some(entry) = detach_client(lobby, broadcaster, &client_id) else {
+            continue;
+        };
+        players.push(MergingPlayer {
+            client_id,
+            client_profile: entry.profile,
+            client_response_tx,
+            lobby_generation: entry.lobby_state.lobby_generation,
+            bot,
+        });
+    }
+    let merge = LobbyMessage::MergeIn {
+        from_code: lobby.code.clone(),
+        players,
+        coordinator_tx: coordinator_tx.clone(),
+    };
+    if let Err(refused) = into_tx.send_control(merge)
+        && let mpsc::error::SendError(LobbyMessage::MergeIn { players, .. }) = *refused
+    {
+        // The other lobby closed since it held the room, nobody has a lobby to go to now
+        for player in players {
+            let _ = player.client_response_tx.send(Arc::new(ServerToClient::Kicked {
+                reason: "Lobby merge failed".to_string(),
+            }));
+        }
+        let _ = coordinator_tx.send(CoordinatorMessage::LobbyMerged {
+            from_code: lobby.code.clone(),
+            into_code: into_code.to_string(),
+            moved: Vec::new(),
+        });
+    }
+    lobby.record_event(None, format!("merged into {}", into_code));
+    info!("Lobby {} merged into {}", lobby.code, into_code);
+    let _ = coordinator_tx.send(CoordinatorMessage::LobbyShutdown {
+        lobby_code: lobby.code.clone(),
+    });
+    true
+}
+
+/// Take in the players of a lobby merging into this one, into the room held for them
+pub fn handle_merge_in(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    from_code: &str,
+    players: Vec<MergingPlayer>,
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+    bot_tx: &mpsc::UnboundedSender<LobbyMessage>,
+    host_id: &mut String,
+) {
+    let admitted = if lobby.take_merge_reservation(from_code) {
+        Ok(())
+    } else {
+        // The held room ran out, they still come in if all of them fit
+        lobby.reserve_merge(from_code, players.len(), Instant::now()).map(|()| {
+            lobby.take_merge_reservation(from_code);
+        })
+    };
+    let mut moved = Vec::new();
+    for player in players {
+        if let Err(reason) = admitted {
+            let _ = player.client_response_tx.send(Arc::new(ServerToClient::Kicked {
+                reason: format!("Lobby merge failed: {}", reason),
+            }));
+            continue;
+        }
+        if let Some(difficulty) = player.bot {
+            seat_bot(
+                lobby,
+                broadcaster,
+                player.client_id,
+                player.client_profile,
+                difficulty,
+                bot_tx,
+                host_id,
+            );
+            continue;
+        }
+        let _ = player.client_response_tx.send(Arc::new(ServerToClient::LobbyMerged {
+            from_code: from_code.to_string(),
+            lobby_code: lobby.code.clone(),
+        }));
+        moved.push(player.client_id.clone());
+        handle_client_join(
+            lobby,
+            broadcaster,
+            player.client_id.clone(),
+            player.client_profile,
+            player.client_response_tx,
+            host_id,
+        );
+        // Moved clients keep sending under the generation they had
+        lobby.set_lobby_generation(&player.client_id, player.lobby_generation);
+    }
+    lobby.record_event(None, format!("{} players merged in from {}", moved.len(), from_code));
+    let _ = coordinator_tx.send(CoordinatorMessage::LobbyMerged {
+        from_code: from_code.to_string(),
+        into_code: lobby.code.clone(),
+        moved,
+    });
+}
+
+/// What players got when the game started, for one who joined while it was
+fn send_game_start_to(lobby: &Lobby, broadcaster: &LobbyBroadcaster, client_id: &str) {
+    if let Some(banned) = lobby.banned_cards() {
+        broadcaster.send_to(client_id, banned);
+    }
+    broadcaster.send_to(
+        client_id,
+        ServerToClient::GameStarted {
+            seed: lobby.seed_for(client_id),
+            stake: lobby.lobby_options.stake as i32,
+        },
+    );
+    if lobby.lobby_options.gamemode == GameMode::Clash {
+        lobby.broadcast_clash_stage(broadcaster);
+    }
+    broadcaster.broadcast(ServerToClient::InGameStatuses {
+        statuses: lobby.get_in_game_statuses(),
+        started: lobby.started(),
+    });
+    info!("Player {} joined lobby {} as its game started", client_id, lobby.code);
+}
+
+/// Apply the lobby's disconnect policy to a player whose connection dropped; true when
+/// their seat stays, held for them or taken over by a bot
+fn handle_mid_game_disconnect(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: &str,
+    bot_tx: &mpsc::UnboundedSender<LobbyMessage>,
+    host_id: &mut String,
+) -> bool {
+    match lobby.lobby_options.disconnect_policy {
+        DisconnectPolicy::Wait => hold_seat_for_reconnect(lobby, broadcaster, client_id),
+        DisconnectPolicy::Forfeit => {
+            lobby.forfeit(client_id, broadcaster);
+            false
+        }
+        DisconnectPolicy::Bot => replace_with_bot(lobby, broadcaster, client_id, bot_tx, host_id),
+    }
+}
+
+/// Hand a dropped player's run to a bot; false when there is no run to take over or
+/// nobody human left to play against
+fn replace_with_bot(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: &str,
+    bot_tx: &mpsc::UnboundedSender<LobbyMessage>,
+    host_id: &mut String,
+) -> bool {
+    let Some(player) = lobby.players().get(client_id) else {
+        return false;
+    };
+    let playing = lobby.started() && player.lobby_state.in_game && player.game_state.lives > 0;
+    let others_human = lobby
+        .players()
+        .iter()
+        .any(|(id, p)| id != client_id && !p.profile.is_bot);
+    if player.profile.is_bot || !playing || !others_human {
+        return false;
+    }
+    let bot_id = format!("bot-{}", Uuid::new_v4());
+    let profile = ClientProfile {
+        id: bot_id.clone(),
+        username: format!("{} (Bot)", player.profile.username),
+        colour: player.profile.colour,
+        is_bot: true,
+        ..ClientProfile::default()
+    };
+    lobby.reclaim_seat(client_id, bot_id.clone(), profile);
+    lobby.set_bot_difficulty(&bot_id, REPLACEMENT_BOT_DIFFICULTY);
+    // Bots act under generation 0 and never run the lobby
+    lobby.set_lobby_generation(&bot_id, 0);
+    if let Some(bot) = lobby.get_player_mut(&bot_id)
+        && bot.lobby_state.is_host
+    {
+        bot.lobby_state.is_host = false;
+        if let Some(new_host_id) = lobby.promote_new_host() {
+            *host_id = new_host_id;
+        }
+    }
+    broadcaster.remove_player(client_id);
+    lobby.cancel_magnet_for(broadcaster, client_id);
+    lobby.record_event(Some(client_id), format!("connection lost, replaced by {}", bot_id));
+
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    broadcaster.add_player(bot_id.clone(), events_tx);
+    if let Some(team) = lobby.team_of(&bot_id) {
+        broadcaster.set_team(&bot_id, team);
+    }
+    tokio::spawn(run_bot(
+        Bot::new(bot_id.clone(), REPLACEMENT_BOT_DIFFICULTY),
+        events_rx,
+        bot_tx.clone(),
+    ));
+    broadcaster.broadcast(ServerToClient::ReplacedByBot {
+        player_id: bot_id.clone(),
+        previous_id: client_id.to_string(),
+    });
+    lobby.broadcast_players(broadcaster);
+    // Gets the bot going on the run it took over
+    broadcaster.send_to(
+        &bot_id,
+        ServerToClient::GameStarted {
+            seed: lobby.seed_for(&bot_id),
+            stake: lobby.lobby_options.stake as i32,
+        },
+    );
+    info!("Bot {} took over from {} in lobby {}", bot_id, client_id, lobby.code);
+    true
+}
+
+/// Park a player whose connection dropped mid-game; false when they should just leave
+fn hold_seat_for_reconnect(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: &str,
+) -> bool {
+    let grace_seconds = CONFIG.get().disconnect_grace_secs;
+    if grace_seconds == 0 {
+        return false;
+    }
+    let until = Instant::now() + Duration::from_secs(grace_seconds);
+    if !lobby.hold_seat(client_id, until) {
+        return false;
+    }
+    broadcaster.park_player(client_id);
+    lobby.cancel_magnet_for(broadcaster, client_id);
+    lobby.record_event(Some(client_id), "connection lost, holding seat");
+    broadcaster.broadcast_except(client_id, ServerToClient::OpponentDisconnected {
+        player_id: client_id.to_string(),
+        grace_seconds,
+    });
+    // Nobody's timer runs out while they wait on the missing player
+    broadcaster.broadcast(ServerToClient::PauseAnteTimer {
+        time: lobby.hold_ante_timer(Instant::now()),
+        server_time: now_millis(),
+    });
+    info!("Holding seat of {} in lobby {} for {}s", client_id, lobby.code, grace_seconds);
+    true
+}
+
+/// Give a held seat to the player's new connection. With `missed` they resume and get
+/// only those messages, otherwise the whole lobby as on a fresh join.
+fn reclaim_seat(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    held_id: String,
+    client_id: String,
+    client_profile: ClientProfile,
+    client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    missed: Option<Vec<Arc<ServerToClient>>>,
+) {
+    let subscriptions = client_profile.subscriptions;
+    let resumable = client_profile.resumable;
+    lobby.reclaim_seat(&held_id, client_id.clone(), client_profile);
+    lobby.record_event(Some(&client_id), format!("reconnected, was {}", held_id));
+    broadcaster.add_player(client_id.clone(), client_response_tx);
+    broadcaster.set_subscriptions(&client_id, subscriptions);
+    if let Some(team) = lobby.team_of(&client_id) {
+        broadcaster.set_team(&client_id, team);
+    }
+    match missed {
+        Some(missed) => {
+            lobby.record_event(Some(&client_id), format!("resumed, {} missed", missed.len()));
+            broadcaster.replay_to(
+                &client_id,
+                std::iter::once(Arc::new(ServerToClient::LobbyResumed {
+                    player_id: client_id.clone(),
+                    previous_id: held_id.clone(),
+                }))
+                .chain(missed)
+                .collect(),
+            );
+        }
+        None => {
+            // Whatever the old connection had buffered is no use to a full resync
+            broadcaster.remove_player(&held_id);
+            if resumable {
+                broadcaster.enable_resume(&client_id);
+            }
+            broadcaster.send_to(
+                &client_id,
+                ServerToClient::joined_lobby(client_id.clone(), lobby.snapshot_for(&client_id)),
+            );
+        }
+    }
+    broadcaster.broadcast_except(
+        &client_id,
+        ServerToClient::OpponentReconnected {
+            player_id: client_id.clone(),
+            previous_id: held_id,
+        },
+    );
+    send_reconnect_token(lobby, broadcaster, &client_id);
+    lobby.broadcast_players(broadcaster);
+    resume_ante_timer(lobby, broadcaster);
+    info!("Player {} reconnected to lobby {}", client_id, lobby.code);
+}
+
+/// Restart the ante timer paused for held seats once none is held anymore
+fn resume_ante_timer(lobby: &mut Lobby, broadcaster: &LobbyBroadcaster) {
+    if let Some(time) = lobby.release_ante_timer(Instant::now()) {
+        broadcaster.broadcast(ServerToClient::StartAnteTimer {
+            time,
+            server_time: now_millis(),
+        });
+    }
+}
+
 pub fn handle_client_leave(
     lobby: &mut Lobby,
     broadcaster: &mut LobbyBroadcaster,
@@ -101,30 +940,210 @@ pub fn handle_client_leave(
     host_id: &mut String,
 ) -> bool {
     debug!("Player {} leaving lobby {}", client_id, lobby.code);
-    broadcaster.remove_player(&client_id);
-    let Some(leaving_player) = lobby.remove_player(&client_id) else {
+    let Some(leaving_player) = detach_client(lobby, broadcaster, &client_id) else {
         return false;
     };
-    if lobby.players().is_empty() {
+    lobby.record_event(Some(&client_id), "left");
+    audit::record(
+        &lobby.code,
+        AuditEvent::PlayerLeft {
+            player_id: client_id.clone(),
+        },
+    );
+    // Bots don't keep a lobby alive on their own
+    if !lobby.has_human_players() {
         let _ = coordinator_tx.send(CoordinatorMessage::LobbyShutdown {
             lobby_code: lobby.code.clone(),
         });
         return true; // signal shutdown
     }
+    notify_client_removed(lobby, broadcaster, &client_id, leaving_player, host_id);
+    false
+}
+
+/// Host request to fill a seat with a practice bot
+pub fn handle_add_bot(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    requester_id: &str,
+    difficulty: BotDifficulty,
+    bot_tx: &mpsc::UnboundedSender<LobbyMessage>,
+    host_id: &mut String,
+) {
+    if !lobby.is_player_host(requester_id) {
+        broadcaster.send_to(requester_id, ServerToClient::error("Only the host can add bots"));
+        return;
+    }
+    if lobby.started() {
+        broadcaster.send_to(requester_id, ServerToClient::error("Game already started"));
+        return;
+    }
+    let bot_id = format!("bot-{}", Uuid::new_v4());
+    let profile = ClientProfile {
+        id: bot_id.clone(),
+        username: difficulty.display_name().to_string(),
+        is_bot: true,
+        ..ClientProfile::default()
+    };
+    if let Err(message) = lobby.check_can_join(&profile, false) {
+        broadcaster.send_to(requester_id, ServerToClient::error(message));
+        return;
+    }
+    seat_bot(lobby, broadcaster, bot_id, profile, difficulty, bot_tx, host_id);
+}
+
+/// Seat a bot and start it playing
+fn seat_bot(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    bot_id: String,
+    profile: ClientProfile,
+    difficulty: BotDifficulty,
+    bot_tx: &mpsc::UnboundedSender<LobbyMessage>,
+    host_id: &mut String,
+) {
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    handle_client_join(lobby, broadcaster, bot_id.clone(), profile, events_tx, host_id);
+    lobby.set_bot_difficulty(&bot_id, difficulty);
+    tokio::spawn(run_bot(Bot::new(bot_id, difficulty), events_rx, bot_tx.clone()));
+}
+
+/// Seat the tutorial's bot opposite its creator, then close the lobby to others
+fn handle_start_tutorial(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: &str,
+    bot_tx: &mpsc::UnboundedSender<LobbyMessage>,
+    host_id: &mut String,
+) {
+    handle_add_bot(lobby, broadcaster, client_id, TUTORIAL_BOT, bot_tx, host_id);
+    lobby.start_tutorial(broadcaster);
+}
+
+fn handle_shuffle_teams(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    requester_id: &str,
+    team_size: u8,
+) {
+    if !lobby.is_player_host(requester_id) {
+        broadcaster.send_to(requester_id, ServerToClient::error("Only the host can shuffle teams"));
+        return;
+    }
+    if lobby.started() {
+        broadcaster.send_to(requester_id, ServerToClient::error("Game already started"));
+        return;
+    }
+    if let Err(message) = lobby.randomize_teams(broadcaster, team_size) {
+        broadcaster.send_to(requester_id, ServerToClient::error(message));
+        return;
+    }
+    lobby.broadcast_players(broadcaster);
+    broadcaster.broadcast(ServerToClient::TeamsUpdated {
+        teams: lobby.teams(),
+    });
+}
+
+/// File a report against another player, stored by the coordinator for review
+fn handle_report_player(
+    lobby: &Lobby,
+    broadcaster: &LobbyBroadcaster,
+    reporter_id: &str,
+    reported_id: &str,
+    reason: String,
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+) {
+    match PlayerReport::new(lobby, reporter_id, reported_id, reason) {
+        Ok(report) => {
+            debug!(
+                "Player {} reported {} in lobby {} ({})",
+                reporter_id, reported_id, lobby.code, report.report_id
+            );
+            broadcaster.send_to(
+                reporter_id,
+                ServerToClient::PlayerReported {
+                    report_id: report.report_id.clone(),
+                },
+            );
+            webhooks::emit(WebhookPayload::PlayerReported {
+                lobby_code: lobby.code.clone(),
+                reporter_id: reporter_id.to_string(),
+                reported_id: reported_id.to_string(),
+                report_id: report.report_id.clone(),
+                reason: report.reason.clone(),
+            });
+            let _ = coordinator_tx.send(CoordinatorMessage::PlayerReported { report });
+        }
+        Err(message) => broadcaster.send_to(reporter_id, ServerToClient::error(message)),
+    }
+}
+
+/// Remove a player on the lobby's own initiative; never empties the lobby
+pub fn handle_client_kick(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: String,
+    reason: &str,
+    host_id: &mut String,
+) {
+    let others_human = lobby
+        .players()
+        .iter()
+        .any(|(id, p)| *id != client_id && !p.profile.is_bot);
+    if !others_human {
+        return;
+    }
+    debug!("Kicking player {} from lobby {}: {}", client_id, lobby.code, reason);
+    broadcaster.send_to(
+        &client_id,
+        ServerToClient::Kicked {
+            reason: reason.to_string(),
+        },
+    );
+    let Some(kicked_player) = detach_client(lobby, broadcaster, &client_id) else {
+        return;
+    };
+    lobby.record_event(Some(&client_id), format!("kicked: {}", reason));
+    audit::record(
+        &lobby.code,
+        AuditEvent::PlayerKicked {
+            player_id: client_id.clone(),
+            reason: reason.to_string(),
+        },
+    );
+    notify_client_removed(lobby, broadcaster, &client_id, kicked_player, host_id);
+}
+
+fn detach_client(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: &str,
+) -> Option<ClientLobbyEntry> {
+    broadcaster.remove_player(client_id);
+    lobby.cancel_magnet_for(broadcaster, client_id);
+    lobby.remove_player(client_id)
+}
+
+fn notify_client_removed(
+    lobby: &mut Lobby,
+    broadcaster: &mut LobbyBroadcaster,
+    client_id: &str,
+    leaving_player: ClientLobbyEntry,
+    host_id: &mut String,
+) {
     if leaving_player.lobby_state.is_host {
         if let Some(new_host_id) = lobby.promote_new_host() {
             *host_id = new_host_id;
         }
     }
     let player_left_response =
-        ServerToClient::player_left_lobby(client_id.clone(), host_id.clone());
+        ServerToClient::player_left_lobby(client_id.to_string(), host_id.clone());
     broadcaster.broadcast(player_left_response);
-    if lobby.started && lobby.get_player_count_in_game() < 2 {
+    if lobby.started() && lobby.get_player_count_in_game() < 2 {
         lobby.stop_game();
-        broadcaster.broadcast(ServerToClient::GameStopped {});
+        broadcaster.broadcast(ServerToClient::GameStopped { reason: StopReason::PlayersLeft });
     }
     debug!("Player {} left lobby {}", client_id, lobby.code);
-    false
 }
 
 mod tests {
@@ -137,6 +1156,8 @@ mod tests {
     #[allow(unused)]
     use crate::test_utils::contains_response_of_type;
     #[allow(unused)]
+    use crate::lobby::{phase::LobbyPhase, tutorial::TUTORIAL_SPEAKER};
+    #[allow(unused)]
     use std::sync::Arc;
     #[allow(unused)]
     use tokio::sync::mpsc;
@@ -218,4 +1239,588 @@ mod tests {
             _ => panic!("Expected LobbyShutdown message"),
         }
     }
+
+    #[tokio::test]
+    async fn test_reserved_slot_rejects_other_joiners() {
+        let (response_tx, mut response_rx) = mpsc::unbounded_channel();
+        let mut lobby = Lobby::new(
+            "TEST".to_string(),
+            "default".to_string(),
+            GameMode::Attrition,
+        );
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        lobby.reserve_slot("friend".to_string()).unwrap();
+
+        handle_client_join(
+            &mut lobby,
+            &mut broadcaster,
+            "stranger".to_string(),
+            ClientProfile::default(),
+            response_tx.clone(),
+            &mut host_id,
+        );
+        let responses: Vec<_> = std::iter::from_fn(|| response_rx.try_recv().ok()).collect();
+        assert!(contains_response_of_type(&responses, &ServerToClient::error("")));
+        assert_eq!(lobby.players().len(), 1);
+
+        // Copying the reserved id without the account's token doesn't get in
+        let impostor = ClientProfile {
+            account_id: Some("friend".to_string()),
+            ..ClientProfile::default()
+        };
+        handle_client_join(
+            &mut lobby,
+            &mut broadcaster,
+            "impostor".to_string(),
+            impostor,
+            response_tx.clone(),
+            &mut host_id,
+        );
+        let responses: Vec<_> = std::iter::from_fn(|| response_rx.try_recv().ok()).collect();
+        assert!(contains_response_of_type(&responses, &ServerToClient::error("")));
+        assert_eq!(lobby.players().len(), 1);
+
+        let friend = ClientProfile {
+            account_id: Some("friend".to_string()),
+            account_verified: true,
+            ..ClientProfile::default()
+        };
+        handle_client_join(
+            &mut lobby,
+            &mut broadcaster,
+            "friend".to_string(),
+            friend,
+            response_tx.clone(),
+            &mut host_id,
+        );
+        assert_eq!(lobby.players().len(), 2);
+        assert!(lobby.reserved_account_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_host_invite_gets_past_reserved_slots_once() {
+        let (response_tx, mut response_rx) = mpsc::unbounded_channel();
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Survival);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        lobby.add_player("host".to_string(), ClientProfile::default());
+        let mut friends = 0;
+        while lobby.reserve_slot(format!("friend{}", friends)).is_ok() {
+            friends += 1;
+        }
+        let now = now_millis() / 1000;
+        let invite = Invite::new("TEST".to_string(), true, now);
+        let mut join = |lobby: &mut Lobby, id: &str, invite: Option<Invite>| {
+            let join = JoinRequest {
+                invite,
+                ..JoinRequest::default()
+            };
+            let (id, profile, tx) = (id.to_string(), ClientProfile::default(), response_tx.clone());
+            join_client(lobby, &mut broadcaster, id, profile, tx, &mut host_id, join);
+            std::iter::from_fn(|| response_rx.try_recv().ok()).collect::<Vec<_>>()
+        };
+
+        let error = ServerToClient::error("");
+        let plain = Invite::new("TEST".to_string(), false, now);
+        assert!(contains_response_of_type(&join(&mut lobby, "p2", Some(plain)), &error));
+        join(&mut lobby, "p2", Some(invite.clone()));
+        assert!(lobby.players().contains_key("p2"));
+        // The same token doesn't get anyone else in
+        let responses = join(&mut lobby, "p3", Some(invite));
+        assert!(responses.iter().any(|m| matches!(
+            m.as_ref(),
+            ServerToClient::Error { message } if message == "Invite was already used"
+        )));
+        let elsewhere = Invite::new("OTHER".to_string(), true, now);
+        assert!(contains_response_of_type(&join(&mut lobby, "p3", Some(elsewhere)), &error));
+        assert!(!lobby.players().contains_key("p3"));
+    }
+
+    #[tokio::test]
+    async fn test_host_adds_bot_and_lobby_closes_without_humans() {
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (guest_tx, mut guest_rx) = mpsc::unbounded_channel();
+        let (bot_tx, _bot_rx) = mpsc::unbounded_channel();
+        let (coordinator_tx, mut coordinator_rx) = mpsc::unbounded_channel();
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        for (id, tx) in [("host", host_tx), ("guest", guest_tx)] {
+            handle_client_join(
+                &mut lobby,
+                &mut broadcaster,
+                id.to_string(),
+                ClientProfile::default(),
+                tx,
+                &mut host_id,
+            );
+        }
+        while guest_rx.try_recv().is_ok() {}
+
+        let difficulty = BotDifficulty::Easy;
+        handle_add_bot(&mut lobby, &mut broadcaster, "guest", difficulty, &bot_tx, &mut host_id);
+        let responses: Vec<_> = std::iter::from_fn(|| guest_rx.try_recv().ok()).collect();
+        assert!(contains_response_of_type(&responses, &ServerToClient::error("")));
+
+        // Make room for the bot, lobbies default to two seats
+        let guest = "guest".to_string();
+        handle_client_leave(&mut lobby, &mut broadcaster, guest, coordinator_tx.clone(), &mut host_id);
+        handle_add_bot(&mut lobby, &mut broadcaster, "host", difficulty, &bot_tx, &mut host_id);
+        assert_eq!(lobby.players().len(), 2);
+        assert!(lobby.players().values().any(|p| p.profile.is_bot));
+        let responses: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok()).collect();
+        assert!(contains_response_of_type(
+            &responses,
+            &ServerToClient::player_joined_lobby(lobby.players()["host"].clone())
+        ));
+
+        let shutdown = handle_client_leave(
+            &mut lobby,
+            &mut broadcaster,
+            "host".to_string(),
+            coordinator_tx,
+            &mut host_id,
+        );
+        assert!(shutdown);
+        assert!(matches!(
+            coordinator_rx.try_recv(),
+            Ok(CoordinatorMessage::LobbyShutdown { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tutorial_seats_a_bot_and_explains_phases() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (bot_tx, _bot_rx) = mpsc::unbounded_channel();
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        let profile = ClientProfile::default();
+        handle_client_join(&mut lobby, &mut broadcaster, "new".to_string(), profile, tx, &mut host_id);
+        handle_start_tutorial(&mut lobby, &mut broadcaster, "new", &bot_tx, &mut host_id);
+        assert_eq!(lobby.players().len(), 2);
+        assert!(lobby.check_can_join(&ClientProfile::default(), false).is_err());
+
+        lobby.set_phase(LobbyPhase::Starting);
+        lobby.broadcast_phase_if_changed(&broadcaster);
+        let responses: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        let tutorial_lines = responses
+            .iter()
+            .filter(|m| match m.as_ref() {
+                ServerToClient::Announcement { player_id, .. } => player_id == TUTORIAL_SPEAKER,
+                _ => false,
+            })
+            .count();
+        // The welcome, then the explanation of the starting game
+        assert_eq!(tutorial_lines, 2);
+    }
+
+    #[tokio::test]
+    async fn test_audit_mode_acknowledges_processed_actions() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (guest_tx, mut guest_rx) = mpsc::unbounded_channel();
+        for (id, tx) in [("host", host_tx), ("guest", guest_tx)] {
+            let profile = ClientProfile::default();
+            let id = id.to_string();
+            handle_client_join(&mut lobby, &mut broadcaster, id, profile, tx, &mut host_id);
+        }
+        let process = |lobby: &mut Lobby, player_id: &str, action: ClientToServer, seq| {
+            let audited = lobby.action_audit().then(|| action.name());
+            LobbyHandlers::handle_player_action(lobby, &broadcaster, player_id.to_string(), action);
+            lobby.action_processed(&broadcaster, player_id, audited, seq);
+        };
+
+        process(&mut lobby, "guest", ClientToServer::SetActionAudit { enabled: true }, None);
+        assert!(!lobby.action_audit());
+        process(&mut lobby, "host", ClientToServer::SetActionAudit { enabled: true }, None);
+        assert!(lobby.action_audit());
+        let _ = std::iter::from_fn(|| host_rx.try_recv().ok()).count();
+        let _ = std::iter::from_fn(|| guest_rx.try_recv().ok()).count();
+
+        process(&mut lobby, "guest", ClientToServer::SetReady { is_ready: true }, Some(7));
+        let acks: Vec<_> = std::iter::from_fn(|| guest_rx.try_recv().ok())
+            .filter_map(|m| match m.as_ref() {
+                ServerToClient::ActionAck { processed, seq, state_version } => {
+                    Some((processed.clone(), *seq, *state_version))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(acks, [("setReady".to_string(), Some(7), 3)]);
+        // Only the sender hears about its action
+        assert!(std::iter::from_fn(|| host_rx.try_recv().ok())
+            .all(|m| !matches!(m.as_ref(), ServerToClient::ActionAck { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_host_shuffles_teams_that_split_evenly() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for id in ["host", "p2", "p3", "p4"] {
+            lobby.add_player(id.to_string(), ClientProfile::default());
+        }
+        broadcaster.add_player("p2".to_string(), tx);
+
+        handle_shuffle_teams(&mut lobby, &mut broadcaster, "p2", 2);
+        handle_shuffle_teams(&mut lobby, &mut broadcaster, "host", 3);
+        let responses: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(responses.len(), 1);
+        assert!(contains_response_of_type(&responses, &ServerToClient::error("")));
+
+        handle_shuffle_teams(&mut lobby, &mut broadcaster, "host", 2);
+        let responses: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        let teams = responses.iter().find_map(|m| match m.as_ref() {
+            ServerToClient::TeamsUpdated { teams } => Some(teams.clone()),
+            _ => None,
+        });
+        let teams = teams.unwrap();
+        assert_eq!(teams.len(), 2);
+        assert!(teams.values().all(|members| members.len() == 2));
+        assert_eq!(teams, lobby.teams());
+        assert!(contains_response_of_type(
+            &responses,
+            &ServerToClient::ResetPlayers { players: Vec::new() }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_quick_reconnect_resumes_without_resync() {
+        let (alice_tx, mut alice_rx) = mpsc::unbounded_channel();
+        let (bob_tx, _bob_rx) = mpsc::unbounded_channel();
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        for (id, tx) in [("alice", alice_tx.clone()), ("bob", bob_tx)] {
+            let profile = ClientProfile {
+                account_id: Some(id.to_string()),
+                resumable: true,
+                ..ClientProfile::default()
+            };
+            let id = id.to_string();
+            handle_client_join(&mut lobby, &mut broadcaster, id, profile, tx, &mut host_id);
+        }
+        lobby.start_game();
+        let received: Vec<_> = std::iter::from_fn(|| alice_rx.try_recv().ok()).collect();
+        let last_seq = received
+            .iter()
+            .filter_map(|m| match m.as_ref() {
+                ServerToClient::Sequenced { seq, .. } => Some(*seq),
+                _ => None,
+            })
+            .max()
+            .unwrap();
+
+        assert!(hold_seat_for_reconnect(&mut lobby, &mut broadcaster, "alice"));
+        broadcaster.broadcast(ServerToClient::ServerNotice {
+            message: "while away".to_string(),
+        });
+        let profile = ClientProfile {
+            account_id: Some("alice".to_string()),
+            resumable: true,
+            ..ClientProfile::default()
+        };
+        let id = "alice2".to_string();
+        let join = JoinRequest {
+            resume_after: Some(last_seq),
+            invite: None,
+            reconnect_token: Some(reconnect_token(&received)),
+        };
+        let tx = alice_tx;
+        join_client(&mut lobby, &mut broadcaster, id, profile, tx, &mut host_id, join);
+        assert!(lobby.players()["alice2"].lobby_state.in_game);
+
+        let responses: Vec<_> = std::iter::from_fn(|| alice_rx.try_recv().ok()).collect();
+        assert!(matches!(responses[0].as_ref(), ServerToClient::LobbyResumed { .. }));
+        let replayed: Vec<_> = responses[1..]
+            .iter()
+            .filter(|m| !matches!(m.as_ref(), ServerToClient::ReconnectToken { .. }))
+            .map(|m| match m.as_ref() {
+                ServerToClient::Sequenced { seq, message } => (*seq, message.clone()),
+                other => panic!("unnumbered {:?}", other),
+            })
+            .collect();
+        assert_eq!(replayed[0].0, last_seq + 1);
+        assert!(replayed.windows(2).all(|w| w[1].0 == w[0].0 + 1));
+        let replayed_any = |f: fn(&ServerToClient) -> bool| replayed.iter().any(|(_, m)| f(m));
+        assert!(replayed_any(|m| matches!(m, ServerToClient::ServerNotice { .. })));
+        assert!(!replayed_any(|m| matches!(m, ServerToClient::JoinedLobby { .. })));
+    }
+
+    /// The last reconnect token among `messages`, numbered or not
+    #[allow(unused)]
+    fn reconnect_token(messages: &[Arc<ServerToClient>]) -> String {
+        messages
+            .iter()
+            .filter_map(|m| match m.as_ref() {
+                ServerToClient::Sequenced { message, .. } => match message.as_ref() {
+                    ServerToClient::ReconnectToken { token } => Some(token.clone()),
+                    _ => None,
+                },
+                ServerToClient::ReconnectToken { token } => Some(token.clone()),
+                _ => None,
+            })
+            .next_back()
+            .expect("no reconnect token sent")
+    }
+
+    #[tokio::test]
+    async fn test_dropped_player_reclaims_seat_mid_game() {
+        let (alice_tx, mut alice_rx) = mpsc::unbounded_channel();
+        let (bob_tx, mut bob_rx) = mpsc::unbounded_channel();
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        for (id, tx) in [("alice", alice_tx.clone()), ("bob", bob_tx)] {
+            let profile = ClientProfile {
+                account_id: Some(id.to_string()),
+                ..ClientProfile::default()
+            };
+            let id = id.to_string();
+            handle_client_join(&mut lobby, &mut broadcaster, id, profile, tx, &mut host_id);
+        }
+        lobby.start_game();
+        let received: Vec<_> = std::iter::from_fn(|| alice_rx.try_recv().ok()).collect();
+        let token = reconnect_token(&received);
+        lobby.ante_timer_started(30, Instant::now());
+        while bob_rx.try_recv().is_ok() {}
+
+        assert!(hold_seat_for_reconnect(&mut lobby, &mut broadcaster, "alice"));
+        let responses: Vec<_> = std::iter::from_fn(|| bob_rx.try_recv().ok()).collect();
+        let disconnected = ServerToClient::OpponentDisconnected {
+            player_id: String::new(),
+            grace_seconds: 0,
+        };
+        assert!(contains_response_of_type(&responses, &disconnected));
+        assert!(lobby.started(), "the game waits for the dropped player");
+        assert!(lobby.expired_seats(Instant::now()).is_empty());
+
+        // Knowing the account isn't enough to take the seat
+        let profile = ClientProfile {
+            account_id: Some("alice".to_string()),
+            ..ClientProfile::default()
+        };
+        let mut rejoin = |id: &str, reconnect_token: Option<String>| {
+            let join = JoinRequest {
+                reconnect_token,
+                ..JoinRequest::default()
+            };
+            let (id, profile, tx) = (id.to_string(), profile.clone(), alice_tx.clone());
+            join_client(&mut lobby, &mut broadcaster, id, profile, tx, &mut host_id, join);
+        };
+        rejoin("mallory", None);
+        rejoin("mallory", Some("0".repeat(32)));
+        assert_eq!(
+            std::iter::from_fn(|| alice_rx.try_recv().ok())
+                .filter(|m| matches!(m.as_ref(), ServerToClient::Error { .. }))
+                .count(),
+            2
+        );
+
+        // Back on a new connection with its token, the full lobby lets the player in
+        rejoin("alice2", Some(token.clone()));
+        assert_eq!(host_id, "alice2");
+        assert!(lobby.players()["alice2"].lobby_state.in_game);
+        assert!(!lobby.players().contains_key("alice"));
+        let responses: Vec<_> = std::iter::from_fn(|| bob_rx.try_recv().ok()).collect();
+        let reconnected = ServerToClient::OpponentReconnected {
+            player_id: String::new(),
+            previous_id: String::new(),
+        };
+        assert!(contains_response_of_type(&responses, &reconnected));
+        let resumed = ServerToClient::StartAnteTimer {
+            time: 0,
+            server_time: 0,
+        };
+        assert!(contains_response_of_type(&responses, &resumed));
+        // The token was used up, the new connection got its own
+        let received: Vec<_> = std::iter::from_fn(|| alice_rx.try_recv().ok()).collect();
+        assert_ne!(reconnect_token(&received), token);
+    }
+
+    #[tokio::test]
+    async fn test_merge_moves_players_into_the_other_lobby() {
+        let (coordinator_tx, mut coordinator_rx) = mpsc::unbounded_channel();
+        let (bot_tx, _bot_rx) = mpsc::unbounded_channel();
+        let (into_tx, mut into_rx) = lobby_channel();
+        let lobby_pair = || {
+            let lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Clash);
+            (lobby, LobbyBroadcaster::new(), String::new())
+        };
+        let (mut from, mut from_bc, mut from_host) = lobby_pair();
+        let (mut into, mut into_broadcaster, mut into_host) = lobby_pair();
+        from.code = "FROM".to_string();
+        into.code = "INTO".to_string();
+        let (mover_tx, mut mover_rx) = mpsc::unbounded_channel();
+        let profile = ClientProfile::default();
+        let mover = "mover".to_string();
+        let mover_profile = profile.clone();
+        handle_client_join(&mut from, &mut from_bc, mover, mover_profile, mover_tx, &mut from_host);
+        let hard = BotDifficulty::Hard;
+        handle_add_bot(&mut from, &mut from_bc, "mover", hard, &bot_tx, &mut from_host);
+        // One seat short of taking both
+        for id in ["host", "filler-1", "filler-2", "filler-3", "filler-4"] {
+            let (tx, _rx) = mpsc::unbounded_channel();
+            let (id, profile) = (id.to_string(), profile.clone());
+            handle_client_join(&mut into, &mut into_broadcaster, id, profile, tx, &mut into_host);
+        }
+        let answer_reserve = |into: &mut Lobby, message: Option<LobbyMessage>| {
+            let Some(LobbyMessage::ReserveMerge { from_code, players, reply_tx }) = message else {
+                panic!("expected a reservation request");
+            };
+            let _ = reply_tx.send(into.reserve_merge(&from_code, players, Instant::now()));
+        };
+
+        // Without the host's consent nothing moves
+        let (into_code, from_tx) = ("INTO", &coordinator_tx);
+        let merged =
+            handle_merge_into(&mut from, &mut from_bc, into_code, &into_tx, from_tx, &from_host);
+        assert!(!merged.await);
+
+        // Refused for lack of room: everyone, the bot too, stays put
+        from.set_merge_offered(true);
+        let (merged, ()) = tokio::join!(
+            handle_merge_into(&mut from, &mut from_bc, into_code, &into_tx, from_tx, &from_host),
+            async { answer_reserve(&mut into, into_rx.recv().await) },
+        );
+        assert!(!merged);
+        assert_eq!(from.players().len(), 2);
+        assert!(from.merge_offer().is_none());
+        assert!(!into.merge_pending());
+        let responses: Vec<_> = std::iter::from_fn(|| mover_rx.try_recv().ok()).collect();
+        let refused = ServerToClient::error("");
+        assert!(contains_response_of_type(&responses, &refused));
+
+        into.remove_player("filler-4");
+        from.set_merge_offered(true);
+        let (merged, ()) = tokio::join!(
+            handle_merge_into(&mut from, &mut from_bc, into_code, &into_tx, from_tx, &from_host),
+            async { answer_reserve(&mut into, into_rx.recv().await) },
+        );
+        assert!(merged);
+        assert!(from.players().is_empty());
+        // The held room is theirs alone
+        assert!(into.is_full());
+
+        let Some(LobbyMessage::MergeIn { from_code, players, coordinator_tx }) = into_rx.recv().await
+        else {
+            panic!("expected the players to be handed over");
+        };
+        handle_merge_in(
+            &mut into,
+            &mut into_broadcaster,
+            &from_code,
+            players,
+            &coordinator_tx,
+            &bot_tx,
+            &mut into_host,
+        );
+        assert_eq!(into.players().len(), 6);
+        assert_eq!(into_host, "host");
+        assert!(!into.merge_pending());
+        let bots: Vec<_> = into.players().keys().filter_map(|id| into.bot_difficulty(id)).collect();
+        assert_eq!(bots, vec![BotDifficulty::Hard]);
+
+        let responses: Vec<_> = std::iter::from_fn(|| mover_rx.try_recv().ok()).collect();
+        let merged = ServerToClient::LobbyMerged {
+            from_code: String::new(),
+            lobby_code: String::new(),
+        };
+        assert!(contains_response_of_type(&responses, &merged));
+        let joined = ServerToClient::joined_lobby("mover".to_string(), into.clone());
+        assert!(contains_response_of_type(&responses, &joined));
+
+        let coordinator_messages: Vec<_> =
+            std::iter::from_fn(|| coordinator_rx.try_recv().ok()).collect();
+        assert!(coordinator_messages.iter().any(|message| matches!(
+            message,
+            CoordinatorMessage::LobbyMerged { into_code, moved, .. }
+                if into_code == "INTO" && moved == &["mover".to_string()]
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_join_during_countdown_restarts_ready_check() {
+        let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Clash);
+        lobby.lobby_options.ready_timeout_seconds = 30;
+        let mut broadcaster = LobbyBroadcaster::new();
+        let mut host_id = String::new();
+        let (host_tx, mut host_rx) = mpsc::unbounded_channel();
+        let (other_tx, _other_rx) = mpsc::unbounded_channel();
+        let profile = ClientProfile::default();
+        let join = |lobby: &mut Lobby,
+                    broadcaster: &mut LobbyBroadcaster,
+                    id: &str,
+                    tx,
+                    host_id: &mut String| {
+            handle_client_join(lobby, broadcaster, id.to_string(), profile.clone(), tx, host_id)
+        };
+        join(&mut lobby, &mut broadcaster, "host", host_tx, &mut host_id);
+        join(&mut lobby, &mut broadcaster, "p2", other_tx.clone(), &mut host_id);
+        join(&mut lobby, &mut broadcaster, "p3", other_tx.clone(), &mut host_id);
+        lobby.set_player_ready("p2", true);
+        lobby.check_ready_timeout(&broadcaster, Instant::now());
+        while host_rx.try_recv().is_ok() {}
+
+        join(&mut lobby, &mut broadcaster, "p4", other_tx.clone(), &mut host_id);
+        let responses: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok()).collect();
+        assert!(contains_response_of_type(&responses, &ServerToClient::ReadyCountdownCancelled {}));
+        assert!(!lobby.players()["p4"].lobby_state.is_ready);
+        // Back to just the host being ready, which doesn't start the clock again
+        lobby.check_ready_timeout(&broadcaster, Instant::now());
+        assert!(std::iter::from_fn(|| host_rx.try_recv().ok()).next().is_none());
+
+        // A join racing the start loads straight into the game
+        lobby.start_game();
+        let (late_tx, mut late_rx) = mpsc::unbounded_channel();
+        join(&mut lobby, &mut broadcaster, "late", late_tx, &mut host_id);
+        let responses: Vec<_> = std::iter::from_fn(|| late_rx.try_recv().ok()).collect();
+        let started = ServerToClient::GameStarted {
+            seed: String::new(),
+            stake: 0,
+        };
+        assert!(contains_response_of_type(&responses, &started));
+        assert!(lobby.players()["late"].lobby_state.in_game);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_policy_decides_the_dropped_players_run() {
+        let start = |policy| {
+            let mut lobby = Lobby::new("TEST".to_string(), "default".to_string(), GameMode::Attrition);
+            lobby.lobby_options.disconnect_policy = policy;
+            let mut broadcaster = LobbyBroadcaster::new();
+            let mut host_id = String::new();
+            let (host_tx, host_rx) = mpsc::unbounded_channel();
+            let (guest_tx, _guest_rx) = mpsc::unbounded_channel();
+            for (id, tx) in [("host", host_tx), ("guest", guest_tx)] {
+                let profile = ClientProfile::default();
+                handle_client_join(&mut lobby, &mut broadcaster, id.to_string(), profile, tx, &mut host_id);
+            }
+            lobby.start_game();
+            (lobby, broadcaster, host_id, host_rx)
+        };
+        let (bot_tx, _bot_rx) = mpsc::unbounded_channel();
+
+        let (mut lobby, mut broadcaster, mut host_id, mut host_rx) = start(DisconnectPolicy::Bot);
+        assert!(handle_mid_game_disconnect(&mut lobby, &mut broadcaster, "guest", &bot_tx, &mut host_id));
+        let (bot_id, bot) = lobby.players().iter().find(|(id, _)| *id != "host").unwrap();
+        assert!(bot.profile.is_bot && bot.lobby_state.in_game);
+        let responses: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok()).collect();
+        let replaced = ServerToClient::ReplacedByBot {
+            player_id: bot_id.clone(),
+            previous_id: "guest".to_string(),
+        };
+        assert!(contains_response_of_type(&responses, &replaced));
+        assert!(lobby.started());
+
+        let (mut lobby, mut broadcaster, mut host_id, mut host_rx) = start(DisconnectPolicy::Forfeit);
+        assert!(!handle_mid_game_disconnect(&mut lobby, &mut broadcaster, "guest", &bot_tx, &mut host_id));
+        let responses: Vec<_> = std::iter::from_fn(|| host_rx.try_recv().ok()).collect();
+        assert!(responses.iter().any(|m| matches!(m.as_ref(), ServerToClient::WinGame { .. })));
+    }
 }
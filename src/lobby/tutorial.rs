@@ -0,0 +1,78 @@
+//! Tutorial lobbies: a new player against an easy bot, with the server explaining
+//! each step of a multiplayer game as the lobby reaches it. The bot plays the
+//! other side of the protocol, so no second human is needed.
+
+use super::bot::BotDifficulty;
+use super::phase::LobbyPhase;
+use crate::messages::ServerToClient;
+
+/// Who tutorial announcements come from
+pub const TUTORIAL_SPEAKER: &str = "tutorial";
+pub const TUTORIAL_BOT: BotDifficulty = BotDifficulty::Easy;
+pub const TUTORIAL_RULESET: &str = "ruleset_mp_standard";
+
+pub const WELCOME: &str = "Welcome! A practice bot joined as your opponent. \
+    Look over the lobby options, then start the game when you're ready.";
+
+#[derive(Debug, Clone, Default)]
+pub struct Tutorial {
+    /// Phases already explained; later visits stay quiet
+    narrated: Vec<LobbyPhase>,
+}
+
+impl Tutorial {
+    /// What to tell the player on reaching `phase`, the first time only
+    pub fn narrate(&mut self, phase: LobbyPhase) -> Option<&'static str> {
+        if self.narrated.contains(&phase) {
+            return None;
+        }
+        let text = narration(phase)?;
+        self.narrated.push(phase);
+        Some(text)
+    }
+}
+
+pub fn tutorial_announcement(text: &str) -> ServerToClient {
+    ServerToClient::Announcement {
+        player_id: TUTORIAL_SPEAKER.to_string(),
+        text: text.to_string(),
+    }
+}
+
+fn narration(phase: LobbyPhase) -> Option<&'static str> {
+    match phase {
+        // The welcome covers the first visit
+        LobbyPhase::Waiting => None,
+        LobbyPhase::Starting => Some(
+            "The game is on. You and the bot play the same seed, so you see the same shops and blinds.",
+        ),
+        LobbyPhase::InRound => Some(
+            "Play your blinds as in a normal run. Your opponent's lives and progress show at the side.",
+        ),
+        LobbyPhase::PvpBlind => {
+            Some("This is the PvP blind: you both play it, and the lower score loses a life.")
+        }
+        LobbyPhase::ShopPhase => {
+            Some("PvP blind done. Shop as usual; the next PvP blind waits until you both ready up.")
+        }
+        LobbyPhase::GameOver => Some(
+            "Game over, the last player with lives left wins. Start again to keep practising, \
+             or leave to find real opponents.",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_phase_is_explained_once() {
+        let mut tutorial = Tutorial::default();
+        assert_eq!(tutorial.narrate(LobbyPhase::Waiting), None);
+        assert!(tutorial.narrate(LobbyPhase::InRound).is_some());
+        assert!(tutorial.narrate(LobbyPhase::PvpBlind).is_some());
+        assert_eq!(tutorial.narrate(LobbyPhase::InRound), None);
+        assert!(tutorial.narrate(LobbyPhase::GameOver).is_some());
+    }
+}
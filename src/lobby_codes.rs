@@ -0,0 +1,105 @@
+//! Codes for new lobbies: five random letters and digits, or a pair of words
+//! (`CRISP-JOKER`) for servers that want codes easy to read out on stream. The word
+//! lists are hand-picked so no pair spells anything offensive.
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+
+/// Tries with the configured style before settling for an alphanumeric code
+const MAX_ATTEMPTS: usize = 32;
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const ALPHANUMERIC_LEN: usize = 5;
+
+const ADJECTIVES: &[&str] = &[
+    "AMBER", "BOLD", "BRAVE", "BRIGHT", "BRISK", "CALM", "CLEVER", "COSMIC", "CRISP", "DARING",
+    "EAGER", "FANCY", "FROSTY", "GENTLE", "GLAD", "GOLDEN", "GRAND", "HAPPY", "HONEST", "JOLLY",
+    "KEEN", "LIVELY", "LUCKY", "MELLOW", "MERRY", "MIGHTY", "NIMBLE", "PLUCKY", "PROUD", "QUICK",
+    "QUIET", "RAPID", "ROYAL", "RUSTY", "SHINY", "SILVER", "SLEEK", "SNOWY", "SOLAR", "SPRY",
+    "STEADY", "SUNNY", "SWIFT", "TIDY", "VIVID", "WARM", "WILD", "WISE",
+];
+
+const NOUNS: &[&str] = &[
+    "ACE", "ANTE", "BARON", "BLIND", "BLUEPRINT", "BOSS", "CARD", "CHIPS", "CLUB", "COMET",
+    "CROWN", "DECK", "DIAMOND", "DICE", "EMPEROR", "FLUSH", "FOOL", "HAND", "HEART", "HERMIT",
+    "JOKER", "JUGGLER", "KING", "KNIGHT", "LOVERS", "MAGICIAN", "MOON", "PAIR", "PLANET",
+    "QUEEN", "RIVER", "ROCKET", "SEAL", "SHOP", "SPADE", "STAKE", "STAR", "STRAIGHT", "SUIT",
+    "SUN", "TAROT", "TOWER", "VOUCHER", "WHEEL", "WORLD",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum LobbyCodeStyle {
+    /// `K3QZ7`
+    #[default]
+    #[serde(rename = "alphanumeric")]
+    Alphanumeric,
+    /// `CRISP-JOKER`
+    #[serde(rename = "words")]
+    Words,
+}
+
+impl std::str::FromStr for LobbyCodeStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alphanumeric" => Ok(Self::Alphanumeric),
+            "words" => Ok(Self::Words),
+            _ => Err(format!("Unknown lobby code style: {}", s)),
+        }
+    }
+}
+
+fn generate(style: LobbyCodeStyle) -> String {
+    let mut rng = rand::rng();
+    match style {
+        LobbyCodeStyle::Alphanumeric => (0..ALPHANUMERIC_LEN)
+            .map(|_| ALPHANUMERIC[rng.random_range(0..ALPHANUMERIC.len())] as char)
+            .collect(),
+        LobbyCodeStyle::Words => {
+            let adjective = ADJECTIVES.choose(&mut rng).copied().unwrap_or("LUCKY");
+            let noun = NOUNS.choose(&mut rng).copied().unwrap_or("JOKER");
+            format!("{}-{}", adjective, noun)
+        }
+    }
+}
+
+/// A code in `style` that `taken` doesn't claim. Once the word pairs run low, codes
+/// fall back to the far larger alphanumeric space.
+pub fn unique_code(style: LobbyCodeStyle, mut taken: impl FnMut(&str) -> bool) -> String {
+    for _ in 0..MAX_ATTEMPTS {
+        let code = generate(style);
+        if !taken(&code) {
+            return code;
+        }
+    }
+    loop {
+        let code = generate(LobbyCodeStyle::Alphanumeric);
+        if !taken(&code) {
+            return code;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_skip_taken_ones_and_fall_back() {
+        for list in [ADJECTIVES, NOUNS] {
+            assert!(list.iter().all(|word| word.chars().all(|c| c.is_ascii_uppercase())));
+            assert_eq!(list.iter().collect::<HashSet<_>>().len(), list.len());
+        }
+
+        let code = unique_code(LobbyCodeStyle::Words, |_| false);
+        let (adjective, noun) = code.split_once('-').unwrap();
+        assert!(ADJECTIVES.contains(&adjective) && NOUNS.contains(&noun));
+
+        // Every word pair taken, the alphanumeric space still has room
+        let code = unique_code(LobbyCodeStyle::Words, |code| code.contains('-'));
+        assert_eq!(code.len(), ALPHANUMERIC_LEN);
+        assert!(code.bytes().all(|b| ALPHANUMERIC.contains(&b)));
+    }
+}
@@ -1,14 +1,186 @@
-use crate::lobby::lobby_task;
-use crate::messages::{CoordinatorMessage, LobbyJoinData, LobbyMessage, ServerToClient};
+use crate::audit::{self, AuditEvent};
+use crate::config::CONFIG;
+use crate::lobby::checkpoint::LobbyCheckpoint;
+use crate::lobby::{lobby_task, pooled_lobby_task, restored_lobby_task};
+use crate::lobby_codes;
+use crate::lobby_limits::LobbyLimits;
+use crate::challenges::SharedChallenges;
+use crate::moderation::{PlayerReports, ReportStatus};
+use crate::presence::PresenceTracker;
+use crate::usage_stats;
+use crate::vanity::VanityCodes;
+use crate::messages::{
+    lobby_channel, CoordinatorHealth, CoordinatorMessage, LobbyAssignment, LobbyChannel,
+    LobbyJoinData, LobbyListing, LobbyMessage, LobbySummary, ListedLobby, MergeOffer,
+    ServerToClient,
+};
+use crate::webhooks::{self, WebhookPayload};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
 use tracing::info;
 
+/// How long after leaving (or crashing out of) a lobby `rejoinLast` still finds it
+const REJOIN_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// An idle lobby task waiting to be handed a lobby
+struct PooledLobby {
+    lobby_tx: LobbyChannel,
+    assign_tx: oneshot::Sender<LobbyAssignment>,
+}
+
+/// Last lobby per verified account, for `rejoinLast`
+#[derive(Default)]
+struct RecentLobbies {
+    /// account id -> (client id, lobby code, last seen in it)
+    entries: HashMap<String, (String, String, Instant)>,
+}
+
+impl RecentLobbies {
+    fn record(
+        &mut self,
+        account_id: Option<&str>,
+        client_id: &str,
+        lobby_code: &str,
+        now: Instant,
+    ) {
+        self.entries
+            .retain(|_, (_, _, seen)| now.duration_since(*seen) < REJOIN_CACHE_TTL);
+        if let Some(account_id) = account_id {
+            self.entries.insert(
+                account_id.to_string(),
+                (client_id.to_string(), lobby_code.to_string(), now),
+            );
+        }
+    }
+
+    /// The client left its lobby, the rejoin window starts now
+    fn left(&mut self, client_id: &str, now: Instant) {
+        for (owner, _, seen) in self.entries.values_mut() {
+            if owner == client_id {
+                *seen = now;
+            }
+        }
+    }
+
+    fn last_lobby(&self, account_id: &str, now: Instant) -> Option<&str> {
+        self.entries
+            .get(account_id)
+            .filter(|(_, _, seen)| now.duration_since(*seen) < REJOIN_CACHE_TTL)
+            .map(|(_, code, _)| code.as_str())
+    }
+
+    fn forget(&mut self, account_id: &str) {
+        self.entries.remove(account_id);
+    }
+}
+
+/// Waiting lobbies whose hosts agreed to a merge, oldest offer first
+#[derive(Default)]
+struct MergeOffers {
+    offers: Vec<MergeOffer>,
+}
+
+impl MergeOffers {
+    /// Take an offer, or replace the lobby's last one. Returns `(from, into)` codes when it
+    /// pairs with an earlier offer of the same mode and ruleset that has room for both;
+    /// the smaller lobby moves, on a tie the one that waited longer stays.
+    fn offer(&mut self, offer: MergeOffer) -> Option<(String, String)> {
+        self.withdraw(&offer.lobby_code);
+        let partner = self.offers.iter().position(|other| {
+            other.game_mode == offer.game_mode
+                && other.ruleset == offer.ruleset
+                && other.players + offer.players <= offer.max_players.min(other.max_players)
+        });
+        let Some(partner) = partner else {
+            self.offers.push(offer);
+            return None;
+        };
+        let earlier = self.offers.remove(partner);
+        if offer.players > earlier.players {
+            Some((earlier.lobby_code, offer.lobby_code))
+        } else {
+            Some((offer.lobby_code, earlier.lobby_code))
+        }
+    }
+
+    fn withdraw(&mut self, lobby_code: &str) {
+        self.offers.retain(|offer| offer.lobby_code != lobby_code);
+    }
+}
+
+/// Waiting lobbies whose hosts listed them publicly, oldest listing first
+#[derive(Default)]
+struct PublicLobbies {
+    listings: Vec<LobbyListing>,
+}
+
+impl PublicLobbies {
+    /// Add the lobby, or refresh its listing in place
+    fn list(&mut self, listing: LobbyListing) {
+        match self.listings.iter_mut().find(|l| l.lobby_code == listing.lobby_code) {
+            Some(existing) => *existing = listing,
+            None => self.listings.push(listing),
+        }
+    }
+
+    fn unlist(&mut self, lobby_code: &str) {
+        self.listings.retain(|listing| listing.lobby_code != lobby_code);
+    }
+
+    /// Listed lobbies for clients browsing; a filter naming another region than this
+    /// server's matches none of them
+    fn listed(&self, region: Option<&str>, server_region: Option<&str>) -> Vec<ListedLobby> {
+        if region.is_some_and(|region| {
+            !server_region.is_some_and(|server| server.eq_ignore_ascii_case(region))
+        }) {
+            return Vec::new();
+        }
+        self.listings
+            .iter()
+            .map(|listing| ListedLobby {
+                code: listing.lobby_code.clone(),
+                game_mode: listing.game_mode,
+                players: listing.players,
+                max_players: listing.max_players,
+                region: server_region.map(str::to_string),
+            })
+            .collect()
+    }
+}
+
 /// Simple lobby coordinator that routes messages to individual lobby tasks
-pub async fn lobby_coordinator(mut rx: mpsc::UnboundedReceiver<CoordinatorMessage>) {
-    let mut lobby_senders: HashMap<String, mpsc::UnboundedSender<LobbyMessage>> = HashMap::new();
+pub async fn lobby_coordinator(
+    mut rx: mpsc::UnboundedReceiver<CoordinatorMessage>,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+) {
+    let mut lobby_senders: HashMap<String, LobbyChannel> = HashMap::new();
     let mut client_lobbies: HashMap<String, String> = HashMap::new();
+    let mut limits = LobbyLimits::default();
+    let mut presence = PresenceTracker::default();
+    let mut vanity = VanityCodes::open(CONFIG.get().vanity_db_path.clone()).await;
+    let mut recent_lobbies = RecentLobbies::default();
+    let reports = PlayerReports::new(CONFIG.get().reports_db_path.clone());
+    let challenges = SharedChallenges::open(CONFIG.get().challenges_db_path.clone()).await;
+    usage_stats::open(CONFIG.get().usage_db_path.clone()).await;
+    let mut lobby_pool: Vec<PooledLobby> = Vec::new();
+    let mut merge_offers = MergeOffers::default();
+    let mut public_lobbies = PublicLobbies::default();
+    for _ in 0..CONFIG.get().lobby_pool_size {
+        tokio::spawn(pooled_lobby_task(coordinator_tx.clone()));
+    }
+
+    // Bring back games that were running when the server last stopped
+    for checkpoint in LobbyCheckpoint::load_all() {
+        let (lobby_tx, lobby_rx) = lobby_channel();
+        lobby_senders.insert(checkpoint.code.clone(), lobby_tx);
+        limits.lobby_opened(checkpoint.code.clone(), checkpoint.options.gamemode);
+        tokio::spawn(restored_lobby_task(checkpoint, lobby_rx, coordinator_tx.clone()));
+    }
+
+    // Set once the operator starts a handoff to a new instance
+    let mut draining: Option<(String, u16)> = None;
 
     info!("Lobby coordinator started");
 
@@ -19,24 +191,82 @@ pub async fn lobby_coordinator(mut rx: mpsc::UnboundedReceiver<CoordinatorMessag
                 ruleset,
                 game_mode,
                 client_profile,
+                lobby_generation,
                 request_tx,
                 client_response_tx,
+                tutorial,
             } => {
-                // Generate a simple lobby code
-                let lobby_code = generate_lobby_code();
+                if let Some((host, port)) = &draining {
+                    let _ = client_response_tx.send(Arc::new(ServerToClient::ServerRedirect {
+                        host: host.clone(),
+                        port: *port,
+                    }));
+                    continue;
+                }
+                let config = CONFIG.get();
+                if !limits.try_admit(&config, &client_id, game_mode) {
+                    let queue_position = config.lobby_queue_enabled.then(|| {
+                        limits.enqueue(client_id.clone(), game_mode, client_response_tx.clone())
+                    });
+                    let _ = client_response_tx.send(Arc::new(ServerToClient::ServerFull {
+                        message: "Server is at its lobby limit".to_string(),
+                        queue_position,
+                    }));
+                    continue;
+                }
+                let lobby_code = lobby_codes::unique_code(config.lobby_code_style, |code| {
+                    lobby_senders.contains_key(code) || vanity.is_claimed(code)
+                });
+                limits.lobby_opened(lobby_code.clone(), game_mode);
+                vanity.lobby_opened(client_profile.verified_account(), &client_id, &lobby_code);
+                recent_lobbies.record(
+                    client_profile.verified_account(),
+                    &client_id,
+                    &lobby_code,
+                    Instant::now(),
+                );
 
                 // Create the lobby task
-                let (lobby_tx, lobby_rx) = mpsc::unbounded_channel::<LobbyMessage>();
+                let lobby_tx = start_lobby(
+                    &mut lobby_pool,
+                    LobbyAssignment {
+                        lobby_code: lobby_code.clone(),
+                        ruleset: ruleset.clone(),
+                        game_mode,
+                    },
+                    &coordinator_tx,
+                );
                 lobby_senders.insert(lobby_code.clone(), lobby_tx.clone());
                 client_lobbies.insert(client_id.clone(), lobby_code.clone());
-                // Spawn the lobby task
-                tokio::spawn(lobby_task(lobby_code.clone(), lobby_rx, ruleset, game_mode));
+                presence.lobbies_changed();
+                audit::record(
+                    &lobby_code,
+                    AuditEvent::LobbyCreated {
+                        host_id: client_id.clone(),
+                        game_mode,
+                        ruleset: ruleset.clone(),
+                    },
+                );
+                webhooks::emit(WebhookPayload::LobbyCreated {
+                    lobby_code: lobby_code.clone(),
+                    game_mode,
+                    ruleset,
+                });
 
-                let _ = lobby_tx.send(LobbyMessage::client_join(
+                let _ = lobby_tx.send_control(LobbyMessage::client_join(
                     client_id.clone(),
                     client_profile.clone(),
                     client_response_tx.clone(),
+                    lobby_generation,
+                    None,
+                    None,
+                    None,
                 ));
+                if tutorial {
+                    let _ = lobby_tx.send_control(LobbyMessage::StartTutorial {
+                        client_id: client_id.clone(),
+                    });
+                }
                 // Give client communication channel to lobby
                 let _ = request_tx.send(LobbyJoinData {
                     lobby_code: lobby_code.clone(),
@@ -50,7 +280,21 @@ pub async fn lobby_coordinator(mut rx: mpsc::UnboundedReceiver<CoordinatorMessag
                 request_tx,
                 client_response_tx,
                 client_profile,
+                lobby_generation,
+                resume_after,
+                invite,
+                reconnect_token,
             } => {
+                // Real codes win, otherwise try it as a vanity code
+                let lobby_code = match vanity.resolve(&lobby_code) {
+                    _ if lobby_senders.contains_key(&lobby_code) => lobby_code,
+                    Some(Ok(hosted_code)) => hosted_code,
+                    Some(Err(message)) => {
+                        let _ = client_response_tx.send(Arc::new(ServerToClient::error(message)));
+                        continue;
+                    }
+                    None => lobby_code,
+                };
                 if let Some(lobby_tx) = lobby_senders.get(&lobby_code) {
                     // Give client communication channel to lobby
                     let _ = request_tx.send(LobbyJoinData {
@@ -58,17 +302,30 @@ pub async fn lobby_coordinator(mut rx: mpsc::UnboundedReceiver<CoordinatorMessag
                         lobby_tx: lobby_tx.clone(),
                     });
                     // Try to forward to lobby task
-                    if let Err(_) = lobby_tx.send(LobbyMessage::client_join(
+                    if lobby_tx.send_control(LobbyMessage::client_join(
                         client_id.clone(),
                         client_profile.clone(),
                         client_response_tx.clone(),
-                    )) {
+                        lobby_generation,
+                        resume_after,
+                        invite,
+                        reconnect_token,
+                    ))
+                    .is_err()
+                    {
                         // Failed to send to lobby, send error response
                         let error_response =
                             Arc::new(ServerToClient::error("Failed to join lobby"));
                         let _ = client_response_tx.send(error_response);
                     } else {
                         client_lobbies.insert(client_id.clone(), lobby_code.clone());
+                        presence.lobbies_changed();
+                        recent_lobbies.record(
+                            client_profile.verified_account(),
+                            &client_id,
+                            &lobby_code,
+                            Instant::now(),
+                        );
                     }
                 } else {
                     // Lobby doesn't exist
@@ -77,33 +334,596 @@ pub async fn lobby_coordinator(mut rx: mpsc::UnboundedReceiver<CoordinatorMessag
                 }
             }
 
+            CoordinatorMessage::SpectateLobby {
+                client_id,
+                lobby_code,
+                request_tx,
+                client_response_tx,
+                subscriptions,
+            } => {
+                let lobby_code = match vanity.resolve(&lobby_code) {
+                    _ if lobby_senders.contains_key(&lobby_code) => lobby_code,
+                    Some(Ok(hosted_code)) => hosted_code,
+                    Some(Err(message)) => {
+                        let _ = client_response_tx.send(Arc::new(ServerToClient::error(message)));
+                        continue;
+                    }
+                    None => lobby_code,
+                };
+                if client_lobbies.get(&client_id) == Some(&lobby_code) {
+                    let _ = client_response_tx
+                        .send(Arc::new(ServerToClient::error("You are playing in this lobby")));
+                    continue;
+                }
+                let Some(lobby_tx) = lobby_senders.get(&lobby_code) else {
+                    let _ = client_response_tx
+                        .send(Arc::new(ServerToClient::error("Lobby does not exist")));
+                    continue;
+                };
+                // The lobby decides whether it can be watched and answers `request_tx`
+                if lobby_tx
+                    .send_control(LobbyMessage::SpectatorJoin {
+                        client_id,
+                        client_response_tx: client_response_tx.clone(),
+                        subscriptions,
+                        request_tx,
+                        join_data: LobbyJoinData {
+                            lobby_code,
+                            lobby_tx: lobby_tx.clone(),
+                        },
+                    })
+                    .is_err()
+                {
+                    let _ = client_response_tx
+                        .send(Arc::new(ServerToClient::error("Failed to spectate lobby")));
+                }
+            }
+
+            CoordinatorMessage::RejoinLast {
+                client_id,
+                request_tx,
+                client_response_tx,
+                client_profile,
+                lobby_generation,
+                reconnect_token,
+            } => {
+                // A claimed id could be anyone's, and would lead into their lobby
+                let Some(account_id) = client_profile.verified_account().map(str::to_string) else {
+                    let _ = client_response_tx.send(Arc::new(ServerToClient::error(
+                        "Rejoining needs a verified account",
+                    )));
+                    continue;
+                };
+                let Some(lobby_code) = recent_lobbies
+                    .last_lobby(&account_id, Instant::now())
+                    .map(str::to_string)
+                else {
+                    let _ = client_response_tx
+                        .send(Arc::new(ServerToClient::error("No recent lobby to rejoin")));
+                    continue;
+                };
+                if !lobby_senders.contains_key(&lobby_code) {
+                    recent_lobbies.forget(&account_id);
+                    let _ = client_response_tx
+                        .send(Arc::new(ServerToClient::error("Your last lobby has closed")));
+                    continue;
+                }
+                // From here on it is a regular join
+                let _ = coordinator_tx.send(CoordinatorMessage::JoinLobby {
+                    client_id,
+                    lobby_code,
+                    request_tx,
+                    client_response_tx,
+                    client_profile,
+                    lobby_generation,
+                    resume_after: None,
+                    invite: None,
+                    reconnect_token,
+                });
+            }
+
             CoordinatorMessage::LobbyShutdown { lobby_code } => {
                 lobby_senders.remove(&lobby_code);
+                merge_offers.withdraw(&lobby_code);
+                public_lobbies.unlist(&lobby_code);
+                presence.lobby_closed(&lobby_code);
+                vanity.lobby_closed(&lobby_code);
+                limits.lobby_closed(&CONFIG.get(), &lobby_code);
+                if draining.is_some() && lobby_senders.is_empty() {
+                    info!("Drain complete, all games finished");
+                    std::process::exit(0);
+                }
+            }
+
+            CoordinatorMessage::LobbyPooled {
+                lobby_tx,
+                assign_tx,
+            } => {
+                // Dropping the assignment sender ends tasks the pool has no room for
+                if draining.is_none() && lobby_pool.len() < CONFIG.get().lobby_pool_size {
+                    lobby_pool.push(PooledLobby {
+                        lobby_tx,
+                        assign_tx,
+                    });
+                }
+            }
+
+            CoordinatorMessage::ListOpenLobbies {
+                region,
+                client_response_tx,
+            } => {
+                let lobbies =
+                    public_lobbies.listed(region.as_deref(), CONFIG.get().region.as_deref());
+                let _ = client_response_tx.send(Arc::new(ServerToClient::LobbyList { lobbies }));
+            }
+
+            CoordinatorMessage::SetVanityCode {
+                account_id,
+                code,
+                client_response_tx,
+            } => {
+                let stored = vanity.change(account_id.clone(), code);
+                let coordinator_tx = coordinator_tx.clone();
+                tokio::spawn(async move {
+                    let _ = coordinator_tx.send(CoordinatorMessage::VanityCodeChanged {
+                        account_id,
+                        code: stored.await,
+                        client_response_tx,
+                    });
+                });
+            }
+
+            CoordinatorMessage::VanityCodeChanged {
+                account_id,
+                code,
+                client_response_tx,
+            } => {
+                let response = match code {
+                    Ok(code) => {
+                        vanity.changed(&account_id, code.clone());
+                        ServerToClient::VanityCode { code }
+                    }
+                    Err(message) => ServerToClient::error(message),
+                };
+                let _ = client_response_tx.send(Arc::new(response));
+            }
+
+            CoordinatorMessage::UploadChallenge {
+                account_id,
+                definition,
+                client_response_tx,
+            } => {
+                let uploaded = challenges.upload(account_id.clone(), definition);
+                tokio::spawn(async move {
+                    let response = match uploaded.await {
+                        Ok(id) => {
+                            let uploader = account_id.as_deref().unwrap_or("anonymous");
+                            info!("Challenge {} uploaded ({})", id, uploader);
+                            ServerToClient::ChallengeUploaded { id }
+                        }
+                        Err(message) => ServerToClient::error(message),
+                    };
+                    let _ = client_response_tx.send(Arc::new(response));
+                });
+            }
+
+            CoordinatorMessage::GetChallenge {
+                id,
+                client_response_tx,
+            } => {
+                let definition = challenges.get(id.clone());
+                tokio::spawn(async move {
+                    let response = match definition.await {
+                        Some(definition) => ServerToClient::Challenge { id, definition },
+                        None => ServerToClient::error("Unknown challenge"),
+                    };
+                    let _ = client_response_tx.send(Arc::new(response));
+                });
+            }
+
+            CoordinatorMessage::LobbyGameState {
+                lobby_code,
+                started,
+            } => {
+                presence.lobby_game_state(lobby_code, started);
+            }
+
+            CoordinatorMessage::OfferMerge { offer } => {
+                let Some((from_code, into_code)) = merge_offers.offer(offer) else {
+                    continue;
+                };
+                if let (Some(from_tx), Some(into_tx)) =
+                    (lobby_senders.get(&from_code), lobby_senders.get(&into_code))
+                {
+                    info!("Merging lobby {} into {}", from_code, into_code);
+                    let _ = from_tx.send_control(LobbyMessage::MergeInto {
+                        into_code,
+                        into_tx: into_tx.clone(),
+                        coordinator_tx: coordinator_tx.clone(),
+                    });
+                }
+            }
+
+            CoordinatorMessage::WithdrawMerge { lobby_code } => {
+                merge_offers.withdraw(&lobby_code);
+            }
+
+            CoordinatorMessage::ListLobby { listing } => {
+                public_lobbies.list(listing);
+            }
+
+            CoordinatorMessage::UnlistLobby { lobby_code } => {
+                public_lobbies.unlist(&lobby_code);
+            }
+
+            CoordinatorMessage::LobbyMerged {
+                from_code,
+                into_code,
+                moved,
+            } => {
+                for (client_id, lobby_code) in client_lobbies.iter_mut() {
+                    if *lobby_code == from_code && moved.contains(client_id) {
+                        *lobby_code = into_code.clone();
+                    }
+                }
+                // Whoever didn't fit is out of a lobby
+                client_lobbies.retain(|_, lobby_code| *lobby_code != from_code);
+                presence.lobbies_changed();
+            }
+
+            CoordinatorMessage::FindClientLobby {
+                client_id,
+                reply_tx,
+            } => {
+                let joined = client_lobbies.get(&client_id).and_then(|lobby_code| {
+                    lobby_senders.get(lobby_code).map(|lobby_tx| LobbyJoinData {
+                        lobby_code: lobby_code.clone(),
+                        lobby_tx: lobby_tx.clone(),
+                    })
+                });
+                let _ = reply_tx.send(joined);
+            }
+
+            CoordinatorMessage::ClientRegistered {
+                client_id,
+                client_profile,
+            } => {
+                presence.client_registered(client_id, &client_profile);
+            }
+
+            CoordinatorMessage::ClientOffline { client_id } => {
+                // Clients queued for a lobby slot never joined one to leave
+                limits.remove_client(&CONFIG.get(), &client_id);
+                presence.client_offline(&client_id);
+            }
+
+            CoordinatorMessage::SubscribePresence { update_tx } => {
+                presence.subscribe(update_tx, &client_lobbies);
+            }
+
+            CoordinatorMessage::Drain { host, port } => {
+                info!("Draining, redirecting clients to {}:{}", host, port);
+                // New lobbies go to the next instance, so the idle tasks can go
+                lobby_pool.clear();
+                if lobby_senders.is_empty() {
+                    info!("Drain complete, no running lobbies");
+                    std::process::exit(0);
+                }
+                for lobby_tx in lobby_senders.values() {
+                    let _ = lobby_tx.send_control(LobbyMessage::Drain {
+                        host: host.clone(),
+                        port,
+                        coordinator_tx: coordinator_tx.clone(),
+                    });
+                }
+                draining = Some((host, port));
+            }
+
+            CoordinatorMessage::Health { reply_tx } => {
+                let _ = reply_tx.send(CoordinatorHealth {
+                    lobbies: lobby_senders.len(),
+                    players_in_lobbies: client_lobbies.len(),
+                    draining: draining.is_some(),
+                });
+            }
+
+            CoordinatorMessage::ListLobbies { reply_tx } => {
+                let mut summaries: Vec<LobbySummary> = lobby_senders
+                    .keys()
+                    .map(|code| LobbySummary {
+                        code: code.clone(),
+                        player_count: client_lobbies.values().filter(|c| *c == code).count(),
+                    })
+                    .collect();
+                summaries.sort_by(|a, b| a.code.cmp(&b.code));
+                let _ = reply_tx.send(summaries);
+            }
+
+            CoordinatorMessage::InspectLobby {
+                lobby_code,
+                reply_tx,
+            } => {
+                let Some(lobby_tx) = lobby_senders.get(&lobby_code) else {
+                    let _ = reply_tx.send(None);
+                    continue;
+                };
+                // Let the lobby answer directly so the coordinator never waits on it
+                let (snapshot_tx, snapshot_rx) = oneshot::channel();
+                let _ = lobby_tx.send_control(LobbyMessage::Snapshot {
+                    reply_tx: snapshot_tx,
+                });
+                tokio::spawn(async move {
+                    let _ = reply_tx.send(snapshot_rx.await.ok());
+                });
+            }
+
+            CoordinatorMessage::SetActionAudit {
+                lobby_code,
+                enabled,
+                reply_tx,
+            } => {
+                let found = lobby_senders.get(&lobby_code).is_some_and(|lobby_tx| {
+                    lobby_tx
+                        .send_control(LobbyMessage::SetActionAudit { enabled })
+                        .is_ok()
+                });
+                let _ = reply_tx.send(found);
+            }
+
+            CoordinatorMessage::KickPlayer {
+                client_id,
+                reason,
+                reply_tx,
+            } => {
+                let kicked = kick_client(&mut client_lobbies, &lobby_senders, client_id, reason);
+                presence.lobbies_changed();
+                let _ = reply_tx.send(kicked);
+            }
+
+            CoordinatorMessage::PlayerReported { report } => {
+                let summary = format!(
+                    "Player {} reported {} in lobby {}: {}",
+                    report.reporter_id, report.reported_id, report.lobby_code, report.report_id
+                );
+                let filed = reports.file(report);
+                tokio::spawn(async move {
+                    if filed.await {
+                        info!("{}", summary);
+                    }
+                });
+            }
+
+            CoordinatorMessage::ListReports { reply_tx } => {
+                let open = reports.open_reports();
+                tokio::spawn(async move {
+                    let _ = reply_tx.send(open.await);
+                });
+            }
+
+            CoordinatorMessage::GetReport {
+                report_id,
+                reply_tx,
+            } => {
+                let report = reports.get(report_id);
+                tokio::spawn(async move {
+                    let _ = reply_tx.send(report.await);
+                });
+            }
+
+            CoordinatorMessage::ResolveReport {
+                report_id,
+                kick_reason,
+                reply_tx,
+            } => {
+                let status = match kick_reason {
+                    Some(_) => ReportStatus::Actioned,
+                    None => ReportStatus::Dismissed,
+                };
+                let resolved = reports.resolve(report_id, status);
+                let coordinator_tx = coordinator_tx.clone();
+                // The kick goes back through the coordinator once the report is closed
+                tokio::spawn(async move {
+                    let Some(report) = resolved.await else {
+                        let _ = reply_tx.send(None);
+                        return;
+                    };
+                    let Some(reason) = kick_reason else {
+                        let _ = reply_tx.send(Some(false));
+                        return;
+                    };
+                    let (kick_tx, kick_rx) = oneshot::channel();
+                    let _ = coordinator_tx.send(CoordinatorMessage::KickPlayer {
+                        client_id: report.reported_id,
+                        reason,
+                        reply_tx: kick_tx,
+                    });
+                    let _ = reply_tx.send(Some(kick_rx.await.unwrap_or(false)));
+                });
+            }
+
+            CoordinatorMessage::BroadcastNotice { message } => {
+                for lobby_tx in lobby_senders.values() {
+                    let _ = lobby_tx.send_control(LobbyMessage::ServerNotice {
+                        message: message.clone(),
+                    });
+                }
             }
 
             CoordinatorMessage::ClientDisconnected {
                 client_id,
                 coordinator_tx,
+                connection_lost,
             } => {
+                limits.remove_client(&CONFIG.get(), &client_id);
+                vanity.host_left(&client_id);
+                recent_lobbies.left(&client_id, Instant::now());
                 if let Some(lobby_code) = client_lobbies.remove(&client_id) {
+                    presence.lobbies_changed();
                     if let Some(lobby_tx) = lobby_senders.get(&lobby_code) {
-                        let _ = lobby_tx.send(LobbyMessage::ClientLeave {
+                        let _ = lobby_tx.send_control(LobbyMessage::ClientLeave {
                             client_id: client_id.clone(),
                             coordinator_tx: coordinator_tx.clone(),
+                            connection_lost,
                         });
                     }
                 }
             }
         }
+        presence.publish(&client_lobbies);
+    }
+}
+
+/// Hand a lobby to a pooled task, or spawn one when the pool is empty. Taking from the
+/// pool starts a replacement, which joins it once running.
+fn start_lobby(
+    lobby_pool: &mut Vec<PooledLobby>,
+    mut assignment: LobbyAssignment,
+    coordinator_tx: &mpsc::UnboundedSender<CoordinatorMessage>,
+) -> LobbyChannel {
+    while let Some(pooled) = lobby_pool.pop() {
+        match pooled.assign_tx.send(assignment) {
+            Ok(()) => {
+                tokio::spawn(pooled_lobby_task(coordinator_tx.clone()));
+                return pooled.lobby_tx;
+            }
+            // The task is gone, try the next one
+            Err(returned) => assignment = returned,
+        }
+    }
+    let (lobby_tx, lobby_rx) = lobby_channel();
+    tokio::spawn(lobby_task(
+        assignment.lobby_code,
+        lobby_rx,
+        assignment.ruleset,
+        assignment.game_mode,
+        coordinator_tx.clone(),
+    ));
+    lobby_tx
+}
+
+/// Remove a client from whatever lobby it is in; false when it isn't in one
+fn kick_client(
+    client_lobbies: &mut HashMap<String, String>,
+    lobby_senders: &HashMap<String, LobbyChannel>,
+    client_id: String,
+    reason: String,
+) -> bool {
+    match client_lobbies.remove(&client_id) {
+        Some(lobby_code) => lobby_senders.get(&lobby_code).is_some_and(|lobby_tx| {
+            lobby_tx
+                .send_control(LobbyMessage::Kick { client_id, reason })
+                .is_ok()
+        }),
+        None => false,
     }
 }
 
-/// Generate a simple 4-character lobby code
-fn generate_lobby_code() -> String {
-    use rand::Rng;
-    let chars = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-    let mut rng = rand::rng();
-    (0..5)
-        .map(|_| chars.chars().nth(rng.random_range(0..chars.len())).unwrap())
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_mode::GameMode;
+
+    #[tokio::test]
+    async fn test_new_lobbies_go_to_pooled_tasks_first() {
+        let (coordinator_tx, mut coordinator_rx) = mpsc::unbounded_channel();
+        let (lobby_tx, mut lobby_rx) = lobby_channel();
+        let (assign_tx, assign_rx) = oneshot::channel();
+        let mut lobby_pool = vec![PooledLobby {
+            lobby_tx,
+            assign_tx,
+        }];
+
+        let handed_out = start_lobby(
+            &mut lobby_pool,
+            LobbyAssignment {
+                lobby_code: "ABCDE".to_string(),
+                ruleset: "default".to_string(),
+                game_mode: GameMode::Attrition,
+            },
+            &coordinator_tx,
+        );
+        assert!(lobby_pool.is_empty());
+        assert_eq!(assign_rx.await.unwrap().lobby_code, "ABCDE");
+        // The channel handed out reaches the pooled task
+        handed_out
+            .send_control(LobbyMessage::Kick {
+                client_id: "p1".to_string(),
+                reason: String::new(),
+            })
+            .unwrap();
+        assert!(lobby_rx.recv().await.is_some());
+        // And a replacement offers itself to the pool
+        assert!(matches!(
+            coordinator_rx.recv().await,
+            Some(CoordinatorMessage::LobbyPooled { .. })
+        ));
+    }
+
+    #[test]
+    fn test_merge_pairs_lobbies_with_room_for_both() {
+        let offer = |code: &str, game_mode, players| MergeOffer {
+            lobby_code: code.to_string(),
+            game_mode,
+            ruleset: "default".to_string(),
+            players,
+            max_players: 4,
+        };
+        let mut offers = MergeOffers::default();
+        assert_eq!(offers.offer(offer("AAAAA", GameMode::Survival, 3)), None);
+        // Another mode, or too many players between them
+        assert_eq!(offers.offer(offer("BBBBB", GameMode::Attrition, 1)), None);
+        assert_eq!(offers.offer(offer("CCCCC", GameMode::Survival, 2)), None);
+        // The smaller lobby moves into the bigger one
+        assert_eq!(
+            offers.offer(offer("DDDDD", GameMode::Survival, 1)),
+            Some(("DDDDD".to_string(), "AAAAA".to_string()))
+        );
+        // A lobby that grew replaces its offer, and stays put on a tie with a newer one
+        offers.offer(offer("CCCCC", GameMode::Survival, 2));
+        assert_eq!(
+            offers.offer(offer("EEEEE", GameMode::Survival, 2)),
+            Some(("EEEEE".to_string(), "CCCCC".to_string()))
+        );
+        offers.withdraw("BBBBB");
+        assert_eq!(offers.offer(offer("FFFFF", GameMode::Attrition, 1)), None);
+    }
+
+    #[test]
+    fn test_listing_filters_by_the_servers_region() {
+        let mut lobbies = PublicLobbies::default();
+        let listing = |players| LobbyListing {
+            lobby_code: "AAAAA".to_string(),
+            game_mode: GameMode::Survival,
+            players,
+            max_players: 4,
+        };
+        lobbies.list(listing(1));
+        lobbies.list(listing(2));
+        let listed = lobbies.listed(None, Some("eu-west"));
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].players, 2);
+        assert_eq!(listed[0].region.as_deref(), Some("eu-west"));
+        assert_eq!(lobbies.listed(Some("EU-WEST"), Some("eu-west")), listed);
+        assert!(lobbies.listed(Some("us-east"), Some("eu-west")).is_empty());
+        // An untagged server can't be in the region asked for
+        assert!(lobbies.listed(Some("eu-west"), None).is_empty());
+        assert_eq!(lobbies.listed(None, None).len(), 1);
+        lobbies.unlist("AAAAA");
+        assert!(lobbies.listed(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_recent_lobby_expires_after_leaving() {
+        let mut recent = RecentLobbies::default();
+        let joined = Instant::now();
+        recent.record(Some("acc"), "c1", "ABCDE", joined);
+        recent.record(None, "c2", "FGHIJ", joined);
+        assert_eq!(recent.last_lobby("acc", joined), Some("ABCDE"));
+
+        // The window counts from leaving, not joining
+        let left = joined + REJOIN_CACHE_TTL * 2;
+        recent.left("c1", left);
+        assert_eq!(recent.last_lobby("acc", left + REJOIN_CACHE_TTL / 2), Some("ABCDE"));
+        assert_eq!(recent.last_lobby("acc", left + REJOIN_CACHE_TTL), None);
+    }
 }
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::{config::ServerConfig, game_mode::GameMode, messages::ServerToClient};
+
+/// How long a client told a lobby slot is free has to claim it
+pub const QUEUE_GRANT_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct QueuedClient {
+    client_id: String,
+    game_mode: GameMode,
+    response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+}
+
+/// Coordinator-side caps on running lobbies and the queue of clients waiting for one
+#[derive(Default)]
+pub struct LobbyLimits {
+    lobby_modes: HashMap<String, GameMode>,
+    queue: VecDeque<QueuedClient>,
+    /// Clients promoted out of the queue, holding a slot until they retry or it lapses
+    grants: HashMap<String, (GameMode, Instant)>,
+}
+
+impl LobbyLimits {
+    fn has_room(&self, config: &ServerConfig, game_mode: GameMode) -> bool {
+        let in_use = self.lobby_modes.len() + self.grants.len();
+        if config.max_lobbies > 0 && in_use >= config.max_lobbies {
+            return false;
+        }
+        let Some(mode_cap) = config.max_lobbies_per_mode.get(&game_mode) else {
+            return true;
+        };
+        let in_mode = self.lobby_modes.values().filter(|m| **m == game_mode).count()
+            + self.grants.values().filter(|(m, _)| *m == game_mode).count();
+        in_mode < *mode_cap
+    }
+
+    /// Whether the client may open a lobby now, using up their grant if they hold one
+    pub fn try_admit(&mut self, config: &ServerConfig, client_id: &str, game_mode: GameMode) -> bool {
+        self.promote(config);
+        if let Some((granted_mode, _)) = self.grants.remove(client_id) {
+            if granted_mode == game_mode {
+                return true;
+            }
+            // Asked for a different mode than they queued for, re-check from scratch
+            self.promote(config);
+        }
+        self.has_room(config, game_mode)
+    }
+
+    /// Add a client to the back of the queue, returning their 1-based position
+    pub fn enqueue(
+        &mut self,
+        client_id: String,
+        game_mode: GameMode,
+        response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    ) -> usize {
+        if let Some(index) = self.queue.iter().position(|q| q.client_id == client_id) {
+            return index + 1;
+        }
+        self.queue.push_back(QueuedClient {
+            client_id,
+            game_mode,
+            response_tx,
+        });
+        self.queue.len()
+    }
+
+    pub fn lobby_opened(&mut self, lobby_code: String, game_mode: GameMode) {
+        self.lobby_modes.insert(lobby_code, game_mode);
+    }
+
+    pub fn lobby_closed(&mut self, config: &ServerConfig, lobby_code: &str) {
+        self.lobby_modes.remove(lobby_code);
+        self.promote(config);
+    }
+
+    /// Forget a disconnected client, handing any slot they held to the next in line
+    pub fn remove_client(&mut self, config: &ServerConfig, client_id: &str) {
+        let queued_before = self.queue.len();
+        self.queue.retain(|q| q.client_id != client_id);
+        let held_grant = self.grants.remove(client_id).is_some();
+        if held_grant || self.queue.len() != queued_before {
+            self.promote(config);
+            self.send_positions();
+        }
+    }
+
+    /// Drop lapsed grants and tell every queued client that now fits that a slot is free
+    fn promote(&mut self, config: &ServerConfig) {
+        let now = Instant::now();
+        self.grants.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let mut promoted = false;
+        let mut index = 0;
+        while index < self.queue.len() {
+            if !self.has_room(config, self.queue[index].game_mode) {
+                index += 1;
+                continue;
+            }
+            let Some(next) = self.queue.remove(index) else {
+                break;
+            };
+            debug!("Lobby slot granted to queued client {}", next.client_id);
+            let _ = next.response_tx.send(Arc::new(ServerToClient::LobbySlotAvailable {}));
+            self.grants
+                .insert(next.client_id, (next.game_mode, now + QUEUE_GRANT_TIMEOUT));
+            promoted = true;
+        }
+        if promoted {
+            self.send_positions();
+        }
+    }
+
+    fn send_positions(&self) {
+        for (index, queued) in self.queue.iter().enumerate() {
+            let _ = queued
+                .response_tx
+                .send(Arc::new(ServerToClient::LobbyQueuePosition { position: index + 1 }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queued_client_gets_slot_when_lobby_closes() {
+        let config = ServerConfig {
+            max_lobbies: 1,
+            ..ServerConfig::default()
+        };
+        let mut limits = LobbyLimits::default();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        assert!(limits.try_admit(&config, "c1", GameMode::Attrition));
+        limits.lobby_opened("AAAAA".to_string(), GameMode::Attrition);
+
+        assert!(!limits.try_admit(&config, "c2", GameMode::Clash));
+        assert_eq!(limits.enqueue("c2".to_string(), GameMode::Clash, tx), 1);
+
+        limits.lobby_closed(&config, "AAAAA");
+        assert!(matches!(
+            rx.try_recv().unwrap().as_ref(),
+            ServerToClient::LobbySlotAvailable {}
+        ));
+        // The freed slot is held for the queued client
+        assert!(!limits.try_admit(&config, "c3", GameMode::Attrition));
+        assert!(limits.try_admit(&config, "c2", GameMode::Clash));
+    }
+}
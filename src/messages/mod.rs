@@ -1,48 +1,271 @@
 mod msg_client_to_server;
 mod msg_coordinator;
 mod msg_server_to_client;
+pub mod protocol;
 
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
+use tracing::warn;
 
 use crate::client::ClientProfile;
+use crate::invites::Invite;
+use crate::lobby::BotDifficulty;
+use crate::lobby::lobby::Lobby;
+use crate::metrics::{METRICS, Metrics};
 
 pub use self::msg_client_to_server::*;
 pub use self::msg_coordinator::*;
 pub use self::msg_server_to_client::*;
 
+/// A player handed from one lobby to another when they merge
+#[derive(Debug)]
+pub struct MergingPlayer {
+    pub client_id: String,
+    pub client_profile: ClientProfile,
+    pub client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    pub lobby_generation: u64,
+    /// Bots move too; the lobby taking them in starts its own at this difficulty
+    pub bot: Option<BotDifficulty>,
+}
+
 #[derive(Debug)]
 pub enum LobbyMessage {
     // Regular client actions - easy to handle
     ClientAction {
         client_id: String,
         action: ClientToServer,
+        seq: Option<u64>,
+        /// The client's stay in the lobby the action was sent for, see `ClientJoin`
+        lobby_generation: u64,
     },
     // Special events with all needed data upfront
     ClientJoin {
         client_id: String,
         client_profile: ClientProfile,
         client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+        /// Bumped by the client on every lobby it enters; actions carrying another
+        /// one were sent on a stale channel and are dropped
+        lobby_generation: u64,
+        /// Resuming a held seat: the last numbered message the client got
+        resume_after: Option<u64>,
+        /// Verified invite the client joins with, the lobby checks it wasn't used
+        invite: Option<Invite>,
+        /// Proves the client owns a seat held since its connection dropped
+        reconnect_token: Option<String>,
     },
     ClientLeave {
         client_id: String,
         coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+        /// Mid-game drops hold the player's seat for a while instead of leaving
+        connection_lost: bool,
+    },
+    /// A client watching the lobby without playing in it
+    SpectatorJoin {
+        client_id: String,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+        subscriptions: Subscriptions,
+        /// Gets `join_data` once the lobby lets the client watch, dropped when it doesn't
+        request_tx: oneshot::Sender<LobbyJoinData>,
+        join_data: LobbyJoinData,
+    },
+    SpectatorLeave {
+        client_id: String,
+    },
+    /// A spectator changed which message classes it wants
+    SpectatorSubscriptions {
+        client_id: String,
+        subscriptions: Subscriptions,
+    },
+    // Measured round-trip time from the client's ping loop
+    LatencyUpdate {
+        client_id: String,
+        rtt_ms: u32,
+    },
+    // Operator requests routed through the coordinator
+    Snapshot {
+        reply_tx: oneshot::Sender<Lobby>,
+    },
+    Kick {
+        client_id: String,
+        reason: String,
+    },
+    ServerNotice {
+        message: String,
+    },
+    /// Operator: switch action audit mode
+    SetActionAudit {
+        enabled: bool,
+    },
+    /// Coordinator: the creator asked for a tutorial, add its bot and start explaining
+    StartTutorial {
+        client_id: String,
+    },
+    /// Coordinator: move this lobby's players into `into_code` and close
+    MergeInto {
+        into_code: String,
+        into_tx: LobbyChannel,
+        coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+    },
+    /// Asked of the lobby to merge into: hold room for all `players` of `from_code`
+    /// and answer whether they fit, before anyone moves
+    ReserveMerge {
+        from_code: String,
+        players: usize,
+        reply_tx: oneshot::Sender<Result<(), &'static str>>,
+    },
+    /// Players of a lobby merging into this one
+    MergeIn {
+        from_code: String,
+        players: Vec<MergingPlayer>,
+        coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+    },
+    /// The server is draining: redirect players and close once no game is running
+    Drain {
+        host: String,
+        port: u16,
+        coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
     },
 }
 impl LobbyMessage {
-    pub fn client_action(client_id: String, action: ClientToServer) -> Self {
-        Self::ClientAction { client_id, action }
+    /// Messages that can be dropped when the lobby is overloaded
+    pub fn is_sheddable(&self) -> bool {
+        match self {
+            Self::ClientAction { action, .. } => action.is_sheddable(),
+            Self::LatencyUpdate { .. } => true,
+            Self::ClientJoin { .. }
+            | Self::ClientLeave { .. }
+            | Self::SpectatorJoin { .. }
+            | Self::SpectatorLeave { .. }
+            | Self::SpectatorSubscriptions { .. }
+            | Self::Snapshot { .. }
+            | Self::Kick { .. }
+            | Self::ServerNotice { .. }
+            | Self::SetActionAudit { .. }
+            | Self::StartTutorial { .. }
+            | Self::MergeInto { .. }
+            | Self::ReserveMerge { .. }
+            | Self::MergeIn { .. }
+            | Self::Drain { .. } => false,
+        }
+    }
+
+    pub fn client_action(
+        client_id: String,
+        action: ClientToServer,
+        seq: Option<u64>,
+        lobby_generation: u64,
+    ) -> Self {
+        Self::ClientAction {
+            client_id,
+            action,
+            seq,
+            lobby_generation,
+        }
     }
 
     pub fn client_join(
         client_id: String,
         client_profile: ClientProfile,
         client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+        lobby_generation: u64,
+        resume_after: Option<u64>,
+        invite: Option<Invite>,
+        reconnect_token: Option<String>,
     ) -> Self {
         Self::ClientJoin {
             client_id,
             client_profile,
             client_response_tx,
+            lobby_generation,
+            resume_after,
+            invite,
+            reconnect_token,
+        }
+    }
+}
+
+/// Maximum number of queued client actions per lobby before shedding kicks in
+pub const LOBBY_ACTION_CAPACITY: usize = 256;
+
+/// Sending half of a lobby's inbox.
+///
+/// Join/leave go through an unbounded control lane that the lobby task always
+/// drains first; client actions go through a bounded lane so a flooding client
+/// can't grow the lobby's queue without limit.
+#[derive(Debug, Clone)]
+pub struct LobbyChannel {
+    control_tx: mpsc::UnboundedSender<LobbyMessage>,
+    action_tx: mpsc::Sender<LobbyMessage>,
+}
+
+pub struct LobbyReceiver {
+    control_rx: mpsc::UnboundedReceiver<LobbyMessage>,
+    action_rx: mpsc::Receiver<LobbyMessage>,
+}
+
+pub fn lobby_channel() -> (LobbyChannel, LobbyReceiver) {
+    let (control_tx, control_rx) = mpsc::unbounded_channel();
+    let (action_tx, action_rx) = mpsc::channel(LOBBY_ACTION_CAPACITY);
+    (
+        LobbyChannel {
+            control_tx,
+            action_tx,
+        },
+        LobbyReceiver {
+            control_rx,
+            action_rx,
+        },
+    )
+}
+
+/// A message the lobby's inbox refused, handed back boxed as it is large
+pub type LobbySendError = Box<mpsc::error::SendError<LobbyMessage>>;
+
+impl LobbyChannel {
+    pub fn send_control(&self, msg: LobbyMessage) -> Result<(), LobbySendError> {
+        self.control_tx.send(msg).map_err(Box::new)
+    }
+
+    /// Queue a client action, shedding non-critical ones when the lobby is saturated
+    pub async fn send_action(&self, msg: LobbyMessage) -> Result<(), LobbySendError> {
+        match self.action_tx.try_send(msg) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(msg)) if msg.is_sheddable() => {
+                Metrics::incr(&METRICS.lobby_actions_shed);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Full(msg)) => {
+                Metrics::incr(&METRICS.lobby_action_backpressure);
+                warn!("Lobby action queue full, applying backpressure");
+                self.action_tx.send(msg).await.map_err(Box::new)
+            }
+            Err(mpsc::error::TrySendError::Closed(msg)) => {
+                Err(Box::new(mpsc::error::SendError(msg)))
+            }
+        }
+    }
+
+    /// The lobby task is gone
+    pub fn is_closed(&self) -> bool {
+        self.control_tx.is_closed()
+    }
+
+    /// Best-effort send for messages that are fine to lose under pressure
+    pub fn send_lossy(&self, msg: LobbyMessage) {
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.action_tx.try_send(msg) {
+            Metrics::incr(&METRICS.lobby_actions_shed);
+        }
+    }
+}
+
+impl LobbyReceiver {
+    /// Receive the next message, always preferring join/leave over client actions
+    pub async fn recv(&mut self) -> Option<LobbyMessage> {
+        tokio::select! {
+            biased;
+            Some(msg) = self.control_rx.recv() => Some(msg),
+            Some(msg) = self.action_rx.recv() => Some(msg),
+            else => None,
         }
     }
 }
@@ -50,5 +273,5 @@ impl LobbyMessage {
 #[derive(Debug)]
 pub struct LobbyJoinData {
     pub lobby_code: String,
-    pub lobby_tx: tokio::sync::mpsc::UnboundedSender<LobbyMessage>,
+    pub lobby_tx: LobbyChannel,
 }
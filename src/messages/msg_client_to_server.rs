@@ -1,20 +1,58 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{game_mode::{GameMode, LobbyOptions}, talisman_number::TalismanNumber};
+use crate::{
+    game_mode::{GameMode, LobbyOptions},
+    lobby::{game_state::Handicap, hand_breakdown::HandBreakdown, BotDifficulty},
+    messages::EventClass,
+    talisman_number::{ScoreFormat, TalismanNumber},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionsRevertTarget {
+    #[serde(rename = "previous")]
+    Previous,
+    #[serde(rename = "default")]
+    Default,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "action")]
 pub enum ClientToServer {
     // Connection actions
-    #[serde(rename = "k")]
-    KeepAlive {},
+    #[serde(rename = "keepAlive")]
+    KeepAlive {
+        #[serde(default)]
+        nonce: Option<u32>,
+    },
+    #[serde(rename = "pong")]
+    Pong { nonce: u32, server_time: u64 },
+    #[serde(rename = "getServerTime")]
+    GetServerTime { client_time: u64 },
     #[serde(rename = "version")]
     Version { version: String },
+    /// Switch framing: with `checksums` every later frame, both ways, carries a CRC32
+    /// of its payload right after the length prefix
+    #[serde(rename = "negotiateFraming")]
+    NegotiateFraming { checksums: bool },
     #[serde(rename = "setClientData")]
     SetClientData {
         username: String,
         colour: u8,
         mod_hash: String,
+        #[serde(default)]
+        account_id: Option<String>,
+        /// Proof from the account service that `account_id` is the client's own
+        #[serde(default)]
+        account_token: Option<String>,
+        /// Receive joker and deck previews as `patchPlayerJokers`/`patchPlayerDeck`
+        #[serde(default)]
+        preview_patches: bool,
+        /// Number lobby messages (`seq`), so a quick reconnect can use `resumeLobby`
+        #[serde(default)]
+        resumable: bool,
+        /// How scores in messages to this client are written
+        #[serde(default)]
+        score_format: ScoreFormat,
     },
 
     // Lobby actions
@@ -25,6 +63,10 @@ pub enum ClientToServer {
         game_mode: GameMode,
     },
 
+    /// Open a single-player tutorial lobby against a bot
+    #[serde(rename = "startTutorial")]
+    StartTutorial {},
+
     #[serde(rename = "failRound")]
     FailRound {},
 
@@ -34,33 +76,111 @@ pub enum ClientToServer {
     #[serde(rename = "sendPlayerJokers")]
     SendPlayerJokers { jokers: String },
 
+    /// Ask for an opponent's current jokers in full, e.g. to resync after a missed patch
+    #[serde(rename = "requestOpponentJokers")]
+    RequestOpponentJokers { player_id: String },
+
     #[serde(rename = "setFurthestBlind")]
     SetFurthestBlind { blind: u32 },
 
+    #[serde(rename = "addBot")]
+    AddBot { difficulty: BotDifficulty },
+
+    /// `reconnect_token` takes back a seat held after a dropped connection
     #[serde(rename = "joinLobby")]
-    JoinLobby { code: String },
+    JoinLobby {
+        code: String,
+        #[serde(default)]
+        reconnect_token: Option<String>,
+    },
+    /// Rejoin a lobby after a dropped connection, sent only the messages after `last_seq`
+    #[serde(rename = "resumeLobby")]
+    ResumeLobby {
+        code: String,
+        last_seq: u64,
+        reconnect_token: String,
+    },
+    /// Join the lobby an invite token or `balatro-mp://` link points at
+    #[serde(rename = "joinInvite")]
+    JoinInvite { token: String },
     #[serde(rename = "leaveLobby")]
     LeaveLobby {},
+    /// Watch a lobby alongside the one the client plays in, messages from it carry its code
+    #[serde(rename = "spectateLobby")]
+    SpectateLobby { code: String },
+    #[serde(rename = "stopSpectating")]
+    StopSpectating { code: String },
+    /// Rejoin the verified account's most recent lobby if it is still open
+    #[serde(rename = "rejoinLast")]
+    RejoinLast {
+        #[serde(default)]
+        reconnect_token: Option<String>,
+    },
+
+    /// Lobbies open to other players, only those in `region` when given
+    #[serde(rename = "listLobbies")]
+    ListLobbies {
+        #[serde(default)]
+        region: Option<String>,
+    },
+
+    /// Bind a persistent lobby code to the client's account
+    #[serde(rename = "claimVanityCode")]
+    ClaimVanityCode { code: String },
+    #[serde(rename = "releaseVanityCode")]
+    ReleaseVanityCode {},
+
+    /// Store a custom challenge (JSON) so lobbies can reference it by the returned id
+    #[serde(rename = "uploadChallenge")]
+    UploadChallenge { definition: String },
+    #[serde(rename = "getChallenge")]
+    GetChallenge { id: String },
 
     #[serde(rename = "updateLobbyOptions")]
     UpdateLobbyOptions { options: LobbyOptions },
 
+    #[serde(rename = "revertLobbyOptions")]
+    RevertLobbyOptions { target: OptionsRevertTarget },
+
     // Game actions (for future expansion)
     #[serde(rename = "setReady")]
     SetReady { is_ready: bool },
 
+    /// Host starts the co-op PvP blind without the players who aren't ready,
+    /// when the lobby's `coop_ready_rule` allows it
+    #[serde(rename = "forceStartBlind")]
+    ForceStartBlind {},
+
+    /// Survival: ask for `survivalStandings` now instead of waiting for the next one
+    #[serde(rename = "getStandings")]
+    GetStandings {},
+
     #[serde(rename = "playHand")]
     PlayHand {
         score: TalismanNumber,
         hands_left: u8,
+        /// Optional, lets opponents see what the hand was
+        #[serde(default)]
+        breakdown: Option<HandBreakdown>,
     },
 
+    #[serde(rename = "roundComplete")]
+    RoundComplete {},
+
     #[serde(rename = "discard")]
     Discard {},
 
     #[serde(rename = "setBossBlind")]
     SetBossBlind { key: String, chips: TalismanNumber },
 
+    /// Ask for a boss the lobby hasn't played lately, answered with `suggestedBoss`
+    #[serde(rename = "suggestBoss")]
+    SuggestBoss {},
+
+    /// Ban a boss from the pool dealt in `bossBanStarted`
+    #[serde(rename = "banBoss")]
+    BanBoss { key: String },
+
     #[serde(rename = "skip")]
     Skip { blind: u32 },
 
@@ -70,6 +190,18 @@ pub enum ClientToServer {
     #[serde(rename = "startGame")]
     StartGame { seed: String, stake: i32 },
 
+    /// Vote to start the game without the host, in lobbies with `vote_to_start` on
+    #[serde(rename = "voteStart")]
+    VoteStart {},
+
+    /// Host deals everyone into new random teams of `team_size` before the game
+    #[serde(rename = "shuffleTeams")]
+    ShuffleTeams { team_size: u8 },
+
+    /// Host gives a player extra lives or a score multiplier for the next game
+    #[serde(rename = "setHandicap")]
+    SetHandicap { player_id: String, handicap: Handicap },
+
     #[serde(rename = "stopGame")]
     StopGame {},
 
@@ -110,7 +242,10 @@ pub enum ClientToServer {
     SpentLastShop { amount: u32 },
 
     #[serde(rename = "magnet")]
-    Magnet {},
+    Magnet {
+        #[serde(default)]
+        target: Option<String>,
+    },
 
     #[serde(rename = "magnetResponse")]
     MagnetResponse { key: String },
@@ -121,4 +256,210 @@ pub enum ClientToServer {
     #[serde(rename = "return_to_lobby")]
     ReturnToLobby {},
 
+    #[serde(rename = "reserveSlot")]
+    ReserveSlot { account_id: String },
+
+    #[serde(rename = "cancelReservation")]
+    CancelReservation { account_id: String },
+
+    /// Host consent to merging this waiting lobby with another of the same mode and ruleset
+    #[serde(rename = "offerLobbyMerge")]
+    OfferLobbyMerge { open: bool },
+
+    /// Show this waiting lobby to clients browsing with `listLobbies`
+    #[serde(rename = "setPublicListing")]
+    SetPublicListing { listed: bool },
+
+    #[serde(rename = "reportBug")]
+    ReportBug { description: String },
+
+    /// Host note shown to the whole lobby
+    #[serde(rename = "announce")]
+    Announce { text: String },
+
+    /// Quick-chat from the fixed emote list. `team_only` keeps it to the sender's team,
+    /// the sender included
+    #[serde(rename = "sendEmote")]
+    SendEmote {
+        emote_id: String,
+        #[serde(default)]
+        team_only: bool,
+    },
+
+    /// Flag another player in the lobby for moderator review
+    #[serde(rename = "reportPlayer")]
+    ReportPlayer { player_id: String, reason: String },
+
+    #[serde(rename = "forfeit")]
+    Forfeit {},
+
+    #[serde(rename = "getPlayerLocations")]
+    GetPlayerLocations {},
+
+    #[serde(rename = "setAnte")]
+    SetAnte { ante: u32 },
+
+    /// Hash of the client's deck, jokers and money, sent every so often during a run
+    #[serde(rename = "runChecksum")]
+    RunChecksum { hash: String },
+
+    /// Roll for a shared random event, e.g. `boss:<ante>`; everyone asking gets the same value
+    #[serde(rename = "requestRoll")]
+    RequestRoll { key: String, sides: u32 },
+
+    /// Stop or resume receiving classes of high-volume messages; lasts for the connection
+    #[serde(rename = "setSubscriptions")]
+    SetSubscriptions {
+        #[serde(default)]
+        subscribe: Vec<EventClass>,
+        #[serde(default)]
+        unsubscribe: Vec<EventClass>,
+    },
+
+    /// Ask for an invite link to the lobby; only the host may let it past reserved slots
+    #[serde(rename = "createInvite")]
+    CreateInvite {
+        #[serde(default)]
+        bypass_reservations: bool,
+    },
+
+    /// Host: switch audit mode, which acknowledges every processed action to its sender
+    #[serde(rename = "setActionAudit")]
+    SetActionAudit { enabled: bool },
+
+    /// A frame too large for one message follows in `chunk`s, `total_len` bytes in all
+    #[serde(rename = "chunkBegin")]
+    ChunkBegin { id: u32, total_len: u32 },
+    /// The next piece of the encoded frame, as MessagePack binary
+    #[serde(rename = "chunk")]
+    Chunk {
+        id: u32,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    /// Every chunk is in, the reassembled frame is handled like any other
+    #[serde(rename = "chunkEnd")]
+    ChunkEnd { id: u32 },
+
+    /// Any action this server doesn't know yet, e.g. from a newer client
+    #[serde(other)]
+    Unknown,
+}
+
+impl ClientToServer {
+    /// Actions whose loss is harmless because a newer one supersedes them
+    pub fn is_sheddable(&self) -> bool {
+        matches!(self, Self::SetLocation { .. })
+    }
+
+    /// The action's tag on the wire, e.g. `playHand`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::KeepAlive { .. } => "keepAlive",
+            Self::Pong { .. } => "pong",
+            Self::GetServerTime { .. } => "getServerTime",
+            Self::Version { .. } => "version",
+            Self::NegotiateFraming { .. } => "negotiateFraming",
+            Self::SetClientData { .. } => "setClientData",
+            Self::CreateLobby { .. } => "createLobby",
+            Self::StartTutorial { .. } => "startTutorial",
+            Self::FailRound { .. } => "failRound",
+            Self::SendPlayerDeck { .. } => "sendPlayerDeck",
+            Self::SendPlayerJokers { .. } => "sendPlayerJokers",
+            Self::RequestOpponentJokers { .. } => "requestOpponentJokers",
+            Self::SetFurthestBlind { .. } => "setFurthestBlind",
+            Self::AddBot { .. } => "addBot",
+            Self::JoinLobby { .. } => "joinLobby",
+            Self::ResumeLobby { .. } => "resumeLobby",
+            Self::JoinInvite { .. } => "joinInvite",
+            Self::LeaveLobby { .. } => "leaveLobby",
+            Self::SpectateLobby { .. } => "spectateLobby",
+            Self::StopSpectating { .. } => "stopSpectating",
+            Self::RejoinLast { .. } => "rejoinLast",
+            Self::ListLobbies { .. } => "listLobbies",
+            Self::ClaimVanityCode { .. } => "claimVanityCode",
+            Self::ReleaseVanityCode { .. } => "releaseVanityCode",
+            Self::UploadChallenge { .. } => "uploadChallenge",
+            Self::GetChallenge { .. } => "getChallenge",
+            Self::UpdateLobbyOptions { .. } => "updateLobbyOptions",
+            Self::RevertLobbyOptions { .. } => "revertLobbyOptions",
+            Self::SetReady { .. } => "setReady",
+            Self::ForceStartBlind { .. } => "forceStartBlind",
+            Self::GetStandings { .. } => "getStandings",
+            Self::PlayHand { .. } => "playHand",
+            Self::RoundComplete { .. } => "roundComplete",
+            Self::Discard { .. } => "discard",
+            Self::SetBossBlind { .. } => "setBossBlind",
+            Self::SuggestBoss { .. } => "suggestBoss",
+            Self::BanBoss { .. } => "banBoss",
+            Self::Skip { .. } => "skip",
+            Self::SetLocation { .. } => "setLocation",
+            Self::StartGame { .. } => "startGame",
+            Self::VoteStart { .. } => "voteStart",
+            Self::ShuffleTeams { .. } => "shuffleTeams",
+            Self::SetHandicap { .. } => "setHandicap",
+            Self::StopGame { .. } => "stopGame",
+            Self::UpdateHandsAndDiscards { .. } => "updateHandsAndDiscards",
+            Self::SendPhantom { .. } => "sendPhantom",
+            Self::RemovePhantom { .. } => "removePhantom",
+            Self::Asteroid { .. } => "asteroid",
+            Self::LetsGoGamblingNemesis { .. } => "letsGoGamblingNemesis",
+            Self::EatPizza { .. } => "eatPizza",
+            Self::SoldJoker { .. } => "soldJoker",
+            Self::StartAnteTimer { .. } => "startAnteTimer",
+            Self::PauseAnteTimer { .. } => "pauseAnteTimer",
+            Self::FailTimer { .. } => "failTimer",
+            Self::SpentLastShop { .. } => "spentLastShop",
+            Self::Magnet { .. } => "magnet",
+            Self::MagnetResponse { .. } => "magnetResponse",
+            Self::SendMoney { .. } => "sendMoney",
+            Self::ReturnToLobby { .. } => "return_to_lobby",
+            Self::ReserveSlot { .. } => "reserveSlot",
+            Self::CancelReservation { .. } => "cancelReservation",
+            Self::OfferLobbyMerge { .. } => "offerLobbyMerge",
+            Self::SetPublicListing { .. } => "setPublicListing",
+            Self::ReportBug { .. } => "reportBug",
+            Self::Announce { .. } => "announce",
+            Self::SendEmote { .. } => "sendEmote",
+            Self::ReportPlayer { .. } => "reportPlayer",
+            Self::Forfeit { .. } => "forfeit",
+            Self::GetPlayerLocations { .. } => "getPlayerLocations",
+            Self::SetAnte { .. } => "setAnte",
+            Self::RunChecksum { .. } => "runChecksum",
+            Self::RequestRoll { .. } => "requestRoll",
+            Self::SetSubscriptions { .. } => "setSubscriptions",
+            Self::CreateInvite { .. } => "createInvite",
+            Self::SetActionAudit { .. } => "setActionAudit",
+            Self::ChunkBegin { .. } => "chunkBegin",
+            Self::Chunk { .. } => "chunk",
+            Self::ChunkEnd { .. } => "chunkEnd",
+            Self::Unknown => "Unknown",
+        }
+    }
+
+    /// Actions whose payload may outgrow a single frame and arrive in chunks
+    pub fn is_chunkable(&self) -> bool {
+        matches!(self, Self::SendPlayerDeck { .. } | Self::SendPlayerJokers { .. })
+    }
+}
+
+/// Just the tag of an action, to name unknown ones
+#[derive(Deserialize)]
+pub struct ActionTag {
+    pub action: String,
+}
+
+/// A single inbound frame: the action plus an optional client sequence id.
+///
+/// Clients that number their actions get replay protection in the lobby;
+/// older clients simply omit `seq`. `v` is the protocol version the client
+/// speaks, see [`super::protocol`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientFrame {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
+    #[serde(default)]
+    pub seq: Option<u64>,
+    #[serde(flatten)]
+    pub action: ClientToServer,
 }
@@ -1,12 +1,58 @@
+use serde::Serialize;
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 
 use crate::{
     client::ClientProfile,
     game_mode::GameMode,
-    messages::{LobbyJoinData, ServerToClient},
+    invites::Invite,
+    lobby::lobby::Lobby,
+    messages::{LobbyChannel, LobbyJoinData, ServerToClient, Subscriptions},
+    moderation::PlayerReport,
 };
 
+/// Coordinator-level view of a running lobby
+#[derive(Debug, Clone)]
+pub struct LobbySummary {
+    pub code: String,
+    pub player_count: usize,
+}
+
+/// What the coordinator reports to health probes
+#[derive(Debug, Clone, Serialize)]
+pub struct CoordinatorHealth {
+    pub lobbies: usize,
+    pub players_in_lobbies: usize,
+    pub draining: bool,
+}
+
+/// The lobby a pooled lobby task is told to run
+#[derive(Debug)]
+pub struct LobbyAssignment {
+    pub lobby_code: String,
+    pub ruleset: String,
+    pub game_mode: GameMode,
+}
+
+/// A waiting lobby whose host agreed to merge it with another like it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeOffer {
+    pub lobby_code: String,
+    pub game_mode: GameMode,
+    pub ruleset: String,
+    pub players: usize,
+    pub max_players: usize,
+}
+
+/// A waiting lobby whose host listed it publicly, shown to clients browsing lobbies
+#[derive(Debug, Clone, PartialEq)]
+pub struct LobbyListing {
+    pub lobby_code: String,
+    pub game_mode: GameMode,
+    pub players: usize,
+    pub max_players: usize,
+}
+
 #[derive(Debug)]
 pub enum CoordinatorMessage {
     /// A client wants to create a new lobby
@@ -17,6 +63,9 @@ pub enum CoordinatorMessage {
         request_tx: oneshot::Sender<LobbyJoinData>,
         client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
         client_profile: ClientProfile,
+        lobby_generation: u64,
+        /// Single-player tutorial against a bot
+        tutorial: bool,
     },
     /// A client wants to join an existing lobby
     JoinLobby {
@@ -25,15 +74,178 @@ pub enum CoordinatorMessage {
         request_tx: oneshot::Sender<LobbyJoinData>,
         client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
         client_profile: ClientProfile,
+        lobby_generation: u64,
+        /// Last numbered message the client got before its connection dropped
+        resume_after: Option<u64>,
+        /// Verified invite the client joins with
+        invite: Option<Invite>,
+        /// Token of a seat held for the client since its connection dropped
+        reconnect_token: Option<String>,
+    },
+
+    /// A client wants to watch a lobby, on top of any it plays in
+    SpectateLobby {
+        client_id: String,
+        lobby_code: String,
+        request_tx: oneshot::Sender<LobbyJoinData>,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+        subscriptions: Subscriptions,
+    },
+
+    /// A client wants back into the last lobby its account was in
+    RejoinLast {
+        client_id: String,
+        request_tx: oneshot::Sender<LobbyJoinData>,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+        client_profile: ClientProfile,
+        lobby_generation: u64,
+        reconnect_token: Option<String>,
     },
 
     LobbyShutdown {
         lobby_code: String,
     },
+    /// An idle lobby task offering itself to the pool, either new or done with its last lobby
+    LobbyPooled {
+        lobby_tx: LobbyChannel,
+        assign_tx: oneshot::Sender<LobbyAssignment>,
+    },
+    /// A client browsing lobbies open to merging, optionally in one region
+    ListOpenLobbies {
+        region: Option<String>,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    },
+    /// Claim a vanity code for a verified account, or release it when `code` is `None`
+    SetVanityCode {
+        account_id: String,
+        code: Option<String>,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    },
+    /// The vanity database answered a `SetVanityCode`: the account's code now, or why not
+    VanityCodeChanged {
+        account_id: String,
+        code: Result<Option<String>, &'static str>,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    },
+    /// Store a custom challenge, owned by the uploader's verified account if any
+    UploadChallenge {
+        account_id: Option<String>,
+        definition: String,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    },
+    GetChallenge {
+        id: String,
+        client_response_tx: mpsc::UnboundedSender<Arc<ServerToClient>>,
+    },
+    /// A lobby's game started or ended
+    LobbyGameState {
+        lobby_code: String,
+        started: bool,
+    },
+    /// A lobby is open to merging, sent again whenever its player count changes
+    OfferMerge {
+        offer: MergeOffer,
+    },
+    /// The lobby is no longer open to merging: the host changed their mind, it filled
+    /// up or its game started
+    WithdrawMerge {
+        lobby_code: String,
+    },
+    /// A lobby is publicly listed, sent again whenever its player count changes
+    ListLobby {
+        listing: LobbyListing,
+    },
+    /// The lobby is no longer listed: the host unlisted it, it filled up or its game started
+    UnlistLobby {
+        lobby_code: String,
+    },
+    /// Players of `from_code` moved into `into_code`, the rest of them left
+    LobbyMerged {
+        from_code: String,
+        into_code: String,
+        moved: Vec<String>,
+    },
+    /// A client found its lobby closed under it, ask where it plays now
+    FindClientLobby {
+        client_id: String,
+        reply_tx: oneshot::Sender<Option<LobbyJoinData>>,
+    },
 
     /// Client disconnected, clean up from any lobby
     ClientDisconnected {
         client_id: String,
         coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+        /// The connection dropped rather than the player choosing to leave
+        connection_lost: bool,
+    },
+
+    /// Client identified itself; registered players show up in presence
+    ClientRegistered {
+        client_id: String,
+        client_profile: ClientProfile,
+    },
+    /// Connection closed for good (unlike `ClientDisconnected`, also sent on leaving a lobby)
+    ClientOffline {
+        client_id: String,
+    },
+    /// Presence relay subscriber, receives a JSON snapshot whenever presence changes
+    SubscribePresence {
+        update_tx: mpsc::UnboundedSender<Arc<String>>,
+    },
+
+    /// Health probe: proves the coordinator is still processing messages
+    Health {
+        reply_tx: oneshot::Sender<CoordinatorHealth>,
+    },
+    /// Operator: list running lobbies
+    ListLobbies {
+        reply_tx: oneshot::Sender<Vec<LobbySummary>>,
+    },
+    /// Operator: fetch a snapshot of one lobby
+    InspectLobby {
+        lobby_code: String,
+        reply_tx: oneshot::Sender<Option<Lobby>>,
+    },
+    /// Operator: remove a player from whatever lobby they are in
+    KickPlayer {
+        client_id: String,
+        reason: String,
+        reply_tx: oneshot::Sender<bool>,
+    },
+    /// A player filed a report from their lobby
+    PlayerReported {
+        report: PlayerReport,
+    },
+    /// Operator: list reports waiting for review
+    ListReports {
+        reply_tx: oneshot::Sender<Vec<PlayerReport>>,
+    },
+    /// Operator: fetch one report with its context
+    GetReport {
+        report_id: String,
+        reply_tx: oneshot::Sender<Option<PlayerReport>>,
+    },
+    /// Operator: close an open report, kicking the reported player when `kick_reason` is set;
+    /// replies `None` for unknown or already closed reports, else whether someone was kicked
+    ResolveReport {
+        report_id: String,
+        kick_reason: Option<String>,
+        reply_tx: oneshot::Sender<Option<bool>>,
+    },
+    /// Operator: switch action audit mode for a lobby, replies whether it exists
+    SetActionAudit {
+        lobby_code: String,
+        enabled: bool,
+        reply_tx: oneshot::Sender<bool>,
+    },
+    /// Operator: send a notice to every lobby
+    BroadcastNotice {
+        message: String,
+    },
+    /// Operator: stop creating lobbies, point clients at `host:port` and exit
+    /// once the running games have finished
+    Drain {
+        host: String,
+        port: u16,
     },
 }
@@ -1,8 +1,155 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::CONFIG,
+    game_mode::{CoopReadyRule, GameMode, LobbyOptions},
+    lobby::{
+        awards::Award,
+        hand_breakdown::HandBreakdown,
+        lobby::{HandScore, Lobby},
+        phase::LobbyPhase,
+        preview::PreviewPatch,
+        ClientGameState, ClientLobbyEntry,
+    },
+    scheduled_events::ActiveEvent,
+    talisman_number::TalismanNumber,
+};
+
+/// Why a game or PvP round ended the way it did
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutcomeReason {
+    #[serde(rename = "opponent_out_of_lives")]
+    OpponentOutOfLives,
+    #[serde(rename = "out_of_lives")]
+    OutOfLives,
+    #[serde(rename = "timer_expired")]
+    TimerExpired,
+    #[serde(rename = "opponent_disconnected")]
+    OpponentDisconnected,
+    #[serde(rename = "boss_defeated")]
+    BossDefeated,
+    #[serde(rename = "boss_not_defeated")]
+    BossNotDefeated,
+    #[serde(rename = "furthest_blind")]
+    FurthestBlind,
+    #[serde(rename = "score")]
+    Score,
+    #[serde(rename = "forfeit")]
+    Forfeit,
+    #[serde(rename = "points_target")]
+    PointsTarget,
+}
+
+/// Why a game was stopped before it finished
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    #[serde(rename = "host")]
+    Host,
+    /// Every player went back to the lobby
+    #[serde(rename = "players_left")]
+    PlayersLeft,
+    /// The host changed options the running game was started with
+    #[serde(rename = "options_changed")]
+    OptionsChanged,
+}
+
+/// A player's final position, sent with game results in 3+ player modes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Standing {
+    pub player_id: String,
+    /// The player's verified account, what stats and leaderboards key results by
+    pub account_id: Option<String>,
+    pub placement: u8,
+    pub lives: u8,
+    pub furthest_blind: u32,
+    pub points: u32,
+    /// False when the run's checksums don't hold up or it played a banned card
+    pub verified: bool,
+}
 
-use serde::Serialize;
+/// Where a player stands in a running Survival game
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SurvivalStanding {
+    pub player_id: String,
+    /// Tied players share a placement
+    pub placement: u8,
+    /// For eliminated players, the blind they were knocked out on
+    pub furthest_blind: u32,
+    pub ante: u32,
+    pub lives: u8,
+    pub eliminated: bool,
+}
+
+/// High-volume messages clients can opt out of with `setSubscriptions`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventClass {
+    /// `playerLocation`/`playerLocations`
+    #[serde(rename = "opponentLocations")]
+    OpponentLocations,
+    /// `receivePlayerJokers`/`patchPlayerJokers`
+    #[serde(rename = "jokerPreviews")]
+    JokerPreviews,
+    /// `receivePlayerDeck`/`patchPlayerDeck`
+    #[serde(rename = "deckPreviews")]
+    DeckPreviews,
+    /// `shopSpending`
+    #[serde(rename = "spending")]
+    Spending,
+}
 
-use crate::{game_mode::LobbyOptions, lobby::{lobby::Lobby, ClientGameState, ClientLobbyEntry}};
+impl EventClass {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Event classes a client receives, every one of them until it opts out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Subscriptions {
+    unsubscribed: u8,
+}
+
+impl Subscriptions {
+    pub fn update(&mut self, subscribe: &[EventClass], unsubscribe: &[EventClass]) {
+        for class in unsubscribe {
+            self.unsubscribed |= class.bit();
+        }
+        for class in subscribe {
+            self.unsubscribed &= !class.bit();
+        }
+    }
+
+    pub fn accepts(self, class: EventClass) -> bool {
+        self.unsubscribed & class.bit() == 0
+    }
+
+    pub fn wants(self, message: &ServerToClient) -> bool {
+        message.event_class().is_none_or(|class| self.accepts(class))
+    }
+}
+
+/// What a player has spent in the shop this game
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShopSpend {
+    pub player_id: String,
+    pub total: u32,
+    /// Spent in the shops of each ante, first ante first
+    pub per_ante: Vec<u32>,
+}
+
+/// A waiting lobby whose host opened it to other players
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ListedLobby {
+    pub code: String,
+    pub game_mode: GameMode,
+    pub players: usize,
+    pub max_players: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
 
 // Server to Client Actions
 #[derive(Serialize, Debug, Clone)]
@@ -10,42 +157,202 @@ use crate::{game_mode::LobbyOptions, lobby::{lobby::Lobby, ClientGameState, Clie
 pub enum ServerToClient {
     // Connection responses
     #[serde(rename = "connected")]
-    Connected { client_id: String },
-    #[serde(rename = "a")]
-    KeepAliveResponse {},
+    Connected {
+        client_id: String,
+        /// The server's region, when the operator set one
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+    #[serde(rename = "keepAliveAck")]
+    KeepAliveResponse { nonce: Option<u32>, server_time: u64 },
+    #[serde(rename = "ping")]
+    Ping { nonce: u32, server_time: u64 },
+    #[serde(rename = "serverTime")]
+    ServerTime { client_time: u64, server_time: u64 },
     #[serde(rename = "versionOk")]
     VersionOk {},
+    /// Scheduled events running as the client connects
+    #[serde(rename = "serverEvents")]
+    ServerEvents { events: Vec<ActiveEvent> },
+    /// Frames the client sent since the last warning that did nothing: unparseable ones
+    /// and actions this server doesn't implement
+    #[serde(rename = "protocolWarnings")]
+    ProtocolWarnings {
+        malformed: u32,
+        unsupported: u32,
+        unsupported_actions: Vec<String>,
+    },
+    /// Last frame in the old framing; everything after it uses the negotiated one
+    #[serde(rename = "framingNegotiated")]
+    FramingNegotiated { checksums: bool },
+    /// A frame failed its checksum and was dropped; resend everything after `last_seq`
+    #[serde(rename = "frameCorrupted")]
+    FrameCorrupted { last_seq: Option<u64> },
     #[serde(rename = "error")]
     Error { message: String },
+    #[serde(rename = "serverNotice")]
+    ServerNotice { message: String },
 
     // Lobby responses
     #[serde(rename = "joinedLobby")]
     JoinedLobby {
         player_id: String,
-        lobby_data: Lobby, // Using Value to avoid circular dependency
+        lobby_data: Box<Lobby>, // Boxed to keep the enum small
+    },
+    /// Lobbies open to others, answering `listLobbies`
+    #[serde(rename = "lobbyList")]
+    LobbyList { lobbies: Vec<ListedLobby> },
+    /// The account's vanity code after a claim or release
+    #[serde(rename = "vanityCode")]
+    VanityCode { code: Option<String> },
+    /// Id to put in `LobbyOptions.challenge` for an uploaded challenge
+    #[serde(rename = "challengeUploaded")]
+    ChallengeUploaded { id: String },
+    #[serde(rename = "challenge")]
+    Challenge { id: String, definition: String },
+    /// Lobby state for a client that started spectating it
+    #[serde(rename = "spectatingLobby")]
+    SpectatingLobby { lobby_data: Box<Lobby> },
+    /// The spectated lobby closed
+    #[serde(rename = "spectatingEnded")]
+    SpectatingEnded {},
+    /// `message` from a lobby the client spectates, sent with the lobby's code in the envelope
+    #[serde(skip)]
+    ForLobby {
+        lobby_code: String,
+        message: Arc<ServerToClient>,
     },
+    /// `message` numbered for a client that can resume, `seq` goes in the envelope
+    #[serde(skip)]
+    Sequenced {
+        seq: u64,
+        message: Arc<ServerToClient>,
+    },
+    /// Secret to present on `joinLobby`/`resumeLobby` to take the seat back after the
+    /// connection drops, replaces any token sent before
+    #[serde(rename = "reconnectToken")]
+    ReconnectToken { token: String },
+    /// Answer to `resumeLobby`: the held seat is the client's again under `player_id`,
+    /// and the messages it missed follow with their original numbers
+    #[serde(rename = "lobbyResumed")]
+    LobbyResumed { player_id: String, previous_id: String },
     #[serde(rename = "playerJoinedLobby")]
     PlayerJoinedLobby { player: ClientLobbyEntry },
     #[serde(rename = "playerLeftLobby")]
     PlayerLeftLobby { player_id: String, host_id: String },
 
     #[serde(rename = "updateLobbyOptions")]
-    UpdateLobbyOptions { options: LobbyOptions },
+    UpdateLobbyOptions {
+        options: LobbyOptions,
+        changed_by: String,
+        changes: BTreeMap<String, serde_json::Value>,
+    },
 
     #[serde(rename = "gameStarted")]
     GameStarted { seed: String, stake: i32 },
 
+    /// Answer to `suggestBoss`
+    #[serde(rename = "suggestedBoss")]
+    SuggestedBoss { key: String },
+
+    /// Votes to start so far; the game starts once `needed` players voted
+    #[serde(rename = "startVotes")]
+    StartVotes { player_ids: Vec<String>, needed: u32 },
+
     #[serde(rename = "startBlind")]
-    StartBlind {},
+    StartBlind { server_time: u64 },
+
+    /// The blind is starting without these players being ready, sent just before `startBlind`
+    #[serde(rename = "readyOverridden")]
+    ReadyOverridden {
+        player_ids: Vec<String>,
+        rule: CoopReadyRule,
+    },
 
     #[serde(rename = "gameStopped")]
-    GameStopped {},
+    GameStopped { reason: StopReason },
+
+    /// The lobby moved on to a new phase of the game
+    #[serde(rename = "lobbyPhase")]
+    LobbyPhase { phase: LobbyPhase },
 
     #[serde(rename = "loseGame")]
-    LoseGame {},
+    LoseGame {
+        reason: OutcomeReason,
+        standings: Vec<Standing>,
+    },
+
+    /// Survival: the player is out and spectates until the others fail or pass them
+    #[serde(rename = "survivalWaiting")]
+    SurvivalWaiting {
+        eliminated_at: u32,
+        leading_blind: u32,
+        remaining_players: Vec<String>,
+    },
+
+    /// Refused for capacity; `queue_position` is set when the client was queued instead
+    #[serde(rename = "serverFull")]
+    ServerFull {
+        message: String,
+        queue_position: Option<usize>,
+    },
+
+    #[serde(rename = "lobbyQueuePosition")]
+    LobbyQueuePosition { position: usize },
+
+    /// A queued client may now send `createLobby` again
+    #[serde(rename = "lobbySlotAvailable")]
+    LobbySlotAvailable {},
+
+    /// The server is being replaced, reconnect to the given address
+    #[serde(rename = "serverRedirect")]
+    ServerRedirect { host: String, port: u16 },
+
+    /// Periodic match summary while a game is running
+    #[serde(rename = "lobbyStats")]
+    LobbyStats {
+        rounds_played: u32,
+        antes: HashMap<String, u32>,
+        average_round_secs: f64,
+    },
+
+    /// Survival progress of everyone in the game, on request and periodically
+    #[serde(rename = "survivalStandings")]
+    SurvivalStandings { standings: Vec<SurvivalStanding> },
+
+    #[serde(rename = "playerNamesRevealed")]
+    PlayerNamesRevealed { usernames: HashMap<String, String> },
+
+    #[serde(rename = "playerLocation")]
+    PlayerLocation { player_id: String, location: String },
+
+    #[serde(rename = "playerLocations")]
+    PlayerLocations { locations: HashMap<String, String> },
+
+    /// Head-start chips per player for the upcoming PvP blind, sent before it starts
+    #[serde(rename = "pvpHandicaps")]
+    PvpHandicaps { handicaps: HashMap<String, f64> },
+
+    #[serde(rename = "leagueTable")]
+    LeagueTable { points: HashMap<String, u32> },
+
+    #[serde(rename = "clashStage")]
+    ClashStage { stage: u32, upcoming_damage: Vec<u8> },
+
+    #[serde(rename = "bossDifficulty")]
+    BossDifficulty {
+        multiplier: f64,
+        boss_chips: TalismanNumber,
+    },
+
+    #[serde(rename = "playerRevived")]
+    PlayerRevived { player_id: String, lives: u8 },
 
     #[serde(rename = "winGame")]
-    WinGame {},
+    WinGame {
+        reason: OutcomeReason,
+        standings: Vec<Standing>,
+    },
 
     #[serde(rename = "receivePlayerJokers")]
     ReceivePlayerJokers { player_id: String, jokers: String },
@@ -53,11 +360,61 @@ pub enum ServerToClient {
     #[serde(rename = "receivePlayerDeck")]
     ReceivePlayerDeck { player_id: String, deck: String },
 
+    /// Changes to the jokers last received for `player_id`
+    #[serde(rename = "patchPlayerJokers")]
+    PatchPlayerJokers { player_id: String, patch: PreviewPatch },
+
+    /// Changes to the deck last received for `player_id`
+    #[serde(rename = "patchPlayerDeck")]
+    PatchPlayerDeck { player_id: String, patch: PreviewPatch },
+
+    /// Cards this game's runs may not hold, sent with `gameStarted` when there are any
+    #[serde(rename = "bannedCards")]
+    BannedCards {
+        jokers: Vec<String>,
+        consumables: Vec<String>,
+    },
+
+    /// Warning to the lobby that a player broke one of its rules, `rule` names the option
+    #[serde(rename = "rulesViolation")]
+    RulesViolation {
+        player_id: String,
+        rule: String,
+        message: String,
+    },
+
     #[serde(rename = "setBossBlind")]
     SetBossBlind { key: String },
 
+    /// Each player bans one boss from `pool`, the PvP boss then follows as `setBossBlind`
+    #[serde(rename = "bossBanStarted")]
+    BossBanStarted { pool: Vec<String>, seconds: u64 },
+
+    #[serde(rename = "bossBanned")]
+    BossBanned { player_id: String, key: String },
+
     #[serde(rename = "endPvp")]
-    EndPvp { won: bool },
+    EndPvp {
+        won: bool,
+        reason: OutcomeReason,
+        timeline: Vec<HandScore>,
+        /// Server time the blind started, matches `startBlind`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        started_at: Option<u64>,
+        /// Milliseconds each player took to finish the blind
+        finish_ms: HashMap<String, u64>,
+    },
+
+    #[serde(rename = "roundRewards")]
+    RoundRewards { gold: u32, blind_reward: bool },
+
+    /// Live feed of an opponent's hand, only sent when the hand came with a breakdown
+    #[serde(rename = "handPlayed")]
+    HandPlayed {
+        player_id: String,
+        score: TalismanNumber,
+        breakdown: HandBreakdown,
+    },
 
     #[serde(rename = "gameStateUpdate")]
     GameStateUpdate {
@@ -68,9 +425,33 @@ pub enum ServerToClient {
     #[serde(rename = "resetPlayers")]
     ResetPlayers { players: Vec<ClientLobbyEntry> },
 
+    /// Members of every team, after the host shuffled them
+    #[serde(rename = "teamsUpdated")]
+    TeamsUpdated { teams: BTreeMap<u8, Vec<String>> },
+
     #[serde(rename = "lobbyReady")]
     LobbyReady { ready_states: HashMap<String, bool> },
 
+    #[serde(rename = "lobbyLatency")]
+    LobbyLatency { latencies: HashMap<String, u32> },
+
+    #[serde(rename = "readyCountdown")]
+    ReadyCountdown { player_ids: Vec<String>, seconds_left: u32 },
+
+    #[serde(rename = "readyCountdownCancelled")]
+    ReadyCountdownCancelled {},
+
+    /// Blitz: the PvP blind just started must be finished by `ends_at` (server time)
+    #[serde(rename = "blitzTimer")]
+    BlitzTimer { seconds: u32, ends_at: u64 },
+
+    /// Blitz: time ran out, these players failed the blind and their score was zeroed
+    #[serde(rename = "blitzExpired")]
+    BlitzExpired { player_ids: Vec<String> },
+
+    #[serde(rename = "kicked")]
+    Kicked { reason: String },
+
     #[serde(rename = "inGameStatuses")]
     InGameStatuses { statuses: HashMap<String, bool>, started: bool },
 
@@ -95,13 +476,18 @@ pub enum ServerToClient {
     #[serde(rename = "soldJoker")]
     SoldJoker {},
 
-    #[serde(rename = "spentLastShop")]
-    SpentLastShop { player_id: String, amount: u32 },
+    /// Shop spending of every player in the game, biggest spender first
+    #[serde(rename = "shopSpending")]
+    ShopSpending { spending: Vec<ShopSpend> },
+
+    /// End-of-game awards for the results screen, sent once the game is over
+    #[serde(rename = "awards")]
+    Awards { awards: Vec<Award> },
 
     #[serde(rename = "startAnteTimer")]
-    StartAnteTimer { time: u32 },
+    StartAnteTimer { time: u32, server_time: u64 },
     #[serde(rename = "pauseAnteTimer")]
-    PauseAnteTimer { time: u32 },
+    PauseAnteTimer { time: u32, server_time: u64 },
 
     #[serde(rename = "magnet")]
     Magnet {},
@@ -109,11 +495,111 @@ pub enum ServerToClient {
     #[serde(rename = "magnetResponse")]
     MagnetResponse { key: String },
 
+    #[serde(rename = "magnetFailed")]
+    MagnetFailed {},
+
     #[serde(rename = "receivedMoney")]
     ReceivedMoney {},
+
+    #[serde(rename = "reservationsUpdated")]
+    ReservationsUpdated { account_ids: Vec<String> },
+
+    /// An invite to the lobby, `expires_at` in server time
+    #[serde(rename = "inviteCreated")]
+    InviteCreated {
+        token: String,
+        link: String,
+        expires_at: u64,
+    },
+    /// Audit mode was switched on or off for the lobby
+    #[serde(rename = "actionAuditChanged")]
+    ActionAuditChanged { enabled: bool },
+    /// Audit mode: the lobby processed the action tagged `processed`, sent as `seq`, and is
+    /// now at `state_version`
+    #[serde(rename = "actionAck")]
+    ActionAck {
+        processed: String,
+        seq: Option<u64>,
+        state_version: u64,
+    },
+    /// The host opened or closed the lobby to merging with another
+    #[serde(rename = "mergeOfferChanged")]
+    MergeOfferChanged { open: bool },
+    #[serde(rename = "publicListingChanged")]
+    PublicListingChanged { listed: bool },
+    /// The client's lobby merged into `lobby_code`, its `joinedLobby` follows
+    #[serde(rename = "lobbyMerged")]
+    LobbyMerged { from_code: String, lobby_code: String },
+
+    #[serde(rename = "bugReported")]
+    BugReported { report_id: String },
+
+    /// `previous_id` dropped mid-game and a bot took over their run as `player_id`
+    #[serde(rename = "replacedByBot")]
+    ReplacedByBot { player_id: String, previous_id: String },
+    /// An opponent dropped mid-game; they forfeit unless back within `grace_seconds`
+    #[serde(rename = "opponentDisconnected")]
+    OpponentDisconnected { player_id: String, grace_seconds: u64 },
+
+    /// A dropped opponent is back, now under a new id
+    #[serde(rename = "opponentReconnected")]
+    OpponentReconnected { player_id: String, previous_id: String },
+
+    #[serde(rename = "emote")]
+    Emote { player_id: String, emote_id: String },
+
+    /// A note from the host, shown apart from chat
+    #[serde(rename = "announcement")]
+    Announcement { player_id: String, text: String },
+
+    #[serde(rename = "playerReported")]
+    PlayerReported { report_id: String },
+
+    /// Outcome of a shared random event, sent to the whole lobby when first rolled
+    #[serde(rename = "sharedRoll")]
+    SharedRoll { key: String, value: u32 },
 }
 
 impl ServerToClient {
+    /// Large payloads the writer sends only once everything else queued has gone out,
+    /// so they never hold up timing-sensitive messages like `startBlind` or pings
+    pub fn is_bulk(&self) -> bool {
+        if let Self::ForLobby { message, .. } = self {
+            return message.is_bulk();
+        }
+        // Numbered messages stay in order, a resume trusts the newest number seen
+        matches!(
+            self,
+            Self::ReceivePlayerDeck { .. }
+                | Self::ReceivePlayerJokers { .. }
+                // Patches apply on top of the full payloads, so they share their lane
+                | Self::PatchPlayerDeck { .. }
+                | Self::PatchPlayerJokers { .. }
+                | Self::LobbyStats { .. }
+                | Self::Challenge { .. }
+        )
+    }
+
+    /// The class clients can unsubscribe this message by, `None` for everything they always get
+    pub fn event_class(&self) -> Option<EventClass> {
+        match self {
+            Self::ForLobby { message, .. } | Self::Sequenced { message, .. } => {
+                message.event_class()
+            }
+            Self::PlayerLocation { .. } | Self::PlayerLocations { .. } => {
+                Some(EventClass::OpponentLocations)
+            }
+            Self::ReceivePlayerJokers { .. } | Self::PatchPlayerJokers { .. } => {
+                Some(EventClass::JokerPreviews)
+            }
+            Self::ReceivePlayerDeck { .. } | Self::PatchPlayerDeck { .. } => {
+                Some(EventClass::DeckPreviews)
+            }
+            Self::ShopSpending { .. } => Some(EventClass::Spending),
+            _ => None,
+        }
+    }
+
     // MessagePack conversion
     pub fn to_msgpack(&self) -> Vec<u8> {
         rmp_serde::to_vec_named(self).unwrap_or_else(|_| {
@@ -129,6 +615,7 @@ impl ServerToClient {
     pub fn connected(client_id: String) -> Self {
         Self::Connected {
             client_id: client_id,
+            region: CONFIG.get().region.clone(),
         }
     }
 
@@ -141,7 +628,7 @@ impl ServerToClient {
     pub fn joined_lobby(player_id: String, lobby_data: Lobby) -> Self {
         Self::JoinedLobby {
             player_id,
-            lobby_data,
+            lobby_data: Box::new(lobby_data),
         }
     }
 
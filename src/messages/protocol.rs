@@ -0,0 +1,315 @@
+//! Wire protocol versions and the shims that keep older clients working.
+//!
+//! Clients announce their version with `v` on any frame. Until they do they are
+//! treated as speaking [`LEGACY_PROTOCOL`], which is what the installed base of
+//! the mod sends. Messages to current clients go out wrapped in an envelope
+//! carrying `v`; legacy clients get the shapes they were built against.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ClientFrame, ClientToServer, ServerToClient};
+use crate::talisman_number::ScoreFormat;
+
+/// Shared encodings kept before dead ones are swept out
+const SHARED_ENCODINGS_SWEEP_AT: usize = 64;
+
+thread_local! {
+    /// Encodings of broadcast messages by message address, version and score format, so a
+    /// lobby-wide message is serialized once per worker thread rather than by every
+    /// client's writer, and writers never wait on each other for it. An entry is only
+    /// used while its message is still alive, after that the address may be reused.
+    static SHARED_ENCODINGS: RefCell<HashMap<SharedKey, SharedEncoding>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Message address, protocol version and score format
+type SharedKey = (usize, u32, ScoreFormat);
+type SharedEncoding = (Weak<ServerToClient>, Bytes);
+
+/// Clients that never send `v`
+pub const LEGACY_PROTOCOL: u32 = 1;
+/// The shapes defined by `ClientToServer`/`ServerToClient`
+pub const CURRENT_PROTOCOL: u32 = 2;
+
+/// A client announcing a newer version than this server knows gets the newest it has
+pub fn negotiate(announced: u32) -> u32 {
+    announced.clamp(LEGACY_PROTOCOL, CURRENT_PROTOCOL)
+}
+
+/// Current action tag for one a client on `version` sent, when it was renamed since
+fn upgrade_action(action: &str, version: u32) -> Option<&'static str> {
+    match action {
+        "k" if version < 2 => Some("keepAlive"),
+        _ => None,
+    }
+}
+
+/// The tag a client on `version` knows `message` by, when it was renamed since
+fn legacy_action(message: &ServerToClient, version: u32) -> Option<&'static str> {
+    match message {
+        ServerToClient::KeepAliveResponse { .. } if version < 2 => Some("a"),
+        _ => None,
+    }
+}
+
+/// Legacy keep-alive as sent under its short tag `k`
+#[derive(Deserialize)]
+struct LegacyKeepAlive {
+    #[serde(default)]
+    seq: Option<u64>,
+    #[serde(default)]
+    nonce: Option<u32>,
+}
+
+/// Legacy keep-alive answer, sent under its short tag `a`
+#[derive(Serialize)]
+struct LegacyKeepAliveAck {
+    action: &'static str,
+    nonce: Option<u32>,
+    server_time: u64,
+}
+
+/// Re-read a frame whose action this server didn't recognise as one an older
+/// client on `version` may have sent under a previous name
+pub fn upgrade_frame(buf: &[u8], action: &str, version: u32) -> Option<ClientFrame> {
+    match upgrade_action(action, version)? {
+        // Legacy clients send these all the time, so they skip the detour through JSON
+        "keepAlive" => {
+            let frame: LegacyKeepAlive = rmp_serde::from_slice(buf).ok()?;
+            Some(ClientFrame {
+                v: None,
+                seq: frame.seq,
+                action: ClientToServer::KeepAlive { nonce: frame.nonce },
+            })
+        }
+        current => {
+            let mut frame: Value = rmp_serde::from_slice(buf).ok()?;
+            frame["action"] = Value::from(current);
+            serde_json::from_value(frame).ok()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    v: u32,
+    /// Code of the spectated lobby the message comes from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lobby: Option<&'a str>,
+    /// Number of the message, for clients that can resume
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+    #[serde(flatten)]
+    message: &'a ServerToClient,
+}
+
+/// Serialize `message` for a client speaking `version`
+pub fn encode_message(message: &ServerToClient, version: u32) -> Vec<u8> {
+    let (seq, message) = match message {
+        ServerToClient::Sequenced { seq, message } => (Some(*seq), message.as_ref()),
+        _ => (None, message),
+    };
+    let (lobby, message) = match message {
+        ServerToClient::ForLobby {
+            lobby_code,
+            message,
+        } => (Some(lobby_code.as_str()), message.as_ref()),
+        _ => (None, message),
+    };
+    if let ServerToClient::KeepAliveResponse { nonce, server_time } = message
+        && version < CURRENT_PROTOCOL
+        && lobby.is_none()
+        && seq.is_none()
+    {
+        let ack = LegacyKeepAliveAck {
+            action: "a",
+            nonce: *nonce,
+            server_time: *server_time,
+        };
+        if let Ok(payload) = rmp_serde::to_vec_named(&ack) {
+            return payload;
+        }
+    }
+    if version >= CURRENT_PROTOCOL {
+        let envelope = Envelope {
+            v: CURRENT_PROTOCOL,
+            lobby,
+            seq,
+            message,
+        };
+        if let Ok(payload) = rmp_serde::to_vec_named(&envelope) {
+            return payload;
+        }
+    } else if (lobby.is_some() || seq.is_some() || legacy_action(message, version).is_some())
+        && let Ok(mut legacy) = serde_json::to_value(message)
+    {
+        if let Some(action) = legacy_action(message, version) {
+            legacy["action"] = Value::from(action);
+        }
+        if let Some(lobby) = lobby {
+            legacy["lobby"] = Value::from(lobby);
+        }
+        if let Some(seq) = seq {
+            legacy["seq"] = Value::from(seq);
+        }
+        if let Ok(payload) = rmp_serde::to_vec_named(&legacy) {
+            return payload;
+        }
+    }
+    message.to_msgpack()
+}
+
+/// [`encode_message`] for a message that may be queued for several clients at once,
+/// with scores written in the client's `format`
+pub fn encode_shared(message: &Arc<ServerToClient>, version: u32, format: ScoreFormat) -> Bytes {
+    let key = (Arc::as_ptr(message) as usize, version, format);
+    let cached = SHARED_ENCODINGS.with_borrow(|cache| {
+        cache
+            .get(&key)
+            .filter(|(sent, _)| sent.strong_count() > 0)
+            .map(|(_, payload)| payload.clone())
+    });
+    if let Some(payload) = cached {
+        return payload;
+    }
+    let payload = Bytes::from(format.apply(|| encode_message(message, version)));
+    // Nobody else holds it, so nobody else will ask for it
+    if Arc::strong_count(message) > 1 {
+        SHARED_ENCODINGS.with_borrow_mut(|cache| {
+            if cache.len() >= SHARED_ENCODINGS_SWEEP_AT {
+                cache.retain(|_, (sent, _)| sent.strong_count() > 0);
+            }
+            cache.insert(key, (Arc::downgrade(message), payload.clone()));
+        });
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(payload: &[u8]) -> Value {
+        rmp_serde::from_slice(payload).unwrap()
+    }
+
+    #[test]
+    fn test_legacy_clients_keep_old_action_names() {
+        let ack = ServerToClient::KeepAliveResponse {
+            nonce: Some(3),
+            server_time: 10,
+        };
+        let legacy = decode(&encode_message(&ack, LEGACY_PROTOCOL));
+        assert_eq!(legacy["action"], "a");
+        assert_eq!(legacy["nonce"], 3);
+        assert!(legacy.get("v").is_none());
+
+        let current = decode(&encode_message(&ack, CURRENT_PROTOCOL));
+        assert_eq!(current["action"], "keepAliveAck");
+        assert_eq!(current["v"], CURRENT_PROTOCOL);
+
+        let payload = rmp_serde::to_vec_named(&serde_json::json!({"action": "k", "seq": 2}));
+        let frame = upgrade_frame(&payload.unwrap(), "k", LEGACY_PROTOCOL).unwrap();
+        assert_eq!(frame.seq, Some(2));
+        assert!(matches!(frame.action, ClientToServer::KeepAlive { nonce: None }));
+        assert!(upgrade_frame(&[], "k", CURRENT_PROTOCOL).is_none());
+    }
+
+    #[test]
+    fn test_action_names_match_their_wire_tags() {
+        let actions = [
+            ClientToServer::KeepAlive { nonce: None },
+            ClientToServer::PlayHand {
+                score: crate::talisman_number::TalismanNumber::new_regular(100.0),
+                hands_left: 1,
+                breakdown: None,
+            },
+            ClientToServer::ReturnToLobby {},
+            ClientToServer::SendEmote {
+                emote_id: "wave".to_string(),
+                team_only: true,
+            },
+            ClientToServer::Unknown,
+        ];
+        for action in actions {
+            let wire = serde_json::to_value(&action).unwrap();
+            assert_eq!(wire["action"], action.name());
+        }
+    }
+
+    #[test]
+    fn test_broadcast_messages_are_encoded_once_per_version() {
+        let message = Arc::new(ServerToClient::error("shared"));
+        let other_client = Arc::clone(&message);
+        let first = encode_shared(&message, CURRENT_PROTOCOL, ScoreFormat::Native);
+        // Clones of the same encoding share its buffer
+        assert_eq!(first.as_ptr(), encode_shared(&other_client, CURRENT_PROTOCOL, ScoreFormat::Native).as_ptr());
+        assert_ne!(first.as_ptr(), encode_shared(&message, LEGACY_PROTOCOL, ScoreFormat::Native).as_ptr());
+        assert_eq!(decode(&first)["message"], "shared");
+
+        let unshared = Arc::new(ServerToClient::error("alone"));
+        assert_eq!(decode(&encode_shared(&unshared, CURRENT_PROTOCOL, ScoreFormat::Native))["message"], "alone");
+    }
+
+    #[test]
+    fn test_scores_follow_each_clients_format() {
+        use crate::lobby::hand_breakdown::HandBreakdown;
+        use crate::talisman_number::TalismanNumber;
+
+        let score = TalismanNumber::Big { m: 1.5, e: 400.0 };
+        let message = Arc::new(ServerToClient::HandPlayed {
+            player_id: "p1".to_string(),
+            score: score.clone(),
+            breakdown: HandBreakdown {
+                hand_type: "Flush".to_string(),
+                chips: TalismanNumber::Regular(250.0),
+                mult: score,
+                jokers: Vec::new(),
+            },
+        });
+        let _queued = Arc::clone(&message);
+
+        let talisman = decode(&encode_shared(&message, CURRENT_PROTOCOL, ScoreFormat::Talisman));
+        assert_eq!(talisman["score"]["e"], 400.0);
+        assert_eq!(talisman["breakdown"]["chips"]["m"], 250.0);
+        assert_eq!(talisman["breakdown"]["chips"]["e"], 0.0);
+
+        let notation = decode(&encode_shared(&message, CURRENT_PROTOCOL, ScoreFormat::Notation));
+        assert_eq!(notation["score"], "1.5e400");
+        assert_eq!(notation["breakdown"]["chips"], 250.0);
+
+        let native = decode(&encode_shared(&message, CURRENT_PROTOCOL, ScoreFormat::Native));
+        assert_eq!(native["breakdown"]["chips"], 250.0);
+    }
+
+    #[test]
+    fn test_spectated_messages_carry_their_lobby_code() {
+        let message = ServerToClient::ForLobby {
+            lobby_code: "ABCDE".to_string(),
+            message: Arc::new(ServerToClient::ServerNotice {
+                message: "hello".to_string(),
+            }),
+        };
+        for version in [LEGACY_PROTOCOL, CURRENT_PROTOCOL] {
+            let decoded = decode(&encode_message(&message, version));
+            assert_eq!(decoded["action"], "serverNotice");
+            assert_eq!(decoded["lobby"], "ABCDE");
+            assert_eq!(decoded["message"], "hello");
+        }
+        let own = decode(&encode_message(&ServerToClient::error("own"), CURRENT_PROTOCOL));
+        assert!(own.get("lobby").is_none());
+    }
+
+    #[test]
+    fn test_newer_clients_get_the_newest_version_known() {
+        assert_eq!(negotiate(0), LEGACY_PROTOCOL);
+        assert_eq!(negotiate(2), CURRENT_PROTOCOL);
+        assert_eq!(negotiate(99), CURRENT_PROTOCOL);
+    }
+}
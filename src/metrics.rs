@@ -0,0 +1,60 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Process-wide counters describing server load
+pub struct Metrics {
+    pub lobby_actions_shed: AtomicU64,
+    pub lobby_action_backpressure: AtomicU64,
+    /// Gauge of currently connected clients
+    pub connected_clients: AtomicU64,
+    /// Connections closed for going idle
+    pub connections_reaped: AtomicU64,
+    /// Actions received that this server doesn't implement
+    pub unsupported_actions: AtomicU64,
+    /// Gauge of listeners currently accepting clients
+    pub accept_loops: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub lobby_actions_shed: u64,
+    pub lobby_action_backpressure: u64,
+    pub connected_clients: u64,
+    pub connections_reaped: u64,
+    pub unsupported_actions: u64,
+    pub accept_loops: u64,
+}
+
+pub static METRICS: Metrics = Metrics::new();
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            lobby_actions_shed: AtomicU64::new(0),
+            lobby_action_backpressure: AtomicU64::new(0),
+            connected_clients: AtomicU64::new(0),
+            connections_reaped: AtomicU64::new(0),
+            unsupported_actions: AtomicU64::new(0),
+            accept_loops: AtomicU64::new(0),
+        }
+    }
+
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decr(counter: &AtomicU64) {
+        counter.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            lobby_actions_shed: self.lobby_actions_shed.load(Ordering::Relaxed),
+            lobby_action_backpressure: self.lobby_action_backpressure.load(Ordering::Relaxed),
+            connected_clients: self.connected_clients.load(Ordering::Relaxed),
+            connections_reaped: self.connections_reaped.load(Ordering::Relaxed),
+            unsupported_actions: self.unsupported_actions.load(Ordering::Relaxed),
+            accept_loops: self.accept_loops.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Player reports filed from inside a lobby, kept in SQLite until an operator
+//! reviews them from the console or the admin endpoints.
+
+use std::future::Future;
+use std::path::PathBuf;
+
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::lobby::event_log::LobbyEvent;
+use crate::lobby::lobby::Lobby;
+use crate::sqlite_store::SqliteStore;
+use crate::utils::now_millis;
+
+/// Longest reason a player can give for a report
+pub const MAX_REPORT_REASON: usize = 500;
+/// Lobby events kept with a report as context
+const REPORT_CONTEXT_EVENTS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Open,
+    Dismissed,
+    Actioned,
+}
+
+impl ReportStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Dismissed => "dismissed",
+            Self::Actioned => "actioned",
+        }
+    }
+
+    fn parse(status: &str) -> Self {
+        match status {
+            "dismissed" => Self::Dismissed,
+            "actioned" => Self::Actioned,
+            _ => Self::Open,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerReport {
+    pub report_id: String,
+    pub created_at: u64,
+    pub lobby_code: String,
+    pub reporter_id: String,
+    pub reporter_account_id: Option<String>,
+    pub reported_id: String,
+    pub reported_username: String,
+    pub reported_account_id: Option<String>,
+    pub reason: String,
+    /// Most recent lobby events when the report was filed
+    pub recent_events: Vec<LobbyEvent>,
+    pub status: ReportStatus,
+    pub resolved_at: Option<u64>,
+}
+
+impl PlayerReport {
+    /// Build a report against `reported_id`, who must be a human in `lobby`
+    pub fn new(
+        lobby: &Lobby,
+        reporter_id: &str,
+        reported_id: &str,
+        mut reason: String,
+    ) -> Result<Self, &'static str> {
+        if reporter_id == reported_id {
+            return Err("You can't report yourself");
+        }
+        let reported = lobby
+            .players()
+            .get(reported_id)
+            .filter(|p| !p.profile.is_bot)
+            .ok_or("That player is not in your lobby")?;
+        if reason.trim().is_empty() {
+            return Err("Please give a reason for the report");
+        }
+        if reason.len() > MAX_REPORT_REASON {
+            let mut cut = MAX_REPORT_REASON;
+            while !reason.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            reason.truncate(cut);
+        }
+        let events: Vec<LobbyEvent> = lobby.event_log().events().cloned().collect();
+        let skip = events.len().saturating_sub(REPORT_CONTEXT_EVENTS);
+        Ok(Self {
+            report_id: Uuid::new_v4().to_string(),
+            created_at: now_millis(),
+            lobby_code: lobby.code.clone(),
+            reporter_id: reporter_id.to_string(),
+            reporter_account_id: lobby
+                .players()
+                .get(reporter_id)
+                .and_then(|p| p.profile.account_id.clone()),
+            reported_id: reported_id.to_string(),
+            reported_username: reported.profile.username.clone(),
+            reported_account_id: reported.profile.account_id.clone(),
+            reason,
+            recent_events: events.into_iter().skip(skip).collect(),
+            status: ReportStatus::Open,
+            resolved_at: None,
+        })
+    }
+}
+
+pub struct PlayerReports {
+    store: SqliteStore,
+}
+
+impl PlayerReports {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            store: SqliteStore::new("Player reports", path, Self::init),
+        }
+    }
+
+    fn init(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS player_reports (
+                report_id TEXT PRIMARY KEY,
+                created_at INTEGER NOT NULL,
+                lobby_code TEXT NOT NULL,
+                reporter_id TEXT NOT NULL,
+                reporter_account_id TEXT,
+                reported_id TEXT NOT NULL,
+                reported_username TEXT NOT NULL,
+                reported_account_id TEXT,
+                reason TEXT NOT NULL,
+                recent_events TEXT NOT NULL,
+                status TEXT NOT NULL,
+                resolved_at INTEGER
+            )",
+        )
+    }
+
+    pub fn file(&self, report: PlayerReport) -> impl Future<Output = bool> + Send + 'static {
+        let stored = self.store.run(move |conn| {
+            let events = serde_json::to_string(&report.recent_events).unwrap_or_default();
+            let result = conn.execute(
+                "INSERT INTO player_reports
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    report.report_id,
+                    report.created_at as i64,
+                    report.lobby_code,
+                    report.reporter_id,
+                    report.reporter_account_id,
+                    report.reported_id,
+                    report.reported_username,
+                    report.reported_account_id,
+                    report.reason,
+                    events,
+                    report.status.as_str(),
+                    report.resolved_at.map(|t| t as i64),
+                ],
+            );
+            if let Err(e) = &result {
+                error!("Failed to store player report {}: {}", report.report_id, e);
+            }
+            result.is_ok()
+        });
+        async move { stored.await.unwrap_or(false) }
+    }
+
+    /// Open reports, oldest first
+    pub fn open_reports(&self) -> impl Future<Output = Vec<PlayerReport>> + Send + 'static {
+        let reports = self.store.run(|conn| {
+            let reports = conn
+                .prepare("SELECT * FROM player_reports WHERE status = 'open' ORDER BY created_at")
+                .and_then(|mut stmt| stmt.query_map([], report_from_row)?.collect());
+            reports.unwrap_or_else(|e| {
+                error!("Failed to list player reports: {}", e);
+                Vec::new()
+            })
+        });
+        async move { reports.await.unwrap_or_default() }
+    }
+
+    pub fn get(
+        &self,
+        report_id: String,
+    ) -> impl Future<Output = Option<PlayerReport>> + Send + 'static {
+        let report = self.store.run(move |conn| get_report(conn, &report_id));
+        async move { report.await.flatten() }
+    }
+
+    /// Close an open report; the report as it was before closing, if it was open
+    pub fn resolve(
+        &self,
+        report_id: String,
+        status: ReportStatus,
+    ) -> impl Future<Output = Option<PlayerReport>> + Send + 'static {
+        let resolved = self.store.run(move |conn| {
+            let report = get_report(conn, &report_id).filter(|r| r.status == ReportStatus::Open)?;
+            let updated = conn.execute(
+                "UPDATE player_reports SET status = ?2, resolved_at = ?3
+                 WHERE report_id = ?1 AND status = 'open'",
+                params![report_id, status.as_str(), now_millis() as i64],
+            );
+            matches!(updated, Ok(1)).then_some(report)
+        });
+        async move { resolved.await.flatten() }
+    }
+}
+
+fn get_report(conn: &Connection, report_id: &str) -> Option<PlayerReport> {
+    conn.query_row(
+        "SELECT * FROM player_reports WHERE report_id = ?1",
+        params![report_id],
+        report_from_row,
+    )
+    .optional()
+    .ok()
+    .flatten()
+}
+
+fn report_from_row(row: &Row) -> rusqlite::Result<PlayerReport> {
+    let events: String = row.get("recent_events")?;
+    let status: String = row.get("status")?;
+    Ok(PlayerReport {
+        report_id: row.get("report_id")?,
+        created_at: row.get::<_, i64>("created_at")? as u64,
+        lobby_code: row.get("lobby_code")?,
+        reporter_id: row.get("reporter_id")?,
+        reporter_account_id: row.get("reporter_account_id")?,
+        reported_id: row.get("reported_id")?,
+        reported_username: row.get("reported_username")?,
+        reported_account_id: row.get("reported_account_id")?,
+        reason: row.get("reason")?,
+        recent_events: serde_json::from_str(&events).unwrap_or_default(),
+        status: ReportStatus::parse(&status),
+        resolved_at: row.get::<_, Option<i64>>("resolved_at")?.map(|t| t as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientProfile;
+    use crate::game_mode::GameMode;
+
+    fn in_memory() -> PlayerReports {
+        PlayerReports {
+            store: SqliteStore::in_memory(PlayerReports::init),
+        }
+    }
+
+    fn lobby_with_players() -> Lobby {
+        let mut lobby = Lobby::new("ABCDE".to_string(), "ranked".to_string(), GameMode::Attrition);
+        for id in ["p1", "p2"] {
+            let profile = ClientProfile {
+                id: id.to_string(),
+                username: id.to_uppercase(),
+                ..ClientProfile::default()
+            };
+            lobby.add_player(id.to_string(), profile);
+            lobby.record_event(Some(id), "joined");
+        }
+        lobby
+    }
+
+    #[test]
+    fn test_report_needs_another_player_in_the_lobby() {
+        let lobby = lobby_with_players();
+        assert!(PlayerReport::new(&lobby, "p1", "p1", "griefing".to_string()).is_err());
+        assert!(PlayerReport::new(&lobby, "p1", "p3", "griefing".to_string()).is_err());
+        assert!(PlayerReport::new(&lobby, "p1", "p2", "  ".to_string()).is_err());
+
+        let report = PlayerReport::new(&lobby, "p1", "p2", "x".repeat(1000)).unwrap();
+        assert_eq!(report.reason.len(), MAX_REPORT_REASON);
+        assert_eq!(report.reported_username, "P2");
+        assert_eq!(report.recent_events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reports_are_resolved_once() {
+        let reports = in_memory();
+        let report = PlayerReport::new(&lobby_with_players(), "p1", "p2", "afk".to_string()).unwrap();
+        let report_id = report.report_id.clone();
+        assert!(reports.file(report).await);
+        let open = reports.open_reports().await;
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].recent_events.len(), 2);
+
+        assert!(reports.resolve(report_id.clone(), ReportStatus::Dismissed).await.is_some());
+        assert!(reports.resolve(report_id.clone(), ReportStatus::Actioned).await.is_none());
+        assert!(reports.open_reports().await.is_empty());
+        let closed = reports.get(report_id).await.unwrap();
+        assert_eq!(closed.status, ReportStatus::Dismissed);
+        assert!(closed.resolved_at.is_some());
+    }
+}
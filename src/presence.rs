@@ -0,0 +1,301 @@
+//! Read-only presence feed for community Discord bots.
+//!
+//! Subscribers connect to `presence_listen`, send one of `presence_tokens` on the
+//! first line and then receive a JSON line with every registered player's status
+//! whenever it changes. Anything they send afterwards is ignored.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::client::ClientProfile;
+use crate::config::CONFIG;
+use crate::messages::CoordinatorMessage;
+use crate::utils::constant_time_eq;
+
+/// Subscribers must authenticate within this long of connecting
+const PRESENCE_AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest token line read before a subscriber is authenticated
+const MAX_PRESENCE_TOKEN_LINE: u64 = 256;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Online,
+    InLobby,
+    InGame,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayerPresence {
+    pub account_id: String,
+    pub username: String,
+    pub status: PresenceStatus,
+    pub lobby_code: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "presence")]
+struct PresenceSnapshot<'a> {
+    players: &'a [PlayerPresence],
+}
+
+/// Coordinator-side view of who is online, fed from client and lobby events
+#[derive(Default)]
+pub struct PresenceTracker {
+    /// Connected clients that sent an account id: client id -> (account id, username)
+    registered: HashMap<String, (String, String)>,
+    started_lobbies: HashSet<String>,
+    subscribers: Vec<mpsc::UnboundedSender<Arc<String>>>,
+    last_published: Option<Arc<String>>,
+    /// Something presence depends on changed since the last publish
+    changed: bool,
+}
+
+impl PresenceTracker {
+    pub fn client_registered(&mut self, client_id: String, profile: &ClientProfile) {
+        self.changed = true;
+        match &profile.account_id {
+            Some(account_id) => {
+                self.registered
+                    .insert(client_id, (account_id.clone(), profile.username.clone()));
+            }
+            None => {
+                self.registered.remove(&client_id);
+            }
+        }
+    }
+
+    pub fn client_offline(&mut self, client_id: &str) {
+        self.changed |= self.registered.remove(client_id).is_some();
+    }
+
+    /// A client joined, left or moved lobby
+    pub fn lobbies_changed(&mut self) {
+        self.changed = true;
+    }
+
+    pub fn lobby_game_state(&mut self, lobby_code: String, started: bool) {
+        self.changed = true;
+        if started {
+            self.started_lobbies.insert(lobby_code);
+        } else {
+            self.started_lobbies.remove(&lobby_code);
+        }
+    }
+
+    pub fn lobby_closed(&mut self, lobby_code: &str) {
+        self.changed |= self.started_lobbies.remove(lobby_code);
+    }
+
+    /// One entry per account, the busiest connection wins
+    pub fn snapshot(&self, client_lobbies: &HashMap<String, String>) -> Vec<PlayerPresence> {
+        let mut players: BTreeMap<&str, PlayerPresence> = BTreeMap::new();
+        for (client_id, (account_id, username)) in &self.registered {
+            let lobby_code = client_lobbies.get(client_id);
+            let status = match lobby_code {
+                Some(code) if self.started_lobbies.contains(code) => PresenceStatus::InGame,
+                Some(_) => PresenceStatus::InLobby,
+                None => PresenceStatus::Online,
+            };
+            let presence = PlayerPresence {
+                account_id: account_id.clone(),
+                username: username.clone(),
+                status,
+                lobby_code: lobby_code.cloned(),
+            };
+            match players.get(account_id.as_str()) {
+                Some(existing) if existing.status >= presence.status => {}
+                _ => {
+                    players.insert(account_id, presence);
+                }
+            }
+        }
+        players.into_values().collect()
+    }
+
+    pub fn subscribe(
+        &mut self,
+        update_tx: mpsc::UnboundedSender<Arc<String>>,
+        client_lobbies: &HashMap<String, String>,
+    ) {
+        // Bring existing subscribers up to date first so everyone shares one baseline
+        self.publish(client_lobbies);
+        let current = match &self.last_published {
+            Some(current) => Arc::clone(current),
+            None => self.render(client_lobbies),
+        };
+        if update_tx.send(Arc::clone(&current)).is_ok() {
+            self.subscribers.push(update_tx);
+            self.last_published = Some(current);
+        }
+    }
+
+    /// Push the current snapshot to subscribers if anything changed since the last one
+    pub fn publish(&mut self, client_lobbies: &HashMap<String, String>) {
+        if !std::mem::take(&mut self.changed) {
+            return;
+        }
+        if self.subscribers.is_empty() {
+            // Nobody is listening, don't track changes until someone subscribes
+            self.last_published = None;
+            return;
+        }
+        let current = self.render(client_lobbies);
+        if self.last_published.as_ref() == Some(&current) {
+            return;
+        }
+        self.subscribers
+            .retain(|subscriber| subscriber.send(Arc::clone(&current)).is_ok());
+        self.last_published = Some(current);
+    }
+
+    fn render(&self, client_lobbies: &HashMap<String, String>) -> Arc<String> {
+        let players = self.snapshot(client_lobbies);
+        let json = serde_json::to_string(&PresenceSnapshot { players: &players })
+            .unwrap_or_default();
+        Arc::new(json)
+    }
+}
+
+/// Accept presence subscribers until the listener fails
+pub async fn run_presence_relay(
+    addr: SocketAddr,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Presence relay listening on {}", addr);
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        tokio::spawn(serve_subscriber(socket, peer, coordinator_tx.clone()));
+    }
+}
+
+async fn serve_subscriber(
+    socket: TcpStream,
+    peer: SocketAddr,
+    coordinator_tx: mpsc::UnboundedSender<CoordinatorMessage>,
+) {
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+
+    // Nobody is trusted yet, so the token line is read with a bound
+    let mut token = String::new();
+    let mut limited = (&mut reader).take(MAX_PRESENCE_TOKEN_LINE);
+    let read = limited.read_line(&mut token);
+    let authorized = match tokio::time::timeout(PRESENCE_AUTH_TIMEOUT, read).await {
+        Ok(Ok(_)) if token.ends_with('\n') => {
+            let token = token.trim().as_bytes();
+            let tokens = &CONFIG.get().presence_tokens;
+            // Check every token so the time taken doesn't say which one came close
+            tokens
+                .iter()
+                .fold(false, |found, t| constant_time_eq(t.as_bytes(), token) | found)
+        }
+        _ => false,
+    };
+    if !authorized {
+        warn!("Rejected presence subscriber {}", peer);
+        let _ = writer
+            .write_all(b"{\"type\":\"error\",\"message\":\"unauthorized\"}\n")
+            .await;
+        return;
+    }
+
+    let (update_tx, mut update_rx) = mpsc::unbounded_channel();
+    if coordinator_tx
+        .send(CoordinatorMessage::SubscribePresence { update_tx })
+        .is_err()
+    {
+        return;
+    }
+    debug!("Presence subscriber {} connected", peer);
+
+    let mut discard = [0u8; 1024];
+    loop {
+        tokio::select! {
+            update = update_rx.recv() => {
+                let Some(update) = update else { break };
+                if writer.write_all(update.as_bytes()).await.is_err()
+                    || writer.write_all(b"\n").await.is_err()
+                {
+                    break;
+                }
+            }
+            // Read-only feed: input is discarded, EOF ends the subscription
+            read = reader.read(&mut discard) => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    break;
+                }
+            }
+        }
+    }
+    debug!("Presence subscriber {} disconnected", peer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(account: &str) -> ClientProfile {
+        ClientProfile {
+            username: account.to_uppercase(),
+            account_id: Some(account.to_string()),
+            ..ClientProfile::default()
+        }
+    }
+
+    #[test]
+    fn test_presence_follows_lobby_and_game() {
+        let mut presence = PresenceTracker::default();
+        let mut client_lobbies = HashMap::new();
+        presence.client_registered("c1".to_string(), &registered("alice"));
+        presence.client_registered("c2".to_string(), &ClientProfile::default());
+
+        let snapshot = presence.snapshot(&client_lobbies);
+        assert_eq!(snapshot.len(), 1, "guests are not listed");
+        assert_eq!(snapshot[0].status, PresenceStatus::Online);
+
+        client_lobbies.insert("c1".to_string(), "ABCDE".to_string());
+        assert_eq!(presence.snapshot(&client_lobbies)[0].status, PresenceStatus::InLobby);
+        presence.lobby_game_state("ABCDE".to_string(), true);
+        let snapshot = presence.snapshot(&client_lobbies);
+        assert_eq!(snapshot[0].status, PresenceStatus::InGame);
+        assert_eq!(snapshot[0].lobby_code.as_deref(), Some("ABCDE"));
+
+        presence.client_offline("c1");
+        assert!(presence.snapshot(&client_lobbies).is_empty());
+    }
+
+    #[test]
+    fn test_publish_only_sends_changes() {
+        let mut presence = PresenceTracker::default();
+        let client_lobbies = HashMap::new();
+        let (update_tx, mut update_rx) = mpsc::unbounded_channel();
+        presence.subscribe(update_tx, &client_lobbies);
+        assert!(update_rx.try_recv().unwrap().contains("\"players\":[]"));
+
+        presence.client_registered("c1".to_string(), &registered("alice"));
+        presence.publish(&client_lobbies);
+        presence.publish(&client_lobbies);
+        let update = update_rx.try_recv().unwrap();
+        assert!(update.contains("\"account_id\":\"alice\""));
+        assert!(update_rx.try_recv().is_err());
+
+        // Lobby moves are only looked at once the coordinator flags them
+        let mut client_lobbies = client_lobbies;
+        client_lobbies.insert("c1".to_string(), "ABCDE".to_string());
+        presence.publish(&client_lobbies);
+        assert!(update_rx.try_recv().is_err());
+        presence.lobbies_changed();
+        presence.publish(&client_lobbies);
+        assert!(update_rx.try_recv().unwrap().contains("\"status\":\"in_lobby\""));
+    }
+}
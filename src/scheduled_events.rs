@@ -0,0 +1,171 @@
+//! Scheduled events: rule modifiers the operator switches on for set windows, like a
+//! double-lives weekend or boss-rush evenings. Lobbies created during a window start
+//! from defaults with its modifiers applied, and clients hear about running events
+//! when they connect.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::CONFIG;
+use crate::game_mode::{GameMode, LobbyOptions};
+use crate::utils::now_millis;
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledEvent {
+    pub name: String,
+    /// Days the window opens on, 0 is Monday; every day when empty
+    #[serde(default)]
+    pub days: Vec<u8>,
+    /// Minute of the day the window opens, in the event's local time
+    pub start_minute: u16,
+    /// Minute of the day it closes; before `start_minute` for windows past midnight
+    pub end_minute: u16,
+    /// Offset of the event's local time from UTC, e.g. 60 for CET
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    #[serde(default)]
+    pub modifiers: EventModifiers,
+}
+
+/// Changes to a lobby's default options, fields left unset keep the mode's default
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventModifiers {
+    /// Starting lives are multiplied by this, e.g. 2 for double lives
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lives_multiplier: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pvp_start_round: Option<i32>,
+    /// Boss rush: the host picks every PvP boss
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normal_bosses: Option<bool>,
+    /// Modes the event applies to, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub game_modes: Vec<GameMode>,
+}
+
+/// A running event as clients see it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActiveEvent {
+    pub name: String,
+    pub modifiers: EventModifiers,
+    /// Unix millis the window closes
+    pub ends_at: u64,
+}
+
+impl ScheduledEvent {
+    /// When the window running at `now` (unix seconds) closes, None outside it
+    fn ends_at(&self, now: u64) -> Option<u64> {
+        let local = now as i64 + i64::from(self.utc_offset_minutes) * 60;
+        let day = local.div_euclid(SECS_PER_DAY);
+        let secs_into_day = local.rem_euclid(SECS_PER_DAY);
+        let minute = (secs_into_day / 60) as u16;
+        // 1970-01-01 was a Thursday
+        let weekday = |day: i64| (day + 3).rem_euclid(7) as u8;
+        let opens_on = |day: i64| self.days.is_empty() || self.days.contains(&weekday(day));
+
+        let (start, end) = (self.start_minute, self.end_minute.min(MINUTES_PER_DAY));
+        let open = if start <= end {
+            (start..end).contains(&minute) && opens_on(day)
+        } else {
+            (minute >= start && opens_on(day)) || (minute < end && opens_on(day - 1))
+        };
+        if !open {
+            return None;
+        }
+        let end_secs = i64::from(end) * 60;
+        let left = if end_secs > secs_into_day {
+            end_secs - secs_into_day
+        } else {
+            SECS_PER_DAY - secs_into_day + end_secs
+        };
+        Some(now + left as u64)
+    }
+
+    fn applies_to(&self, game_mode: GameMode) -> bool {
+        self.modifiers.game_modes.is_empty() || self.modifiers.game_modes.contains(&game_mode)
+    }
+}
+
+impl EventModifiers {
+    fn apply(&self, options: &mut LobbyOptions) {
+        if let Some(multiplier) = self.lives_multiplier {
+            options.starting_lives = options.starting_lives.saturating_mul(multiplier.max(1));
+        }
+        if let Some(round) = self.pvp_start_round {
+            options.pvp_start_round = round;
+        }
+        if let Some(normal_bosses) = self.normal_bosses {
+            options.normal_bosses = normal_bosses;
+        }
+    }
+}
+
+fn active_at(events: &[ScheduledEvent], now: u64) -> Vec<ActiveEvent> {
+    events
+        .iter()
+        .filter_map(|event| {
+            event.ends_at(now).map(|ends_at| ActiveEvent {
+                name: event.name.clone(),
+                modifiers: event.modifiers.clone(),
+                ends_at: ends_at * 1000,
+            })
+        })
+        .collect()
+}
+
+/// Events running right now, in config order
+pub fn active_events() -> Vec<ActiveEvent> {
+    active_at(&CONFIG.get().scheduled_events, now_millis() / 1000)
+}
+
+/// The mode's default options with every running event that covers it applied
+pub fn event_defaults(game_mode: GameMode) -> LobbyOptions {
+    let mut options = game_mode.get_default_options();
+    let now = now_millis() / 1000;
+    for event in &CONFIG.get().scheduled_events {
+        if event.applies_to(game_mode) && event.ends_at(now).is_some() {
+            event.modifiers.apply(&mut options);
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_follow_local_days_and_wrap_past_midnight() {
+        // Friday 2024-01-05 22:30 UTC
+        let friday_night = 1_704_493_800;
+        let weekend = ScheduledEvent {
+            name: "Double lives weekend".to_string(),
+            days: vec![4, 5, 6],
+            start_minute: 20 * 60,
+            end_minute: 2 * 60,
+            utc_offset_minutes: 60,
+            modifiers: EventModifiers {
+                lives_multiplier: Some(2),
+                ..EventModifiers::default()
+            },
+        };
+        // 23:30 local, closing at 02:00 local
+        assert_eq!(weekend.ends_at(friday_night), Some(friday_night + 150 * 60));
+        // Saturday 01:30 local still belongs to Friday's window
+        assert!(weekend.ends_at(friday_night + 2 * 3600).is_some());
+        // Thursday evening is not part of the weekend
+        assert_eq!(weekend.ends_at(friday_night - 24 * 3600), None);
+        assert_eq!(weekend.ends_at(friday_night + 4 * 3600), None);
+
+        let active = active_at(std::slice::from_ref(&weekend), friday_night);
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].ends_at, (friday_night + 150 * 60) * 1000);
+
+        let mut options = GameMode::Attrition.get_default_options();
+        let lives = options.starting_lives;
+        weekend.modifiers.apply(&mut options);
+        assert_eq!(options.starting_lives, lives * 2);
+    }
+}
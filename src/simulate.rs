@@ -0,0 +1,317 @@
+//! Headless bot-vs-bot games played through the real lobby logic, for tuning
+//! game modes without playtesting:
+//! `BalatroRustServer simulate --games 1000 --mode Attrition --bots easy,hard --seed 7`
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{Context, bail};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::mpsc;
+
+use crate::audit;
+use crate::client::ClientProfile;
+use crate::federation;
+use crate::game_mode::GameMode;
+use crate::lobby::bot::Bot;
+use crate::lobby::broadcaster::LobbyBroadcaster;
+use crate::lobby::handlers::LobbyHandlers;
+use crate::lobby::lobby::Lobby;
+use crate::lobby::task::handle_client_join;
+use crate::lobby::BotDifficulty;
+use crate::messages::{ClientToServer, ServerToClient};
+use crate::usage_stats;
+use crate::webhooks;
+
+#[derive(Debug, Clone)]
+pub struct SimulationSettings {
+    pub games: u32,
+    pub game_mode: GameMode,
+    pub bots: Vec<BotDifficulty>,
+    /// Games still running after this many PvP rounds count as unfinished
+    pub max_rounds: u32,
+    /// Seeds the bots' rolls so a run can be replayed, random when unset
+    pub seed: Option<u64>,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            games: 1000,
+            game_mode: GameMode::Attrition,
+            bots: vec![BotDifficulty::Medium, BotDifficulty::Medium],
+            max_rounds: 50,
+            seed: None,
+        }
+    }
+}
+
+impl SimulationSettings {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .with_context(|| format!("Missing value for {flag}"))?;
+            match flag.as_str() {
+                "--games" => settings.games = value.parse().context("Invalid --games")?,
+                "--max-rounds" => {
+                    settings.max_rounds = value.parse().context("Invalid --max-rounds")?
+                }
+                "--seed" => settings.seed = Some(value.parse().context("Invalid --seed")?),
+                "--mode" => settings.game_mode = value.parse().map_err(anyhow::Error::msg)?,
+                "--bots" => {
+                    settings.bots = value
+                        .split(',')
+                        .map(|d| d.trim().parse().map_err(anyhow::Error::msg))
+                        .collect::<anyhow::Result<_>>()?
+                }
+                other => bail!("Unknown simulate option: {other}"),
+            }
+        }
+        if settings.bots.len() < 2 {
+            bail!("A simulation needs at least two bots");
+        }
+        let max_players = settings.game_mode.get_max_players() as usize;
+        if settings.bots.len() > max_players {
+            bail!("{} lobbies hold at most {} players", settings.game_mode, max_players);
+        }
+        Ok(settings)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SimulationReport {
+    pub settings: Option<SimulationSettings>,
+    pub games: u32,
+    pub unfinished: u32,
+    /// Wins per seat, seats follow the order of `--bots`
+    pub wins: Vec<u32>,
+    pub total_rounds: u64,
+    /// Per PvP round: (games that reached it, lives lost in it)
+    pub life_losses: Vec<(u32, u32)>,
+}
+
+impl SimulationReport {
+    pub fn average_rounds(&self) -> f64 {
+        let finished = self.games - self.unfinished;
+        if finished == 0 {
+            return 0.0;
+        }
+        self.total_rounds as f64 / finished as f64
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(settings) = &self.settings {
+            writeln!(
+                f,
+                "Simulated {} {} games with bots {:?}",
+                self.games, settings.game_mode, settings.bots
+            )?;
+        }
+        writeln!(f, "Unfinished after the round cap: {}", self.unfinished)?;
+        writeln!(f, "Average game length: {:.2} PvP rounds", self.average_rounds())?;
+        writeln!(f, "Win rate per seat:")?;
+        for (seat, wins) in self.wins.iter().enumerate() {
+            let rate = *wins as f64 / self.games.max(1) as f64 * 100.0;
+            writeln!(f, "  seat {}: {:>5.1}% ({} wins)", seat + 1, rate, wins)?;
+        }
+        writeln!(f, "Lives lost per round (games reaching it, average lives lost):")?;
+        for (round, (reached, lost)) in self.life_losses.iter().enumerate() {
+            let average = *lost as f64 / (*reached).max(1) as f64;
+            writeln!(f, "  round {:>3}: {:>6} games, {:.2}", round + 1, reached, average)?;
+        }
+        Ok(())
+    }
+}
+
+/// Entry point for the `simulate` subcommand
+pub fn run_cli(args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let settings = SimulationSettings::from_args(args)?;
+    webhooks::mute();
+    audit::mute();
+    usage_stats::mute();
+    federation::mute();
+    print!("{}", run_simulation(&settings));
+    Ok(())
+}
+
+pub fn run_simulation(settings: &SimulationSettings) -> SimulationReport {
+    let mut report = SimulationReport {
+        settings: Some(settings.clone()),
+        wins: vec![0; settings.bots.len()],
+        ..SimulationReport::default()
+    };
+    let mut rng = match settings.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
+    for game in 0..settings.games {
+        report.games += 1;
+        let outcome = simulate_game(settings, game, &mut rng);
+        for (round, lost) in outcome.life_losses.iter().enumerate() {
+            if report.life_losses.len() <= round {
+                report.life_losses.push((0, 0));
+            }
+            report.life_losses[round].0 += 1;
+            report.life_losses[round].1 += lost;
+        }
+        match outcome.winners {
+            Some(winners) => {
+                report.total_rounds += outcome.life_losses.len() as u64;
+                for seat in winners {
+                    report.wins[seat] += 1;
+                }
+            }
+            None => report.unfinished += 1,
+        }
+    }
+    report
+}
+
+struct GameOutcome {
+    /// Seats that won, `None` when the game hit the round cap or stalled
+    winners: Option<Vec<usize>>,
+    life_losses: Vec<u32>,
+}
+
+struct SimulatedPlayer {
+    bot: Bot,
+    events: mpsc::UnboundedReceiver<Arc<ServerToClient>>,
+}
+
+fn simulate_game(settings: &SimulationSettings, game: u32, rng: &mut StdRng) -> GameOutcome {
+    let mut lobby = Lobby::new(format!("SIM{game}"), "ranked".to_string(), settings.game_mode);
+    let mut broadcaster = LobbyBroadcaster::new();
+    let mut host_id = String::new();
+
+    let mut players = Vec::new();
+    for (seat, difficulty) in settings.bots.iter().enumerate() {
+        let id = format!("seat{}", seat + 1);
+        let profile = ClientProfile {
+            id: id.clone(),
+            username: difficulty.display_name().to_string(),
+            is_bot: true,
+            ..ClientProfile::default()
+        };
+        let (events_tx, events) = mpsc::unbounded_channel();
+        handle_client_join(&mut lobby, &mut broadcaster, id.clone(), profile, events_tx, &mut host_id);
+        players.push(SimulatedPlayer {
+            bot: Bot::with_seed(id, *difficulty, rng.random()),
+            events,
+        });
+    }
+
+    let start = ClientToServer::StartGame {
+        seed: "random".to_string(),
+        stake: 1,
+    };
+    LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, host_id.clone(), start);
+
+    let mut life_losses = Vec::new();
+    let mut lives = current_lives(&lobby);
+    loop {
+        // Deliver everything the lobby sent, collecting the bots' replies
+        let mut actions = Vec::new();
+        let mut results: Vec<(usize, bool)> = Vec::new();
+        let mut round_ended = false;
+        for (seat, player) in players.iter_mut().enumerate() {
+            while let Ok(event) = player.events.try_recv() {
+                match event.as_ref() {
+                    ServerToClient::WinGame { .. } => results.push((seat, true)),
+                    ServerToClient::LoseGame { .. } => results.push((seat, false)),
+                    ServerToClient::EndPvp { .. } => round_ended = true,
+                    _ => {}
+                }
+                let id = player.bot.id().to_string();
+                actions.extend(player.bot.handle(&event).into_iter().map(|a| (id.clone(), a)));
+            }
+        }
+        // Game over also ends the round, but without an EndPvp
+        if round_ended || !results.is_empty() {
+            let now = current_lives(&lobby);
+            let lost = lives
+                .iter()
+                .map(|(id, before)| before.saturating_sub(*now.get(id).unwrap_or(&0)) as u32)
+                .sum();
+            life_losses.push(lost);
+            lives = now;
+        }
+        if results.iter().any(|(_, won)| *won) || results.len() == players.len() {
+            let winners = results.iter().filter(|(_, won)| *won).map(|(seat, _)| *seat);
+            return GameOutcome {
+                winners: Some(winners.collect()),
+                life_losses,
+            };
+        }
+        if life_losses.len() as u32 >= settings.max_rounds {
+            break;
+        }
+
+        if actions.is_empty() {
+            // Nothing to react to, so the bots take turns playing hands
+            for player in players.iter_mut() {
+                if let Some(hand) = player.bot.next_hand() {
+                    actions.push((player.bot.id().to_string(), hand));
+                }
+            }
+        }
+        if actions.is_empty() {
+            break;
+        }
+        for (id, action) in actions {
+            LobbyHandlers::handle_player_action(&mut lobby, &broadcaster, id, action);
+        }
+    }
+    GameOutcome {
+        winners: None,
+        life_losses,
+    }
+}
+
+fn current_lives(lobby: &Lobby) -> HashMap<String, u8> {
+    lobby
+        .players()
+        .iter()
+        .map(|(id, p)| (id.clone(), p.game_state.lives))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attrition_games_finish_and_favour_the_stronger_bot() {
+        let settings = SimulationSettings {
+            games: 200,
+            bots: vec![BotDifficulty::Easy, BotDifficulty::Hard],
+            seed: Some(7),
+            ..SimulationSettings::default()
+        };
+        let report = run_simulation(&settings);
+        assert_eq!(report.games, 200);
+        assert_eq!(report.unfinished, 0);
+        assert!(report.average_rounds() >= settings.bots.len() as f64);
+        assert!(report.wins[1] > report.wins[0], "{report}");
+        assert!(report.life_losses[0].1 > 0);
+    }
+
+    #[test]
+    fn test_parse_simulate_args() {
+        let parse = |args: &[&str]| SimulationSettings::from_args(args.iter().map(|a| a.to_string()));
+        let settings = parse(&["--games", "10", "--mode", "Clash", "--bots", "easy,hard,medium"]);
+        let settings = settings.unwrap();
+        assert_eq!(settings.games, 10);
+        assert_eq!(settings.seed, None);
+        assert_eq!(parse(&["--seed", "7"]).unwrap().seed, Some(7));
+        assert_eq!(settings.game_mode, GameMode::Clash);
+        assert_eq!(settings.bots.len(), 3);
+        assert!(parse(&["--bots", "easy"]).is_err());
+        assert!(parse(&["--mode", "Attrition", "--bots", "easy,easy,easy"]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! SQLite databases the server keeps next to the coordinator. Each one lives on a
+//! thread of its own and queries are handed to it, so a slow disk never holds up
+//! message routing. The database is opened on first use, servers that never need
+//! it never create it.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use rusqlite::Connection;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+
+type Query = Box<dyn FnOnce(Option<&Connection>) + Send>;
+
+#[derive(Clone)]
+pub struct SqliteStore {
+    queries: mpsc::Sender<Query>,
+}
+
+impl SqliteStore {
+    /// The database at `path`, set up with `init` when it is first opened
+    pub fn new(
+        name: &'static str,
+        path: PathBuf,
+        init: fn(&Connection) -> rusqlite::Result<()>,
+    ) -> Self {
+        Self::spawn(name, move || {
+            let opened = Connection::open(&path).and_then(|conn| init(&conn).map(|()| conn));
+            match &opened {
+                Ok(_) => info!("{} stored in {:?}", name, path),
+                Err(e) => error!("{} unavailable ({:?}): {}", name, path, e),
+            }
+            opened.ok()
+        })
+    }
+
+    #[cfg(test)]
+    pub fn in_memory(init: fn(&Connection) -> rusqlite::Result<()>) -> Self {
+        Self::spawn("Test database", move || {
+            let conn = Connection::open_in_memory().unwrap();
+            init(&conn).unwrap();
+            Some(conn)
+        })
+    }
+
+    fn spawn(
+        name: &'static str,
+        open: impl FnOnce() -> Option<Connection> + Send + 'static,
+    ) -> Self {
+        let (queries, rx) = mpsc::channel::<Query>();
+        let spawned = std::thread::Builder::new()
+            .name(name.to_lowercase().replace(' ', "-"))
+            .spawn(move || {
+                let Ok(first) = rx.recv() else {
+                    return;
+                };
+                let conn = open();
+                first(conn.as_ref());
+                for query in rx {
+                    query(conn.as_ref());
+                }
+            });
+        if let Err(e) = spawned {
+            error!("Failed to start the {} thread: {}", name, e);
+        }
+        Self { queries }
+    }
+
+    /// Queue `query` for the database's thread, queries run in the order they were
+    /// queued. Resolves to `None` when the database is unavailable
+    pub fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&Connection) -> T + Send + 'static,
+    ) -> impl Future<Output = Option<T>> + Send + 'static {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let query: Query = Box::new(move |conn| {
+            let _ = reply_tx.send(conn.map(query));
+        });
+        let queued = self.queries.send(query).is_ok();
+        async move {
+            if !queued {
+                return None;
+            }
+            reply_rx.await.ok().flatten()
+        }
+    }
+}
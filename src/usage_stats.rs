@@ -0,0 +1,381 @@
+//! Anonymous balance data for the mod authors: which jokers finished games, how
+//! often each mode is played and how long its games run. Nothing here says who
+//! played; operators read it as JSON on `/admin/stats`. The totals are kept in a
+//! SQLite database so they outlive restarts.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::Duration;
+
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use tracing::error;
+
+use crate::game_mode::GameMode;
+use crate::sqlite_store::SqliteStore;
+
+/// Set for offline runs (e.g. `simulate`) whose games are not real
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+static USAGE: LazyLock<Mutex<UsageStats>> = LazyLock::new(Mutex::default);
+
+static STORE: OnceLock<SqliteStore> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageStats {
+    /// Games that ran to a result, per mode
+    pub modes: HashMap<GameMode, ModeUsage>,
+    /// Runs that finished a game holding each joker
+    pub jokers: HashMap<&'static str, u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModeUsage {
+    pub games: u64,
+    pub total_game_secs: u64,
+    pub average_game_secs: f64,
+}
+
+impl ModeUsage {
+    fn add(&mut self, games: u64, game_secs: u64) {
+        self.games += games;
+        self.total_game_secs += game_secs;
+        self.average_game_secs = self.total_game_secs as f64 / self.games as f64;
+    }
+}
+
+impl UsageStats {
+    /// Count a finished game; `jokers` is the last joker preview of every player
+    /// in it, as clients sent it with `sendPlayerJokers`. Returns the jokers counted,
+    /// once per run holding them
+    pub fn record_game<'a>(
+        &mut self,
+        mode: GameMode,
+        length: Duration,
+        jokers: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<&'static str> {
+        self.modes.entry(mode).or_default().add(1, length.as_secs());
+
+        let mut counted = Vec::new();
+        for preview in jokers {
+            let mut keys = joker_keys(preview);
+            keys.sort_unstable();
+            keys.dedup();
+            for &key in &keys {
+                *self.jokers.entry(key).or_default() += 1;
+            }
+            counted.extend(keys);
+        }
+        counted
+    }
+}
+
+/// Jokers of the base game in a preview payload; the rest of its format is up to the
+/// client. Only known keys are counted, so made-up ones can't crowd the table
+fn joker_keys(preview: &str) -> Vec<&'static str> {
+    preview
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter_map(known_joker)
+        .collect()
+}
+
+fn known_joker(key: &str) -> Option<&'static str> {
+    JOKERS.iter().find(|joker| **joker == key).copied()
+}
+
+pub fn mute() {
+    MUTED.store(true, Ordering::Relaxed);
+}
+
+/// Keep the totals in the database at `path` and pick up the ones it already holds;
+/// a database that doesn't exist yet is left for the first game to create
+pub async fn open(path: PathBuf) {
+    let existing = path.exists();
+    let store = SqliteStore::new("Usage stats", path, init);
+    if existing {
+        match store.run(load).await {
+            Some(Ok(stored)) => *USAGE.lock().unwrap_or_else(|e| e.into_inner()) = stored,
+            Some(Err(e)) => error!("Failed to load usage stats: {}", e),
+            None => {}
+        }
+    }
+    let _ = STORE.set(store);
+}
+
+fn init(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS mode_usage (
+            mode TEXT PRIMARY KEY,
+            games INTEGER NOT NULL,
+            total_game_secs INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS joker_usage (
+            joker TEXT PRIMARY KEY,
+            runs INTEGER NOT NULL
+        )",
+    )
+}
+
+fn load(conn: &Connection) -> rusqlite::Result<UsageStats> {
+    let mut usage = UsageStats::default();
+    let mut stmt = conn.prepare("SELECT mode, games, total_game_secs FROM mode_usage")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if let Ok(mode) = row.get::<_, String>(0)?.parse::<GameMode>() {
+            usage.modes.entry(mode).or_default().add(row.get(1)?, row.get(2)?);
+        }
+    }
+    let mut stmt = conn.prepare("SELECT joker, runs FROM joker_usage")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if let Some(joker) = known_joker(&row.get::<_, String>(0)?) {
+            usage.jokers.insert(joker, row.get(1)?);
+        }
+    }
+    Ok(usage)
+}
+
+fn store_game(
+    conn: &Connection,
+    mode: GameMode,
+    game_secs: u64,
+    jokers: &[&str],
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO mode_usage (mode, games, total_game_secs) VALUES (?1, 1, ?2)
+         ON CONFLICT (mode) DO UPDATE SET games = games + 1,
+             total_game_secs = total_game_secs + excluded.total_game_secs",
+        params![mode.to_string(), game_secs],
+    )?;
+    for joker in jokers {
+        tx.execute(
+            "INSERT INTO joker_usage (joker, runs) VALUES (?1, 1)
+             ON CONFLICT (joker) DO UPDATE SET runs = runs + 1",
+            params![joker],
+        )?;
+    }
+    tx.commit()
+}
+
+pub fn record_game<'a>(mode: GameMode, length: Duration, jokers: impl IntoIterator<Item = &'a str>) {
+    if MUTED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut usage = USAGE.lock().unwrap_or_else(|e| e.into_inner());
+    let counted = usage.record_game(mode, length, jokers);
+    drop(usage);
+    if let Some(store) = STORE.get() {
+        // The write is queued for the database thread as it is made, nothing waits for it
+        drop(store.run(move |conn| {
+            if let Err(e) = store_game(conn, mode, length.as_secs(), &counted) {
+                error!("Failed to store usage stats: {}", e);
+            }
+        }));
+    }
+}
+
+pub fn snapshot() -> UsageStats {
+    USAGE.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Jokers of the base game, the only keys usage is counted for
+const JOKERS: &[&str] = &[
+    "j_joker",
+    "j_greedy_joker",
+    "j_lusty_joker",
+    "j_wrathful_joker",
+    "j_gluttenous_joker",
+    "j_jolly",
+    "j_zany",
+    "j_mad",
+    "j_crazy",
+    "j_droll",
+    "j_sly",
+    "j_wily",
+    "j_clever",
+    "j_devious",
+    "j_crafty",
+    "j_half",
+    "j_stencil",
+    "j_four_fingers",
+    "j_mime",
+    "j_credit_card",
+    "j_ceremonial",
+    "j_banner",
+    "j_mystic_summit",
+    "j_marble",
+    "j_loyalty_card",
+    "j_8_ball",
+    "j_misprint",
+    "j_dusk",
+    "j_raised_fist",
+    "j_chaos",
+    "j_fibonacci",
+    "j_steel_joker",
+    "j_scary_face",
+    "j_abstract",
+    "j_delayed_grat",
+    "j_hack",
+    "j_pareidolia",
+    "j_gros_michel",
+    "j_even_steven",
+    "j_odd_todd",
+    "j_scholar",
+    "j_business",
+    "j_supernova",
+    "j_ride_the_bus",
+    "j_space",
+    "j_egg",
+    "j_burglar",
+    "j_blackboard",
+    "j_runner",
+    "j_ice_cream",
+    "j_dna",
+    "j_splash",
+    "j_blue_joker",
+    "j_sixth_sense",
+    "j_constellation",
+    "j_hiker",
+    "j_faceless",
+    "j_green_joker",
+    "j_superposition",
+    "j_todo_list",
+    "j_cavendish",
+    "j_card_sharp",
+    "j_red_card",
+    "j_madness",
+    "j_square",
+    "j_seance",
+    "j_riff_raff",
+    "j_vampire",
+    "j_shortcut",
+    "j_hologram",
+    "j_vagabond",
+    "j_baron",
+    "j_cloud_9",
+    "j_rocket",
+    "j_obelisk",
+    "j_midas_mask",
+    "j_luchador",
+    "j_photograph",
+    "j_gift",
+    "j_turtle_bean",
+    "j_erosion",
+    "j_reserved_parking",
+    "j_mail",
+    "j_to_the_moon",
+    "j_hallucination",
+    "j_fortune_teller",
+    "j_juggler",
+    "j_drunkard",
+    "j_stone",
+    "j_golden",
+    "j_lucky_cat",
+    "j_baseball",
+    "j_bull",
+    "j_diet_cola",
+    "j_trading",
+    "j_flash",
+    "j_popcorn",
+    "j_trousers",
+    "j_ancient",
+    "j_ramen",
+    "j_walkie_talkie",
+    "j_selzer",
+    "j_castle",
+    "j_smiley",
+    "j_campfire",
+    "j_ticket",
+    "j_mr_bones",
+    "j_acrobat",
+    "j_sock_and_buskin",
+    "j_swashbuckler",
+    "j_troubadour",
+    "j_certificate",
+    "j_smeared",
+    "j_throwback",
+    "j_hanging_chad",
+    "j_rough_gem",
+    "j_bloodstone",
+    "j_arrowhead",
+    "j_onyx_agate",
+    "j_glass",
+    "j_ring_master",
+    "j_flower_pot",
+    "j_blueprint",
+    "j_wee",
+    "j_merry_andy",
+    "j_oops",
+    "j_idol",
+    "j_seeing_double",
+    "j_matador",
+    "j_hit_the_road",
+    "j_duo",
+    "j_trio",
+    "j_family",
+    "j_order",
+    "j_tribe",
+    "j_stuntman",
+    "j_invisible",
+    "j_brainstorm",
+    "j_satellite",
+    "j_shoot_the_moon",
+    "j_drivers_license",
+    "j_cartomancer",
+    "j_astronomer",
+    "j_burnt",
+    "j_bootstraps",
+    "j_caino",
+    "j_triboulet",
+    "j_yorick",
+    "j_chicot",
+    "j_perkeo",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_games_count_each_joker_once_per_run() {
+        let mut usage = UsageStats::default();
+        usage.record_game(
+            GameMode::Attrition,
+            Duration::from_secs(600),
+            ["j_joker;j_droll;j_joker", "{\"j_blueprint\":1,\"j_joker\":2}"],
+        );
+        // Keys the game doesn't have aren't counted
+        let counted =
+            usage.record_game(GameMode::Attrition, Duration::from_secs(900), ["j_;j_made_up", ""]);
+        assert!(counted.is_empty());
+
+        assert_eq!(usage.jokers["j_joker"], 2);
+        assert_eq!(usage.jokers["j_droll"], 1);
+        assert_eq!(usage.jokers["j_blueprint"], 1);
+        assert_eq!(usage.jokers.len(), 3);
+        let attrition = &usage.modes[&GameMode::Attrition];
+        assert_eq!(attrition.games, 2);
+        assert_eq!(attrition.average_game_secs, 750.0);
+        assert!(!usage.modes.contains_key(&GameMode::Clash));
+
+        let json = serde_json::to_value(&usage).unwrap();
+        assert_eq!(json["modes"]["gamemode_mp_attrition"]["games"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_stored_totals_load_back() {
+        let store = SqliteStore::in_memory(init);
+        let stored = store.run(|conn| {
+            store_game(conn, GameMode::Clash, 300, &["j_joker", "j_baron"])?;
+            store_game(conn, GameMode::Clash, 500, &["j_joker"])?;
+            load(conn)
+        });
+        let usage = stored.await.unwrap().unwrap();
+        assert_eq!(usage.modes[&GameMode::Clash].games, 2);
+        assert_eq!(usage.modes[&GameMode::Clash].average_game_secs, 400.0);
+        assert_eq!((usage.jokers["j_joker"], usage.jokers["j_baron"]), (2, 1));
+    }
+}
@@ -1,5 +1,13 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Milliseconds since the Unix epoch, used as the shared server clock
+pub fn now_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
 pub fn time_based_string(n: usize) -> String {
   const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
   let mut result = String::with_capacity(n + 1);
@@ -24,4 +32,41 @@ pub fn time_based_string(n: usize) -> String {
     result.push(CHARSET[idx] as char);
   }
   result
-}
\ No newline at end of file
+}
+
+/// Same shape as `time_based_string`, but from the thread rng, so strings made in the
+/// same instant still differ
+pub fn random_seed_string(n: usize) -> String {
+  use rand::Rng;
+  const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+  let mut rng = rand::rng();
+  let mut result = String::with_capacity(n + 1);
+  result.push('*');
+  for _ in 0..n {
+    result.push(CHARSET[rng.random_range(0..CHARSET.len())] as char);
+  }
+  result
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+  if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+    return None;
+  }
+  (0..hex.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+    .collect()
+}
+
+/// Whether two secrets match, taking as long wherever they differ. Only the length leaks
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+  std::hint::black_box(diff) == 0
+}
@@ -0,0 +1,233 @@
+//! Persistent vanity lobby codes (e.g. `FILS-HOUSE`) owned by a verified account.
+//!
+//! Claims live in SQLite so they survive restarts, with a copy in memory so codes
+//! resolve without waiting on the database; which lobby a code currently points at
+//! is only known while its owner is hosting.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use tracing::error;
+
+use crate::sqlite_store::SqliteStore;
+
+const MIN_VANITY_LEN: usize = 6;
+const MAX_VANITY_LEN: usize = 16;
+const UNAVAILABLE: &str = "Vanity codes are unavailable";
+
+pub struct VanityCodes {
+    store: SqliteStore,
+    /// Every claim as stored: code -> account id
+    claims: HashMap<String, String>,
+    /// Lobbies hosted by vanity owners right now: account id -> (client id, lobby code)
+    hosting: HashMap<String, (String, String)>,
+}
+
+impl VanityCodes {
+    /// The codes claimed so far; a database that doesn't exist yet is left for the
+    /// first claim to create
+    pub async fn open(path: PathBuf) -> Self {
+        let existing = path.exists();
+        let mut vanity = Self::with_store(SqliteStore::new("Vanity codes", path, Self::init));
+        if existing {
+            vanity.load().await;
+        }
+        vanity
+    }
+
+    fn with_store(store: SqliteStore) -> Self {
+        Self {
+            store,
+            claims: HashMap::new(),
+            hosting: HashMap::new(),
+        }
+    }
+
+    fn init(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS vanity_codes (
+                code TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL UNIQUE,
+                claimed_at INTEGER NOT NULL
+            )",
+        )
+    }
+
+    async fn load(&mut self) {
+        let claims = self.store.run(|conn| {
+            let mut stmt = conn.prepare("SELECT code, account_id FROM vanity_codes")?;
+            let claims = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            claims.collect::<rusqlite::Result<HashMap<String, String>>>()
+        });
+        match claims.await {
+            Some(Ok(claims)) => self.claims = claims,
+            Some(Err(e)) => error!("Failed to load vanity codes: {}", e),
+            None => {}
+        }
+    }
+
+    /// Claim `code` for `account_id`, replacing any code the account held before, or
+    /// release the account's code when `None`. Codes known to be taken are refused
+    /// right away, the database has the last word; pass what it says to [`Self::changed`]
+    pub fn change(
+        &self,
+        account_id: String,
+        code: Option<String>,
+    ) -> impl Future<Output = Result<Option<String>, &'static str>> + Send + 'static {
+        let checked = code.as_deref().map(normalize).transpose().and_then(|code| {
+            match code.as_ref().and_then(|code| self.claims.get(code)) {
+                Some(owner) if *owner != account_id => Err("That code is already taken"),
+                _ => Ok(code),
+            }
+        });
+        let stored = checked.map(|code| {
+            self.store.run(move |conn| match code {
+                Some(code) => store_claim(conn, &account_id, code).map(Some),
+                None => conn
+                    .execute("DELETE FROM vanity_codes WHERE account_id = ?1", params![account_id])
+                    .map(|_| None)
+                    .map_err(|_| UNAVAILABLE),
+            })
+        });
+        async move { stored?.await.unwrap_or(Err(UNAVAILABLE)) }
+    }
+
+    /// The database took a change made through [`Self::change`]
+    pub fn changed(&mut self, account_id: &str, code: Option<String>) {
+        self.claims.retain(|_, owner| owner != account_id);
+        if let Some(code) = code {
+            self.claims.insert(code, account_id.to_string());
+        }
+    }
+
+    fn owner_of(&self, code: &str) -> Option<&String> {
+        self.claims.get(&normalize(code).ok()?)
+    }
+
+    /// Whether someone owns `code`, generated lobby codes must not shadow it
+    pub fn is_claimed(&self, code: &str) -> bool {
+        self.owner_of(code).is_some()
+    }
+
+    /// `account_id` is the host's verified account, if any
+    pub fn lobby_opened(&mut self, account_id: Option<&str>, client_id: &str, lobby_code: &str) {
+        if let Some(account_id) = account_id {
+            self.hosting.insert(
+                account_id.to_string(),
+                (client_id.to_string(), lobby_code.to_string()),
+            );
+        }
+    }
+
+    /// The owner left their lobby or it closed, the code no longer leads anywhere
+    pub fn host_left(&mut self, client_id: &str) {
+        self.hosting.retain(|_, (host, _)| host != client_id);
+    }
+
+    pub fn lobby_closed(&mut self, lobby_code: &str) {
+        self.hosting.retain(|_, (_, code)| code != lobby_code);
+    }
+
+    /// Resolve a vanity code to the lobby its owner is hosting
+    pub fn resolve(&self, code: &str) -> Option<Result<String, &'static str>> {
+        let owner = self.owner_of(code)?;
+        Some(
+            self.hosting
+                .get(owner)
+                .map(|(_, lobby_code)| lobby_code.clone())
+                .ok_or("Host is not hosting right now"),
+        )
+    }
+}
+
+fn store_claim(conn: &Connection, account_id: &str, code: String) -> Result<String, &'static str> {
+    let owner: Option<String> = conn
+        .query_row(
+            "SELECT account_id FROM vanity_codes WHERE code = ?1",
+            params![code],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|_| UNAVAILABLE)?;
+    if owner.is_some_and(|owner| owner != account_id) {
+        return Err("That code is already taken");
+    }
+    conn.execute(
+        "INSERT INTO vanity_codes (code, account_id, claimed_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(account_id) DO UPDATE SET code = ?1, claimed_at = ?3",
+        params![code, account_id, crate::utils::now_millis() as i64],
+    )
+    .map_err(|_| UNAVAILABLE)?;
+    Ok(code)
+}
+
+fn normalize(code: &str) -> Result<String, &'static str> {
+    let code = code.trim().to_ascii_uppercase();
+    // Shorter codes could collide with generated ones
+    if !(MIN_VANITY_LEN..=MAX_VANITY_LEN).contains(&code.len()) {
+        return Err("Vanity codes must be 6 to 16 characters");
+    }
+    if !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err("Vanity codes may only use letters, digits and '-'");
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory() -> VanityCodes {
+        VanityCodes::with_store(SqliteStore::in_memory(VanityCodes::init))
+    }
+
+    /// What the coordinator does with a change once the database answered
+    async fn change(
+        vanity: &mut VanityCodes,
+        account_id: &str,
+        code: Option<&str>,
+    ) -> Result<Option<String>, &'static str> {
+        let stored = vanity.change(account_id.to_string(), code.map(str::to_string)).await;
+        if let Ok(code) = &stored {
+            vanity.changed(account_id, code.clone());
+        }
+        stored
+    }
+
+    #[tokio::test]
+    async fn test_claims_are_unique_per_code_and_account() {
+        let mut vanity = in_memory();
+        let claimed = change(&mut vanity, "fil", Some("fils-house")).await;
+        assert_eq!(claimed, Ok(Some("FILS-HOUSE".to_string())));
+        assert!(change(&mut vanity, "other", Some("FILS-HOUSE")).await.is_err());
+        assert!(change(&mut vanity, "fil", Some("ABCDE")).await.is_err(), "too short");
+        assert!(change(&mut vanity, "fil", Some("FILS HOUSE")).await.is_err());
+
+        // Claiming again moves the account's code
+        change(&mut vanity, "fil", Some("FILS-CASTLE")).await.unwrap();
+        assert!(change(&mut vanity, "other", Some("FILS-HOUSE")).await.is_ok());
+        assert_eq!(change(&mut vanity, "fil", None).await, Ok(None));
+        assert!(vanity.resolve("FILS-CASTLE").is_none());
+
+        // Claims come back from the database
+        let mut reloaded = VanityCodes::with_store(vanity.store.clone());
+        reloaded.load().await;
+        assert!(reloaded.is_claimed("fils-house"));
+        assert!(!reloaded.is_claimed("FILS-CASTLE"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_follows_the_hosted_lobby() {
+        let mut vanity = in_memory();
+        change(&mut vanity, "fil", Some("FILS-HOUSE")).await.unwrap();
+        assert_eq!(vanity.resolve("fils-house"), Some(Err("Host is not hosting right now")));
+
+        vanity.lobby_opened(Some("fil"), "client1", "AB12C");
+        assert_eq!(vanity.resolve("FILS-HOUSE"), Some(Ok("AB12C".to_string())));
+        vanity.host_left("client1");
+        assert!(matches!(vanity.resolve("FILS-HOUSE"), Some(Err(_))));
+        assert!(vanity.resolve("NOT-CLAIMED").is_none());
+    }
+}
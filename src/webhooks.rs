@@ -0,0 +1,206 @@
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::CONFIG;
+use crate::game_mode::GameMode;
+use crate::messages::Standing;
+use crate::utils::now_millis;
+
+/// Give up on a webhook endpoint that takes longer than this
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+static AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(WEBHOOK_TIMEOUT))
+        .build()
+        .into()
+});
+
+/// Set for offline runs (e.g. `simulate`) whose games must not reach external services
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    LobbyCreated,
+    GameStarted,
+    GameEnded,
+    PlayerReported,
+    BugReported,
+}
+
+/// An endpoint that receives server events as JSON POSTs
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events to deliver, all of them when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl WebhookConfig {
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookPayload {
+    LobbyCreated {
+        lobby_code: String,
+        game_mode: GameMode,
+        ruleset: String,
+    },
+    GameStarted {
+        lobby_code: String,
+        game_mode: GameMode,
+        players: Vec<String>,
+    },
+    GameEnded {
+        lobby_code: String,
+        game_mode: GameMode,
+        standings: Vec<Standing>,
+        /// Accounts whose runs don't hold up, leaderboards should skip them
+        unverified: Vec<String>,
+    },
+    PlayerReported {
+        lobby_code: String,
+        reporter_id: String,
+        reported_id: String,
+        report_id: String,
+        reason: String,
+    },
+    BugReported {
+        lobby_code: String,
+        player_id: String,
+        report_id: String,
+        description: String,
+    },
+}
+
+impl WebhookPayload {
+    pub fn event(&self) -> WebhookEvent {
+        match self {
+            Self::LobbyCreated { .. } => WebhookEvent::LobbyCreated,
+            Self::GameStarted { .. } => WebhookEvent::GameStarted,
+            Self::GameEnded { .. } => WebhookEvent::GameEnded,
+            Self::PlayerReported { .. } => WebhookEvent::PlayerReported,
+            Self::BugReported { .. } => WebhookEvent::BugReported,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    timestamp: u64,
+    #[serde(flatten)]
+    payload: &'a WebhookPayload,
+}
+
+pub fn mute() {
+    MUTED.store(true, Ordering::Relaxed);
+}
+
+/// Deliver an event to every interested webhook in the background
+pub fn emit(payload: WebhookPayload) {
+    if MUTED.load(Ordering::Relaxed) {
+        return;
+    }
+    let event = payload.event();
+    let urls: Vec<String> = CONFIG
+        .get()
+        .webhooks
+        .iter()
+        .filter(|hook| hook.wants(event))
+        .map(|hook| hook.url.clone())
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+    let timestamp = now_millis();
+    tokio::task::spawn_blocking(move || {
+        let body = WebhookBody {
+            timestamp,
+            payload: &payload,
+        };
+        for url in urls {
+            match deliver(&url, &body) {
+                Ok(()) => debug!("Delivered {:?} webhook to {}", event, url),
+                Err(e) => warn!("Webhook {} failed for {:?}: {}", url, event, e),
+            }
+        }
+    });
+}
+
+fn deliver(url: &str, body: &WebhookBody) -> Result<(), ureq::Error> {
+    AGENT.post(url).send_json(body)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_webhook_event_filter() {
+        let hook: WebhookConfig =
+            serde_json::from_str(r#"{ "url": "http://example.invalid", "events": ["game_ended"] }"#)
+                .unwrap();
+        assert!(hook.wants(WebhookEvent::GameEnded));
+        assert!(!hook.wants(WebhookEvent::LobbyCreated));
+        let all = WebhookConfig {
+            events: Vec::new(),
+            ..hook
+        };
+        assert!(all.wants(WebhookEvent::PlayerReported));
+    }
+
+    #[test]
+    fn test_deliver_posts_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the JSON body's closing brace arrives
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let payload = WebhookPayload::LobbyCreated {
+            lobby_code: "ABCDE".to_string(),
+            game_mode: GameMode::Attrition,
+            ruleset: "ranked".to_string(),
+        };
+        let body = WebhookBody {
+            timestamp: 1,
+            payload: &payload,
+        };
+        deliver(&url, &body).unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /hook"));
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["event"], "lobby_created");
+        assert_eq!(body["lobby_code"], "ABCDE");
+        assert_eq!(body["timestamp"], 1);
+    }
+}